@@ -1,13 +1,525 @@
 // scripts/compression_benchmark.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[SCRIPTS]Xyn>=====S===t===u===d===i===o===s======[R|$>
-// scripts/compression_benchmark.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[SCRIPTS]Xyn>=====S===t===u===d===i===o===s======[R|$>
-// scripts/compression_benchmark.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[SCRIPTS]Xyn>=====S===t===u===d===i===o===s======[R|$>
-// scripts/compression_benchmark.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[SCRIPTS]Xyn>=====S===t===u===d===i===o===s======[R|$>
-// scripts/compression_benchmark.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[SCRIPTS]Xyn>=====S===t===u===d===i===o===s======[R|$>
-// scripts/compression_benchmark.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[SCRIPTS]Xyn>=====S===t===u===d===i===o===s======[R|$>
-// scripts/compression_benchmark.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[SCRIPTS]Xyn>=====S===t===u===d===i===o===s======[R|$>
-// scripts/compression_benchmark.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[SCRIPTS]Xyn>=====S===t===u===d===i===o===s======[R|$>
-// scripts/compression_benchmark.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[SCRIPTS]Xyn>=====S===t===u===d===i===o===s======[R|$>
-// scripts/compression_benchmark.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[SCRIPTS]Xyn>=====S===t===u===d===i===o===s======[R|$>
-// scripts/compression_benchmark.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[SCRIPTS]Xyn>=====S===t===u===d===i===o===s======[R|$>
-// scripts/compression_benchmark.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[SCRIPTS]Xyn>=====S===t===u===d===i===o===s======[R|$>
-// scripts/compression_benchmark.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[SCRIPTS]Xyn>=====S===t===u===d===i===o===s======[R|$>
\ No newline at end of file
+
+// Runs every codec in the build's `CodecRegistry`, each at several levels
+// where it has them, over a corpus directory and/or synthetic inputs, and
+// reports the ratio, compress and decompress throughput and peak memory of
+// each run.
+//
+//   cargo run --release --bin compression_benchmark -- \
+//       [--corpus DIR] [--generators text,floats,random] [--size BYTES] \
+//       [--levels CODEC=1,3,9 ...] [--iterations N] [--json PATH|-] [--metrics PATH]
+//
+// With no `--corpus`, all three generators run. `--levels` replaces the
+// default sweep of one codec and may be repeated. `--metrics` writes every
+// result in the Prometheus text format under `benchmark_compression_*`,
+// for node_exporter's textfile collector or a push to a Pushgateway:
+//
+//   curl --data-binary @PATH http://gateway:9091/metrics/job/compression_benchmark
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Serialize;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use xage::constants::{ADAPTIVE_ZSTD_LEVELS, NTM_MEMORY_SIZE, NTM_MEMORY_VECTOR_SIZE};
+use xage::{CodecKind, CodecRegistry, CompressionChoice};
+
+const MIB: f64 = 1024.0 * 1024.0;
+const DEFAULT_SYNTHETIC_SIZE: usize = 16 * 1024 * 1024;
+const DEFAULT_ITERATIONS: usize = 3;
+
+/// Counts the bytes live on the Rust heap and their high-water mark.
+/// Native codec libraries allocate with `malloc` directly, so their buffers
+/// only show up in the resident set; see `ResidentSet`.
+struct PeakAllocator;
+
+static HEAP_CURRENT: AtomicUsize = AtomicUsize::new(0);
+static HEAP_PEAK: AtomicUsize = AtomicUsize::new(0);
+
+#[global_allocator]
+static ALLOCATOR: PeakAllocator = PeakAllocator;
+
+impl PeakAllocator {
+    fn grew(size: usize) {
+        let now = HEAP_CURRENT.fetch_add(size, Ordering::Relaxed) + size;
+        HEAP_PEAK.fetch_max(now, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for PeakAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            Self::grew(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            Self::grew(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        HEAP_CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            if new_size > layout.size() {
+                Self::grew(new_size - layout.size());
+            } else {
+                HEAP_CURRENT.fetch_sub(layout.size() - new_size, Ordering::Relaxed);
+            }
+        }
+        new_ptr
+    }
+}
+
+/// The process's resident set, read from `/proc/self/status`. Resetting
+/// its high-water mark needs Linux; elsewhere every reading is `None`.
+struct ResidentSet;
+
+impl ResidentSet {
+    /// Resets the high-water mark and returns the current resident size.
+    fn reset() -> Option<u64> {
+        std::fs::write("/proc/self/clear_refs", "5").ok()?;
+        Self::read("VmRSS:")
+    }
+
+    fn peak() -> Option<u64> {
+        Self::read("VmHWM:")
+    }
+
+    fn read(field: &str) -> Option<u64> {
+        let status = std::fs::read_to_string("/proc/self/status").ok()?;
+        let line = status.lines().find(|line| line.starts_with(field))?;
+        let kib: u64 = line[field.len()..].trim().trim_end_matches("kB").trim().parse().ok()?;
+        Some(kib * 1024)
+    }
+}
+
+/// How much memory `run` needed beyond what was live before it.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+struct PeakMemory {
+    heap_bytes: usize,
+    rss_bytes: Option<u64>,
+}
+
+impl PeakMemory {
+    /// The resident-set growth where known, as it includes native buffers,
+    /// and never less than the heap growth.
+    fn bytes(&self) -> u64 {
+        self.rss_bytes.unwrap_or(0).max(self.heap_bytes as u64)
+    }
+}
+
+fn measure<T>(run: impl FnOnce() -> T) -> (T, Duration, PeakMemory) {
+    let heap_baseline = HEAP_CURRENT.load(Ordering::Relaxed);
+    HEAP_PEAK.store(heap_baseline, Ordering::Relaxed);
+    let rss_baseline = ResidentSet::reset();
+
+    let start_time = Instant::now();
+    let output = run();
+    let elapsed = start_time.elapsed();
+
+    let peak = PeakMemory {
+        heap_bytes: HEAP_PEAK.load(Ordering::Relaxed).saturating_sub(heap_baseline),
+        rss_bytes: rss_baseline.zip(ResidentSet::peak()).map(|(baseline, peak)| peak.saturating_sub(baseline)),
+    };
+    (output, elapsed, peak)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Generator {
+    /// JSON context chunks like those APROAR stores for the context window.
+    Text,
+    /// Little-endian `f32` rows shaped like NTM memory.
+    Floats,
+    /// Incompressible bytes.
+    Random,
+}
+
+impl Generator {
+    fn parse(name: &str) -> Result<Self> {
+        match name {
+            "text" => Ok(Generator::Text),
+            "floats" => Ok(Generator::Floats),
+            "random" => Ok(Generator::Random),
+            other => bail!("Unknown generator '{}'; expected text, floats or random", other),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Generator::Text => "synthetic:text",
+            Generator::Floats => "synthetic:floats",
+            Generator::Random => "synthetic:random",
+        }
+    }
+
+    fn generate(&self, size: usize) -> Vec<u8> {
+        let mut rng = XorShift(0x2545_F491_4F6C_DD1D);
+        let mut data = Vec::with_capacity(size + 256);
+        match self {
+            Generator::Text => {
+                let mut i = 0u64;
+                while data.len() < size {
+                    let line = format!(
+                        "{{\"session\":\"{:04}\",\"role\":\"{}\",\"tokens\":{},\"memory_row\":{},\"text\":\"context chunk {} recalled after {} steps\"}}\n",
+                        rng.next_u64() % 97,
+                        if i % 3 == 0 { "user" } else { "assistant" },
+                        100 + rng.next_u64() % 400,
+                        rng.next_u64() % NTM_MEMORY_SIZE as u64,
+                        i,
+                        rng.next_u64() % 2048
+                    );
+                    data.extend_from_slice(line.as_bytes());
+                    i += 1;
+                }
+            }
+            Generator::Floats => {
+                let mut i = 0usize;
+                while data.len() < size {
+                    let row = i / NTM_MEMORY_VECTOR_SIZE;
+                    let column = i % NTM_MEMORY_VECTOR_SIZE;
+                    let noise = (rng.next_u64() >> 40) as f32 / (1u64 << 24) as f32 - 0.5;
+                    let value = (row as f32 * 0.01 + column as f32 * 0.1).sin() * 0.8 + noise * 0.05;
+                    data.extend_from_slice(&value.to_le_bytes());
+                    i += 1;
+                }
+            }
+            Generator::Random => {
+                while data.len() < size {
+                    data.extend_from_slice(&rng.next_u64().to_le_bytes());
+                }
+            }
+        }
+        data.truncate(size);
+        data
+    }
+}
+
+struct XorShift(u64);
+
+impl XorShift {
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+struct Options {
+    corpus: Option<PathBuf>,
+    generators: Vec<Generator>,
+    size: usize,
+    levels: HashMap<CodecKind, Vec<i32>>,
+    iterations: usize,
+    json: Option<String>,
+    metrics: Option<PathBuf>,
+}
+
+impl Options {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut options = Options {
+            corpus: None,
+            generators: Vec::new(),
+            size: DEFAULT_SYNTHETIC_SIZE,
+            levels: HashMap::new(),
+            iterations: DEFAULT_ITERATIONS,
+            json: None,
+            metrics: None,
+        };
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| anyhow!("{} needs a value", arg));
+            match arg.as_str() {
+                "--corpus" => options.corpus = Some(PathBuf::from(value()?)),
+                "--generators" => {
+                    options.generators = value()?.split(',').map(Generator::parse).collect::<Result<_>>()?;
+                }
+                "--size" => options.size = value()?.parse().context("--size must be a byte count")?,
+                "--levels" => {
+                    let (codec, levels) = parse_levels(&value()?)?;
+                    options.levels.insert(codec, levels);
+                }
+                "--iterations" => options.iterations = value()?.parse().context("--iterations must be a count")?,
+                "--json" => options.json = Some(value()?),
+                "--metrics" => options.metrics = Some(PathBuf::from(value()?)),
+                other => bail!("Unknown argument '{}'", other),
+            }
+        }
+        if options.corpus.is_none() && options.generators.is_empty() {
+            options.generators = vec![Generator::Text, Generator::Floats, Generator::Random];
+        }
+        options.iterations = options.iterations.max(1);
+        Ok(options)
+    }
+}
+
+/// Parses `CODEC=L1,L2,...` as `--levels` takes it.
+fn parse_levels(arg: &str) -> Result<(CodecKind, Vec<i32>)> {
+    let (name, levels) = arg.split_once('=').ok_or_else(|| anyhow!("--levels takes CODEC=L1,L2,..., got '{}'", arg))?;
+    let codec = CodecRegistry::builtin()
+        .codecs()
+        .find(|codec| codec.name() == name)
+        .ok_or_else(|| anyhow!("Codec '{}' is not in this build", name))?;
+    if default_levels(codec).is_empty() {
+        bail!("Codec '{}' has no levels", name);
+    }
+    let levels = levels
+        .split(',')
+        .map(|level| level.parse().context("--levels must be comma-separated integers"))
+        .collect::<Result<_>>()?;
+    Ok((codec, levels))
+}
+
+/// The levels each codec is swept across unless `--levels` says otherwise:
+/// the fastest, the codec's default and the strongest, plus the levels the
+/// adaptive selector picks between for Zstd. Empty for codecs without levels.
+fn default_levels(codec: CodecKind) -> Vec<i32> {
+    match codec {
+        CodecKind::Zstd => ADAPTIVE_ZSTD_LEVELS.to_vec(),
+        CodecKind::Lz4 => vec![0, 3, 9, 12],
+        CodecKind::Gzip | CodecKind::Deflate => vec![1, 6, 9],
+        CodecKind::Brotli => vec![1, 5, 9, 11],
+        CodecKind::Uncompressed | CodecKind::ZstdDict | CodecKind::Snappy => Vec::new(),
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct BenchmarkResult {
+    input: String,
+    /// The codec and level as metric keys name them, e.g. `zstd9`.
+    label: String,
+    codec: String,
+    level: Option<i32>,
+    original_bytes: usize,
+    compressed_bytes: usize,
+    ratio: f64,
+    compress_mib_s: f64,
+    decompress_mib_s: f64,
+    compress_peak: PeakMemory,
+    decompress_peak: PeakMemory,
+}
+
+/// Every codec in the registry once per level, or once if it has none.
+/// Dictionary Zstd needs a trained dictionary and is left out.
+fn choices(levels: &HashMap<CodecKind, Vec<i32>>) -> Vec<CompressionChoice> {
+    let mut choices = Vec::new();
+    for codec in CodecRegistry::builtin().codecs().filter(|codec| *codec != CodecKind::ZstdDict) {
+        let levels = levels.get(&codec).cloned().unwrap_or_else(|| default_levels(codec));
+        if levels.is_empty() {
+            choices.push(CompressionChoice::from(codec));
+        } else {
+            choices.extend(levels.into_iter().map(|level| CompressionChoice::with_level(codec, level)));
+        }
+    }
+    choices
+}
+
+/// The fastest of `iterations` runs, and the largest peak among them.
+fn bench(input: &str, data: &[u8], choice: CompressionChoice, iterations: usize) -> Result<BenchmarkResult> {
+    let strategy = choice.strategy();
+    let mut compressed = Vec::new();
+    let (mut compress_time, mut decompress_time) = (Duration::MAX, Duration::MAX);
+    let (mut compress_peak, mut decompress_peak) = (PeakMemory::default(), PeakMemory::default());
+
+    for _ in 0..iterations {
+        let (output, elapsed, peak) = measure(|| strategy.compress(data));
+        compressed = output.map_err(|e| anyhow!("{} compression of {} failed: {}", choice.label(), input, e))?;
+        compress_time = compress_time.min(elapsed);
+        compress_peak = max_peak(compress_peak, peak);
+    }
+    for _ in 0..iterations {
+        let (output, elapsed, peak) = measure(|| strategy.decompress(&compressed));
+        let decompressed = output.map_err(|e| anyhow!("{} decompression of {} failed: {}", choice.label(), input, e))?;
+        if decompressed != data {
+            bail!("{} did not round-trip {}", choice.label(), input);
+        }
+        decompress_time = decompress_time.min(elapsed);
+        decompress_peak = max_peak(decompress_peak, peak);
+    }
+
+    let throughput = |elapsed: Duration| data.len() as f64 / MIB / elapsed.as_secs_f64().max(f64::EPSILON);
+    Ok(BenchmarkResult {
+        input: input.to_string(),
+        label: choice.label(),
+        codec: choice.codec.name().to_string(),
+        level: choice.level,
+        original_bytes: data.len(),
+        compressed_bytes: compressed.len(),
+        ratio: data.len() as f64 / compressed.len().max(1) as f64,
+        compress_mib_s: throughput(compress_time),
+        decompress_mib_s: throughput(decompress_time),
+        compress_peak,
+        decompress_peak,
+    })
+}
+
+fn max_peak(a: PeakMemory, b: PeakMemory) -> PeakMemory {
+    PeakMemory {
+        heap_bytes: a.heap_bytes.max(b.heap_bytes),
+        rss_bytes: match (a.rss_bytes, b.rss_bytes) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        },
+    }
+}
+
+/// Every regular file under `dir`, sorted by path.
+fn corpus_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir).with_context(|| format!("Failed to read corpus directory {}", dir.display()))? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.is_file() {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn inputs(options: &Options) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut inputs = Vec::new();
+    if let Some(corpus) = &options.corpus {
+        for path in corpus_files(corpus)? {
+            let data = std::fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+            if !data.is_empty() {
+                let name = path.strip_prefix(corpus).unwrap_or(&path).display().to_string();
+                inputs.push((name, data));
+            }
+        }
+        if inputs.is_empty() {
+            bail!("Corpus directory {} holds no non-empty files", corpus.display());
+        }
+    }
+    for generator in &options.generators {
+        inputs.push((generator.name().to_string(), generator.generate(options.size)));
+    }
+    Ok(inputs)
+}
+
+fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1 << 30 => format!("{:.1} GiB", b as f64 / (1u64 << 30) as f64),
+        b if b >= 1 << 20 => format!("{:.1} MiB", b as f64 / MIB),
+        b if b >= 1 << 10 => format!("{:.1} KiB", b as f64 / 1024.0),
+        b => format!("{} B", b),
+    }
+}
+
+fn table(results: &[BenchmarkResult]) -> Result<String> {
+    let input_width = results.iter().map(|result| result.input.len()).max().unwrap_or(0).max("input".len());
+    let mut table = String::new();
+    writeln!(
+        table,
+        "{:<input_width$}  {:<10}  {:>10}  {:>8}  {:>12}  {:>12}  {:>11}  {:>11}",
+        "input", "codec", "size", "ratio", "comp MiB/s", "decomp MiB/s", "comp peak", "decomp peak"
+    )?;
+    writeln!(table, "{}", "-".repeat(input_width + 88))?;
+    for result in results {
+        writeln!(
+            table,
+            "{:<input_width$}  {:<10}  {:>10}  {:>8.3}  {:>12.1}  {:>12.1}  {:>11}  {:>11}",
+            result.input,
+            result.label,
+            format_bytes(result.compressed_bytes as u64),
+            result.ratio,
+            result.compress_mib_s,
+            result.decompress_mib_s,
+            format_bytes(result.compress_peak.bytes()),
+            format_bytes(result.decompress_peak.bytes())
+        )?;
+    }
+    Ok(table)
+}
+
+/// Escapes a Prometheus label value.
+fn label_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Every result as Prometheus gauges, one series per input and codec level.
+fn prometheus_text(results: &[BenchmarkResult]) -> Result<String> {
+    let gauges: [(&str, &str, fn(&BenchmarkResult) -> f64); 5] = [
+        ("ratio", "Original size over compressed size", |result| result.ratio),
+        ("compress_mib_per_second", "Compression throughput of the fastest run", |result| result.compress_mib_s),
+        ("decompress_mib_per_second", "Decompression throughput of the fastest run", |result| result.decompress_mib_s),
+        ("compress_peak_bytes", "Peak memory while compressing", |result| result.compress_peak.bytes() as f64),
+        ("decompress_peak_bytes", "Peak memory while decompressing", |result| result.decompress_peak.bytes() as f64),
+    ];
+    let mut text = String::new();
+    for (name, help, value) in gauges {
+        writeln!(text, "# HELP benchmark_compression_{} {}", name, help)?;
+        writeln!(text, "# TYPE benchmark_compression_{} gauge", name)?;
+        for result in results {
+            let level = result.level.map(|level| level.to_string()).unwrap_or_default();
+            writeln!(
+                text,
+                "benchmark_compression_{}{{input=\"{}\",codec=\"{}\",level=\"{}\"}} {}",
+                name,
+                label_value(&result.input),
+                result.codec,
+                level,
+                value(result)
+            )?;
+        }
+    }
+    Ok(text)
+}
+
+/// Writes next to `path` and renames over it, so a collector never reads
+/// half a file.
+fn write_metrics(path: &Path, results: &[BenchmarkResult]) -> Result<()> {
+    let staging = path.with_extension("prom.tmp");
+    std::fs::write(&staging, prometheus_text(results)?).with_context(|| format!("Failed to write {}", staging.display()))?;
+    std::fs::rename(&staging, path).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn main() -> Result<()> {
+    let options = Options::parse(std::env::args().skip(1))?;
+    let inputs = inputs(&options)?;
+    let choices = choices(&options.levels);
+
+    let mut results = Vec::with_capacity(inputs.len() * choices.len());
+    for (name, data) in &inputs {
+        for &choice in &choices {
+            results.push(bench(name, data, choice, options.iterations)?);
+        }
+    }
+
+    // With the JSON on stdout, the table goes to stderr to keep it parseable.
+    match options.json.as_deref() {
+        Some("-") => {
+            eprint!("{}", table(&results)?);
+            println!("{}", serde_json::to_string_pretty(&results)?);
+        }
+        Some(path) => {
+            print!("{}", table(&results)?);
+            std::fs::write(path, serde_json::to_vec_pretty(&results)?).with_context(|| format!("Failed to write {}", path))?;
+            println!("Wrote {} results to {}", results.len(), path);
+        }
+        None => print!("{}", table(&results)?),
+    }
+    if let Some(path) = &options.metrics {
+        write_metrics(path, &results)?;
+        eprintln!("Wrote metrics to {}", path.display());
+    }
+    Ok(())
+}
\ No newline at end of file
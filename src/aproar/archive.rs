@@ -0,0 +1,482 @@
+// src/aproar/archive.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[APROAR]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use crate::aproar::compression::{compression_threads, CodecKind, Envelope, ZstdCompression};
+use crate::aproar::integrity;
+use crate::aproar::storage::{is_not_found, run_blocking};
+use crate::aproar::{AproarManager, JournalOp};
+use crate::omnixtracker::OmniXError;
+use crate::security::AESEncryption;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tokio::sync::mpsc;
+
+pub const ARCHIVE_FORMAT_VERSION: u32 = 1;
+const MANIFEST_FILE: &str = "manifest.json";
+/// Entries in flight between the storage side and the archive file.
+const ARCHIVE_CHANNEL_CAPACITY: usize = 16;
+/// Most that a tar header can make an import reserve up front; larger
+/// entries grow their buffer as they are read.
+const MAX_ENTRY_PREALLOCATION: u64 = 1024 * 1024;
+
+/// Describes an archive: a zstd-compressed tar holding one file per key with
+/// the stored bytes exactly as the tiers hold them, followed by this
+/// manifest as `manifest.json`. The manifest comes last because the
+/// checksums are only known once every key has been read.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub format_version: u32,
+    pub created_ms: i64,
+    /// Set on incremental exports, which hold only the keys modified at or
+    /// after this time. Deletions are not recorded.
+    pub since_ms: Option<i64>,
+    pub tier_count: usize,
+    pub replicas: usize,
+    pub entries: Vec<ArchiveEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+    pub key: String,
+    /// Path of the key's data inside the archive.
+    pub file: String,
+    /// Primary tier on the exporting store.
+    pub tier: usize,
+    pub codec: CodecKind,
+    /// Length of the stored (framed and compressed) bytes.
+    pub size: u64,
+    /// Hex BLAKE3 digest of the stored bytes.
+    pub checksum: String,
+    /// Master key the value is encrypted under. The stored bytes are
+    /// archived as they are, so importing needs the same key.
+    #[serde(default)]
+    pub encryption_key_id: Option<u32>,
+    pub modified_ms: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ArchiveReport {
+    pub keys: usize,
+    pub bytes: u64,
+}
+
+/// Reads only the manifest of the archive at `path`.
+pub fn read_archive_manifest(path: impl AsRef<Path>) -> Result<ArchiveManifest, OmniXError> {
+    let path = path.as_ref();
+    let mut archive = open_archive(path)?;
+    for entry in archive.entries().map_err(|e| archive_error(path, e))? {
+        let mut entry = entry.map_err(|e| archive_error(path, e))?;
+        if entry.path().map_err(|e| archive_error(path, e))?.as_ref() != Path::new(MANIFEST_FILE) {
+            continue;
+        }
+        let mut raw = Vec::new();
+        entry.read_to_end(&mut raw).map_err(|e| archive_error(path, e))?;
+        let manifest: ArchiveManifest = serde_json::from_slice(&raw)
+            .map_err(|e| OmniXError::ValidationError(format!("Invalid archive manifest in {}: {}", path.display(), e)))?;
+        if manifest.format_version != ARCHIVE_FORMAT_VERSION {
+            return Err(OmniXError::ValidationError(format!(
+                "Unsupported archive format version {} in {}",
+                manifest.format_version,
+                path.display()
+            )));
+        }
+        return Ok(manifest);
+    }
+    Err(OmniXError::ValidationError(format!("{} has no archive manifest", path.display())))
+}
+
+impl AproarManager {
+    /// Writes every stored key, internal ones included, to a single archive
+    /// at `path`. The archive is written next to `path` and renamed into
+    /// place once complete, so a failed export never leaves a truncated file.
+    pub async fn export(&self, path: impl AsRef<Path>) -> Result<ArchiveReport, OmniXError> {
+        self.export_archive(path.as_ref(), None).await
+    }
+
+    /// Like `export`, but only includes keys modified at or after `since`.
+    /// Keys on backends that do not track modification times are always
+    /// included.
+    pub async fn export_since(&self, path: impl AsRef<Path>, since: DateTime<Utc>) -> Result<ArchiveReport, OmniXError> {
+        self.export_archive(path.as_ref(), Some(since)).await
+    }
+
+    /// Loads an archive written by `export`, overwriting keys that already
+    /// exist. Each key goes to the tier it was exported from, or the coldest
+    /// tier when this store has fewer, with this store's replica count.
+    /// Entries are verified before they are written, so a damaged archive
+    /// stops the import at the first bad entry and keeps the keys before it.
+    pub async fn import(&self, path: impl AsRef<Path>) -> Result<ArchiveReport, OmniXError> {
+        let start_time = Instant::now();
+        let path = path.as_ref().to_path_buf();
+        let manifest_path = path.clone();
+        let manifest = run_blocking(move || read_archive_manifest(&manifest_path)).await?;
+        let key_ids: BTreeSet<u32> = manifest.entries.iter().filter_map(|entry| entry.encryption_key_id).collect();
+        self.check_encryption_keys(key_ids)?;
+
+        let (sender, mut receiver) = mpsc::channel(ARCHIVE_CHANNEL_CAPACITY);
+        let reader = tokio::task::spawn_blocking(move || read_archive_entries(&path, manifest, sender));
+
+        let mut report = ArchiveReport::default();
+        let mut imported = Ok(());
+        while let Some((entry, stored)) = receiver.recv().await {
+            if let Err(e) = self.import_entry(&entry, stored).await {
+                imported = Err(e);
+                break;
+            }
+            report.keys += 1;
+            report.bytes += entry.size;
+        }
+        drop(receiver);
+        let read = reader.await.map_err(|e| OmniXError::OperationFailed {
+            operation: "Archive import".to_string(),
+            details: e.to_string(),
+        })?;
+        // Imported manifests and chunks invalidate the dedup refcounts.
+        *self.content_refs.lock().await = None;
+        imported?;
+        read?;
+
+        self.metrics.record_histogram("archive.import.duration".to_string(), start_time.elapsed().as_secs_f64());
+        self.metrics.increment_counter("archive.import.keys".to_string(), report.keys as u64);
+        Ok(report)
+    }
+
+    async fn export_archive(&self, path: &Path, since: Option<DateTime<Utc>>) -> Result<ArchiveReport, OmniXError> {
+        let start_time = Instant::now();
+        let partial = partial_path(path);
+        let (sender, receiver) = mpsc::channel(ARCHIVE_CHANNEL_CAPACITY);
+        let writer_path = partial.clone();
+        let writer = tokio::task::spawn_blocking(move || write_archive(&writer_path, receiver));
+
+        let collected = self.send_archive_entries(&sender, since).await;
+        drop(sender);
+        let written = writer.await.map_err(|e| OmniXError::OperationFailed {
+            operation: "Archive export".to_string(),
+            details: e.to_string(),
+        })?;
+
+        // A failed write closes the channel, so its error is the one to report.
+        let report = match written.and(collected) {
+            Ok(report) => report,
+            Err(e) => {
+                let _ = tokio::fs::remove_file(&partial).await;
+                return Err(e);
+            }
+        };
+        tokio::fs::rename(&partial, path).await.map_err(|e| archive_error(path, e))?;
+
+        self.metrics.record_histogram("archive.export.duration".to_string(), start_time.elapsed().as_secs_f64());
+        self.metrics.increment_counter("archive.export.keys".to_string(), report.keys as u64);
+        self.metrics.increment_counter("archive.export.bytes".to_string(), report.bytes);
+        Ok(report)
+    }
+
+    /// Reads every key selected for export, hands its bytes to the archive
+    /// writer and finishes with the manifest.
+    async fn send_archive_entries(
+        &self,
+        sender: &mpsc::Sender<(String, Vec<u8>)>,
+        since: Option<DateTime<Utc>>,
+    ) -> Result<ArchiveReport, OmniXError> {
+        let mut entries = Vec::new();
+        let mut report = ArchiveReport::default();
+
+        for key in self.list_stored_keys("").await? {
+            if self.is_expired(&key) {
+                continue;
+            }
+            let tier = match self.locate(&key).await? {
+                Some(tier) => tier,
+                None => continue,
+            };
+            let backend = self.storage_backends[tier].clone();
+            let owned_key = key.clone();
+            // Keys deleted since the listing are skipped rather than failing the export.
+            let modified = match run_blocking(move || backend.metadata(&owned_key)).await {
+                Ok(metadata) => metadata.modified,
+                Err(e) if is_not_found(&e) => continue,
+                Err(e) => return Err(e),
+            };
+            if let (Some(since), Some(modified)) = (since, modified) {
+                if modified < since {
+                    continue;
+                }
+            }
+
+            let stored = match self.read_replicas(&key).await {
+                Ok(stored) => stored,
+                Err(e) if is_not_found(&e) => continue,
+                Err(e) => return Err(e),
+            };
+            let body = integrity::unframe(&key, &stored)?;
+            let encryption_key_id = AESEncryption::key_id(body);
+            let codec = Envelope::parse(&self.sealed_payload(&key, body)?)?.codec;
+            let file = format!("data/{:08}", entries.len());
+            entries.push(ArchiveEntry {
+                key,
+                file: file.clone(),
+                tier,
+                codec,
+                size: stored.len() as u64,
+                checksum: blake3::hash(&stored).to_hex().to_string(),
+                encryption_key_id,
+                modified_ms: modified.map(|modified| modified.timestamp_millis()),
+            });
+            report.keys += 1;
+            report.bytes += stored.len() as u64;
+            if sender.send((file, stored)).await.is_err() {
+                return Err(writer_closed());
+            }
+        }
+
+        let manifest = ArchiveManifest {
+            format_version: ARCHIVE_FORMAT_VERSION,
+            created_ms: Utc::now().timestamp_millis(),
+            since_ms: since.map(|since| since.timestamp_millis()),
+            tier_count: self.storage_backends.len(),
+            replicas: self.replicas,
+            entries,
+        };
+        let raw = serde_json::to_vec_pretty(&manifest).map_err(|e| OmniXError::OperationFailed {
+            operation: "Archive export".to_string(),
+            details: format!("Failed to encode manifest: {}", e),
+        })?;
+        if sender.send((MANIFEST_FILE.to_string(), raw)).await.is_err() {
+            return Err(writer_closed());
+        }
+        Ok(report)
+    }
+
+    async fn import_entry(&self, entry: &ArchiveEntry, stored: Vec<u8>) -> Result<(), OmniXError> {
+        let tier = entry.tier.min(self.storage_backends.len() - 1);
+        let seq = self.journal_intent(JournalOp::Store { key: entry.key.clone(), tier }).await?;
+        // Imported markers carry their deadlines over to this store, and
+        // imported dictionaries are loaded for the values that need them.
+        self.note_expiry_marker(&entry.key, &stored)?;
+        self.note_dictionary(&entry.key, &stored)?;
+        self.store_replicas(&entry.key, stored, tier).await?;
+
+        // Cached copies predate the import.
+        self.evict_from_caches(&entry.key).await;
+        self.journal_complete(seq);
+        Ok(())
+    }
+}
+
+fn write_archive(path: &Path, mut receiver: mpsc::Receiver<(String, Vec<u8>)>) -> Result<(), OmniXError> {
+    let file = File::create(path).map_err(|e| archive_error(path, e))?;
+    let encoder = ZstdCompression.writer(file, compression_threads())?;
+    let mut builder = tar::Builder::new(encoder);
+    let mtime = Utc::now().timestamp().max(0) as u64;
+
+    while let Some((name, data)) = receiver.blocking_recv() {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        builder.append_data(&mut header, &name, data.as_slice()).map_err(|e| archive_error(path, e))?;
+    }
+
+    let encoder = builder.into_inner().map_err(|e| archive_error(path, e))?;
+    let file = encoder.finish()?;
+    file.sync_all().map_err(|e| archive_error(path, e))
+}
+
+/// Verifies each data file against `manifest` and sends it on. Stops quietly
+/// when the receiver is gone, since the import has then already failed.
+fn read_archive_entries(
+    path: &Path,
+    manifest: ArchiveManifest,
+    sender: mpsc::Sender<(ArchiveEntry, Vec<u8>)>,
+) -> Result<(), OmniXError> {
+    let mut expected: HashMap<String, ArchiveEntry> =
+        manifest.entries.into_iter().map(|entry| (entry.file.clone(), entry)).collect();
+    let mut archive = open_archive(path)?;
+
+    for file in archive.entries().map_err(|e| archive_error(path, e))? {
+        let mut file = file.map_err(|e| archive_error(path, e))?;
+        let name = file.path().map_err(|e| archive_error(path, e))?.to_string_lossy().into_owned();
+        if name == MANIFEST_FILE {
+            continue;
+        }
+        let entry = expected.remove(&name).ok_or_else(|| {
+            OmniXError::ValidationError(format!("{} holds {}, which its manifest does not list", path.display(), name))
+        })?;
+
+        let mut stored = Vec::with_capacity(file.size().min(MAX_ENTRY_PREALLOCATION) as usize);
+        file.read_to_end(&mut stored).map_err(|e| archive_error(path, e))?;
+        if stored.len() as u64 != entry.size || blake3::hash(&stored).to_hex().as_str() != entry.checksum {
+            return Err(OmniXError::DataCorruption {
+                key: entry.key,
+                details: format!("Archived copy in {} does not match its manifest checksum", path.display()),
+            });
+        }
+        integrity::unframe(&entry.key, &stored)?;
+
+        if sender.blocking_send((entry, stored)).is_err() {
+            return Ok(());
+        }
+    }
+
+    if !expected.is_empty() {
+        return Err(OmniXError::ValidationError(format!(
+            "{} is missing {} entries listed in its manifest",
+            path.display(),
+            expected.len()
+        )));
+    }
+    Ok(())
+}
+
+fn open_archive(path: &Path) -> Result<tar::Archive<zstd::Decoder<'static, BufReader<File>>>, OmniXError> {
+    let file = File::open(path).map_err(|e| archive_error(path, e))?;
+    let decoder = zstd::Decoder::new(file).map_err(|e| archive_error(path, e))?;
+    Ok(tar::Archive::new(decoder))
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    PathBuf::from(partial)
+}
+
+fn archive_error(path: &Path, e: std::io::Error) -> OmniXError {
+    OmniXError::FileSystemError(format!("Archive {}: {}", path.display(), e))
+}
+
+fn writer_closed() -> OmniXError {
+    OmniXError::OperationFailed {
+        operation: "Archive export".to_string(),
+        details: "Archive writer stopped early".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aproar::storage::{MemoryStorage, StorageBackend};
+    use crate::aproar::{AproarManagerBuilder, CompressionPolicy, DedupConfig};
+    use crate::omnixtracker::OmniXMetry;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn manager(tiers: usize) -> Result<(AproarManager, Vec<Arc<MemoryStorage>>), OmniXError> {
+        let backends: Vec<Arc<MemoryStorage>> = (0..tiers).map(|_| Arc::new(MemoryStorage::new())).collect();
+        let policy = CompressionPolicy { dedup: Some(DedupConfig { min_payload: 64 * 1024 }), ..CompressionPolicy::default() };
+        let mut builder = AproarManagerBuilder::new(OmniXMetry::init()?).compression_policy(policy).background_tasks(false);
+        for backend in &backends {
+            builder = builder.storage_backend(backend.clone());
+        }
+        Ok((builder.build()?, backends))
+    }
+
+    #[tokio::test]
+    async fn test_export_import_round_trip_and_incremental() -> Result<(), OmniXError> {
+        let dir = tempfile::tempdir().map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+        let (source, _) = manager(2)?;
+        source.store_data("hot", &b"frequently read".repeat(100), usize::MAX).await?;
+        source.store_data("cold", b"rarely read", 0).await?;
+        let deduplicated = b"shared context ".repeat(10_000);
+        source.store_data("dedup", &deduplicated, 0).await?;
+
+        let full = dir.path().join("full.tar.zst");
+        let report = source.export(&full).await?;
+        let manifest = read_archive_manifest(&full)?;
+        assert_eq!(manifest.entries.len(), report.keys);
+        assert!(manifest.entries.iter().any(|entry| entry.key == "hot" && entry.tier == 0));
+        assert!(!dir.path().join("full.tar.zst.partial").exists());
+
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        let since = Utc::now();
+        source.store_data("cold", b"updated", 0).await?;
+        let incremental = dir.path().join("incremental.tar.zst");
+        assert_eq!(source.export_since(&incremental, since).await?.keys, 1);
+
+        let (target, target_tiers) = manager(1)?;
+        assert_eq!(target.import(&full).await?.keys, report.keys);
+        target.import(&incremental).await?;
+        assert_eq!(target.retrieve_data("hot").await?, b"frequently read".repeat(100));
+        assert_eq!(target.retrieve_data("cold").await?, b"updated");
+        assert_eq!(target.retrieve_data("dedup").await?, deduplicated);
+        assert!(target_tiers[0].exists("hot")?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_import_rejects_a_damaged_archive() -> Result<(), OmniXError> {
+        let dir = tempfile::tempdir().map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+        let (source, _) = manager(1)?;
+        source.store_data("key", b"value", 0).await?;
+        let path = dir.path().join("store.tar.zst");
+        source.export(&path).await?;
+
+        // One data file altered but the manifest intact.
+        let damaged = dir.path().join("damaged.tar.zst");
+        repack(&path, &damaged, |name, data| {
+            if name != MANIFEST_FILE {
+                let last = data.len() - 1;
+                data[last] ^= 0xff;
+            }
+        })
+        .await?;
+
+        let (target, _) = manager(1)?;
+        assert!(matches!(target.import(&damaged).await, Err(OmniXError::DataCorruption { .. })));
+        assert!(target.retrieve_data("key").await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_import_does_not_trust_manifest_sizes() -> Result<(), OmniXError> {
+        let dir = tempfile::tempdir().map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+        let (source, _) = manager(1)?;
+        source.store_data("key", b"value", 0).await?;
+        let path = dir.path().join("store.tar.zst");
+        source.export(&path).await?;
+
+        let oversized = dir.path().join("oversized.tar.zst");
+        repack(&path, &oversized, |name, data| {
+            if name == MANIFEST_FILE {
+                let mut manifest: ArchiveManifest = serde_json::from_slice(data).expect("manifest is valid JSON");
+                for entry in &mut manifest.entries {
+                    entry.size = u64::MAX;
+                }
+                *data = serde_json::to_vec(&manifest).expect("manifest serializes");
+            }
+        })
+        .await?;
+
+        let (target, _) = manager(1)?;
+        assert!(matches!(target.import(&oversized).await, Err(OmniXError::DataCorruption { .. })));
+        assert!(target.retrieve_data("key").await.is_err());
+
+        Ok(())
+    }
+
+    /// Copies the archive at `path` to `dest`, passing every file through `alter`.
+    async fn repack(path: &Path, dest: &Path, alter: impl Fn(&str, &mut Vec<u8>)) -> Result<(), OmniXError> {
+        let mut archive = open_archive(path)?;
+        let mut files = Vec::new();
+        for file in archive.entries().map_err(|e| archive_error(path, e))? {
+            let mut file = file.map_err(|e| archive_error(path, e))?;
+            let name = file.path().map_err(|e| archive_error(path, e))?.to_string_lossy().into_owned();
+            let mut data = Vec::new();
+            file.read_to_end(&mut data).map_err(|e| archive_error(path, e))?;
+            alter(&name, &mut data);
+            files.push((name, data));
+        }
+        let (sender, receiver) = mpsc::channel(files.len());
+        for file in files {
+            sender.try_send(file).expect("channel sized for every file");
+        }
+        drop(sender);
+        let dest = dest.to_path_buf();
+        run_blocking(move || write_archive(&dest, receiver)).await
+    }
+}
\ No newline at end of file
@@ -0,0 +1,409 @@
+// src/aproar/batch.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[APROAR]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use crate::aproar::expiry::{deadline_after, expiry_key};
+use crate::aproar::integrity;
+use crate::aproar::replication::replica_tiers;
+use crate::aproar::retrieval::RetrievalCache;
+use crate::aproar::storage::{is_not_found, run_blocking};
+use crate::aproar::versioning::INTERNAL_KEY_PREFIX;
+use crate::aproar::{AproarManager, JournalOp};
+use crate::omnixtracker::{OmniXError, OmniXMetry};
+use futures::future::join_all;
+use rayon::prelude::*;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use std::time::Instant;
+
+impl AproarManager {
+    /// Stores every entry as `store_data` would, but seals them in parallel,
+    /// journals them in a single write and hands each tier and cache all of
+    /// its keys at once, expiry markers included. When a key appears more
+    /// than once the last entry wins. Values the dedup policy covers are
+    /// chunked one by one as by `store_data`.
+    pub async fn store_batch(&self, entries: &[(String, Vec<u8>)], usage_frequency: usize) -> Result<(), OmniXError> {
+        let start_time = Instant::now();
+        let mut last_index = HashMap::new();
+        for (index, (key, _)) in entries.iter().enumerate() {
+            last_index.insert(key.as_str(), index);
+        }
+        let entries: Vec<&(String, Vec<u8>)> = entries
+            .iter()
+            .enumerate()
+            .filter(|(index, (key, _))| last_index[key.as_str()] == *index)
+            .map(|(_, entry)| entry)
+            .collect();
+        let (chunked, entries): (Vec<_>, Vec<_>) =
+            entries.into_iter().partition(|(key, data)| self.deduplicates(key, data.len()));
+        for (key, data) in &chunked {
+            self.store_data(key, data, usage_frequency).await?;
+        }
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let stored_keys = chunked.len() + entries.len();
+        let replaced: Vec<String> = entries.iter().filter(|(key, _)| self.dedup_enabled(key)).map(|(key, _)| key.clone()).collect();
+
+        // As in `update_expiry`, deadlines enter the map before the writes so
+        // a racing reaper pass leaves the keys alone.
+        let mut writes: Vec<(String, Vec<u8>, usize)> = Vec::with_capacity(entries.len());
+        for (key, data) in entries {
+            match self.namespace_ttl(key) {
+                Some(ttl) => {
+                    let deadline = deadline_after(ttl);
+                    self.expiries.insert(key.clone(), deadline);
+                    writes.push((expiry_key(key), deadline.to_be_bytes().to_vec(), 0));
+                }
+                None => self.update_expiry(key, None).await?,
+            }
+            writes.push((key.clone(), data.clone(), usage_frequency));
+        }
+
+        let sealer = self.sealer();
+        let (sealed, frequencies): (Vec<(String, Vec<u8>)>, Vec<usize>) = run_blocking(move || {
+            writes
+                .par_iter()
+                .map(|(key, data, frequency)| Ok(((key.clone(), sealer.seal(key, data)?), *frequency)))
+                .collect::<Result<Vec<_>, OmniXError>>()
+        })
+        .await?
+        .into_iter()
+        .unzip();
+        let targets: Vec<usize> = sealed
+            .iter()
+            .zip(frequencies)
+            .map(|((key, _), frequency)| {
+                self.access_tracker.record(key);
+                self.select_storage_tier(frequency.max(self.access_tracker.frequency(key)))
+            })
+            .collect();
+        let ops = sealed
+            .iter()
+            .zip(&targets)
+            .map(|((key, _), &tier)| JournalOp::Store { key: key.clone(), tier })
+            .collect();
+        let seqs = self.journal_intents(ops).await?;
+
+        let placement_index = self.placement_index.clone();
+        let storage_backends = self.storage_backends.clone();
+        let replicas = self.replicas;
+        let sealed = run_blocking(move || {
+            // The pins keep the migrator off these keys between the tier
+            // writes and the placement updates below.
+            let _pins: Vec<_> = sealed.iter().map(|(key, _)| placement_index.pin(key)).collect();
+            let tier_count = storage_backends.len();
+            let mut per_tier: Vec<Vec<(String, Vec<u8>)>> = vec![Vec::new(); tier_count];
+            for ((key, stored), &target) in sealed.iter().zip(&targets) {
+                for tier in replica_tiers(target, replicas, tier_count) {
+                    per_tier[tier].push((key.clone(), stored.clone()));
+                }
+            }
+            for (tier, batch) in per_tier.iter().enumerate().filter(|(_, batch)| !batch.is_empty()) {
+                storage_backends[tier].store_batch(batch)?;
+            }
+
+            for ((key, _), &target) in sealed.iter().zip(&targets) {
+                placement_index.place(key, |current| {
+                    if let Some(previous) = current {
+                        let replica_set = replica_tiers(target, replicas, tier_count);
+                        for stale in replica_tiers(previous, replicas, tier_count) {
+                            if !replica_set.contains(&stale) {
+                                storage_backends[stale].delete(key)?;
+                            }
+                        }
+                    }
+                    Ok(target)
+                })?;
+            }
+            Ok(sealed)
+        })
+        .await?;
+
+        self.fill_caches(sealed).await;
+        for seq in seqs {
+            self.journal_complete(seq);
+        }
+        for key in &replaced {
+            self.release_content(key).await?;
+        }
+
+        self.metrics.record_histogram("storage.store_batch.duration".to_string(), start_time.elapsed().as_secs_f64());
+        self.metrics.increment_counter("storage.store_batch.keys".to_string(), stored_keys as u64);
+        Ok(())
+    }
+
+    /// Retrieves `keys` in order, `None` for keys that are not stored. Cache
+    /// misses are read with one request per tier and fall back to the other
+    /// replicas like `retrieve_data`; the batch fails only when a stored key
+    /// has no readable replica.
+    pub async fn retrieve_batch(&self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>, OmniXError> {
+        let start_time = Instant::now();
+        for key in keys {
+            self.access_tracker.record(key);
+        }
+        let values = self.read_batch(keys, None).await?;
+
+        self.metrics.record_histogram("storage.retrieve_batch.duration".to_string(), start_time.elapsed().as_secs_f64());
+        self.metrics.increment_counter("storage.retrieve_batch.keys".to_string(), keys.len() as u64);
+        Ok(values)
+    }
+
+    /// Every stored key starting with `prefix` with its data, in key order.
+    /// Internal keys are only included when `prefix` itself is internal.
+    /// Caches that can enumerate their keys are scanned instead of queried
+    /// key by key.
+    pub async fn scan_prefix(&self, prefix: &str) -> Result<Vec<(String, Vec<u8>)>, OmniXError> {
+        let start_time = Instant::now();
+        let include_internal = prefix.starts_with(INTERNAL_KEY_PREFIX);
+        let keys: Vec<String> = self
+            .list_stored_keys(prefix)
+            .await?
+            .into_iter()
+            .filter(|key| include_internal || !key.starts_with(INTERNAL_KEY_PREFIX))
+            .collect();
+        let values = self.read_batch(&keys, Some(prefix)).await?;
+
+        // Keys deleted since they were listed come back as `None`.
+        let entries: Vec<(String, Vec<u8>)> = keys
+            .into_iter()
+            .zip(values)
+            .filter_map(|(key, value)| value.map(|value| (key, value)))
+            .collect();
+
+        self.metrics.record_histogram("storage.scan_prefix.duration".to_string(), start_time.elapsed().as_secs_f64());
+        self.metrics.increment_counter("storage.scan_prefix.keys".to_string(), entries.len() as u64);
+        Ok(entries)
+    }
+
+    /// The sorted union of the keys starting with `prefix` on every tier.
+    pub(crate) async fn list_stored_keys(&self, prefix: &str) -> Result<Vec<String>, OmniXError> {
+        let listings = join_all(self.storage_backends.iter().map(|backend| {
+            let backend = backend.clone();
+            let prefix = prefix.to_string();
+            run_blocking(move || backend.list_keys(&prefix))
+        }))
+        .await;
+
+        let mut keys = BTreeSet::new();
+        for listing in listings {
+            keys.extend(listing?);
+        }
+        Ok(keys.into_iter().collect())
+    }
+
+    async fn read_batch(&self, keys: &[String], prefix: Option<&str>) -> Result<Vec<Option<Vec<u8>>>, OmniXError> {
+        // Expired keys read as missing whether or not the reaper got to them.
+        let expired: Vec<bool> = keys.iter().map(|key| self.is_expired(key)).collect();
+        let live: Vec<String> = keys
+            .iter()
+            .zip(&expired)
+            .filter(|(_, &expired)| !expired)
+            .map(|(key, _)| key.clone())
+            .collect();
+        let keys = live.as_slice();
+
+        let (cache_keys, cache_prefix) = (keys.to_vec(), prefix.map(str::to_string));
+        let mut found = self
+            .with_caches(move |caches, metrics| read_caches(caches, metrics, &cache_keys, cache_prefix.as_deref()))
+            .await?;
+        let hits = found.iter().filter(|stored| stored.is_some()).count();
+        self.metrics.increment_counter("cache.hit".to_string(), hits as u64);
+        self.metrics.increment_counter("cache.miss".to_string(), (keys.len() - hits) as u64);
+
+        let fetched = self.read_tiers(keys, &mut found).await?;
+        self.fill_caches(fetched).await;
+
+        let mut values = Vec::with_capacity(keys.len());
+        for (key, stored) in keys.iter().zip(found) {
+            values.push(match stored {
+                Some(stored) => Some(self.resolve_content(key, self.open_value(key, &stored)?).await?),
+                None => None,
+            });
+        }
+        let mut values = values.into_iter();
+        Ok(expired.into_iter().map(|expired| if expired { None } else { values.next().flatten() }).collect())
+    }
+
+    /// Fills the gaps in `found` from the primary tiers, one batch per tier,
+    /// and returns the copies read so they can be cached.
+    async fn read_tiers(&self, keys: &[String], found: &mut [Option<Vec<u8>>]) -> Result<Vec<(String, Vec<u8>)>, OmniXError> {
+        let mut by_tier: HashMap<usize, Vec<usize>> = HashMap::new();
+        for index in (0..keys.len()).filter(|&index| found[index].is_none()) {
+            if let Some(tier) = self.locate(&keys[index]).await? {
+                by_tier.entry(tier).or_default().push(index);
+            }
+        }
+
+        let reads = join_all(by_tier.into_iter().map(|(tier, indices)| {
+            let backend = self.storage_backends[tier].clone();
+            let tier_keys: Vec<String> = indices.iter().map(|&index| keys[index].clone()).collect();
+            async move { (indices, run_blocking(move || Ok(backend.retrieve_batch(&tier_keys))).await) }
+        }))
+        .await;
+
+        let mut fetched = Vec::new();
+        for (indices, results) in reads {
+            for (index, result) in indices.into_iter().zip(results?) {
+                let key = &keys[index];
+                let verified = result.and_then(|stored| {
+                    integrity::unframe(key, &stored)?;
+                    Ok(stored)
+                });
+                let stored = match verified {
+                    Ok(stored) => stored,
+                    // The replica path counts, logs and repairs the bad copy.
+                    Err(_) => match self.read_replicas(key).await {
+                        Ok(stored) => stored,
+                        // Deleted since it was located; it reads as `None`.
+                        Err(e) if is_not_found(&e) => continue,
+                        Err(e) => return Err(e),
+                    },
+                };
+                fetched.push((key.clone(), stored.clone()));
+                found[index] = Some(stored);
+            }
+        }
+        Ok(fetched)
+    }
+
+    async fn fill_caches(&self, entries: Vec<(String, Vec<u8>)>) {
+        if entries.is_empty() || self.retrieval_caches.is_empty() {
+            return;
+        }
+        let entries = if self.expiries.is_empty() {
+            entries
+        } else {
+            let (expiring, lasting): (Vec<_>, Vec<_>) =
+                entries.into_iter().partition(|(key, _)| self.expiries.contains_key(key));
+            for (key, stored) in &expiring {
+                self.cache_stored(key, stored).await;
+            }
+            lasting
+        };
+        if entries.is_empty() {
+            return;
+        }
+        let filled = self
+            .with_caches(move |caches, metrics| {
+                for cache in caches {
+                    if let Err(e) = cache.set_many(&entries) {
+                        metrics.increment_counter("cache.set.failure".to_string(), 1);
+                        e.log();
+                    }
+                }
+            })
+            .await;
+        if let Err(e) = filled {
+            e.log();
+        }
+    }
+}
+
+/// Verified cached copies of `keys`, querying each cache only for the keys
+/// the caches before it missed. Corrupted copies are evicted and skipped.
+fn read_caches(caches: &[Arc<dyn RetrievalCache>], metrics: &OmniXMetry, keys: &[String], prefix: Option<&str>) -> Vec<Option<Vec<u8>>> {
+    let mut found = vec![None; keys.len()];
+    for cache in caches {
+        let missing: Vec<usize> = (0..keys.len()).filter(|&index| found[index].is_none()).collect();
+        if missing.is_empty() {
+            break;
+        }
+
+        let scanned = match prefix {
+            Some(prefix) => cache.scan_prefix(prefix),
+            None => Ok(None),
+        };
+        let cached = match scanned {
+            Ok(Some(entries)) => {
+                let mut entries: HashMap<String, Vec<u8>> = entries.into_iter().collect();
+                Ok(missing.iter().map(|&index| entries.remove(&keys[index])).collect())
+            }
+            Ok(None) => cache.get_many(&missing.iter().map(|&index| keys[index].clone()).collect::<Vec<_>>()),
+            Err(e) => Err(e),
+        };
+        let cached = match cached {
+            Ok(cached) => cached,
+            Err(e) => {
+                metrics.increment_counter("cache.get.failure".to_string(), 1);
+                e.log();
+                continue;
+            }
+        };
+
+        for (&index, stored) in missing.iter().zip(cached) {
+            let stored = match stored {
+                Some(stored) => stored,
+                None => continue,
+            };
+            match integrity::unframe(&keys[index], &stored) {
+                Ok(_) => found[index] = Some(stored),
+                Err(e) => {
+                    metrics.increment_counter("cache.corruption".to_string(), 1);
+                    e.log();
+                    if let Err(e) = cache.delete(&keys[index]) {
+                        e.log();
+                    }
+                }
+            }
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aproar::retrieval::{MemoryCache, RetrievalCache};
+    use crate::aproar::storage::{MemoryStorage, StorageBackend};
+    use crate::aproar::AproarManagerBuilder;
+    use crate::omnixtracker::{OmniXError, OmniXMetry};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_batches_round_trip_and_scan_by_prefix() -> Result<(), OmniXError> {
+        let hot = Arc::new(MemoryStorage::new());
+        let cold = Arc::new(MemoryStorage::new());
+        let cache = Arc::new(MemoryCache::new());
+        let manager = AproarManagerBuilder::new(OmniXMetry::init()?)
+            .storage_backend(hot.clone())
+            .storage_backend(cold.clone())
+            .retrieval_cache(cache.clone())
+            .background_tasks(false)
+            .build()?;
+
+        let entries: Vec<(String, Vec<u8>)> = (0..20)
+            .map(|i| (format!("context/{:02}", i), format!("chunk {}", i).repeat(i + 1).into_bytes()))
+            .chain([("other".to_string(), b"unrelated".to_vec()), ("context/00".to_string(), b"latest".to_vec())])
+            .collect();
+        manager.store_batch(&entries, 0).await?;
+        assert_eq!(cold.list_keys("")?.len(), 21);
+        assert!(hot.list_keys("")?.is_empty());
+
+        // Half the keys are only on the backend, and one cached copy is corrupt.
+        for i in 0..10 {
+            cache.delete(&format!("context/{:02}", i))?;
+        }
+        let mut cached = cache.get("context/15")?.expect("stored keys are cached");
+        cached[6] ^= 0xff;
+        cache.set("context/15", &cached)?;
+
+        let keys = vec!["context/03".to_string(), "missing".to_string(), "context/15".to_string(), "other".to_string()];
+        let values = manager.retrieve_batch(&keys).await?;
+        assert_eq!(values[0].as_deref(), Some("chunk 3".repeat(4).as_bytes()));
+        assert_eq!(values[1], None);
+        assert_eq!(values[2].as_deref(), Some("chunk 15".repeat(16).as_bytes()));
+        assert_eq!(values[3].as_deref(), Some(&b"unrelated"[..]));
+
+        let scanned = manager.scan_prefix("context/").await?;
+        assert_eq!(scanned.len(), 20);
+        assert_eq!(scanned[0], ("context/00".to_string(), b"latest".to_vec()));
+        assert_eq!(scanned[19], ("context/19".to_string(), "chunk 19".repeat(20).into_bytes()));
+        assert!(scanned.windows(2).all(|pair| pair[0].0 < pair[1].0));
+
+        // A key removed behind the placement index's back reads as missing.
+        cold.delete("other")?;
+        cache.delete("other")?;
+        assert_eq!(manager.retrieve_batch(&["other".to_string()]).await?, vec![None]);
+
+        Ok(())
+    }
+}
\ No newline at end of file
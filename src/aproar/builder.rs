@@ -0,0 +1,434 @@
+// src/aproar/builder.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[APROAR]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use crate::aproar::{AproarConfig, AproarManager, CompressionPolicy, Namespace, NtmDimensions, RetentionPolicy, RetentionRule, WriteJournal};
+use crate::aproar::tiering::{AccessTracker, PlacementIndex, TierMigrator};
+use crate::aproar::compression::{AdaptiveSelector, CompressionManager};
+use crate::aproar::dictionary::DictionaryTrainer;
+use crate::aproar::expiry::ExpiryReaper;
+use crate::aproar::memory::{ContextWindowManager, MemoryConsolidator, SimpleAveragingStrategy};
+use crate::aproar::ntm::{NTM, NTMConfig};
+use crate::aproar::retrieval::RetrievalCache;
+#[cfg(feature = "rocksdb-storage")]
+use crate::aproar::retrieval::RocksDBJournal;
+use crate::aproar::storage::StorageBackend;
+use crate::omnixelerator::ResourceMonitor;
+use crate::security::AESEncryption;
+use crate::omnixtracker::{OmniXMetry, OmniXError};
+use crate::constants::*;
+use dashmap::DashMap;
+use parking_lot::RwLock;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize};
+use std::time::Duration;
+use tracing::warn;
+
+/// Assembles an `AproarManager` from whatever storage tiers and cache layers
+/// the caller hands it. Tiers are ordered hottest first, the same order
+/// `select_storage_backend` walks them in.
+pub struct AproarManagerBuilder {
+    metrics: OmniXMetry,
+    storage_backends: Vec<Arc<dyn StorageBackend>>,
+    retrieval_caches: Vec<Arc<dyn RetrievalCache>>,
+    tier_thresholds: Vec<usize>,
+    replicas: usize,
+    compression_policy: CompressionPolicy,
+    ntm: NtmDimensions,
+    retention_policies: Vec<(Namespace, RetentionPolicy)>,
+    ttl_policies: Vec<(Namespace, Duration)>,
+    retention_rules: Vec<RetentionRule>,
+    encryption: Option<Arc<AESEncryption>>,
+    journal: Option<Arc<dyn WriteJournal>>,
+    max_concurrent_tasks: usize,
+    background_tasks: bool,
+}
+
+impl AproarManagerBuilder {
+    pub fn new(metrics: OmniXMetry) -> Self {
+        Self {
+            metrics,
+            storage_backends: Vec::new(),
+            retrieval_caches: Vec::new(),
+            tier_thresholds: vec![HIGH_FREQUENCY_THRESHOLD, MEDIUM_FREQUENCY_THRESHOLD],
+            replicas: 1,
+            compression_policy: CompressionPolicy::default(),
+            ntm: NtmDimensions::default(),
+            retention_policies: Vec::new(),
+            ttl_policies: Vec::new(),
+            retention_rules: Vec::new(),
+            encryption: None,
+            journal: None,
+            max_concurrent_tasks: DEFAULT_MAX_CONCURRENT_TASKS,
+            background_tasks: true,
+        }
+    }
+
+    /// Opens every tier and cache declared in `config` and carries over its
+    /// thresholds, replica count, compression policy, NTM dimensions,
+    /// retention rules and master keys.
+    pub fn from_config(metrics: OmniXMetry, config: &AproarConfig) -> Result<Self, OmniXError> {
+        config.validate()?;
+        let mut builder = Self::new(metrics)
+            .tier_thresholds(config.tier_thresholds.clone())
+            .replicas(config.replicas)
+            .compression_policy(config.compression.clone())
+            .ntm_dimensions(config.ntm.clone())
+            .max_concurrent_tasks(config.max_concurrent_tasks);
+        for rule in &config.retention_rules {
+            builder = builder.retention_rule(rule.clone());
+        }
+        if let Some(encryption) = &config.encryption {
+            builder = builder.encryption(encryption.open()?);
+        }
+
+        for tier in &config.storage_tiers {
+            let backend = tier.open(&builder.metrics)?;
+            builder = builder.storage_backend(backend);
+        }
+        for cache in &config.caches {
+            let cache = cache.open(&builder.metrics)?;
+            builder = builder.retrieval_cache(cache);
+        }
+        #[cfg(feature = "rocksdb-storage")]
+        if let Some(journal_path) = &config.journal_path {
+            let journal = RocksDBJournal::open(journal_path, builder.metrics.clone())?;
+            builder = builder.journal(Arc::new(journal));
+        }
+        Ok(builder)
+    }
+
+    pub fn storage_backend(mut self, backend: Arc<dyn StorageBackend>) -> Self {
+        self.storage_backends.push(backend);
+        self
+    }
+
+    pub fn retrieval_cache(mut self, cache: Arc<dyn RetrievalCache>) -> Self {
+        self.retrieval_caches.push(cache);
+        self
+    }
+
+    pub fn tier_thresholds(mut self, tier_thresholds: Vec<usize>) -> Self {
+        self.tier_thresholds = tier_thresholds;
+        self
+    }
+
+    /// Keeps `replicas` copies of every key on distinct tiers (see
+    /// `replica_tiers`). Must be between 1 and the number of tiers.
+    pub fn replicas(mut self, replicas: usize) -> Self {
+        self.replicas = replicas;
+        self
+    }
+
+    pub fn compression_policy(mut self, compression_policy: CompressionPolicy) -> Self {
+        self.compression_policy = compression_policy;
+        self
+    }
+
+    pub fn ntm_dimensions(mut self, ntm: NtmDimensions) -> Self {
+        self.ntm = ntm;
+        self
+    }
+
+    /// Turns on versioning for `namespace` and everything below it.
+    pub fn retention(mut self, namespace: Namespace, policy: RetentionPolicy) -> Self {
+        self.retention_policies.push((namespace, policy));
+        self
+    }
+
+    /// Expires keys written to `namespace` and everything below it `ttl`
+    /// after each write.
+    pub fn ttl(mut self, namespace: Namespace, ttl: Duration) -> Self {
+        self.ttl_policies.push((namespace, ttl));
+        self
+    }
+
+    /// Adds a rule for the background reaper to enforce on every pass.
+    pub fn retention_rule(mut self, rule: RetentionRule) -> Self {
+        self.retention_rules.push(rule);
+        self
+    }
+
+    /// Encrypts every value after compression, before it reaches a tier or
+    /// cache.
+    pub fn encryption(mut self, encryption: Arc<AESEncryption>) -> Self {
+        self.encryption = Some(encryption);
+        self
+    }
+
+    /// Journals every write so `build` can repair writes a crash cut short.
+    pub fn journal(mut self, journal: Arc<dyn WriteJournal>) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    pub fn max_concurrent_tasks(mut self, max_concurrent_tasks: usize) -> Self {
+        self.max_concurrent_tasks = max_concurrent_tasks;
+        self
+    }
+
+    /// Skips spawning the resource-monitoring and metrics loops, which keeps
+    /// short-lived managers in tests from leaving tasks behind.
+    pub fn background_tasks(mut self, enabled: bool) -> Self {
+        self.background_tasks = enabled;
+        self
+    }
+
+    pub fn build(self) -> Result<AproarManager, OmniXError> {
+        if self.storage_backends.is_empty() {
+            return Err(OmniXError::ValidationError(
+                "AproarManager needs at least one storage backend".to_string(),
+            ));
+        }
+        if self.replicas == 0 || self.replicas > self.storage_backends.len() {
+            return Err(OmniXError::ValidationError(format!(
+                "Replica count must be between 1 and the {} configured storage backends, got {}",
+                self.storage_backends.len(),
+                self.replicas
+            )));
+        }
+        for rule in &self.retention_rules {
+            if let RetentionRule::MaxBytesPerTier { tier, .. } = rule {
+                if *tier >= self.storage_backends.len() {
+                    return Err(OmniXError::ValidationError(format!(
+                        "Retention rule targets tier {}, but only {} storage backends are configured",
+                        tier,
+                        self.storage_backends.len()
+                    )));
+                }
+            }
+        }
+
+        let metrics = self.metrics;
+        let ntm_config = NTMConfig {
+            input_size: self.ntm.input_size,
+            output_size: self.ntm.output_size,
+            memory_size: self.ntm.memory_size,
+            memory_vector_size: self.ntm.memory_vector_size,
+            controller_size: self.ntm.controller_size,
+        };
+
+        let ntm = NTM::new(
+            self.ntm.input_size,
+            self.ntm.output_size,
+            self.ntm.memory_size,
+            self.ntm.memory_vector_size,
+            self.ntm.controller_size,
+            &ntm_config,
+        ).map_err(|e| OmniXError::InitializationError(format!("Failed to initialize NTM: {}", e)))?;
+
+        let context_window_manager = Arc::new(ContextWindowManager::new(self.ntm.context_window_size, metrics.clone()));
+        let memory_consolidator = Arc::new(MemoryConsolidator::new(Box::new(SimpleAveragingStrategy), metrics.clone()));
+        let compression_manager = CompressionManager::new(metrics.clone());
+        let compression_selector = self
+            .compression_policy
+            .adaptive
+            .map(|goal| Arc::new(AdaptiveSelector::new(goal, metrics.clone())));
+        let dictionary_trainer = Arc::new(DictionaryTrainer::new(
+            self.storage_backends.clone(),
+            self.encryption.clone(),
+            compression_manager.clone(),
+            self.compression_policy.dictionary.clone().unwrap_or_default(),
+            metrics.clone(),
+        ));
+        let access_tracker = Arc::new(AccessTracker::new());
+        let placement_index = Arc::new(PlacementIndex::new());
+        let tier_migrator = Arc::new(TierMigrator::new(
+            self.storage_backends.clone(),
+            self.tier_thresholds.clone(),
+            self.replicas,
+            access_tracker.clone(),
+            placement_index.clone(),
+            metrics.clone(),
+            Duration::from_secs(TIER_IDLE_DEMOTION_SECS),
+            TIER_ACCESS_DECAY,
+            TIER_MAX_MOVES_PER_PASS,
+        ));
+        let expiries = Arc::new(DashMap::new());
+        let content_refs_stale = Arc::new(AtomicBool::new(false));
+        let expiry_reaper = Arc::new(ExpiryReaper::new(
+            self.storage_backends.clone(),
+            self.retrieval_caches.clone(),
+            placement_index.clone(),
+            access_tracker.clone(),
+            tier_migrator.clone(),
+            self.journal.clone(),
+            expiries.clone(),
+            content_refs_stale.clone(),
+            self.retention_rules,
+            metrics.clone(),
+        ));
+
+        let manager = AproarManager {
+            ntm: Arc::new(RwLock::new(ntm)),
+            context_window_manager,
+            memory_consolidator,
+            compression_manager,
+            compression_policy: self.compression_policy,
+            compression_selector,
+            dictionary_trainer,
+            storage_backends: self.storage_backends,
+            tier_thresholds: self.tier_thresholds,
+            replicas: self.replicas,
+            retrieval_caches: self.retrieval_caches,
+            access_tracker,
+            placement_index,
+            tier_migrator,
+            retention_policies: self.retention_policies.into_iter().collect(),
+            versioning_locks: DashMap::new(),
+            content_refs: tokio::sync::Mutex::new(None),
+            content_refs_stale,
+            ttl_policies: self.ttl_policies.into_iter().collect(),
+            expiries,
+            expiry_reaper,
+            encryption: self.encryption,
+            journal: self.journal,
+            metrics,
+            tasks: Arc::new(DashMap::new()),
+            resource_monitor: Arc::new(RwLock::new(ResourceMonitor::default())),
+            max_concurrent_tasks: self.max_concurrent_tasks,
+            current_task_count: Arc::new(AtomicUsize::new(0)),
+        };
+
+        // Values written with a dictionary are unreadable until it is loaded.
+        manager.load_dictionaries()?;
+        let replayed = manager.recover_from_journal()?;
+        if replayed > 0 {
+            warn!("Repaired {} interrupted writes from the journal", replayed);
+        }
+        manager.load_expiries()?;
+
+        if self.background_tasks {
+            manager.start_resource_monitoring();
+            manager.start_metrics_collection();
+            manager.start_tier_migration();
+            manager.start_expiry_reaper();
+            manager.start_dictionary_training();
+        }
+        Ok(manager)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aproar::compression::{CodecKind, Envelope};
+    use crate::aproar::retrieval::MemoryCache;
+    use crate::aproar::storage::{FsStorage, MemoryStorage};
+    use tempfile::tempdir;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_in_memory_manager_round_trip() -> Result<(), OmniXError> {
+        let storage = Arc::new(MemoryStorage::new());
+        let cache = Arc::new(MemoryCache::new());
+        let manager = AproarManagerBuilder::new(OmniXMetry::init()?)
+            .storage_backend(storage.clone())
+            .retrieval_cache(cache.clone())
+            .background_tasks(false)
+            .build()?;
+
+        manager.store_data("chunk", b"in-process context", 0).await?;
+        assert!(storage.exists("chunk")?);
+        assert!(cache.get("chunk")?.is_some());
+        assert_eq!(manager.retrieve_data("chunk").await?, b"in-process context");
+
+        manager.delete_data("chunk").await?;
+        assert!(!manager.data_exists("chunk").await?);
+        assert_eq!(cache.get("chunk")?, None);
+
+        Ok(())
+    }
+
+    /// Waits on its own runtime in every call, as `RedisCache` used to, so
+    /// any call made on a runtime worker panics.
+    struct BlockingCache {
+        inner: MemoryCache,
+        runtime: Option<tokio::runtime::Runtime>,
+    }
+
+    impl BlockingCache {
+        fn wait<T>(&self, call: impl FnOnce(&MemoryCache) -> T) -> T {
+            let runtime = self.runtime.as_ref().expect("the runtime is only taken on drop");
+            runtime.block_on(async { call(&self.inner) })
+        }
+    }
+
+    impl Drop for BlockingCache {
+        fn drop(&mut self) {
+            if let Some(runtime) = self.runtime.take() {
+                runtime.shutdown_background();
+            }
+        }
+    }
+
+    impl RetrievalCache for BlockingCache {
+        fn get(&self, key: &str) -> Result<Option<Vec<u8>>, OmniXError> {
+            self.wait(|cache| cache.get(key))
+        }
+
+        fn set(&self, key: &str, value: &[u8]) -> Result<(), OmniXError> {
+            self.wait(|cache| cache.set(key, value))
+        }
+
+        fn delete(&self, key: &str) -> Result<(), OmniXError> {
+            self.wait(|cache| cache.delete(key))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blocking_caches_stay_off_the_runtime() -> Result<(), OmniXError> {
+        let runtime = tokio::runtime::Builder::new_current_thread().build().map_err(|e| OmniXError::OperationFailed {
+            operation: "Creating Tokio runtime".to_string(),
+            details: e.to_string(),
+        })?;
+        let cache = Arc::new(BlockingCache { inner: MemoryCache::new(), runtime: Some(runtime) });
+        let manager = AproarManagerBuilder::new(OmniXMetry::init()?)
+            .storage_backend(Arc::new(MemoryStorage::new()))
+            .retrieval_cache(cache.clone())
+            .background_tasks(false)
+            .build()?;
+
+        manager.store_data("chunk", b"cached context", 0).await?;
+        assert!(cache.inner.get("chunk")?.is_some());
+        assert_eq!(manager.retrieve_data("chunk").await?, b"cached context");
+
+        let entries = vec![("a".to_string(), b"first".to_vec()), ("b".to_string(), b"second".to_vec())];
+        manager.store_batch(&entries, 0).await?;
+        let keys = vec!["a".to_string(), "b".to_string(), "missing".to_string()];
+        assert_eq!(manager.retrieve_batch(&keys).await?, vec![Some(b"first".to_vec()), Some(b"second".to_vec()), None]);
+
+        manager.delete_data("chunk").await?;
+        assert_eq!(cache.inner.get("chunk")?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_streamed_artifact_round_trip() -> Result<(), OmniXError> {
+        let dir = tempdir().unwrap();
+        let metrics = OmniXMetry::init()?;
+        let manager = AproarManagerBuilder::new(metrics.clone())
+            .storage_backend(Arc::new(FsStorage::new(dir.path().to_path_buf(), metrics)?))
+            .background_tasks(false)
+            .build()?;
+
+        let artifact: Vec<u8> = (0..8 * 1024 * 1024).map(|i| (i % 241) as u8).collect();
+        let written = manager
+            .store_stream("model/checkpoint", Box::new(std::io::Cursor::new(artifact.clone())), 0)
+            .await?;
+        assert!(written < artifact.len() as u64);
+
+        let mut streamed = Vec::new();
+        manager.retrieve_stream("model/checkpoint").await?.read_to_end(&mut streamed).await.unwrap();
+        assert_eq!(streamed, artifact);
+        assert_eq!(manager.retrieve_data("model/checkpoint").await?, artifact);
+
+        // Small streams get the codec `store_data` would pick, not Zstd.
+        let config = b"layers: 12, heads: 8\n".repeat(64);
+        manager.store_stream("model/config", Box::new(std::io::Cursor::new(config.clone())), 0).await?;
+        let stored = manager.storage_backends()[0].retrieve("model/config")?;
+        let body = crate::aproar::unframe("model/config", &stored)?;
+        assert_eq!(Envelope::parse(body)?.codec, CodecKind::Lz4);
+        assert_eq!(manager.retrieve_data("model/config").await?, config);
+
+        Ok(())
+    }
+}
\ No newline at end of file
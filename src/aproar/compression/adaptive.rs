@@ -0,0 +1,331 @@
+// src/aproar/compression/adaptive.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[COMPRESSION]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use super::{CodecKind, CompressionChoice, ENVELOPE_MAGIC, LZ4_FRAME_MAGIC, QUANTIZED_MAGIC};
+use crate::constants::{
+    ADAPTIVE_EXPLORE_INTERVAL, ADAPTIVE_MIN_PAYLOAD, ADAPTIVE_PRIOR_WEIGHT, ADAPTIVE_SAMPLE_WINDOW, ADAPTIVE_SAMPLE_WINDOWS,
+    ADAPTIVE_SKIP_ENTROPY, ADAPTIVE_ZSTD_LEVELS, ZSTD_COMPRESSION_LEVEL,
+};
+use crate::omnixtracker::OmniXMetry;
+use crate::security::ENCRYPTION_MAGIC;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+const MIB: f64 = 1024.0 * 1024.0;
+
+/// Leading bytes of formats that are already compressed or encrypted.
+const COMPRESSED_SIGNATURES: &[&[u8]] = &[
+    &[0x1F, 0x8B],
+    &[0x28, 0xB5, 0x2F, 0xFD],
+    &LZ4_FRAME_MAGIC,
+    b"PK\x03\x04",
+    b"\x89PNG",
+    &[0xFF, 0xD8, 0xFF],
+    &[0xFD, b'7', b'z', b'X', b'Z', 0x00],
+    b"BZh",
+    &[b'7', b'z', 0xBC, 0xAF, 0x27, 0x1C],
+    &ENVELOPE_MAGIC,
+    &ENCRYPTION_MAGIC,
+    &QUANTIZED_MAGIC,
+];
+
+/// What the adaptive selector optimises for. Of the codecs expected to
+/// compress and decompress at `min_throughput` or faster, it picks the one
+/// expected to save the most space.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompressionGoal {
+    /// Combined compression and decompression throughput in MiB/s. Lower
+    /// values trade latency for ratio.
+    pub min_throughput: f64,
+    /// Payloads expected to shrink by less than this fraction are stored
+    /// uncompressed.
+    pub min_savings: f64,
+}
+
+impl CompressionGoal {
+    pub fn latency() -> Self {
+        Self { min_throughput: 200.0, min_savings: 0.10 }
+    }
+
+    pub fn balanced() -> Self {
+        Self { min_throughput: 32.0, min_savings: 0.05 }
+    }
+
+    pub fn ratio() -> Self {
+        Self { min_throughput: 0.0, min_savings: 0.02 }
+    }
+}
+
+impl Default for CompressionGoal {
+    fn default() -> Self {
+        Self::balanced()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    /// Shannon entropy of the sampled bytes, in bits per byte.
+    pub entropy: f64,
+    /// The payload starts with the signature of a compressed or encrypted format.
+    pub precompressed: bool,
+}
+
+/// Estimates how compressible `data` is from `ADAPTIVE_SAMPLE_WINDOWS`
+/// windows spread across it, so large payloads cost the same to sample as
+/// small ones.
+pub fn sample(data: &[u8]) -> Sample {
+    let mut counts = [0u64; 256];
+    let sampled = ADAPTIVE_SAMPLE_WINDOW * ADAPTIVE_SAMPLE_WINDOWS;
+    if data.len() <= sampled {
+        data.iter().for_each(|&byte| counts[byte as usize] += 1);
+    } else {
+        let stride = (data.len() - ADAPTIVE_SAMPLE_WINDOW) / (ADAPTIVE_SAMPLE_WINDOWS - 1).max(1);
+        for window in 0..ADAPTIVE_SAMPLE_WINDOWS {
+            let start = window * stride;
+            data[start..start + ADAPTIVE_SAMPLE_WINDOW].iter().for_each(|&byte| counts[byte as usize] += 1);
+        }
+    }
+
+    let total = counts.iter().sum::<u64>() as f64;
+    let entropy = counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / total;
+            -p * p.log2()
+        })
+        .sum();
+    let precompressed = COMPRESSED_SIGNATURES.iter().any(|signature| data.starts_with(signature));
+    Sample { entropy, precompressed }
+}
+
+/// Expected result of compressing a sampled payload with one choice.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    /// Fraction of the payload saved.
+    pub savings: f64,
+    /// Combined compression and decompression throughput in MiB/s.
+    pub throughput: f64,
+}
+
+/// The choice made for one payload, kept so the outcome can be recorded
+/// against the sample that led to it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Selection {
+    pub choice: CompressionChoice,
+    pub sample: Sample,
+}
+
+/// Picks a codec and level per payload from its sample and the goal.
+/// Estimates start from built-in figures per codec and move toward the
+/// throughput and savings recorded in OmniXMetry as payloads are compressed.
+///
+/// Savings are tracked as an efficiency: the fraction of the order-0
+/// entropy bound (`1 - entropy / 8`) a codec achieves. That lets one figure
+/// per codec carry over between payloads of different entropy.
+pub struct AdaptiveSelector {
+    goal: CompressionGoal,
+    candidates: Vec<CompressionChoice>,
+    metrics: OmniXMetry,
+    selections: AtomicU64,
+}
+
+impl AdaptiveSelector {
+    pub fn new(goal: CompressionGoal, metrics: OmniXMetry) -> Self {
+        let candidates = std::iter::once(CompressionChoice::from(CodecKind::Lz4))
+            .chain(ADAPTIVE_ZSTD_LEVELS.iter().map(|&level| CompressionChoice::zstd(level)))
+            .collect();
+        Self { goal, candidates, metrics, selections: AtomicU64::new(0) }
+    }
+
+    pub fn goal(&self) -> CompressionGoal {
+        self.goal
+    }
+
+    pub fn select(&self, data: &[u8]) -> Selection {
+        let sample = sample(data);
+        let choice = self.choose(data.len(), &sample);
+        self.metrics.increment_counter(format!("compression.adaptive.{}", choice.label()), 1);
+        Selection { choice, sample }
+    }
+
+    pub fn estimate(&self, choice: CompressionChoice, sample: &Sample) -> Estimate {
+        if choice.codec == CodecKind::Uncompressed {
+            return Estimate { savings: 0.0, throughput: f64::INFINITY };
+        }
+        let prior = Prior::of(choice);
+        let label = choice.label();
+        let efficiency = self.learned(&format!("compression.{}.efficiency", label), prior.efficiency);
+        let compress = self.learned(&format!("compression.{}.throughput", label), prior.compress);
+        let decompress = self.learned(&format!("decompression.{}.throughput", choice.codec.name()), prior.decompress);
+
+        let bound = (1.0 - sample.entropy / 8.0).max(0.0);
+        Estimate {
+            savings: (efficiency * bound).clamp(0.0, 0.99),
+            throughput: 1.0 / (1.0 / compress + 1.0 / decompress),
+        }
+    }
+
+    fn choose(&self, len: usize, sample: &Sample) -> CompressionChoice {
+        let stored = CompressionChoice::from(CodecKind::Uncompressed);
+        if len < ADAPTIVE_MIN_PAYLOAD || sample.precompressed || sample.entropy >= ADAPTIVE_SKIP_ENTROPY {
+            return stored;
+        }
+
+        let estimates: Vec<(CompressionChoice, Estimate)> =
+            self.candidates.iter().map(|&choice| (choice, self.estimate(choice, sample))).collect();
+        let mut eligible: Vec<(CompressionChoice, Estimate)> =
+            estimates.iter().copied().filter(|(_, estimate)| estimate.throughput >= self.goal.min_throughput).collect();
+        if eligible.is_empty() {
+            eligible.extend(estimates.into_iter().max_by(|a, b| a.1.throughput.total_cmp(&b.1.throughput)));
+        }
+        eligible.sort_by(|a, b| b.1.savings.total_cmp(&a.1.savings).then(b.1.throughput.total_cmp(&a.1.throughput)));
+
+        // Now and then the runner-up is used, so a codec that has fallen
+        // behind on old measurements gets the chance to catch up.
+        let explore = self.selections.fetch_add(1, Ordering::Relaxed) % ADAPTIVE_EXPLORE_INTERVAL
+            == ADAPTIVE_EXPLORE_INTERVAL - 1;
+        let (choice, estimate) = if explore && eligible.len() > 1 { eligible[1] } else { eligible[0] };
+        if estimate.savings < self.goal.min_savings {
+            stored
+        } else {
+            choice
+        }
+    }
+
+    /// `prior` blended with the recent values of `histogram`, with the prior
+    /// counting as `ADAPTIVE_PRIOR_WEIGHT` observations.
+    fn learned(&self, histogram: &str, prior: f64) -> f64 {
+        let observed = self.metrics.histogram_values(histogram);
+        (prior * ADAPTIVE_PRIOR_WEIGHT + observed.iter().sum::<f64>()) / (ADAPTIVE_PRIOR_WEIGHT + observed.len() as f64)
+    }
+}
+
+impl Selection {
+    /// Records how the choice did on this payload. Throughput is skipped for
+    /// payloads too small for their timing to mean much.
+    pub(crate) fn record(&self, metrics: &OmniXMetry, original_len: usize, compressed_len: usize, elapsed: Duration) {
+        if self.choice.codec == CodecKind::Uncompressed || original_len == 0 {
+            return;
+        }
+        let label = self.choice.label();
+        if original_len >= ADAPTIVE_SAMPLE_WINDOW && elapsed > Duration::ZERO {
+            metrics.record_histogram(format!("compression.{}.throughput", label), original_len as f64 / MIB / elapsed.as_secs_f64());
+        }
+        metrics.record_histogram(format!("compression.{}.ratio", label), original_len as f64 / compressed_len.max(1) as f64);
+
+        let bound = 1.0 - self.sample.entropy / 8.0;
+        if bound > 0.05 {
+            let savings = 1.0 - compressed_len as f64 / original_len as f64;
+            metrics.record_histogram(format!("compression.{}.efficiency", label), savings / bound);
+        }
+    }
+}
+
+/// Records decompression throughput per codec. Every Zstd level decodes at
+/// much the same speed, so levels share one figure.
+pub(crate) fn record_decompression(metrics: &OmniXMetry, codec: CodecKind, original_len: usize, elapsed: Duration) {
+    if codec != CodecKind::Uncompressed && original_len >= ADAPTIVE_SAMPLE_WINDOW && elapsed > Duration::ZERO {
+        metrics.record_histogram(
+            format!("decompression.{}.throughput", codec.name()),
+            original_len as f64 / MIB / elapsed.as_secs_f64(),
+        );
+    }
+}
+
+/// Rough single-core figures used until measurements come in.
+struct Prior {
+    efficiency: f64,
+    compress: f64,
+    decompress: f64,
+}
+
+impl Prior {
+    fn of(choice: CompressionChoice) -> Self {
+        match choice.codec {
+            CodecKind::Uncompressed => Self { efficiency: 0.0, compress: f64::INFINITY, decompress: f64::INFINITY },
+            CodecKind::Lz4 => Self { efficiency: 0.8, compress: 40.0, decompress: 2500.0 },
+            CodecKind::Zstd | CodecKind::ZstdDict => {
+                let level = choice.level.unwrap_or(ZSTD_COMPRESSION_LEVEL).max(1) as f64;
+                Self { efficiency: 0.85 + 0.02 * level, compress: 400.0 / level.powf(1.5), decompress: 900.0 }
+            }
+            CodecKind::Snappy => Self { efficiency: 0.6, compress: 400.0, decompress: 1200.0 },
+            CodecKind::Gzip | CodecKind::Deflate => Self { efficiency: 0.9, compress: 25.0, decompress: 300.0 },
+            CodecKind::Brotli => Self { efficiency: 1.0, compress: 15.0, decompress: 350.0 },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aproar::compression::CompressionManager;
+    use crate::omnixtracker::OmniXError;
+
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn text(len: usize) -> Vec<u8> {
+        b"context window entry 42: the controller attends to memory row 7. ".iter().copied().cycle().take(len).collect()
+    }
+
+    #[test]
+    fn test_incompressible_payloads_are_stored() -> Result<(), OmniXError> {
+        let selector = AdaptiveSelector::new(CompressionGoal::ratio(), OmniXMetry::init()?);
+        let stored = CompressionChoice::from(CodecKind::Uncompressed);
+
+        let random = noise(256 * 1024);
+        assert!(sample(&random).entropy > ADAPTIVE_SKIP_ENTROPY);
+        assert_eq!(selector.select(&random).choice, stored);
+
+        let mut gzipped = vec![0x1F, 0x8B, 0x08];
+        gzipped.extend(text(8192));
+        assert!(sample(&gzipped).precompressed);
+        assert_eq!(selector.select(&gzipped).choice, stored);
+        assert_eq!(selector.select(b"tiny").choice, stored);
+        assert_ne!(selector.select(&text(8192)).choice, stored);
+
+        let manager = CompressionManager::new(OmniXMetry::init()?);
+        for payload in [random, text(64 * 1024)] {
+            let sealed = manager.seal_adaptive(&selector, &payload)?;
+            assert_eq!(manager.unseal(&sealed)?, payload);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_goal_and_measurements_steer_the_choice() -> Result<(), OmniXError> {
+        let metrics = OmniXMetry::init()?;
+        let payload = text(64 * 1024);
+        let fast = AdaptiveSelector::new(CompressionGoal::latency(), metrics.clone());
+        let small = AdaptiveSelector::new(CompressionGoal::ratio(), metrics.clone());
+
+        let fast_choice = fast.select(&payload).choice;
+        let small_choice = small.select(&payload).choice;
+        let sampled = sample(&payload);
+        assert_eq!(small_choice, CompressionChoice::zstd(19));
+        assert!(fast.estimate(fast_choice, &sampled).throughput > fast.estimate(small_choice, &sampled).throughput);
+
+        let manager = CompressionManager::new(metrics.clone());
+        let sealed = manager.seal_adaptive(&small, &payload)?;
+        assert_eq!(manager.unseal(&sealed)?, payload);
+        assert_eq!(metrics.histogram_values("compression.zstd19.ratio").len(), 1);
+
+        // Measurements showing level 19 to be fast make it the latency pick too.
+        for _ in 0..32 {
+            metrics.record_histogram("compression.zstd19.throughput".to_string(), 5000.0);
+        }
+        assert_eq!(fast.select(&payload).choice, CompressionChoice::zstd(19));
+        Ok(())
+    }
+}
\ No newline at end of file
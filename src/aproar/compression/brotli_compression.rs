@@ -0,0 +1,79 @@
+// src/aproar/compression/brotli_compression.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[COMPRESSION]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use super::{CompressionStrategy, OmniXError, StreamEncoder};
+use crate::constants::{BROTLI_BUFFER_SIZE, BROTLI_QUALITY, BROTLI_WINDOW_BITS};
+use brotli::{CompressorWriter, Decompressor};
+use std::io::{self, Read, Write};
+
+/// Brotli (RFC 7932), as served with `Content-Encoding: br`.
+pub struct BrotliCompression;
+
+/// Brotli at a chosen quality (0-11). Any quality decodes the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BrotliLevel(pub u32);
+
+impl BrotliLevel {
+    fn writer<W: Write>(&self, writer: W) -> CompressorWriter<W> {
+        CompressorWriter::new(writer, BROTLI_BUFFER_SIZE, self.0, BROTLI_WINDOW_BITS)
+    }
+}
+
+impl CompressionStrategy for BrotliCompression {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        BrotliLevel(BROTLI_QUALITY).compress(data)
+    }
+
+    fn decompress(&self, compressed_data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        BrotliLevel(BROTLI_QUALITY).decompress(compressed_data)
+    }
+
+    fn encoder<'a>(&self, writer: Box<dyn Write + 'a>, threads: usize) -> Result<Box<dyn StreamEncoder + 'a>, OmniXError> {
+        BrotliLevel(BROTLI_QUALITY).encoder(writer, threads)
+    }
+
+    fn decoder<'a>(&self, reader: Box<dyn Read + 'a>) -> Result<Box<dyn Read + 'a>, OmniXError> {
+        BrotliLevel(BROTLI_QUALITY).decoder(reader)
+    }
+}
+
+impl CompressionStrategy for BrotliLevel {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        let mut writer = self.writer(Vec::with_capacity(data.len() / 2));
+        writer.write_all(data).map_err(|e| brotli_error("Brotli compression", e))?;
+        Ok(writer.into_inner())
+    }
+
+    fn decompress(&self, compressed_data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        let mut decompressed = Vec::with_capacity(compressed_data.len() * 3);
+        Decompressor::new(compressed_data, BROTLI_BUFFER_SIZE)
+            .read_to_end(&mut decompressed)
+            .map_err(|e| brotli_error("Brotli decompression", e))?;
+        Ok(decompressed)
+    }
+
+    /// The Brotli encoder is single-threaded, so `threads` is ignored.
+    fn encoder<'a>(&self, writer: Box<dyn Write + 'a>, _threads: usize) -> Result<Box<dyn StreamEncoder + 'a>, OmniXError> {
+        Ok(Box::new(self.writer(writer)))
+    }
+
+    fn decoder<'a>(&self, reader: Box<dyn Read + 'a>) -> Result<Box<dyn Read + 'a>, OmniXError> {
+        Ok(Box::new(Decompressor::new(reader, BROTLI_BUFFER_SIZE)))
+    }
+}
+
+impl<W: Write> StreamEncoder for CompressorWriter<W> {
+    fn finish(mut self: Box<Self>) -> Result<(), OmniXError> {
+        // `into_inner` writes the end of the stream but swallows write
+        // errors, so flush first to surface them.
+        self.flush().map_err(|e| brotli_error("Brotli compression", e))?;
+        let mut writer = (*self).into_inner();
+        writer.flush().map_err(|e| brotli_error("Brotli compression", e))
+    }
+}
+
+fn brotli_error(operation: &str, e: io::Error) -> OmniXError {
+    OmniXError::OperationFailed {
+        operation: operation.to_string(),
+        details: e.to_string(),
+    }
+}
\ No newline at end of file
@@ -0,0 +1,191 @@
+// src/aproar/compression/envelope.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[COMPRESSION]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use super::{CodecKind, OmniXError};
+
+/// Marks a payload as enveloped. Neither LZ4 blocks nor Zstd frames start
+/// with these bytes in practice, and Zstd frames are told apart by their own
+/// magic below.
+pub const ENVELOPE_MAGIC: [u8; 4] = *b"APRX";
+pub const ENVELOPE_VERSION: u8 = 1;
+/// magic (4) + version (1) + codec id (1) + original length (8) + CRC32 (4)
+pub const ENVELOPE_HEADER_LEN: usize = 18;
+/// Streamed payloads do not know their length or checksum up front, so their
+/// header stops after the codec id and integrity comes from the codec's own
+/// frame checksum instead.
+pub const STREAM_ENVELOPE_VERSION: u8 = 2;
+/// magic (4) + version (1) + codec id (1)
+pub const STREAM_ENVELOPE_HEADER_LEN: usize = 6;
+
+const ZSTD_FRAME_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EnvelopeHeader {
+    pub version: u8,
+    pub codec: CodecKind,
+    pub original_len: Option<u64>,
+    pub checksum: Option<u32>,
+}
+
+/// A stored payload split into its codec and compressed bytes. `header` is
+/// `None` for blobs written before the envelope existed; their codec is
+/// inferred from the payload itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Envelope<'a> {
+    pub header: Option<EnvelopeHeader>,
+    pub codec: CodecKind,
+    pub payload: &'a [u8],
+}
+
+impl<'a> Envelope<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, OmniXError> {
+        if !data.starts_with(&ENVELOPE_MAGIC) {
+            let codec = if data.starts_with(&ZSTD_FRAME_MAGIC) { CodecKind::Zstd } else { CodecKind::Lz4 };
+            return Ok(Self { header: None, codec, payload: data });
+        }
+
+        if data.len() < STREAM_ENVELOPE_HEADER_LEN {
+            return Err(envelope_error(format!("Truncated header: {} bytes", data.len())));
+        }
+        let version = data[4];
+        let codec = CodecKind::from_id(data[5])
+            .ok_or_else(|| envelope_error(format!("Unknown codec id {}", data[5])))?;
+
+        match version {
+            ENVELOPE_VERSION => {
+                if data.len() < ENVELOPE_HEADER_LEN {
+                    return Err(envelope_error(format!("Truncated header: {} bytes", data.len())));
+                }
+                let original_len = u64::from_le_bytes(data[6..14].try_into().expect("slice is 8 bytes"));
+                let checksum = u32::from_le_bytes(data[14..18].try_into().expect("slice is 4 bytes"));
+                Ok(Self {
+                    header: Some(EnvelopeHeader {
+                        version,
+                        codec,
+                        original_len: Some(original_len),
+                        checksum: Some(checksum),
+                    }),
+                    codec,
+                    payload: &data[ENVELOPE_HEADER_LEN..],
+                })
+            }
+            STREAM_ENVELOPE_VERSION => Ok(Self {
+                header: Some(EnvelopeHeader { version, codec, original_len: None, checksum: None }),
+                codec,
+                payload: &data[STREAM_ENVELOPE_HEADER_LEN..],
+            }),
+            _ => Err(envelope_error(format!("Unsupported envelope version {}", version))),
+        }
+    }
+
+    /// The number of leading bytes `parse` needs to see to decide on the
+    /// header, used by readers that only hold the start of a stream.
+    pub fn header_len(prefix: &[u8]) -> usize {
+        if !prefix.starts_with(&ENVELOPE_MAGIC) {
+            0
+        } else if prefix.get(4) == Some(&STREAM_ENVELOPE_VERSION) {
+            STREAM_ENVELOPE_HEADER_LEN
+        } else {
+            ENVELOPE_HEADER_LEN
+        }
+    }
+
+    /// Checks the decompressed bytes against the recorded length and CRC32.
+    /// Legacy and streamed blobs carry nothing to check against and always pass.
+    pub fn verify(&self, original: &[u8]) -> Result<(), OmniXError> {
+        let header = match &self.header {
+            Some(header) => header,
+            None => return Ok(()),
+        };
+        verify_decoded(header, original.len() as u64, || crc32fast::hash(original))
+    }
+}
+
+/// Prefixes `compressed` with an envelope header describing `original`.
+pub fn seal(codec: CodecKind, original: &[u8], compressed: &[u8]) -> Vec<u8> {
+    let mut sealed = Vec::with_capacity(ENVELOPE_HEADER_LEN + compressed.len());
+    sealed.extend_from_slice(&ENVELOPE_MAGIC);
+    sealed.push(ENVELOPE_VERSION);
+    sealed.push(codec.id());
+    sealed.extend_from_slice(&(original.len() as u64).to_le_bytes());
+    sealed.extend_from_slice(&crc32fast::hash(original).to_le_bytes());
+    sealed.extend_from_slice(compressed);
+    sealed
+}
+
+/// Header for a payload whose compressed bytes are streamed right after it.
+pub fn stream_header(codec: CodecKind) -> [u8; STREAM_ENVELOPE_HEADER_LEN] {
+    let mut header = [0u8; STREAM_ENVELOPE_HEADER_LEN];
+    header[..4].copy_from_slice(&ENVELOPE_MAGIC);
+    header[4] = STREAM_ENVELOPE_VERSION;
+    header[5] = codec.id();
+    header
+}
+
+/// Shared by `Envelope::verify` and readers that hash the output as it streams past.
+pub(crate) fn verify_decoded(header: &EnvelopeHeader, len: u64, checksum: impl FnOnce() -> u32) -> Result<(), OmniXError> {
+    if let Some(original_len) = header.original_len {
+        if len != original_len {
+            return Err(envelope_error(format!(
+                "Length mismatch: header says {} bytes, decoded {}",
+                original_len, len
+            )));
+        }
+    }
+    if let Some(expected) = header.checksum {
+        let actual = checksum();
+        if actual != expected {
+            return Err(envelope_error(format!(
+                "Checksum mismatch: expected {:08x}, got {:08x}",
+                expected, actual
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn envelope_error(details: String) -> OmniXError {
+    OmniXError::OperationFailed {
+        operation: "Envelope decoding".to_string(),
+        details,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_envelope_round_trips_header() -> Result<(), OmniXError> {
+        let sealed = seal(CodecKind::Zstd, b"original bytes", b"compressed");
+        let envelope = Envelope::parse(&sealed)?;
+
+        assert_eq!(envelope.codec, CodecKind::Zstd);
+        assert_eq!(envelope.payload, b"compressed");
+        assert_eq!(envelope.header.and_then(|header| header.original_len), Some(14));
+        envelope.verify(b"original bytes")?;
+        assert!(envelope.verify(b"original bytez").is_err());
+        assert!(Envelope::parse(&sealed[..10]).is_err());
+
+        let mut streamed = stream_header(CodecKind::Zstd).to_vec();
+        streamed.extend_from_slice(b"frame");
+        let envelope = Envelope::parse(&streamed)?;
+        assert_eq!(envelope.payload, b"frame");
+        assert_eq!(Envelope::header_len(&streamed), STREAM_ENVELOPE_HEADER_LEN);
+        envelope.verify(b"anything")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_legacy_payloads_are_detected() -> Result<(), OmniXError> {
+        let zstd = Envelope::parse(&[0x28, 0xB5, 0x2F, 0xFD, 0x00])?;
+        assert_eq!(zstd.codec, CodecKind::Zstd);
+        assert!(zstd.header.is_none());
+
+        let lz4 = Envelope::parse(b"\x1fplain lz4 block")?;
+        assert_eq!(lz4.codec, CodecKind::Lz4);
+        assert_eq!(lz4.payload, b"\x1fplain lz4 block");
+
+        Ok(())
+    }
+}
\ No newline at end of file
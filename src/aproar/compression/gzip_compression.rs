@@ -0,0 +1,131 @@
+// src/aproar/compression/gzip_compression.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[COMPRESSION]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use super::{CompressionStrategy, OmniXError, StreamEncoder};
+use crate::constants::GZIP_COMPRESSION_LEVEL;
+use flate2::read::{MultiGzDecoder, ZlibDecoder};
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+use std::io::{self, Read, Write};
+
+/// Gzip (RFC 1952), as served with `Content-Encoding: gzip`. Concatenated
+/// members decode as one payload, the way `gunzip` reads them.
+pub struct GzipCompression;
+
+/// Deflate in the zlib wrapper (RFC 1950), as served with
+/// `Content-Encoding: deflate`.
+pub struct DeflateCompression;
+
+/// Gzip at a chosen level (0-9). Any level decodes the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GzipLevel(pub u32);
+
+/// Deflate at a chosen level (0-9). Any level decodes the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeflateLevel(pub u32);
+
+impl CompressionStrategy for GzipCompression {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        GzipLevel(GZIP_COMPRESSION_LEVEL).compress(data)
+    }
+
+    fn decompress(&self, compressed_data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        GzipLevel(GZIP_COMPRESSION_LEVEL).decompress(compressed_data)
+    }
+
+    fn encoder<'a>(&self, writer: Box<dyn Write + 'a>, threads: usize) -> Result<Box<dyn StreamEncoder + 'a>, OmniXError> {
+        GzipLevel(GZIP_COMPRESSION_LEVEL).encoder(writer, threads)
+    }
+
+    fn decoder<'a>(&self, reader: Box<dyn Read + 'a>) -> Result<Box<dyn Read + 'a>, OmniXError> {
+        GzipLevel(GZIP_COMPRESSION_LEVEL).decoder(reader)
+    }
+}
+
+impl CompressionStrategy for DeflateCompression {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        DeflateLevel(GZIP_COMPRESSION_LEVEL).compress(data)
+    }
+
+    fn decompress(&self, compressed_data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        DeflateLevel(GZIP_COMPRESSION_LEVEL).decompress(compressed_data)
+    }
+
+    fn encoder<'a>(&self, writer: Box<dyn Write + 'a>, threads: usize) -> Result<Box<dyn StreamEncoder + 'a>, OmniXError> {
+        DeflateLevel(GZIP_COMPRESSION_LEVEL).encoder(writer, threads)
+    }
+
+    fn decoder<'a>(&self, reader: Box<dyn Read + 'a>) -> Result<Box<dyn Read + 'a>, OmniXError> {
+        DeflateLevel(GZIP_COMPRESSION_LEVEL).decoder(reader)
+    }
+}
+
+impl CompressionStrategy for GzipLevel {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        let mut encoder = GzEncoder::new(Vec::with_capacity(data.len() / 2), Compression::new(self.0));
+        encoder.write_all(data).map_err(|e| flate_error("Gzip compression", e))?;
+        encoder.finish().map_err(|e| flate_error("Gzip compression", e))
+    }
+
+    fn decompress(&self, compressed_data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        let mut decompressed = Vec::with_capacity(compressed_data.len() * 2);
+        MultiGzDecoder::new(compressed_data)
+            .read_to_end(&mut decompressed)
+            .map_err(|e| flate_error("Gzip decompression", e))?;
+        Ok(decompressed)
+    }
+
+    /// Deflate streams are sequential, so `threads` is ignored.
+    fn encoder<'a>(&self, writer: Box<dyn Write + 'a>, _threads: usize) -> Result<Box<dyn StreamEncoder + 'a>, OmniXError> {
+        Ok(Box::new(GzEncoder::new(writer, Compression::new(self.0))))
+    }
+
+    fn decoder<'a>(&self, reader: Box<dyn Read + 'a>) -> Result<Box<dyn Read + 'a>, OmniXError> {
+        Ok(Box::new(MultiGzDecoder::new(reader)))
+    }
+}
+
+impl CompressionStrategy for DeflateLevel {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        let mut encoder = ZlibEncoder::new(Vec::with_capacity(data.len() / 2), Compression::new(self.0));
+        encoder.write_all(data).map_err(|e| flate_error("Deflate compression", e))?;
+        encoder.finish().map_err(|e| flate_error("Deflate compression", e))
+    }
+
+    fn decompress(&self, compressed_data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        let mut decompressed = Vec::with_capacity(compressed_data.len() * 2);
+        ZlibDecoder::new(compressed_data)
+            .read_to_end(&mut decompressed)
+            .map_err(|e| flate_error("Deflate decompression", e))?;
+        Ok(decompressed)
+    }
+
+    /// Deflate streams are sequential, so `threads` is ignored.
+    fn encoder<'a>(&self, writer: Box<dyn Write + 'a>, _threads: usize) -> Result<Box<dyn StreamEncoder + 'a>, OmniXError> {
+        Ok(Box::new(ZlibEncoder::new(writer, Compression::new(self.0))))
+    }
+
+    fn decoder<'a>(&self, reader: Box<dyn Read + 'a>) -> Result<Box<dyn Read + 'a>, OmniXError> {
+        Ok(Box::new(ZlibDecoder::new(reader)))
+    }
+}
+
+impl<W: Write> StreamEncoder for GzEncoder<W> {
+    fn finish(self: Box<Self>) -> Result<(), OmniXError> {
+        let mut writer = GzEncoder::finish(*self).map_err(|e| flate_error("Gzip compression", e))?;
+        writer.flush().map_err(|e| flate_error("Gzip compression", e))
+    }
+}
+
+impl<W: Write> StreamEncoder for ZlibEncoder<W> {
+    fn finish(self: Box<Self>) -> Result<(), OmniXError> {
+        let mut writer = ZlibEncoder::finish(*self).map_err(|e| flate_error("Deflate compression", e))?;
+        writer.flush().map_err(|e| flate_error("Deflate compression", e))
+    }
+}
+
+fn flate_error(operation: &str, e: io::Error) -> OmniXError {
+    OmniXError::OperationFailed {
+        operation: operation.to_string(),
+        details: e.to_string(),
+    }
+}
\ No newline at end of file
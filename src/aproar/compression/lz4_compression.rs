@@ -1,87 +1,332 @@
 // src/aproar/compression/lz4_compression.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[COMPRESSION]Xyn>=====S===t===u===d===i===o===s======[R|$>
 
-use lz4::{block::compress, block::decompress, block::CompressionMode};
-use super::{CompressionStrategy, OmniXError};
+use lz4::{block::compress, block::decompress, block::CompressionMode, BlockSize, ContentChecksum, Decoder, Encoder, EncoderBuilder};
+use super::{compression_threads, CompressionStrategy, OmniXError, StreamEncoder};
+use crate::constants::{LZ4_COMPRESSION_LEVEL, PARALLEL_LZ4_BLOCKS_PER_THREAD};
 use anyhow::{Context, Result};
-use std::io::{Read, Write};
+use rayon::prelude::*;
+use std::io::{self, Read, Write};
+use xxhash_rust::xxh32::{xxh32, Xxh32};
 
 use std::fs::File;
 
+/// Every LZ4 frame starts with this, stored little-endian.
+pub const LZ4_FRAME_MAGIC: [u8; 4] = [0x04, 0x22, 0x4D, 0x18];
+const LZ4_BLOCK_SIZE: usize = 64 * 1024;
+/// Frame format version 01, independent blocks, content checksum.
+const LZ4_FRAME_FLAGS: u8 = 0b0110_0100;
+/// 64 KiB maximum block size.
+const LZ4_BLOCK_DESCRIPTOR: u8 = 4 << 4;
+/// Set in a block's size field when the block is stored uncompressed.
+const LZ4_UNCOMPRESSED_BLOCK: u32 = 1 << 31;
+/// Largest block the pre-frame `compress_data_with_lz4` wrote; raw blocks
+/// that decode to more than this are rejected as corrupt.
+const LZ4_LEGACY_BLOCK_SIZE: usize = 8 * 1024;
 
+/// LZ4 in the standard frame format: independent 64 KiB blocks, each with
+/// its compressed length up front, and a content checksum at the end. Any
+/// `lz4` command-line tool can read what this writes.
 pub struct LZ4Compression;
 
+/// LZ4 at a chosen frame level; 3 and above select LZ4-HC. The level only
+/// affects compression, so frames from every level share `CodecKind::Lz4`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lz4Level(pub u32);
+
+impl LZ4Compression {
+    /// Compresses everything written to it into `writer` as a single frame.
+    /// The frame is only complete once `finish` has been called.
+    pub fn writer<W: Write>(&self, writer: W) -> Result<Lz4FrameWriter<W>, OmniXError> {
+        Lz4Level(LZ4_COMPRESSION_LEVEL).writer(writer)
+    }
+
+    /// Decompresses the frame read from `reader`. A checksum mismatch or a
+    /// frame cut short fails the read that reaches the end of the input.
+    pub fn reader<R: Read>(&self, reader: R) -> Result<Lz4FrameReader<R>, OmniXError> {
+        let decoder = Decoder::new(reader).map_err(|e| lz4_error("LZ4 decompression", e))?;
+        Ok(Lz4FrameReader { decoder: Some(decoder), finished: None })
+    }
+
+    /// Like `writer`, but compresses batches of blocks on a pool of
+    /// `threads` rayon workers. The frame is the same standard format.
+    pub fn parallel_writer<W: Write>(&self, writer: W, threads: usize) -> Result<ParallelLz4Writer<W>, OmniXError> {
+        Lz4Level(LZ4_COMPRESSION_LEVEL).parallel_writer(writer, threads)
+    }
+}
+
+impl Lz4Level {
+    pub fn writer<W: Write>(&self, writer: W) -> Result<Lz4FrameWriter<W>, OmniXError> {
+        let encoder = EncoderBuilder::new()
+            .level(self.0)
+            .block_size(BlockSize::Max64KB)
+            .checksum(ContentChecksum::ChecksumEnabled)
+            .build(writer)
+            .map_err(|e| lz4_error("LZ4 compression", e))?;
+        Ok(Lz4FrameWriter { encoder })
+    }
+
+    pub fn parallel_writer<W: Write>(&self, writer: W, threads: usize) -> Result<ParallelLz4Writer<W>, OmniXError> {
+        ParallelLz4Writer::new(writer, threads, self.0)
+    }
+}
+
 impl CompressionStrategy for LZ4Compression {
     fn compress(&self, data: &[u8]) -> Result<Vec<u8>, OmniXError> {
-        compress(data, Some(CompressionMode::HIGHCOMPRESSION(9)), false)
-            .map_err(|e| OmniXError::OperationFailed {
-                operation: "LZ4 compression".to_string(),
-                details: e.to_string(),
-            })
+        Lz4Level(LZ4_COMPRESSION_LEVEL).compress(data)
     }
 
+    /// Also accepts the raw blocks, without a size prefix, written before
+    /// the frame format.
     fn decompress(&self, compressed_data: &[u8]) -> Result<Vec<u8>, OmniXError> {
-        decompress(compressed_data, None)
+        if !compressed_data.starts_with(&LZ4_FRAME_MAGIC) {
+            return decompress_raw_block(compressed_data);
+        }
+        let mut decompressed = Vec::with_capacity(compressed_data.len() * 2);
+        self.reader(compressed_data)?
+            .read_to_end(&mut decompressed)
+            .map_err(|e| lz4_error("LZ4 decompression", e))?;
+        Ok(decompressed)
+    }
+
+    fn encoder<'a>(&self, writer: Box<dyn Write + 'a>, threads: usize) -> Result<Box<dyn StreamEncoder + 'a>, OmniXError> {
+        Lz4Level(LZ4_COMPRESSION_LEVEL).encoder(writer, threads)
+    }
+
+    fn decoder<'a>(&self, reader: Box<dyn Read + 'a>) -> Result<Box<dyn Read + 'a>, OmniXError> {
+        Ok(Box::new(self.reader(reader)?))
+    }
+}
+
+impl CompressionStrategy for Lz4Level {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        let mut writer = self.writer(Vec::with_capacity(data.len() / 2))?;
+        writer.write_all(data).map_err(|e| lz4_error("LZ4 compression", e))?;
+        writer.finish()
+    }
+
+    fn decompress(&self, compressed_data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        LZ4Compression.decompress(compressed_data)
+    }
+
+    fn encoder<'a>(&self, writer: Box<dyn Write + 'a>, threads: usize) -> Result<Box<dyn StreamEncoder + 'a>, OmniXError> {
+        if threads > 1 {
+            Ok(Box::new(self.parallel_writer(writer, threads)?))
+        } else {
+            Ok(Box::new(self.writer(writer)?))
+        }
+    }
+
+    fn decoder<'a>(&self, reader: Box<dyn Read + 'a>) -> Result<Box<dyn Read + 'a>, OmniXError> {
+        LZ4Compression.decoder(reader)
+    }
+}
+
+pub struct Lz4FrameWriter<W: Write> {
+    encoder: Encoder<W>,
+}
+
+impl<W: Write> Lz4FrameWriter<W> {
+    /// Writes the end mark and checksum and hands back the inner writer.
+    pub fn finish(self) -> Result<W, OmniXError> {
+        let (mut writer, result) = self.encoder.finish();
+        result.map_err(|e| lz4_error("LZ4 compression", e))?;
+        writer.flush().map_err(|e| lz4_error("LZ4 compression", e))?;
+        Ok(writer)
+    }
+}
+
+impl<W: Write> Write for Lz4FrameWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.encoder.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder.flush()
+    }
+}
+
+impl<W: Write> StreamEncoder for Lz4FrameWriter<W> {
+    fn finish(self: Box<Self>) -> Result<(), OmniXError> {
+        Lz4FrameWriter::finish(*self).map(drop)
+    }
+}
+
+/// Writes the LZ4 frame format by hand so its independent blocks can be
+/// compressed in parallel. Input is buffered until a batch of
+/// `PARALLEL_LZ4_BLOCKS_PER_THREAD` blocks per worker is ready.
+pub struct ParallelLz4Writer<W: Write> {
+    writer: W,
+    pool: rayon::ThreadPool,
+    pending: Vec<u8>,
+    batch_len: usize,
+    checksum: Xxh32,
+    level: u32,
+}
+
+impl<W: Write> ParallelLz4Writer<W> {
+    fn new(mut writer: W, threads: usize, level: u32) -> Result<Self, OmniXError> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads.max(1))
+            .build()
             .map_err(|e| OmniXError::OperationFailed {
-                operation: "LZ4 decompression".to_string(),
+                operation: "LZ4 compression".to_string(),
                 details: e.to_string(),
-            })
+            })?;
+        let descriptor = [LZ4_FRAME_FLAGS, LZ4_BLOCK_DESCRIPTOR];
+        let mut header = LZ4_FRAME_MAGIC.to_vec();
+        header.extend_from_slice(&descriptor);
+        header.push((xxh32(&descriptor, 0) >> 8) as u8);
+        writer.write_all(&header).map_err(|e| lz4_error("LZ4 compression", e))?;
+
+        let batch_len = LZ4_BLOCK_SIZE * PARALLEL_LZ4_BLOCKS_PER_THREAD * pool.current_num_threads();
+        Ok(Self { writer, pool, pending: Vec::with_capacity(batch_len), batch_len, checksum: Xxh32::new(0), level })
+    }
+
+    /// Compresses and writes the first `len` pending bytes.
+    fn write_blocks(&mut self, len: usize) -> io::Result<()> {
+        let input = &self.pending[..len];
+        // Frame levels below 3 use the fast compressor, as `EncoderBuilder` does.
+        let mode = match self.level {
+            level if level >= 3 => Some(CompressionMode::HIGHCOMPRESSION(level as i32)),
+            _ => Some(CompressionMode::DEFAULT),
+        };
+        let blocks = self.pool.install(|| {
+            input
+                .par_chunks(LZ4_BLOCK_SIZE)
+                .map(|block| compress(block, mode, false))
+                .collect::<io::Result<Vec<Vec<u8>>>>()
+        })?;
+
+        self.checksum.update(input);
+        for (raw, compressed) in input.chunks(LZ4_BLOCK_SIZE).zip(&blocks) {
+            if compressed.len() < raw.len() {
+                self.writer.write_all(&(compressed.len() as u32).to_le_bytes())?;
+                self.writer.write_all(compressed)?;
+            } else {
+                self.writer.write_all(&(raw.len() as u32 | LZ4_UNCOMPRESSED_BLOCK).to_le_bytes())?;
+                self.writer.write_all(raw)?;
+            }
+        }
+        self.pending.drain(..len);
+        Ok(())
+    }
+
+    /// Writes the remaining blocks, the end mark and the content checksum.
+    pub fn finish(mut self) -> Result<W, OmniXError> {
+        let remaining = self.pending.len();
+        self.write_blocks(remaining).map_err(|e| lz4_error("LZ4 compression", e))?;
+        let mut trailer = 0u32.to_le_bytes().to_vec();
+        trailer.extend_from_slice(&self.checksum.digest().to_le_bytes());
+        self.writer.write_all(&trailer).map_err(|e| lz4_error("LZ4 compression", e))?;
+        self.writer.flush().map_err(|e| lz4_error("LZ4 compression", e))?;
+        Ok(self.writer)
     }
 }
 
-const BUFFER_SIZE: usize = 8192;
+impl<W: Write> Write for ParallelLz4Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        while self.pending.len() >= self.batch_len {
+            self.write_blocks(self.batch_len)?;
+        }
+        Ok(buf.len())
+    }
 
-/// Compresses data using LZ4 and writes to a file
-pub fn compress_data_with_lz4(input_path: &str, output_path: &str) -> Result<()> {
-    let mut input_file = File::open(input_path)
-        .with_context(|| format!("Failed to open input file: {}", input_path))?;
-    let mut output_file = File::create(output_path)
-        .with_context(|| format!("Failed to create output file: {}", output_path))?;
+    /// Writes what is pending as blocks, short ones included, which the
+    /// frame format allows anywhere.
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_blocks(self.pending.len())?;
+        self.writer.flush()
+    }
+}
 
-    let mut buffer = Vec::with_capacity(BUFFER_SIZE);
-    let lz4_compressor = LZ4Compression;
+impl<W: Write> StreamEncoder for ParallelLz4Writer<W> {
+    fn finish(self: Box<Self>) -> Result<(), OmniXError> {
+        ParallelLz4Writer::finish(*self).map(drop)
+    }
+}
 
-    loop {
-        buffer.clear();
-        let bytes_read = input_file
-            .by_ref()
-            .take(BUFFER_SIZE as u64)
-            .read_to_end(&mut buffer)
-            .with_context(|| "Failed to read input file")?;
+pub struct Lz4FrameReader<R: Read> {
+    decoder: Option<Decoder<R>>,
+    /// The inner reader once the decoder has been checked for a complete frame.
+    finished: Option<R>,
+}
 
-        if bytes_read == 0 {
-            break;
+impl<R: Read> Lz4FrameReader<R> {
+    pub fn into_inner(self) -> R {
+        match self.decoder {
+            Some(decoder) => decoder.finish().0,
+            None => self.finished.expect("finished reader is kept"),
         }
+    }
+}
 
-        let compressed_chunk = lz4_compressor.compress(&buffer)
-            .with_context(|| "Failed to compress data chunk with LZ4")?;
+impl<R: Read> Read for Lz4FrameReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let decoder = match self.decoder.as_mut() {
+            Some(decoder) => decoder,
+            None => return Ok(0),
+        };
+        let read = decoder.read(buf)?;
+        if read == 0 && !buf.is_empty() {
+            // The decoder reports EOF the same way whether or not it saw the
+            // end mark; only `finish` tells the two apart.
+            let (inner, complete) = self.decoder.take().expect("decoder checked above").finish();
+            self.finished = Some(inner);
+            complete.map_err(|_| io::Error::new(io::ErrorKind::UnexpectedEof, "LZ4 frame is truncated"))?;
+        }
+        Ok(read)
+    }
+}
 
-        output_file
-            .write_all(&compressed_chunk)
-            .with_context(|| "Failed to write compressed data to file")?;
+fn lz4_error(operation: &str, e: io::Error) -> OmniXError {
+    OmniXError::OperationFailed {
+        operation: operation.to_string(),
+        details: e.to_string(),
     }
+}
+
+/// Compresses a file into a single LZ4 frame on every core, streaming it
+/// through in blocks
+pub fn compress_data_with_lz4(input_path: &str, output_path: &str) -> Result<()> {
+    let mut input_file = File::open(input_path)
+        .with_context(|| format!("Failed to open input file: {}", input_path))?;
+    let output_file = File::create(output_path)
+        .with_context(|| format!("Failed to create output file: {}", output_path))?;
+
+    let mut writer = LZ4Compression.parallel_writer(output_file, compression_threads())
+        .with_context(|| "Failed to start LZ4 frame")?;
+    io::copy(&mut input_file, &mut writer)
+        .with_context(|| "Failed to compress data with LZ4")?;
+    writer
+        .finish()
+        .with_context(|| "Failed to finish LZ4 frame")?
+        .sync_all()
+        .with_context(|| "Failed to write compressed data to file")?;
 
     println!("Data compressed with LZ4 and written to {}", output_path);
     Ok(())
 }
 
-/// Decompresses LZ4 compressed data from a file
+/// Raw blocks do not record their decoded size, so they are decoded into a
+/// buffer of the legacy block size and anything larger is an error.
+fn decompress_raw_block(block: &[u8]) -> Result<Vec<u8>, OmniXError> {
+    decompress(block, Some(LZ4_LEGACY_BLOCK_SIZE as i32)).map_err(|e| lz4_error("LZ4 decompression", e))
+}
+
+/// Decompresses an LZ4 frame file written by `compress_data_with_lz4`
 pub fn decompress_data_with_lz4(input_path: &str, output_path: &str) -> Result<()> {
-    let mut input_file = File::open(input_path)
+    let input_file = File::open(input_path)
         .with_context(|| format!("Failed to open input file: {}", input_path))?;
     let mut output_file = File::create(output_path)
         .with_context(|| format!("Failed to create output file: {}", output_path))?;
 
-    let mut compressed_data = Vec::new();
-    input_file
-        .read_to_end(&mut compressed_data)
-        .with_context(|| "Failed to read compressed file")?;
-
-    let lz4_decompressor = LZ4Compression;
-    let decompressed_data = lz4_decompressor.decompress(&compressed_data)
+    let mut reader = LZ4Compression.reader(input_file)
+        .with_context(|| "Failed to read LZ4 frame header")?;
+    io::copy(&mut reader, &mut output_file)
         .with_context(|| "Failed to decompress data with LZ4")?;
-
     output_file
-        .write_all(&decompressed_data)
+        .sync_all()
         .with_context(|| "Failed to write decompressed data to file")?;
 
     println!("Data decompressed with LZ4 and written to {}", output_path);
@@ -93,6 +338,25 @@ mod tests {
     use super::*;
     use tempfile::tempdir;
 
+    /// Text-like runs mixed with incompressible noise, so blocks compress
+    /// unevenly and block boundaries land mid-run.
+    fn mixed_payload(len: usize) -> Vec<u8> {
+        let mut state = 0x9E37_79B9_7F4A_7C15u64;
+        let mut payload = Vec::with_capacity(len);
+        while payload.len() < len {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            if state % 3 == 0 {
+                payload.extend_from_slice(&state.to_le_bytes());
+            } else {
+                payload.extend_from_slice(b"context chunk for the neural turing machine ");
+            }
+        }
+        payload.truncate(len);
+        payload
+    }
+
     #[test]
     fn test_lz4_compression_decompression() -> Result<()> {
         let dir = tempdir()?;
@@ -117,4 +381,98 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_multi_megabyte_files_round_trip() -> Result<()> {
+        let dir = tempdir()?;
+        for (name, len) in [("exact", 8 * 1024 * 1024), ("ragged", 5 * 1024 * 1024 + 12_345)] {
+            let input_path = dir.path().join(format!("{}.bin", name));
+            let compressed_path = dir.path().join(format!("{}.lz4", name));
+            let decompressed_path = dir.path().join(format!("{}.out", name));
+            let payload = mixed_payload(len);
+            std::fs::write(&input_path, &payload)?;
+
+            compress_data_with_lz4(input_path.to_str().unwrap(), compressed_path.to_str().unwrap())?;
+            let compressed = std::fs::read(&compressed_path)?;
+            assert!(compressed.starts_with(&LZ4_FRAME_MAGIC));
+            assert!(compressed.len() < payload.len());
+
+            decompress_data_with_lz4(compressed_path.to_str().unwrap(), decompressed_path.to_str().unwrap())?;
+            assert_eq!(std::fs::read(&decompressed_path)?, payload);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_adapters_stream_in_small_writes_and_detect_corruption() -> Result<(), OmniXError> {
+        let payload = mixed_payload(3 * 1024 * 1024 + 7);
+        let mut writer = LZ4Compression.writer(Vec::new())?;
+        for piece in payload.chunks(1000) {
+            writer.write_all(piece).map_err(|e| lz4_error("LZ4 compression", e))?;
+        }
+        let framed = writer.finish()?;
+        assert_eq!(LZ4Compression.decompress(&framed)?, payload);
+
+        let mut reader = LZ4Compression.reader(framed.as_slice())?;
+        let mut decoded = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            let read = reader.read(&mut buf).map_err(|e| lz4_error("LZ4 decompression", e))?;
+            if read == 0 {
+                break;
+            }
+            decoded.extend_from_slice(&buf[..read]);
+        }
+        assert_eq!(decoded, payload);
+
+        let mut corrupted = framed.clone();
+        let middle = corrupted.len() / 2;
+        corrupted[middle] ^= 0x55;
+        assert!(LZ4Compression.decompress(&corrupted).is_err());
+        assert!(LZ4Compression.decompress(&framed[..framed.len() - 4]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parallel_writer_produces_standard_frames() -> Result<(), OmniXError> {
+        let payload = mixed_payload(6 * 1024 * 1024 + 4321);
+
+        let parallel = LZ4Compression.compress_parallel(&payload, 4)?;
+        assert!(parallel.starts_with(&LZ4_FRAME_MAGIC));
+        assert_eq!(LZ4Compression.decompress(&parallel)?, payload);
+
+        // Flushing mid-stream leaves short blocks, which readers must accept.
+        let mut writer = LZ4Compression.parallel_writer(Vec::new(), 3)?;
+        for piece in payload.chunks(1_000_003) {
+            writer.write_all(piece).map_err(|e| lz4_error("LZ4 compression", e))?;
+            writer.flush().map_err(|e| lz4_error("LZ4 compression", e))?;
+        }
+        let flushed = writer.finish()?;
+        let mut decoded = Vec::new();
+        LZ4Compression
+            .decoder(Box::new(flushed.as_slice()))?
+            .read_to_end(&mut decoded)
+            .map_err(|e| lz4_error("LZ4 decompression", e))?;
+        assert_eq!(decoded, payload);
+
+        let mut corrupted = parallel.clone();
+        let checksum_at = corrupted.len() - 1;
+        corrupted[checksum_at] ^= 0x01;
+        assert!(LZ4Compression.decompress(&corrupted).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reads_raw_blocks_from_before_the_frame_format() -> Result<(), OmniXError> {
+        for payload in [mixed_payload(LZ4_LEGACY_BLOCK_SIZE), vec![0u8; 100], Vec::new()] {
+            let block = compress(&payload, Some(CompressionMode::HIGHCOMPRESSION(9)), false)
+                .map_err(|e| lz4_error("LZ4 compression", e))?;
+            assert_eq!(LZ4Compression.decompress(&block)?, payload);
+        }
+
+        let oversized = compress(&mixed_payload(4 * LZ4_LEGACY_BLOCK_SIZE), Some(CompressionMode::HIGHCOMPRESSION(9)), false)
+            .map_err(|e| lz4_error("LZ4 compression", e))?;
+        assert!(LZ4Compression.decompress(&oversized).is_err());
+        Ok(())
+    }
 }
\ No newline at end of file
@@ -1,31 +1,252 @@
 // src/aproar/compression/mod.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[COMPRESSION]Xyn>=====S===t===u===d===i===o===s======[R|$>
 
+use crate::aproar::storage::ByteReader;
 use crate::omnixtracker::{OmniXMetry, OmniXError};
 use crate::constants::*;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
+mod adaptive;
+#[cfg(feature = "brotli-compression")]
+mod brotli_compression;
+mod envelope;
+#[cfg(feature = "gzip-compression")]
+mod gzip_compression;
 mod lz4_compression;
+mod no_compression;
+mod quantization;
+mod registry;
+#[cfg(feature = "snappy-compression")]
+mod snappy_compression;
+mod streaming;
 mod zstd_compression;
 
-pub use lz4_compression::{compress_data_with_lz4, decompress_data_with_lz4};
-pub use zstd_compression::{compress_data_with_zstd, decompress_data_with_zstd};
+pub use envelope::{
+    seal, stream_header, Envelope, EnvelopeHeader, ENVELOPE_HEADER_LEN, ENVELOPE_MAGIC, ENVELOPE_VERSION,
+    STREAM_ENVELOPE_HEADER_LEN, STREAM_ENVELOPE_VERSION,
+};
+pub(crate) use envelope::verify_decoded;
+pub use streaming::{decode_stream, encode_stream, encode_stream_with, streams};
+
+pub use lz4_compression::{
+    LZ4Compression, Lz4FrameReader, Lz4FrameWriter, Lz4Level, ParallelLz4Writer, LZ4_FRAME_MAGIC, compress_data_with_lz4,
+    decompress_data_with_lz4,
+};
+pub use no_compression::NoCompression;
+#[cfg(feature = "brotli-compression")]
+pub use brotli_compression::{BrotliCompression, BrotliLevel};
+#[cfg(feature = "gzip-compression")]
+pub use gzip_compression::{DeflateCompression, DeflateLevel, GzipCompression, GzipLevel};
+#[cfg(feature = "snappy-compression")]
+pub use snappy_compression::{SnappyCompression, SNAPPY_STREAM_MAGIC};
+pub use quantization::{FloatArray, FloatCodec, Quantization, QUANTIZED_HEADER_LEN, QUANTIZED_MAGIC, QUANTIZED_VERSION};
+pub use registry::{CodecFactory, CodecRegistry};
+use registry::UnavailableCodec;
+pub use zstd_compression::{
+    dictionary_id, ZstdCompression, ZstdDictCompression, ZstdDictionaries, ZstdDictionary, ZstdFrameWriter, ZstdLevel,
+    ZSTD_DICT_ID_LEN, compress_data_with_zstd, decompress_data_with_zstd,
+};
+pub use adaptive::{sample, AdaptiveSelector, CompressionGoal, Sample, Selection};
 
 pub trait CompressionStrategy {
     fn compress(&self, data: &[u8]) -> Result<Vec<u8>, OmniXError>;
     fn decompress(&self, compressed_data: &[u8]) -> Result<Vec<u8>, OmniXError>;
+
+    /// Compresses everything written to the returned encoder into `writer`,
+    /// in a format `decompress` reads. With `threads` above one the work is
+    /// spread over that many cores while holding only a few blocks in memory.
+    fn encoder<'a>(&self, writer: Box<dyn Write + 'a>, threads: usize) -> Result<Box<dyn StreamEncoder + 'a>, OmniXError>;
+
+    /// Decompresses what `compress` or `encoder` produced as it is read.
+    fn decoder<'a>(&self, reader: Box<dyn Read + 'a>) -> Result<Box<dyn Read + 'a>, OmniXError>;
+
+    /// `compress` spread over `threads` cores. Single-threaded decoders read
+    /// the output as well.
+    fn compress_parallel(&self, data: &[u8], threads: usize) -> Result<Vec<u8>, OmniXError> {
+        let mut compressed = Vec::with_capacity(data.len() / 2);
+        let mut encoder = self.encoder(Box::new(&mut compressed), threads)?;
+        encoder.write_all(data).map_err(|e| OmniXError::OperationFailed {
+            operation: "Parallel compression".to_string(),
+            details: e.to_string(),
+        })?;
+        encoder.finish()?;
+        Ok(compressed)
+    }
+}
+
+/// A compressing writer handed out by `CompressionStrategy::encoder`.
+pub trait StreamEncoder: Write {
+    /// Writes the end of the stream and flushes the inner writer. Dropping
+    /// an encoder without calling this leaves the output truncated.
+    fn finish(self: Box<Self>) -> Result<(), OmniXError>;
+}
+
+/// The number of cores compression may use: all of them.
+pub fn compression_threads() -> usize {
+    std::thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1)
+}
+
+/// Names a codec in configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CodecKind {
+    Uncompressed,
+    Lz4,
+    Zstd,
+    /// Zstd primed with a trained dictionary; see `ZstdDictionary`.
+    ZstdDict,
+    Snappy,
+    Gzip,
+    /// Deflate in the zlib wrapper, as HTTP's `deflate` encoding uses.
+    Deflate,
+    Brotli,
+}
+
+impl CodecKind {
+    /// Stable on-disk identifier written into the envelope header.
+    pub fn id(&self) -> u8 {
+        match self {
+            CodecKind::Uncompressed => 0,
+            CodecKind::Lz4 => 1,
+            CodecKind::Zstd => 2,
+            CodecKind::ZstdDict => 3,
+            CodecKind::Snappy => 4,
+            CodecKind::Gzip => 5,
+            CodecKind::Deflate => 6,
+            CodecKind::Brotli => 7,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(CodecKind::Uncompressed),
+            1 => Some(CodecKind::Lz4),
+            2 => Some(CodecKind::Zstd),
+            3 => Some(CodecKind::ZstdDict),
+            4 => Some(CodecKind::Snappy),
+            5 => Some(CodecKind::Gzip),
+            6 => Some(CodecKind::Deflate),
+            7 => Some(CodecKind::Brotli),
+            _ => None,
+        }
+    }
+
+    /// The codec's name in metric keys and configuration.
+    pub fn name(&self) -> &'static str {
+        match self {
+            CodecKind::Uncompressed => "uncompressed",
+            CodecKind::Lz4 => "lz4",
+            CodecKind::Zstd => "zstd",
+            CodecKind::ZstdDict => "zstd_dict",
+            CodecKind::Snappy => "snappy",
+            CodecKind::Gzip => "gzip",
+            CodecKind::Deflate => "deflate",
+            CodecKind::Brotli => "brotli",
+        }
+    }
+
+    /// The cargo feature the codec is built behind, if any.
+    pub fn feature(&self) -> Option<&'static str> {
+        match self {
+            CodecKind::Uncompressed | CodecKind::Lz4 | CodecKind::Zstd | CodecKind::ZstdDict => None,
+            CodecKind::Snappy => Some("snappy-compression"),
+            CodecKind::Gzip | CodecKind::Deflate => Some("gzip-compression"),
+            CodecKind::Brotli => Some("brotli-compression"),
+        }
+    }
+
+    /// Whether this build includes the codec.
+    pub fn is_available(&self) -> bool {
+        CodecRegistry::builtin().contains(*self)
+    }
+
+    /// The codec's strategy from `CodecRegistry::builtin`. One left out of
+    /// this build gets a strategy whose every call fails. Dictionary payloads
+    /// need their dictionary resolved first, through
+    /// `ZstdDictionaries::strategy_for`.
+    pub fn strategy(&self) -> Box<dyn CompressionStrategy> {
+        CodecRegistry::builtin().get(self.id()).unwrap_or_else(|| Box::new(UnavailableCodec(*self)))
+    }
+}
+
+/// A codec and, for Zstd, LZ4, gzip, Deflate and Brotli, the level to
+/// compress at. Only the codec is recorded in the envelope; any level
+/// decodes the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CompressionChoice {
+    pub codec: CodecKind,
+    pub level: Option<i32>,
 }
 
+impl CompressionChoice {
+    pub fn zstd(level: i32) -> Self {
+        Self::with_level(CodecKind::Zstd, level)
+    }
+
+    pub fn with_level(codec: CodecKind, level: i32) -> Self {
+        Self { codec, level: Some(level) }
+    }
+
+    /// The codec's strategy at the chosen level. Codecs without levels, and
+    /// those left out of this build, ignore it.
+    pub fn strategy(&self) -> Box<dyn CompressionStrategy> {
+        match (self.codec, self.level) {
+            (CodecKind::Zstd, Some(level)) => Box::new(ZstdLevel(level)),
+            (CodecKind::Lz4, Some(level)) => Box::new(Lz4Level(level.max(0) as u32)),
+            #[cfg(feature = "gzip-compression")]
+            (CodecKind::Gzip, Some(level)) => Box::new(GzipLevel(level.max(0) as u32)),
+            #[cfg(feature = "gzip-compression")]
+            (CodecKind::Deflate, Some(level)) => Box::new(DeflateLevel(level.max(0) as u32)),
+            #[cfg(feature = "brotli-compression")]
+            (CodecKind::Brotli, Some(level)) => Box::new(BrotliLevel(level.max(0) as u32)),
+            (codec, _) => codec.strategy(),
+        }
+    }
+
+    /// Names the choice in metric keys, e.g. `zstd9`.
+    pub fn label(&self) -> String {
+        match self.level {
+            Some(level) => format!("{}{}", self.codec.name(), level),
+            None => self.codec.name().to_string(),
+        }
+    }
+}
+
+impl From<CodecKind> for CompressionChoice {
+    fn from(codec: CodecKind) -> Self {
+        Self { codec, level: None }
+    }
+}
+
+#[derive(Clone)]
 pub struct CompressionManager {
     metrics: OmniXMetry,
+    dictionaries: Arc<ZstdDictionaries>,
+    dictionary_seals: Arc<AtomicU64>,
 }
 
 impl CompressionManager {
     pub fn new(metrics: OmniXMetry) -> Self {
-        Self { metrics }
+        Self { metrics, dictionaries: Arc::new(ZstdDictionaries::default()), dictionary_seals: Arc::new(AtomicU64::new(0)) }
+    }
+
+    /// The dictionaries `unseal` can decode with.
+    pub fn dictionaries(&self) -> &Arc<ZstdDictionaries> {
+        &self.dictionaries
     }
 
+    /// Inputs of `PARALLEL_COMPRESSION_THRESHOLD` bytes or more are
+    /// compressed on every core.
     pub fn compress(&self, strategy: &dyn CompressionStrategy, data: &[u8]) -> Result<Vec<u8>, OmniXError> {
         let start_time = std::time::Instant::now();
-        let result = strategy.compress(data);
+        let result = if data.len() >= PARALLEL_COMPRESSION_THRESHOLD {
+            self.metrics.increment_counter("compression.parallel".to_string(), 1);
+            strategy.compress_parallel(data, compression_threads())
+        } else {
+            strategy.compress(data)
+        };
         let duration = start_time.elapsed();
 
         self.metrics.record_histogram("compression.duration".to_string(), duration.as_secs_f64());
@@ -56,4 +277,76 @@ impl CompressionManager {
 
         result
     }
+
+    /// Compresses `data` with `codec` and wraps the result in an envelope so
+    /// the reader does not have to guess the codec.
+    pub fn seal(&self, codec: CodecKind, data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        self.seal_choice(codec.into(), data).map(|(sealed, _)| sealed)
+    }
+
+    /// Like `seal`, with the codec and level picked by `selector`. The
+    /// timing and ratio are recorded per choice for the selector to learn from.
+    pub fn seal_adaptive(&self, selector: &AdaptiveSelector, data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        let selection = selector.select(data);
+        let (sealed, elapsed) = self.seal_choice(selection.choice, data)?;
+        selection.record(&self.metrics, data.len(), sealed.len() - ENVELOPE_HEADER_LEN, elapsed);
+        Ok(sealed)
+    }
+
+    /// Like `seal`, compressing with `dictionary`. Every
+    /// `ZSTD_DICT_COMPARE_INTERVAL` payloads are also compressed without it
+    /// to record how much the dictionary gains.
+    pub fn seal_with_dictionary(&self, dictionary: &Arc<ZstdDictionary>, data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        let compressed = self.compress(&ZstdCompression.with_dictionary(dictionary.clone()), data)?;
+        self.metrics.increment_counter("compression.dictionary.used".to_string(), 1);
+        if !data.is_empty() {
+            self.metrics.record_histogram(
+                "compression.dictionary.ratio".to_string(),
+                data.len() as f64 / compressed.len() as f64,
+            );
+        }
+        if self.dictionary_seals.fetch_add(1, Ordering::Relaxed) % ZSTD_DICT_COMPARE_INTERVAL == 0 {
+            let plain = ZstdCompression.compress(data)?;
+            self.metrics.record_histogram(
+                "compression.dictionary.improvement".to_string(),
+                plain.len() as f64 / compressed.len() as f64,
+            );
+        }
+        Ok(seal(CodecKind::ZstdDict, data, &compressed))
+    }
+
+    fn seal_choice(&self, choice: CompressionChoice, data: &[u8]) -> Result<(Vec<u8>, std::time::Duration), OmniXError> {
+        let start_time = std::time::Instant::now();
+        let compressed = self.compress(choice.strategy().as_ref(), data)?;
+        let elapsed = start_time.elapsed();
+        Ok((seal(choice.codec, data, &compressed), elapsed))
+    }
+
+    /// Decodes either an enveloped payload or a legacy bare LZ4/Zstd blob.
+    pub fn unseal(&self, stored: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        let envelope = Envelope::parse(stored)?;
+        if envelope.header.is_none() {
+            self.metrics.increment_counter("decompression.legacy".to_string(), 1);
+        }
+        let start_time = std::time::Instant::now();
+        let data = self.decompress(self.strategy_for(&envelope)?.as_ref(), envelope.payload)?;
+        adaptive::record_decompression(&self.metrics, envelope.codec, data.len(), start_time.elapsed());
+        if let Err(e) = envelope.verify(&data) {
+            self.metrics.increment_counter("decompression.checksum_mismatch".to_string(), 1);
+            return Err(e);
+        }
+        Ok(data)
+    }
+    /// Decodes a stored stream like `decode_stream`, including payloads
+    /// compressed with one of this manager's dictionaries.
+    pub async fn decode_stream(&self, reader: ByteReader) -> Result<ByteReader, OmniXError> {
+        streaming::decode_stream_with(reader, Some(&self.dictionaries)).await
+    }
+
+    fn strategy_for(&self, envelope: &Envelope<'_>) -> Result<Box<dyn CompressionStrategy>, OmniXError> {
+        match envelope.codec {
+            CodecKind::ZstdDict => Ok(Box::new(self.dictionaries.strategy_for(envelope.payload)?)),
+            codec => Ok(codec.strategy()),
+        }
+    }
 }
\ No newline at end of file
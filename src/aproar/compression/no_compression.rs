@@ -0,0 +1,48 @@
+// src/aproar/compression/no_compression.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[COMPRESSION]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use super::{CompressionStrategy, OmniXError, StreamEncoder};
+use std::io::{self, Read, Write};
+
+/// Stores payloads as they are, for data that does not compress.
+pub struct NoCompression;
+
+impl CompressionStrategy for NoCompression {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        Ok(data.to_vec())
+    }
+
+    fn decompress(&self, compressed_data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        Ok(compressed_data.to_vec())
+    }
+
+    fn encoder<'a>(&self, writer: Box<dyn Write + 'a>, _threads: usize) -> Result<Box<dyn StreamEncoder + 'a>, OmniXError> {
+        Ok(Box::new(PassThrough { writer }))
+    }
+
+    fn decoder<'a>(&self, reader: Box<dyn Read + 'a>) -> Result<Box<dyn Read + 'a>, OmniXError> {
+        Ok(reader)
+    }
+}
+
+struct PassThrough<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> Write for PassThrough<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> StreamEncoder for PassThrough<W> {
+    fn finish(mut self: Box<Self>) -> Result<(), OmniXError> {
+        self.writer.flush().map_err(|e| OmniXError::OperationFailed {
+            operation: "Uncompressed write".to_string(),
+            details: e.to_string(),
+        })
+    }
+}
\ No newline at end of file
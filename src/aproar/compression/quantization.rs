@@ -0,0 +1,412 @@
+// src/aproar/compression/quantization.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[COMPRESSION]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use super::{CompressionStrategy, OmniXError, ZstdCompression, ZstdLevel};
+use crate::constants::ZSTD_COMPRESSION_LEVEL;
+use half::{bf16, f16};
+use serde::{Deserialize, Serialize};
+
+/// Every encoded float array starts with this.
+pub const QUANTIZED_MAGIC: [u8; 4] = *b"APRQ";
+pub const QUANTIZED_VERSION: u8 = 1;
+/// Magic, version, quantization, flags, row length (u32 LE) and value
+/// count (u64 LE), followed by one Zstd frame.
+pub const QUANTIZED_HEADER_LEN: usize = 19;
+const FLAG_SHUFFLED: u8 = 1;
+/// Scale and zero point, both `f32`, ahead of each row's int8 values.
+const INT8_ROW_PARAMS_LEN: usize = 8;
+
+/// How `FloatCodec` reduces each value before compressing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Quantization {
+    /// Keeps all 32 bits; decoding is exact.
+    Lossless,
+    /// IEEE half precision: 11 significant bits, finite values up to ±65504.
+    Float16,
+    /// bfloat16: 8 significant bits over the whole `f32` range.
+    BFloat16,
+    /// One byte per value, spread over its row's range by a per-row scale
+    /// and zero point.
+    Int8,
+}
+
+impl Quantization {
+    fn id(&self) -> u8 {
+        match self {
+            Quantization::Lossless => 0,
+            Quantization::Float16 => 1,
+            Quantization::BFloat16 => 2,
+            Quantization::Int8 => 3,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Quantization::Lossless),
+            1 => Some(Quantization::Float16),
+            2 => Some(Quantization::BFloat16),
+            3 => Some(Quantization::Int8),
+            _ => None,
+        }
+    }
+
+    /// Bytes each quantized value takes.
+    pub fn width(&self) -> usize {
+        match self {
+            Quantization::Lossless => 4,
+            Quantization::Float16 | Quantization::BFloat16 => 2,
+            Quantization::Int8 => 1,
+        }
+    }
+}
+
+/// A decoded float array, `row_len` values to a row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FloatArray {
+    pub values: Vec<f32>,
+    pub row_len: usize,
+}
+
+impl FloatArray {
+    pub fn rows(&self) -> usize {
+        self.values.len().checked_div(self.row_len).unwrap_or(0)
+    }
+}
+
+/// Compresses `f32` arrays such as NTM memory matrices and embeddings,
+/// which general-purpose codecs barely shrink. Values are quantized, their
+/// bytes optionally shuffled so each byte position is stored contiguously,
+/// and the result is compressed with Zstd.
+///
+/// Every element of `decode(encode(values))` is within
+/// `error_bound(values)` of the original. NaN and infinities survive
+/// `Float16` and `BFloat16` as they are; `Int8` rejects them.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FloatCodec {
+    pub quantization: Quantization,
+    pub shuffle: bool,
+    pub level: i32,
+}
+
+impl FloatCodec {
+    /// Shuffled, at `ZSTD_COMPRESSION_LEVEL`.
+    pub fn new(quantization: Quantization) -> Self {
+        Self { quantization, shuffle: true, level: ZSTD_COMPRESSION_LEVEL }
+    }
+
+    pub fn with_shuffle(mut self, shuffle: bool) -> Self {
+        self.shuffle = shuffle;
+        self
+    }
+
+    pub fn with_level(mut self, level: i32) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Encodes `values` as rows of `row_len`. Fails when `row_len` does not
+    /// divide the length, when `Float16` meets a finite value beyond ±65504,
+    /// or when `Int8` meets a value that is not finite.
+    pub fn encode(&self, values: &[f32], row_len: usize) -> Result<Vec<u8>, OmniXError> {
+        check_shape(values.len(), row_len)?;
+        let (mut body, quantized) = match self.quantization {
+            Quantization::Lossless => (Vec::new(), values.iter().flat_map(|value| value.to_le_bytes()).collect()),
+            Quantization::Float16 => {
+                if let Some(value) = values.iter().find(|value| value.is_finite() && value.abs() > f16::MAX.to_f32()) {
+                    return Err(OmniXError::ValidationError(format!(
+                        "{} is outside the float16 range; use bfloat16 or int8 quantization",
+                        value
+                    )));
+                }
+                (Vec::new(), values.iter().flat_map(|&value| f16::from_f32(value).to_le_bytes()).collect())
+            }
+            Quantization::BFloat16 => (Vec::new(), values.iter().flat_map(|&value| to_bf16(value).to_le_bytes()).collect()),
+            Quantization::Int8 => quantize_rows(values, row_len)?,
+        };
+        let width = self.quantization.width();
+        if self.shuffle && width > 1 {
+            body.extend(shuffle(&quantized, width));
+        } else {
+            body.extend(quantized);
+        }
+
+        let row_len = u32::try_from(row_len)
+            .map_err(|_| OmniXError::ValidationError(format!("row length {} does not fit in 32 bits", row_len)))?;
+        let mut encoded = Vec::with_capacity(QUANTIZED_HEADER_LEN + body.len() / 2);
+        encoded.extend_from_slice(&QUANTIZED_MAGIC);
+        encoded.push(QUANTIZED_VERSION);
+        encoded.push(self.quantization.id());
+        encoded.push(if self.shuffle { FLAG_SHUFFLED } else { 0 });
+        encoded.extend_from_slice(&row_len.to_le_bytes());
+        encoded.extend_from_slice(&(values.len() as u64).to_le_bytes());
+        encoded.extend(ZstdLevel(self.level).compress(&body)?);
+        Ok(encoded)
+    }
+
+    /// Decodes what `encode` produced, with any settings.
+    pub fn decode(encoded: &[u8]) -> Result<FloatArray, OmniXError> {
+        if encoded.len() < QUANTIZED_HEADER_LEN || !encoded.starts_with(&QUANTIZED_MAGIC) {
+            return Err(decoding_error("Not an encoded float array".to_string()));
+        }
+        if encoded[4] != QUANTIZED_VERSION {
+            return Err(decoding_error(format!("Unsupported version {}", encoded[4])));
+        }
+        let quantization = Quantization::from_id(encoded[5])
+            .ok_or_else(|| decoding_error(format!("Unknown quantization id {}", encoded[5])))?;
+        let shuffled = encoded[6] & FLAG_SHUFFLED != 0;
+        let row_len = u32::from_le_bytes(encoded[7..11].try_into().expect("4-byte slice")) as usize;
+        let count = usize::try_from(u64::from_le_bytes(encoded[11..19].try_into().expect("8-byte slice")))
+            .map_err(|_| decoding_error("Value count does not fit in memory".to_string()))?;
+        check_shape(count, row_len).map_err(|e| decoding_error(e.to_string()))?;
+
+        let body = ZstdCompression.decompress(&encoded[QUANTIZED_HEADER_LEN..])?;
+        let width = quantization.width();
+        let params_len = match quantization {
+            Quantization::Int8 => count.checked_div(row_len).unwrap_or(0) * INT8_ROW_PARAMS_LEN,
+            _ => 0,
+        };
+        if count.checked_mul(width).and_then(|len| len.checked_add(params_len)) != Some(body.len()) {
+            return Err(decoding_error(format!("{} bytes do not hold {} values", body.len(), count)));
+        }
+        let (params, quantized) = body.split_at(params_len);
+        let quantized = if shuffled && width > 1 { unshuffle(quantized, width) } else { quantized.to_vec() };
+
+        let values = match quantization {
+            Quantization::Lossless => quantized
+                .chunks_exact(4)
+                .map(|bytes| f32::from_le_bytes(bytes.try_into().expect("4-byte chunk")))
+                .collect(),
+            Quantization::Float16 => quantized
+                .chunks_exact(2)
+                .map(|bytes| f16::from_le_bytes([bytes[0], bytes[1]]).to_f32())
+                .collect(),
+            Quantization::BFloat16 => quantized
+                .chunks_exact(2)
+                .map(|bytes| bf16::from_le_bytes([bytes[0], bytes[1]]).to_f32())
+                .collect(),
+            Quantization::Int8 => params
+                .chunks_exact(INT8_ROW_PARAMS_LEN)
+                .zip(quantized.chunks(row_len.max(1)))
+                .flat_map(|(params, row)| {
+                    let scale = f32::from_le_bytes(params[..4].try_into().expect("4-byte slice"));
+                    let zero_point = f32::from_le_bytes(params[4..].try_into().expect("4-byte slice"));
+                    row.iter().map(move |&level| dequantize(level, scale, zero_point))
+                })
+                .collect(),
+        };
+        Ok(FloatArray { values, row_len })
+    }
+
+    /// The most any finite element of `values` can be off by once encoded
+    /// and decoded with this codec.
+    ///
+    /// - `Lossless`: zero.
+    /// - `Float16`: 2^-11 of the largest magnitude, and at least 2^-25 for
+    ///   values in the subnormal range.
+    /// - `BFloat16`: 2^-8 of the largest magnitude, and at least 2^-134.
+    /// - `Int8`: half of the widest row's step, `(max - min) / 255 / 2`,
+    ///   plus the `f32` rounding of the reconstructed value.
+    pub fn error_bound(&self, values: &[f32], row_len: usize) -> f64 {
+        let max_abs = |values: &[f32]| {
+            values.iter().filter(|value| value.is_finite()).fold(0f64, |max, value| max.max(value.abs() as f64))
+        };
+        match self.quantization {
+            Quantization::Lossless => 0.0,
+            Quantization::Float16 => (max_abs(values) * 2f64.powi(-11)).max(2f64.powi(-25)),
+            Quantization::BFloat16 => (max_abs(values) * 2f64.powi(-8)).max(2f64.powi(-134)),
+            Quantization::Int8 => values
+                .chunks(row_len.max(1))
+                .map(|row| {
+                    let scale = row_params(row).0 as f64;
+                    scale / 2.0 + (max_abs(row) + scale) * f64::from(f32::EPSILON) + 2f64.powi(-149)
+                })
+                .fold(0.0, f64::max),
+        }
+    }
+}
+
+fn check_shape(count: usize, row_len: usize) -> Result<(), OmniXError> {
+    if (row_len == 0 && count > 0) || count.checked_rem(row_len).is_some_and(|rest| rest != 0) {
+        return Err(OmniXError::ValidationError(format!(
+            "{} values do not divide into rows of {}",
+            count, row_len
+        )));
+    }
+    Ok(())
+}
+
+/// Rounds to the nearest bfloat16, saturating finite values that would
+/// round up to infinity.
+fn to_bf16(value: f32) -> bf16 {
+    let rounded = bf16::from_f32(value);
+    if rounded.is_infinite() && value.is_finite() {
+        if value > 0.0 { bf16::MAX } else { bf16::MIN }
+    } else {
+        rounded
+    }
+}
+
+/// Returns the per-row parameters followed by the quantized values.
+fn quantize_rows(values: &[f32], row_len: usize) -> Result<(Vec<u8>, Vec<u8>), OmniXError> {
+    if let Some(value) = values.iter().find(|value| !value.is_finite()) {
+        return Err(OmniXError::ValidationError(format!("int8 quantization needs finite values, got {}", value)));
+    }
+    let mut params = Vec::with_capacity(values.len() / row_len.max(1) * INT8_ROW_PARAMS_LEN);
+    let mut quantized = Vec::with_capacity(values.len());
+    for row in values.chunks(row_len.max(1)) {
+        let (scale, zero_point) = row_params(row);
+        params.extend_from_slice(&scale.to_le_bytes());
+        params.extend_from_slice(&zero_point.to_le_bytes());
+        quantized.extend(row.iter().map(|&value| quantize(value, scale, zero_point)));
+    }
+    Ok((params, quantized))
+}
+
+/// The row minimum is the zero point, and the scale is rounded up so the
+/// row maximum lands on level 255 at most.
+fn row_params(row: &[f32]) -> (f32, f32) {
+    let finite = || row.iter().copied().filter(|value| value.is_finite());
+    let min = finite().fold(f32::INFINITY, f32::min);
+    let max = finite().fold(f32::NEG_INFINITY, f32::max);
+    if min >= max {
+        return (0.0, if min.is_finite() { min } else { 0.0 });
+    }
+    let range = max as f64 - min as f64;
+    let mut scale = (range / 255.0) as f32;
+    if (scale as f64) * 255.0 < range {
+        scale = f32::from_bits(scale.to_bits() + 1);
+    }
+    (scale, min)
+}
+
+fn quantize(value: f32, scale: f32, zero_point: f32) -> u8 {
+    if scale == 0.0 {
+        return 0;
+    }
+    ((value as f64 - zero_point as f64) / scale as f64).round().clamp(0.0, 255.0) as u8
+}
+
+/// Clamped because the rounded-up scale can put level 255 a hair past
+/// `f32::MAX` for rows spanning the whole range.
+fn dequantize(level: u8, scale: f32, zero_point: f32) -> f32 {
+    (level as f64 * scale as f64 + zero_point as f64).clamp(f32::MIN as f64, f32::MAX as f64) as f32
+}
+
+/// Stores byte `b` of every value together, for `b` in `0..width`.
+fn shuffle(bytes: &[u8], width: usize) -> Vec<u8> {
+    let count = bytes.len() / width;
+    let mut shuffled = vec![0; bytes.len()];
+    for (index, value) in bytes.chunks_exact(width).enumerate() {
+        for (byte, &b) in value.iter().enumerate() {
+            shuffled[byte * count + index] = b;
+        }
+    }
+    shuffled
+}
+
+fn unshuffle(bytes: &[u8], width: usize) -> Vec<u8> {
+    let count = bytes.len() / width;
+    let mut values = vec![0; bytes.len()];
+    for (byte, plane) in bytes.chunks_exact(count.max(1)).enumerate().take(width) {
+        for (index, &b) in plane.iter().enumerate() {
+            values[index * width + byte] = b;
+        }
+    }
+    values
+}
+
+fn decoding_error(details: String) -> OmniXError {
+    OmniXError::OperationFailed {
+        operation: "Float array decoding".to_string(),
+        details,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A memory-like matrix: small activations, a few outliers, subnormals
+    /// and rows of very different ranges.
+    fn matrix(rows: usize, row_len: usize) -> Vec<f32> {
+        let mut state = 0x9E37_79B9_7F4A_7C15u64;
+        (0..rows * row_len)
+            .map(|i| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                let unit = (state >> 40) as f32 / (1u64 << 24) as f32 - 0.5;
+                let row_scale = 10f32.powi((i / row_len % 7) as i32 - 3);
+                match i % 97 {
+                    0 => unit * 4000.0,
+                    1 => unit * 1e-40,
+                    2 => 0.0,
+                    _ => unit * row_scale,
+                }
+            })
+            .collect()
+    }
+
+    fn max_error(original: &[f32], decoded: &[f32]) -> f64 {
+        original
+            .iter()
+            .zip(decoded)
+            .map(|(&original, &decoded)| (original as f64 - decoded as f64).abs())
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn test_reconstruction_stays_within_the_error_bound() -> Result<(), OmniXError> {
+        let (rows, row_len) = (256, 64);
+        let values = matrix(rows, row_len);
+        let raw_len = values.len() * 4;
+
+        for quantization in [Quantization::Lossless, Quantization::Float16, Quantization::BFloat16, Quantization::Int8] {
+            for shuffle in [true, false] {
+                let codec = FloatCodec::new(quantization).with_shuffle(shuffle);
+                let encoded = codec.encode(&values, row_len)?;
+                let decoded = FloatCodec::decode(&encoded)?;
+                assert_eq!(decoded.row_len, row_len);
+                assert_eq!(decoded.rows(), rows);
+
+                let bound = codec.error_bound(&values, row_len);
+                let error = max_error(&values, &decoded.values);
+                assert!(error <= bound, "{:?}: error {} exceeds bound {}", quantization, error, bound);
+                if quantization != Quantization::Lossless {
+                    assert!(encoded.len() * 100 <= raw_len * 55, "{:?}: {} of {} bytes", quantization, encoded.len(), raw_len);
+                }
+            }
+        }
+
+        assert_eq!(FloatCodec::decode(&FloatCodec::new(Quantization::Lossless).encode(&values, row_len)?)?.values, values);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bounds_hold_at_the_edges_of_each_format() -> Result<(), OmniXError> {
+        let bf16_edges = [f32::MAX, f32::MIN, f32::MIN_POSITIVE, 1e-42, -0.0, 1.0 + f32::EPSILON];
+        let codec = FloatCodec::new(Quantization::BFloat16);
+        let decoded = FloatCodec::decode(&codec.encode(&bf16_edges, 2)?)?;
+        assert!(decoded.values.iter().all(|value| value.is_finite()));
+        assert!(max_error(&bf16_edges, &decoded.values) <= codec.error_bound(&bf16_edges, 2));
+
+        let specials = [f32::NAN, f32::INFINITY, f32::NEG_INFINITY, 65504.0];
+        let decoded = FloatCodec::decode(&FloatCodec::new(Quantization::Float16).encode(&specials, 4)?)?;
+        assert!(decoded.values[0].is_nan());
+        assert_eq!(&decoded.values[1..], &specials[1..]);
+        assert!(FloatCodec::new(Quantization::Float16).encode(&[70000.0], 1).is_err());
+
+        let constant = [3.25f32; 16];
+        let codec = FloatCodec::new(Quantization::Int8);
+        assert_eq!(FloatCodec::decode(&codec.encode(&constant, 8)?)?.values, constant);
+        let wide = [f32::MIN, f32::MAX, 0.0, 1.0];
+        let decoded = FloatCodec::decode(&codec.encode(&wide, 4)?)?;
+        assert!(max_error(&wide, &decoded.values) <= codec.error_bound(&wide, 4));
+        assert!(codec.encode(&[1.0, f32::NAN], 2).is_err());
+        assert!(codec.encode(&[1.0, 2.0, 3.0], 2).is_err());
+
+        let empty = FloatCodec::decode(&codec.encode(&[], 0)?)?;
+        assert!(empty.values.is_empty());
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,178 @@
+// src/aproar/compression/registry.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[COMPRESSION]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use super::*;
+use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
+
+/// Builds a fresh strategy for a registered codec.
+pub type CodecFactory = fn() -> Box<dyn CompressionStrategy>;
+
+static BUILTIN: Lazy<CodecRegistry> = Lazy::new(|| {
+    let mut registry = CodecRegistry { codecs: BTreeMap::new() };
+    registry.register(CodecKind::Uncompressed, || Box::new(NoCompression));
+    registry.register(CodecKind::Lz4, || Box::new(LZ4Compression));
+    registry.register(CodecKind::Zstd, || Box::new(ZstdCompression));
+    registry.register(CodecKind::ZstdDict, || Box::new(ZstdDictCompression::unresolved()));
+    #[cfg(feature = "snappy-compression")]
+    registry.register(CodecKind::Snappy, || Box::new(SnappyCompression));
+    #[cfg(feature = "gzip-compression")]
+    registry.register(CodecKind::Gzip, || Box::new(GzipCompression));
+    #[cfg(feature = "gzip-compression")]
+    registry.register(CodecKind::Deflate, || Box::new(DeflateCompression));
+    #[cfg(feature = "brotli-compression")]
+    registry.register(CodecKind::Brotli, || Box::new(BrotliCompression));
+    registry
+});
+
+/// The codecs this build can compress and decompress with, keyed by the id
+/// envelopes record. Codecs whose cargo feature is off are missing; their
+/// ids still parse, so a payload naming one fails to decode with an error
+/// saying which feature to enable rather than as corrupt.
+pub struct CodecRegistry {
+    codecs: BTreeMap<u8, (CodecKind, CodecFactory)>,
+}
+
+impl CodecRegistry {
+    /// The registry of codecs compiled into this build.
+    pub fn builtin() -> &'static CodecRegistry {
+        &BUILTIN
+    }
+
+    fn register(&mut self, codec: CodecKind, factory: CodecFactory) {
+        self.codecs.insert(codec.id(), (codec, factory));
+    }
+
+    /// The strategy for the codec with envelope id `id`.
+    pub fn get(&self, id: u8) -> Option<Box<dyn CompressionStrategy>> {
+        self.codecs.get(&id).map(|(_, factory)| factory())
+    }
+
+    pub fn contains(&self, codec: CodecKind) -> bool {
+        self.codecs.contains_key(&codec.id())
+    }
+
+    /// Every registered codec, in id order.
+    pub fn codecs(&self) -> impl Iterator<Item = CodecKind> + '_ {
+        self.codecs.values().map(|(codec, _)| *codec)
+    }
+}
+
+/// Stands in for a codec left out of this build; every call fails.
+pub(crate) struct UnavailableCodec(pub(crate) CodecKind);
+
+impl UnavailableCodec {
+    fn error(&self) -> OmniXError {
+        OmniXError::OperationFailed {
+            operation: format!("{} compression", self.0.name()),
+            details: format!(
+                "Codec '{}' is not compiled into this build; enable the '{}' cargo feature",
+                self.0.name(),
+                self.0.feature().unwrap_or("default")
+            ),
+        }
+    }
+}
+
+impl CompressionStrategy for UnavailableCodec {
+    fn compress(&self, _data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        Err(self.error())
+    }
+
+    fn decompress(&self, _compressed_data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        Err(self.error())
+    }
+
+    fn encoder<'a>(&self, _writer: Box<dyn Write + 'a>, _threads: usize) -> Result<Box<dyn StreamEncoder + 'a>, OmniXError> {
+        Err(self.error())
+    }
+
+    fn decoder<'a>(&self, _reader: Box<dyn Read + 'a>) -> Result<Box<dyn Read + 'a>, OmniXError> {
+        Err(self.error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(len: usize) -> Vec<u8> {
+        b"row 17, column 3: the quick brown fox jumps over the lazy dog. ".iter().copied().cycle().take(len).collect()
+    }
+
+    #[test]
+    fn test_every_registered_codec_round_trips() -> Result<(), OmniXError> {
+        let registry = CodecRegistry::builtin();
+        let manager = CompressionManager::new(OmniXMetry::init()?);
+        let payload = text(256 * 1024);
+
+        // Dictionary payloads need a dictionary; `ZstdDictionaries` covers them.
+        for codec in registry.codecs().filter(|codec| *codec != CodecKind::ZstdDict) {
+            let strategy = registry.get(codec.id()).expect("registered codec");
+
+            let compressed = strategy.compress(&payload)?;
+            assert_eq!(strategy.decompress(&compressed)?, payload, "{}", codec.name());
+            assert_eq!(strategy.decompress(&strategy.compress(&[])?)?, Vec::<u8>::new(), "{}", codec.name());
+
+            let streamed = strategy.compress_parallel(&payload, 4)?;
+            assert_eq!(strategy.decompress(&streamed)?, payload, "{}", codec.name());
+            for encoded in [&compressed, &streamed] {
+                let mut decoded = Vec::new();
+                strategy
+                    .decoder(Box::new(encoded.as_slice()))?
+                    .read_to_end(&mut decoded)
+                    .map_err(|e| OmniXError::OperationFailed { operation: codec.name().to_string(), details: e.to_string() })?;
+                assert_eq!(decoded, payload, "{}", codec.name());
+            }
+
+            let sealed = manager.seal(codec, &payload)?;
+            assert_eq!(Envelope::parse(&sealed)?.codec, codec);
+            assert_eq!(manager.unseal(&sealed)?, payload, "{}", codec.name());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_levels_decode_with_the_plain_codec() -> Result<(), OmniXError> {
+        let payload = text(256 * 1024);
+        let registry = CodecRegistry::builtin();
+        let leveled = [
+            (CodecKind::Zstd, 1, 19),
+            (CodecKind::Lz4, 0, 12),
+            (CodecKind::Gzip, 1, 9),
+            (CodecKind::Deflate, 1, 9),
+            (CodecKind::Brotli, 1, 11),
+        ];
+        for (codec, fast, small) in leveled.into_iter().filter(|(codec, _, _)| registry.contains(*codec)) {
+            let fast = CompressionChoice::with_level(codec, fast).strategy().compress(&payload)?;
+            let small = CompressionChoice::with_level(codec, small).strategy().compress(&payload)?;
+            assert!(small.len() <= fast.len(), "{}", codec.name());
+            for compressed in [&fast, &small] {
+                assert_eq!(codec.strategy().decompress(compressed)?, payload, "{}", codec.name());
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_ids_name_every_codec() {
+        let registry = CodecRegistry::builtin();
+        for codec in registry.codecs() {
+            assert_eq!(CodecKind::from_id(codec.id()), Some(codec));
+            assert!(codec.is_available());
+        }
+        assert!(registry.contains(CodecKind::Zstd));
+        assert!(registry.get(u8::MAX).is_none());
+    }
+
+    #[cfg(feature = "snappy-compression")]
+    #[test]
+    fn test_snappy_reads_raw_blocks_and_framed_streams() -> Result<(), OmniXError> {
+        let payload = text(64 * 1024);
+        let raw = SnappyCompression.compress(&payload)?;
+        assert!(!raw.starts_with(&SNAPPY_STREAM_MAGIC));
+        let framed = SnappyCompression.compress_parallel(&payload, 1)?;
+        assert!(framed.starts_with(&SNAPPY_STREAM_MAGIC));
+        assert_eq!(SnappyCompression.decompress(&raw)?, SnappyCompression.decompress(&framed)?);
+        Ok(())
+    }
+}
\ No newline at end of file
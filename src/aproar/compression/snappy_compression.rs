@@ -0,0 +1,71 @@
+// src/aproar/compression/snappy_compression.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[COMPRESSION]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use super::{CompressionStrategy, OmniXError, StreamEncoder};
+use snap::read::FrameDecoder;
+use snap::write::FrameEncoder;
+use std::io::{self, Cursor, Read, Write};
+
+/// Every Snappy framed stream starts with this stream identifier chunk.
+pub const SNAPPY_STREAM_MAGIC: [u8; 10] = [0xFF, 0x06, 0x00, 0x00, b's', b'N', b'a', b'P', b'p', b'Y'];
+
+/// Snappy. `compress` writes a raw block, the form Parquet and Arrow use
+/// for their pages; `encoder` writes the framed stream format, which
+/// checksums each chunk. `decompress` and `decoder` read either.
+pub struct SnappyCompression;
+
+impl CompressionStrategy for SnappyCompression {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        snap::raw::Encoder::new()
+            .compress_vec(data)
+            .map_err(|e| snappy_error("Snappy compression", e.into()))
+    }
+
+    fn decompress(&self, compressed_data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        if !compressed_data.starts_with(&SNAPPY_STREAM_MAGIC) {
+            return snap::raw::Decoder::new()
+                .decompress_vec(compressed_data)
+                .map_err(|e| snappy_error("Snappy decompression", e.into()));
+        }
+        let mut decompressed = Vec::with_capacity(compressed_data.len() * 2);
+        FrameDecoder::new(compressed_data)
+            .read_to_end(&mut decompressed)
+            .map_err(|e| snappy_error("Snappy decompression", e))?;
+        Ok(decompressed)
+    }
+
+    /// Snappy is fast enough that one core keeps up with storage, so
+    /// `threads` is ignored.
+    fn encoder<'a>(&self, writer: Box<dyn Write + 'a>, _threads: usize) -> Result<Box<dyn StreamEncoder + 'a>, OmniXError> {
+        Ok(Box::new(FrameEncoder::new(writer)))
+    }
+
+    /// A raw block has no framing to stream by, so it is read whole first.
+    fn decoder<'a>(&self, mut reader: Box<dyn Read + 'a>) -> Result<Box<dyn Read + 'a>, OmniXError> {
+        let mut prefix = Vec::with_capacity(SNAPPY_STREAM_MAGIC.len());
+        (&mut reader)
+            .take(SNAPPY_STREAM_MAGIC.len() as u64)
+            .read_to_end(&mut prefix)
+            .map_err(|e| snappy_error("Snappy decompression", e))?;
+        if prefix == SNAPPY_STREAM_MAGIC {
+            return Ok(Box::new(FrameDecoder::new(Cursor::new(prefix).chain(reader))));
+        }
+        reader.read_to_end(&mut prefix).map_err(|e| snappy_error("Snappy decompression", e))?;
+        Ok(Box::new(Cursor::new(self.decompress(&prefix)?)))
+    }
+}
+
+impl<W: Write> StreamEncoder for FrameEncoder<W> {
+    fn finish(self: Box<Self>) -> Result<(), OmniXError> {
+        let mut writer = self
+            .into_inner()
+            .map_err(|e| snappy_error("Snappy compression", io::Error::new(e.error().kind(), e.to_string())))?;
+        writer.flush().map_err(|e| snappy_error("Snappy compression", e))
+    }
+}
+
+fn snappy_error(operation: &str, e: io::Error) -> OmniXError {
+    OmniXError::OperationFailed {
+        operation: operation.to_string(),
+        details: e.to_string(),
+    }
+}
\ No newline at end of file
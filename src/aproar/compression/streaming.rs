@@ -0,0 +1,196 @@
+// src/aproar/compression/streaming.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[COMPRESSION]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use super::{
+    compression_threads, stream_header, verify_decoded, CodecKind, CompressionChoice, CompressionStrategy, Envelope, EnvelopeHeader,
+    OmniXError, ZstdDictionaries, ENVELOPE_HEADER_LEN,
+};
+use crate::aproar::storage::ByteReader;
+use crate::constants::ZSTD_COMPRESSION_LEVEL;
+use async_compression::tokio::bufread::{ZstdDecoder, ZstdEncoder};
+use async_compression::zstd::CParameter;
+use async_compression::Level;
+use std::io::{self, Cursor};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, BufReader, ReadBuf};
+
+/// Compresses `reader` with Zstd as it is read and prefixes the stream
+/// envelope header. LZ4 frames have no async encoder, so streamed payloads
+/// always use Zstd, with its frame checksum turned on and one worker per
+/// core.
+pub fn encode_stream(reader: ByteReader, level: i32) -> ByteReader {
+    let encoder = ZstdEncoder::with_quality_and_params(
+        BufReader::new(reader),
+        Level::Precise(level),
+        &[CParameter::checksum_flag(true), CParameter::nb_workers(compression_threads() as u32)],
+    );
+    Box::new(Cursor::new(stream_header(CodecKind::Zstd).to_vec()).chain(encoder))
+}
+
+/// Whether `encode_stream_with` can encode `codec` as it is read. The
+/// other codecs have no async encoder and have to be sealed whole.
+pub fn streams(codec: CodecKind) -> bool {
+    matches!(codec, CodecKind::Zstd | CodecKind::Uncompressed)
+}
+
+/// Like `encode_stream`, for a codec and level picked by the compression
+/// policy or the adaptive selector.
+pub fn encode_stream_with(reader: ByteReader, choice: CompressionChoice) -> Result<ByteReader, OmniXError> {
+    match choice.codec {
+        CodecKind::Zstd => Ok(encode_stream(reader, choice.level.unwrap_or(ZSTD_COMPRESSION_LEVEL))),
+        CodecKind::Uncompressed => Ok(Box::new(Cursor::new(stream_header(CodecKind::Uncompressed).to_vec()).chain(reader))),
+        codec => Err(OmniXError::OperationFailed {
+            operation: "Stream compression".to_string(),
+            details: format!("Codec '{}' cannot compress a stream", codec.name()),
+        }),
+    }
+}
+
+/// Decodes anything `encode_stream` or `CompressionManager::seal` produced,
+/// plus legacy bare blobs. Zstd and uncompressed payloads are read
+/// incrementally; LZ4 payloads have to be read whole first. Dictionary
+/// payloads need `CompressionManager::decode_stream`.
+pub async fn decode_stream(reader: ByteReader) -> Result<ByteReader, OmniXError> {
+    decode_stream_with(reader, None).await
+}
+
+pub(crate) async fn decode_stream_with(
+    mut reader: ByteReader,
+    dictionaries: Option<&ZstdDictionaries>,
+) -> Result<ByteReader, OmniXError> {
+    let mut prefix = Vec::with_capacity(ENVELOPE_HEADER_LEN);
+    (&mut reader)
+        .take(ENVELOPE_HEADER_LEN as u64)
+        .read_to_end(&mut prefix)
+        .await
+        .map_err(stream_error)?;
+
+    let envelope = Envelope::parse(&prefix)?;
+    let (codec, header) = (envelope.codec, envelope.header);
+
+    match codec {
+        CodecKind::Zstd => {
+            let rest = prefix[Envelope::header_len(&prefix)..].to_vec();
+            let body = Cursor::new(rest).chain(reader);
+            let mut decoder = ZstdDecoder::new(BufReader::new(body));
+            // Keeps the decoder reading to the end of `reader`, so wrappers
+            // that check a trailer at EOF (see `integrity::unframe_stream`) run.
+            decoder.multiple_members(true);
+            match header {
+                Some(header) if header.checksum.is_some() => Ok(Box::new(VerifyingReader::new(decoder, header))),
+                _ => Ok(Box::new(decoder)),
+            }
+        }
+        CodecKind::Uncompressed => {
+            let rest = prefix[Envelope::header_len(&prefix)..].to_vec();
+            let body = Cursor::new(rest).chain(reader);
+            match header {
+                Some(header) if header.checksum.is_some() => Ok(Box::new(VerifyingReader::new(body, header))),
+                _ => Ok(Box::new(body)),
+            }
+        }
+        // Dictionary payloads are small, so reading them whole costs little.
+        // The other codecs have no async decoder and are read whole as well.
+        CodecKind::Lz4
+        | CodecKind::ZstdDict
+        | CodecKind::Snappy
+        | CodecKind::Gzip
+        | CodecKind::Deflate
+        | CodecKind::Brotli => {
+            let mut stored = prefix;
+            reader.read_to_end(&mut stored).await.map_err(stream_error)?;
+            let envelope = Envelope::parse(&stored)?;
+            let data = match (envelope.codec, dictionaries) {
+                (CodecKind::ZstdDict, Some(dictionaries)) => dictionaries.strategy_for(envelope.payload)?.decompress(envelope.payload)?,
+                (codec, _) => codec.strategy().decompress(envelope.payload)?,
+            };
+            envelope.verify(&data)?;
+            Ok(Box::new(Cursor::new(data)))
+        }
+    }
+}
+
+fn stream_error(e: io::Error) -> OmniXError {
+    OmniXError::OperationFailed {
+        operation: "Stream decompression".to_string(),
+        details: e.to_string(),
+    }
+}
+
+/// Hashes decompressed bytes as they pass and fails the read that hits EOF
+/// if the length or CRC32 disagree with the envelope header.
+struct VerifyingReader<R> {
+    inner: R,
+    header: EnvelopeHeader,
+    hasher: crc32fast::Hasher,
+    len: u64,
+    verified: bool,
+}
+
+impl<R> VerifyingReader<R> {
+    fn new(inner: R, header: EnvelopeHeader) -> Self {
+        Self { inner, header, hasher: crc32fast::Hasher::new(), len: 0, verified: false }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for VerifyingReader<R> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let had_room = buf.remaining() > 0;
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        let read = &buf.filled()[before..];
+
+        if !read.is_empty() {
+            this.hasher.update(read);
+            this.len += read.len() as u64;
+        } else if had_room && !this.verified {
+            this.verified = true;
+            let hasher = std::mem::replace(&mut this.hasher, crc32fast::Hasher::new());
+            verify_decoded(&this.header, this.len, || hasher.finalize())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aproar::compression::CompressionManager;
+    use crate::omnixtracker::OmniXMetry;
+
+    async fn read_all(reader: ByteReader) -> Vec<u8> {
+        let mut out = Vec::new();
+        decode_stream(reader).await.unwrap().read_to_end(&mut out).await.unwrap();
+        out
+    }
+
+    #[tokio::test]
+    async fn test_stream_round_trip_and_sealed_payloads() -> Result<(), OmniXError> {
+        let payload: Vec<u8> = (0..3 * 1024 * 1024).map(|i| (i % 97) as u8).collect();
+
+        let mut encoded = Vec::new();
+        encode_stream(Box::new(Cursor::new(payload.clone())), ZSTD_COMPRESSION_LEVEL)
+            .read_to_end(&mut encoded)
+            .await
+            .unwrap();
+        assert!(encoded.len() < payload.len());
+        assert_eq!(read_all(Box::new(Cursor::new(encoded.clone()))).await, payload);
+
+        let manager = CompressionManager::new(OmniXMetry::init()?);
+        assert_eq!(manager.unseal(&encoded)?, payload);
+
+        let sealed = manager.seal(CodecKind::Zstd, b"sealed in one piece")?;
+        assert_eq!(read_all(Box::new(Cursor::new(sealed.clone()))).await, b"sealed in one piece");
+
+        // Flip a byte of the recorded CRC32 so only the trailing check can catch it.
+        let mut corrupted = sealed;
+        corrupted[14] ^= 0xff;
+        let mut out = Vec::new();
+        let result = decode_stream(Box::new(Cursor::new(corrupted))).await?.read_to_end(&mut out).await;
+        assert!(result.is_err());
+
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -1,17 +1,75 @@
 // src/aproar/compression/zstd_compression.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[COMPRESSION]Xyn>=====S===t===u===d===i===o===s======[R|$>
 
-use super::{CompressionStrategy, OmniXError};
+use super::{compression_threads, CompressionStrategy, OmniXError, StreamEncoder};
 use crate::constants::ZSTD_COMPRESSION_LEVEL;
-use zstd::stream::{encode_all, decode_all};
+use zstd::dict::{from_samples, DecoderDictionary, EncoderDictionary};
+use zstd::stream::{encode_all, decode_all, read::Decoder, write::Encoder};
 use anyhow::{Context, Result};
-use std::io::{Read, Write};
+use parking_lot::RwLock;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, BufReader, Read, Write};
 use std::fs::File;
+use std::sync::Arc;
+
+/// Dictionary-compressed payloads start with the id of their dictionary,
+/// little-endian, ahead of the Zstd frame.
+pub const ZSTD_DICT_ID_LEN: usize = 4;
 
 pub struct ZstdCompression;
 
+/// Zstd at a chosen level. The level only affects compression, so frames
+/// from every level share `CodecKind::Zstd`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZstdLevel(pub i32);
+
+impl ZstdCompression {
+    /// A checksummed Zstd frame writer at `ZSTD_COMPRESSION_LEVEL`.
+    pub fn writer<W: Write>(&self, writer: W, threads: usize) -> Result<ZstdFrameWriter<W>, OmniXError> {
+        ZstdLevel(ZSTD_COMPRESSION_LEVEL).writer(writer, threads)
+    }
+
+    /// Zstd primed with `dictionary`, for small payloads that share most of
+    /// their content with each other.
+    pub fn with_dictionary(&self, dictionary: Arc<ZstdDictionary>) -> ZstdDictCompression {
+        ZstdDictCompression { dictionary: Some(dictionary) }
+    }
+}
+
+impl ZstdLevel {
+    /// A checksummed Zstd frame writer. `threads` above one hands the frame
+    /// to that many zstd worker threads, which compress it in parallel jobs.
+    pub fn writer<W: Write>(&self, writer: W, threads: usize) -> Result<ZstdFrameWriter<W>, OmniXError> {
+        let mut encoder = Encoder::new(writer, self.0).map_err(|e| zstd_error("Zstd compression", e))?;
+        encoder.include_checksum(true).map_err(|e| zstd_error("Zstd compression", e))?;
+        if threads > 1 {
+            encoder.multithread(threads as u32).map_err(|e| zstd_error("Zstd compression", e))?;
+        }
+        Ok(ZstdFrameWriter { encoder })
+    }
+}
+
 impl CompressionStrategy for ZstdCompression {
     fn compress(&self, data: &[u8]) -> Result<Vec<u8>, OmniXError> {
-        encode_all(data, ZSTD_COMPRESSION_LEVEL)
+        ZstdLevel(ZSTD_COMPRESSION_LEVEL).compress(data)
+    }
+
+    fn decompress(&self, compressed_data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        ZstdLevel(ZSTD_COMPRESSION_LEVEL).decompress(compressed_data)
+    }
+
+    fn encoder<'a>(&self, writer: Box<dyn Write + 'a>, threads: usize) -> Result<Box<dyn StreamEncoder + 'a>, OmniXError> {
+        ZstdLevel(ZSTD_COMPRESSION_LEVEL).encoder(writer, threads)
+    }
+
+    fn decoder<'a>(&self, reader: Box<dyn Read + 'a>) -> Result<Box<dyn Read + 'a>, OmniXError> {
+        ZstdLevel(ZSTD_COMPRESSION_LEVEL).decoder(reader)
+    }
+}
+
+impl CompressionStrategy for ZstdLevel {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        encode_all(data, self.0)
             .map_err(|e| OmniXError::OperationFailed {
                 operation: "Zstd compression".to_string(),
                 details: e.to_string(),
@@ -25,48 +83,266 @@ impl CompressionStrategy for ZstdCompression {
                 details: e.to_string(),
             })
     }
+
+    fn encoder<'a>(&self, writer: Box<dyn Write + 'a>, threads: usize) -> Result<Box<dyn StreamEncoder + 'a>, OmniXError> {
+        Ok(Box::new(self.writer(writer, threads)?))
+    }
+
+    fn decoder<'a>(&self, reader: Box<dyn Read + 'a>) -> Result<Box<dyn Read + 'a>, OmniXError> {
+        let decoder = Decoder::new(reader).map_err(|e| zstd_error("Zstd decompression", e))?;
+        Ok(Box::new(decoder))
+    }
+}
+
+pub struct ZstdFrameWriter<W: Write> {
+    encoder: Encoder<'static, W>,
+}
+
+impl<W: Write> ZstdFrameWriter<W> {
+    /// Ends the frame and hands back the inner writer.
+    pub fn finish(self) -> Result<W, OmniXError> {
+        let mut writer = self.encoder.finish().map_err(|e| zstd_error("Zstd compression", e))?;
+        writer.flush().map_err(|e| zstd_error("Zstd compression", e))?;
+        Ok(writer)
+    }
+}
+
+impl<W: Write> Write for ZstdFrameWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.encoder.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder.flush()
+    }
+}
+
+impl<W: Write> StreamEncoder for ZstdFrameWriter<W> {
+    fn finish(self: Box<Self>) -> Result<(), OmniXError> {
+        ZstdFrameWriter::finish(*self).map(drop)
+    }
+}
+
+/// A trained Zstd dictionary. Ids are assigned by APROAR, grow with every
+/// dictionary adopted, and are never reused, so a blob's id names exactly
+/// the dictionary it was compressed with.
+pub struct ZstdDictionary {
+    id: u32,
+    bytes: Vec<u8>,
+    encoder: EncoderDictionary<'static>,
+    decoder: DecoderDictionary<'static>,
+}
+
+impl ZstdDictionary {
+    pub fn new(id: u32, bytes: Vec<u8>) -> Self {
+        let encoder = EncoderDictionary::copy(&bytes, ZSTD_COMPRESSION_LEVEL);
+        let decoder = DecoderDictionary::copy(&bytes);
+        Self { id, bytes, encoder, decoder }
+    }
+
+    /// Trains a dictionary of at most `max_size` bytes on `samples`. Zstd
+    /// needs a few dozen samples at least and fails on fewer.
+    pub fn train<S: AsRef<[u8]>>(id: u32, samples: &[S], max_size: usize) -> Result<Self, OmniXError> {
+        let bytes = from_samples(samples, max_size).map_err(|e| zstd_error("Zstd dictionary training", e))?;
+        Ok(Self::new(id, bytes))
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl fmt::Debug for ZstdDictionary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ZstdDictionary").field("id", &self.id).field("len", &self.bytes.len()).finish()
+    }
+}
+
+/// The id of the dictionary a dictionary-compressed payload needs.
+pub fn dictionary_id(payload: &[u8]) -> Option<u32> {
+    payload.get(..ZSTD_DICT_ID_LEN).map(|id| u32::from_le_bytes(id.try_into().expect("slice is 4 bytes")))
+}
+
+/// Zstd with a dictionary. Without one, as handed out by
+/// `CodecKind::strategy`, every call fails; `ZstdDictionaries::strategy_for`
+/// resolves the dictionary a payload needs.
+pub struct ZstdDictCompression {
+    dictionary: Option<Arc<ZstdDictionary>>,
+}
+
+impl ZstdDictCompression {
+    pub(crate) fn unresolved() -> Self {
+        Self { dictionary: None }
+    }
+
+    fn dictionary(&self) -> Result<&ZstdDictionary, OmniXError> {
+        self.dictionary.as_deref().ok_or_else(|| OmniXError::OperationFailed {
+            operation: "Zstd dictionary compression".to_string(),
+            details: "No dictionary was resolved for this payload".to_string(),
+        })
+    }
+
+    /// Splits off and checks the dictionary id.
+    fn frame<'a>(&self, payload: &'a [u8]) -> Result<(&ZstdDictionary, &'a [u8]), OmniXError> {
+        let dictionary = self.dictionary()?;
+        match dictionary_id(payload) {
+            Some(id) if id == dictionary.id => Ok((dictionary, &payload[ZSTD_DICT_ID_LEN..])),
+            id => Err(OmniXError::OperationFailed {
+                operation: "Zstd decompression".to_string(),
+                details: format!("Payload needs dictionary {:?}, not {}", id, dictionary.id),
+            }),
+        }
+    }
+}
+
+impl CompressionStrategy for ZstdDictCompression {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        let dictionary = self.dictionary()?;
+        let mut compressor = zstd::bulk::Compressor::with_prepared_dictionary(&dictionary.encoder)
+            .map_err(|e| zstd_error("Zstd compression", e))?;
+        let frame = compressor.compress(data).map_err(|e| zstd_error("Zstd compression", e))?;
+        let mut compressed = Vec::with_capacity(ZSTD_DICT_ID_LEN + frame.len());
+        compressed.extend_from_slice(&dictionary.id.to_le_bytes());
+        compressed.extend_from_slice(&frame);
+        Ok(compressed)
+    }
+
+    fn decompress(&self, compressed_data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        let (dictionary, frame) = self.frame(compressed_data)?;
+        let mut decompressed = Vec::with_capacity(frame.len() * 4);
+        Decoder::with_prepared_dictionary(frame, &dictionary.decoder)
+            .and_then(|mut decoder| decoder.read_to_end(&mut decompressed))
+            .map_err(|e| zstd_error("Zstd decompression", e))?;
+        Ok(decompressed)
+    }
+
+    fn encoder<'a>(&self, mut writer: Box<dyn Write + 'a>, threads: usize) -> Result<Box<dyn StreamEncoder + 'a>, OmniXError> {
+        let dictionary = self.dictionary()?;
+        writer.write_all(&dictionary.id.to_le_bytes()).map_err(|e| zstd_error("Zstd compression", e))?;
+        let mut encoder = Encoder::with_dictionary(writer, ZSTD_COMPRESSION_LEVEL, &dictionary.bytes)
+            .map_err(|e| zstd_error("Zstd compression", e))?;
+        encoder.include_checksum(true).map_err(|e| zstd_error("Zstd compression", e))?;
+        if threads > 1 {
+            encoder.multithread(threads as u32).map_err(|e| zstd_error("Zstd compression", e))?;
+        }
+        Ok(Box::new(ZstdFrameWriter { encoder }))
+    }
+
+    fn decoder<'a>(&self, mut reader: Box<dyn Read + 'a>) -> Result<Box<dyn Read + 'a>, OmniXError> {
+        let mut id = [0u8; ZSTD_DICT_ID_LEN];
+        reader.read_exact(&mut id).map_err(|e| zstd_error("Zstd decompression", e))?;
+        let (dictionary, _) = self.frame(&id)?;
+        let decoder = Decoder::with_dictionary(BufReader::new(reader), &dictionary.bytes)
+            .map_err(|e| zstd_error("Zstd decompression", e))?;
+        Ok(Box::new(decoder))
+    }
+}
+
+/// Every dictionary stored blobs may refer to, by id. New payloads use the
+/// active dictionary; older ones stay loaded for as long as the process runs.
+#[derive(Debug, Default)]
+pub struct ZstdDictionaries {
+    set: RwLock<DictionarySet>,
+}
+
+#[derive(Debug, Default)]
+struct DictionarySet {
+    dictionaries: BTreeMap<u32, Arc<ZstdDictionary>>,
+    active: Option<u32>,
 }
 
-/// Compresses data using Zstandard (Zstd) and writes to a file
+impl ZstdDictionaries {
+    /// Adds `dictionary` without making it active.
+    pub fn insert(&self, dictionary: Arc<ZstdDictionary>) {
+        self.set.write().dictionaries.insert(dictionary.id, dictionary);
+    }
+
+    pub fn activate(&self, id: u32) -> Result<(), OmniXError> {
+        let mut set = self.set.write();
+        if !set.dictionaries.contains_key(&id) {
+            return Err(OmniXError::ValidationError(format!("Zstd dictionary {} is not loaded", id)));
+        }
+        set.active = Some(id);
+        Ok(())
+    }
+
+    pub fn active(&self) -> Option<Arc<ZstdDictionary>> {
+        let set = self.set.read();
+        set.active.and_then(|id| set.dictionaries.get(&id).cloned())
+    }
+
+    pub fn get(&self, id: u32) -> Option<Arc<ZstdDictionary>> {
+        self.set.read().dictionaries.get(&id).cloned()
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        self.set.read().dictionaries.contains_key(&id)
+    }
+
+    /// The id the next trained dictionary gets.
+    pub fn next_id(&self) -> u32 {
+        self.set.read().dictionaries.keys().next_back().map_or(1, |id| id + 1)
+    }
+
+    /// The strategy that decodes `payload`, a dictionary-compressed blob.
+    pub fn strategy_for(&self, payload: &[u8]) -> Result<ZstdDictCompression, OmniXError> {
+        let id = dictionary_id(payload).ok_or_else(|| OmniXError::OperationFailed {
+            operation: "Zstd decompression".to_string(),
+            details: format!("Truncated dictionary id: {} bytes", payload.len()),
+        })?;
+        let dictionary = self.get(id).ok_or_else(|| OmniXError::OperationFailed {
+            operation: "Zstd decompression".to_string(),
+            details: format!("Zstd dictionary {} is not loaded", id),
+        })?;
+        Ok(ZstdCompression.with_dictionary(dictionary))
+    }
+}
+
+fn zstd_error(operation: &str, e: io::Error) -> OmniXError {
+    OmniXError::OperationFailed {
+        operation: operation.to_string(),
+        details: e.to_string(),
+    }
+}
+
+/// Compresses a file with Zstandard (Zstd) on every core, streaming it through
 pub fn compress_data_with_zstd(input_path: &str, output_path: &str) -> Result<()> {
     let mut input_file = File::open(input_path)
         .with_context(|| format!("Failed to open input file: {}", input_path))?;
-    let mut buffer = Vec::new();
-    input_file
-        .read_to_end(&mut buffer)
-        .with_context(|| format!("Failed to read input file: {}", input_path))?;
-
-    let zstd_compressor = ZstdCompression;
-    let compressed_data = zstd_compressor.compress(&buffer)
-        .with_context(|| "Failed to compress data with Zstd")?;
-
-    let mut output_file = File::create(output_path)
+    let output_file = File::create(output_path)
         .with_context(|| format!("Failed to create output file: {}", output_path))?;
-    output_file
-        .write_all(&compressed_data)
+
+    let mut writer = ZstdCompression.writer(output_file, compression_threads())
+        .with_context(|| "Failed to start Zstd frame")?;
+    io::copy(&mut input_file, &mut writer)
+        .with_context(|| format!("Failed to compress {} with Zstd", input_path))?;
+    writer
+        .finish()
+        .with_context(|| "Failed to finish Zstd frame")?
+        .sync_all()
         .with_context(|| "Failed to write compressed data to file")?;
 
     println!("Data compressed with Zstd and written to {}", output_path);
     Ok(())
 }
 
-/// Decompresses Zstd compressed data from a file
+/// Decompresses a Zstd file, streaming it through
 pub fn decompress_data_with_zstd(input_path: &str, output_path: &str) -> Result<()> {
-    let mut input_file = File::open(input_path)
+    let input_file = File::open(input_path)
         .with_context(|| format!("Failed to open input file: {}", input_path))?;
-    let mut compressed_data = Vec::new();
-    input_file
-        .read_to_end(&mut compressed_data)
-        .with_context(|| format!("Failed to read compressed file: {}", input_path))?;
-
-    let zstd_decompressor = ZstdCompression;
-    let decompressed_data = zstd_decompressor.decompress(&compressed_data)
-        .with_context(|| "Failed to decompress data with Zstd")?;
-
     let mut output_file = File::create(output_path)
         .with_context(|| format!("Failed to create output file: {}", output_path))?;
+
+    let mut reader = Decoder::new(input_file)
+        .with_context(|| format!("Failed to read compressed file: {}", input_path))?;
+    io::copy(&mut reader, &mut output_file)
+        .with_context(|| "Failed to decompress data with Zstd")?;
     output_file
-        .write_all(&decompressed_data)
+        .sync_all()
         .with_context(|| "Failed to write decompressed data to file")?;
 
     println!("Data decompressed with Zstd and written to {}", output_path);
@@ -102,4 +378,29 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_parallel_frames_round_trip_through_streams() -> Result<(), OmniXError> {
+        let payload: Vec<u8> = (0..12 * 1024 * 1024u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 27) as u8).collect();
+
+        let compressed = ZstdCompression.compress_parallel(&payload, 4)?;
+        assert!(compressed.len() < payload.len());
+        assert_eq!(ZstdCompression.decompress(&compressed)?, payload);
+
+        let mut decoded = Vec::new();
+        ZstdCompression
+            .decoder(Box::new(compressed.as_slice()))?
+            .read_to_end(&mut decoded)
+            .map_err(|e| zstd_error("Zstd decompression", e))?;
+        assert_eq!(decoded, payload);
+
+        let mut streamed = Vec::new();
+        let mut encoder = ZstdCompression.encoder(Box::new(&mut streamed), 1)?;
+        for piece in payload.chunks(100_000) {
+            encoder.write_all(piece).map_err(|e| zstd_error("Zstd compression", e))?;
+        }
+        encoder.finish()?;
+        assert_eq!(ZstdCompression.decompress(&streamed)?, payload);
+        Ok(())
+    }
 }
\ No newline at end of file
@@ -0,0 +1,606 @@
+// src/aproar/config.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[APROAR]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use crate::aproar::compression::{CodecKind, CompressionGoal};
+use crate::aproar::RetentionRule;
+use crate::security::{AESEncryption, MasterKey};
+use crate::aproar::retrieval::{MemoryCache, RedisCache, RetrievalCache};
+#[cfg(feature = "rocksdb-storage")]
+use crate::aproar::retrieval::RocksDBStorage;
+use crate::aproar::storage::{FsStorage, MemoryStorage, StorageBackend};
+#[cfg(feature = "hdf5-storage")]
+use crate::aproar::storage::HDF5Storage;
+#[cfg(feature = "parquet-storage")]
+use crate::aproar::storage::ParquetStorage;
+#[cfg(feature = "tiledb-storage")]
+use crate::aproar::storage::TileDBStorage;
+use crate::omnixtracker::{OmniXError, OmniXMetry};
+use crate::constants::*;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+include!(concat!(env!("OUT_DIR"), "/config.rs"));
+
+/// Deployment layout for APROAR. Loaded from the `[aproar]` table of the
+/// file at `CONFIG_PATH` (or `APROAR_CONFIG_PATH`), then overridden by any
+/// `APROAR_*` environment variables.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AproarConfig {
+    /// Storage tiers, hottest first.
+    pub storage_tiers: Vec<StorageTierConfig>,
+    pub caches: Vec<CacheConfig>,
+    pub compression: CompressionPolicy,
+    pub ntm: NtmDimensions,
+    /// Usage frequency above `tier_thresholds[i]` selects tier `i`; anything
+    /// at or below every threshold lands on the last tier.
+    pub tier_thresholds: Vec<usize>,
+    /// Copies kept of every key, each on a different tier.
+    pub replicas: usize,
+    pub max_concurrent_tasks: usize,
+    /// RocksDB directory for the write-ahead journal. Kept apart from the
+    /// RocksDB cache so cache eviction can never drop journal entries.
+    /// `None` turns journaling off; setting it needs `rocksdb-storage`.
+    pub journal_path: Option<PathBuf>,
+    /// Enforced by the background reaper alongside key TTLs.
+    pub retention_rules: Vec<RetentionRule>,
+    /// Encrypts values at rest when set. Setting `APROAR_MASTER_KEY` or
+    /// `APROAR_MASTER_KEYFILE` turns it on as well.
+    pub encryption: Option<EncryptionConfig>,
+}
+
+/// Where the master keys come from. Without `master_keyfile` the active key
+/// is read from `APROAR_MASTER_KEY` or `APROAR_MASTER_KEYFILE`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EncryptionConfig {
+    pub master_keyfile: Option<PathBuf>,
+    /// Earlier master keys, kept so values not yet re-encrypted stay readable.
+    pub previous_keyfiles: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StorageTierConfig {
+    Hdf5 { path: PathBuf },
+    Parquet { path: PathBuf },
+    Tiledb { uri: String },
+    Fs { path: PathBuf },
+    Memory { max_bytes: Option<usize> },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CacheConfig {
+    Redis { url: String },
+    Rocksdb { path: PathBuf },
+    Memory { max_bytes: Option<usize> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompressionPolicy {
+    /// Payloads larger than this use `large_payload_codec`.
+    pub large_payload_threshold: usize,
+    pub small_payload_codec: CodecKind,
+    pub large_payload_codec: CodecKind,
+    /// Picks the codec per payload from a sample of it when set, in place
+    /// of the size rule above.
+    pub adaptive: Option<CompressionGoal>,
+    /// Trains Zstd dictionaries on stored values when set, and compresses
+    /// small payloads with the latest one ahead of any other codec.
+    pub dictionary: Option<DictionaryConfig>,
+    /// Stores large values as content-defined chunks shared across keys
+    /// when set. Reads, overwrites and deletes stay transparent; turning it
+    /// off later leaves chunked values readable but no longer releases
+    /// their chunks on overwrite or delete.
+    pub dedup: Option<DedupConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DictionaryConfig {
+    /// Payloads up to this many bytes use the dictionary and serve as
+    /// training samples.
+    pub max_payload: usize,
+    pub dictionary_size: usize,
+    pub sample_keys: usize,
+    pub retrain_interval_secs: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DedupConfig {
+    /// Values smaller than this many bytes are stored whole.
+    pub min_payload: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NtmDimensions {
+    pub input_size: usize,
+    pub output_size: usize,
+    pub memory_size: usize,
+    pub memory_vector_size: usize,
+    pub controller_size: usize,
+    pub context_window_size: usize,
+}
+
+impl Default for AproarConfig {
+    fn default() -> Self {
+        Self {
+            storage_tiers: default_storage_tiers(),
+            caches: [
+                Some(CacheConfig::Redis { url: "redis://127.0.0.1/".to_string() }),
+                cfg!(feature = "rocksdb-storage").then(|| CacheConfig::Rocksdb { path: PathBuf::from("rocksdb_data") }),
+            ]
+            .into_iter()
+            .flatten()
+            .collect(),
+            compression: CompressionPolicy::default(),
+            ntm: NtmDimensions::default(),
+            tier_thresholds: vec![HIGH_FREQUENCY_THRESHOLD, MEDIUM_FREQUENCY_THRESHOLD],
+            replicas: 1,
+            max_concurrent_tasks: DEFAULT_MAX_CONCURRENT_TASKS,
+            journal_path: cfg!(feature = "rocksdb-storage").then(|| PathBuf::from("rocksdb_journal")),
+            retention_rules: Vec::new(),
+            encryption: None,
+        }
+    }
+}
+
+/// The native stores when they are compiled in, plain files otherwise, so a
+/// default build runs without any native library.
+fn default_storage_tiers() -> Vec<StorageTierConfig> {
+    vec![
+        if cfg!(feature = "hdf5-storage") {
+            StorageTierConfig::Hdf5 { path: PathBuf::from("data.h5") }
+        } else {
+            StorageTierConfig::Fs { path: PathBuf::from("aproar_data/hot") }
+        },
+        if cfg!(feature = "parquet-storage") {
+            StorageTierConfig::Parquet { path: PathBuf::from("parquet_data") }
+        } else {
+            StorageTierConfig::Fs { path: PathBuf::from("aproar_data/warm") }
+        },
+        if cfg!(feature = "tiledb-storage") {
+            StorageTierConfig::Tiledb { uri: "tiledb_array".to_string() }
+        } else {
+            StorageTierConfig::Fs { path: PathBuf::from("aproar_data/cold") }
+        },
+    ]
+}
+
+impl Default for CompressionPolicy {
+    fn default() -> Self {
+        Self {
+            large_payload_threshold: MAX_DATA_SIZE,
+            small_payload_codec: CodecKind::Lz4,
+            large_payload_codec: CodecKind::Zstd,
+            adaptive: None,
+            dictionary: None,
+            dedup: None,
+        }
+    }
+}
+
+impl Default for DictionaryConfig {
+    fn default() -> Self {
+        Self {
+            max_payload: ZSTD_DICT_MAX_PAYLOAD,
+            dictionary_size: ZSTD_DICT_SIZE,
+            sample_keys: ZSTD_DICT_SAMPLE_KEYS,
+            retrain_interval_secs: ZSTD_DICT_RETRAIN_INTERVAL_SECS,
+        }
+    }
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        Self { min_payload: DEDUP_MIN_PAYLOAD }
+    }
+}
+
+impl CompressionPolicy {
+    pub fn codec_for(&self, data_size: usize) -> CodecKind {
+        if data_size > self.large_payload_threshold {
+            self.large_payload_codec
+        } else {
+            self.small_payload_codec
+        }
+    }
+}
+
+impl Default for NtmDimensions {
+    fn default() -> Self {
+        Self {
+            input_size: NTM_INPUT_SIZE,
+            output_size: NTM_OUTPUT_SIZE,
+            memory_size: NTM_MEMORY_SIZE,
+            memory_vector_size: NTM_MEMORY_VECTOR_SIZE,
+            controller_size: NTM_CONTROLLER_SIZE,
+            context_window_size: CONTEXT_WINDOW_SIZE,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    aproar: Option<AproarConfig>,
+}
+
+impl AproarConfig {
+    /// Loads the config file named by `APROAR_CONFIG_PATH`, falling back to
+    /// the build-time `CONFIG_PATH`, then applies environment overrides. A
+    /// missing file is not an error; the defaults are used instead.
+    pub fn load() -> Result<Self, OmniXError> {
+        let path = env::var("APROAR_CONFIG_PATH").unwrap_or_else(|_| CONFIG_PATH.to_string());
+        let mut config = if Path::new(&path).exists() {
+            Self::from_toml_file(Path::new(&path))?
+        } else {
+            Self::default()
+        };
+        config.apply_env_overrides()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_toml_file(path: &Path) -> Result<Self, OmniXError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| OmniXError::FileSystemError(format!("Failed to read {}: {}", path.display(), e)))?;
+        Self::from_toml_str(&contents)
+    }
+
+    /// Parses either a whole application config with an `[aproar]` table or
+    /// a file holding only the APROAR keys.
+    pub fn from_toml_str(contents: &str) -> Result<Self, OmniXError> {
+        let parse_err = |e: toml::de::Error| OmniXError::ValidationError(format!("Invalid APROAR config: {}", e));
+        if let Some(config) = toml::from_str::<ConfigFile>(contents).map_err(parse_err)?.aproar {
+            return Ok(config);
+        }
+        toml::from_str(contents).map_err(parse_err)
+    }
+
+    /// Applies `APROAR_*` environment variables on top of the loaded values.
+    /// Tier and cache lists use `kind:location` entries separated by commas,
+    /// e.g. `APROAR_STORAGE_TIERS=fs:/var/aproar/hot,parquet:/var/aproar/warm`.
+    pub fn apply_env_overrides(&mut self) -> Result<(), OmniXError> {
+        if let Ok(value) = env::var("APROAR_STORAGE_TIERS") {
+            self.storage_tiers = split_list(&value).map(parse_storage_tier).collect::<Result<_, _>>()?;
+        }
+        if let Ok(value) = env::var("APROAR_CACHES") {
+            self.caches = split_list(&value).map(parse_cache).collect::<Result<_, _>>()?;
+        }
+        if let Ok(value) = env::var("APROAR_TIER_THRESHOLDS") {
+            self.tier_thresholds = split_list(&value)
+                .map(|threshold| parse_env_number("APROAR_TIER_THRESHOLDS", threshold))
+                .collect::<Result<_, _>>()?;
+        }
+        if let Ok(value) = env::var("APROAR_LARGE_PAYLOAD_THRESHOLD") {
+            self.compression.large_payload_threshold = parse_env_number("APROAR_LARGE_PAYLOAD_THRESHOLD", &value)?;
+        }
+        if let Ok(value) = env::var("APROAR_REPLICAS") {
+            self.replicas = parse_env_number("APROAR_REPLICAS", &value)?;
+        }
+        if let Ok(value) = env::var("APROAR_MAX_CONCURRENT_TASKS") {
+            self.max_concurrent_tasks = parse_env_number("APROAR_MAX_CONCURRENT_TASKS", &value)?;
+        }
+        if let Ok(value) = env::var("APROAR_JOURNAL_PATH") {
+            self.journal_path = Some(value.trim()).filter(|path| !path.is_empty()).map(PathBuf::from);
+        }
+        if self.encryption.is_none() && (env::var_os(MASTER_KEY_ENV).is_some() || env::var_os(MASTER_KEYFILE_ENV).is_some()) {
+            self.encryption = Some(EncryptionConfig::default());
+        }
+
+        let ntm_overrides: [(&str, &mut usize); 6] = [
+            ("APROAR_NTM_INPUT_SIZE", &mut self.ntm.input_size),
+            ("APROAR_NTM_OUTPUT_SIZE", &mut self.ntm.output_size),
+            ("APROAR_NTM_MEMORY_SIZE", &mut self.ntm.memory_size),
+            ("APROAR_NTM_MEMORY_VECTOR_SIZE", &mut self.ntm.memory_vector_size),
+            ("APROAR_NTM_CONTROLLER_SIZE", &mut self.ntm.controller_size),
+            ("APROAR_CONTEXT_WINDOW_SIZE", &mut self.ntm.context_window_size),
+        ];
+        for (name, field) in ntm_overrides {
+            if let Ok(value) = env::var(name) {
+                *field = parse_env_number(name, &value)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), OmniXError> {
+        if self.storage_tiers.is_empty() {
+            return Err(OmniXError::ValidationError("At least one storage tier must be configured".to_string()));
+        }
+        if self.tier_thresholds.windows(2).any(|pair| pair[0] < pair[1]) {
+            return Err(OmniXError::ValidationError("Tier thresholds must be in descending order".to_string()));
+        }
+        for tier in &self.storage_tiers {
+            if !tier.is_available() {
+                return Err(tier.unavailable());
+            }
+        }
+        for cache in &self.caches {
+            if !cache.is_available() {
+                return Err(cache.unavailable());
+            }
+        }
+        if self.journal_path.is_some() && !cfg!(feature = "rocksdb-storage") {
+            return Err(OmniXError::ValidationError(
+                "journal_path needs the 'rocksdb-storage' cargo feature".to_string(),
+            ));
+        }
+        if self.replicas == 0 || self.replicas > self.storage_tiers.len() {
+            return Err(OmniXError::ValidationError(format!(
+                "replicas must be between 1 and the number of storage tiers ({}), got {}",
+                self.storage_tiers.len(),
+                self.replicas
+            )));
+        }
+        for codec in [self.compression.small_payload_codec, self.compression.large_payload_codec] {
+            if codec == CodecKind::ZstdDict {
+                return Err(OmniXError::ValidationError(
+                    "zstd_dict is picked per payload once a dictionary is trained; configure the dictionary section instead".to_string(),
+                ));
+            }
+            if !codec.is_available() {
+                return Err(OmniXError::ValidationError(format!(
+                    "compression codec '{}' needs the '{}' cargo feature",
+                    codec.name(),
+                    codec.feature().unwrap_or_default()
+                )));
+            }
+        }
+        if let Some(goal) = &self.compression.adaptive {
+            if goal.min_throughput.is_nan() || goal.min_throughput < 0.0 || !(0.0..1.0).contains(&goal.min_savings) {
+                return Err(OmniXError::ValidationError(format!(
+                    "adaptive compression needs min_throughput >= 0 and min_savings in [0, 1), got {} and {}",
+                    goal.min_throughput, goal.min_savings
+                )));
+            }
+        }
+        if let Some(dictionary) = &self.compression.dictionary {
+            if dictionary.max_payload == 0 || dictionary.dictionary_size == 0 || dictionary.retrain_interval_secs == 0 {
+                return Err(OmniXError::ValidationError(
+                    "dictionary max_payload, dictionary_size and retrain_interval_secs must be positive".to_string(),
+                ));
+            }
+        }
+        if let Some(dedup) = &self.compression.dedup {
+            if dedup.min_payload == 0 {
+                return Err(OmniXError::ValidationError("dedup min_payload must be positive".to_string()));
+            }
+        }
+        for rule in &self.retention_rules {
+            if let RetentionRule::MaxBytesPerTier { tier, .. } = rule {
+                if *tier >= self.storage_tiers.len() {
+                    return Err(OmniXError::ValidationError(format!(
+                        "retention rule targets tier {}, but only {} storage tiers are configured",
+                        tier,
+                        self.storage_tiers.len()
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl StorageTierConfig {
+    pub fn open(&self, metrics: &OmniXMetry) -> Result<Arc<dyn StorageBackend>, OmniXError> {
+        match self {
+            #[cfg(feature = "hdf5-storage")]
+            StorageTierConfig::Hdf5 { path } => Ok(Arc::new(HDF5Storage::new(path.clone(), metrics.clone()))),
+            #[cfg(feature = "parquet-storage")]
+            StorageTierConfig::Parquet { path } => Ok(Arc::new(ParquetStorage::new(path.clone())?)),
+            #[cfg(feature = "tiledb-storage")]
+            StorageTierConfig::Tiledb { uri } => Ok(Arc::new(TileDBStorage::new(uri))),
+            StorageTierConfig::Fs { path } => Ok(Arc::new(FsStorage::new(path.clone(), metrics.clone())?)),
+            StorageTierConfig::Memory { max_bytes: Some(max_bytes) } => Ok(Arc::new(MemoryStorage::with_max_bytes(*max_bytes))),
+            StorageTierConfig::Memory { max_bytes: None } => Ok(Arc::new(MemoryStorage::new())),
+            #[allow(unreachable_patterns)]
+            other => Err(other.unavailable()),
+        }
+    }
+
+    /// The cargo feature that compiles this kind of store in, if any.
+    pub fn feature(&self) -> Option<&'static str> {
+        match self {
+            StorageTierConfig::Hdf5 { .. } => Some("hdf5-storage"),
+            StorageTierConfig::Parquet { .. } => Some("parquet-storage"),
+            StorageTierConfig::Tiledb { .. } => Some("tiledb-storage"),
+            StorageTierConfig::Fs { .. } | StorageTierConfig::Memory { .. } => None,
+        }
+    }
+
+    pub fn is_available(&self) -> bool {
+        match self {
+            StorageTierConfig::Hdf5 { .. } => cfg!(feature = "hdf5-storage"),
+            StorageTierConfig::Parquet { .. } => cfg!(feature = "parquet-storage"),
+            StorageTierConfig::Tiledb { .. } => cfg!(feature = "tiledb-storage"),
+            StorageTierConfig::Fs { .. } | StorageTierConfig::Memory { .. } => true,
+        }
+    }
+
+    fn unavailable(&self) -> OmniXError {
+        OmniXError::ValidationError(format!(
+            "{} storage needs the '{}' cargo feature",
+            self.kind(),
+            self.feature().unwrap_or_default()
+        ))
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            StorageTierConfig::Hdf5 { .. } => "hdf5",
+            StorageTierConfig::Parquet { .. } => "parquet",
+            StorageTierConfig::Tiledb { .. } => "tiledb",
+            StorageTierConfig::Fs { .. } => "fs",
+            StorageTierConfig::Memory { .. } => "memory",
+        }
+    }
+}
+
+impl CacheConfig {
+    pub fn open(&self, metrics: &OmniXMetry) -> Result<Arc<dyn RetrievalCache>, OmniXError> {
+        match self {
+            CacheConfig::Redis { url } => Ok(Arc::new(RedisCache::new(url, metrics.clone())?)),
+            #[cfg(feature = "rocksdb-storage")]
+            CacheConfig::Rocksdb { path } => Ok(Arc::new(RocksDBStorage::new(path, metrics.clone())?)),
+            CacheConfig::Memory { max_bytes } => {
+                let cache = match max_bytes {
+                    Some(max_bytes) => MemoryCache::with_max_bytes(*max_bytes),
+                    None => MemoryCache::new(),
+                };
+                Ok(Arc::new(cache.with_metrics(metrics.clone())))
+            }
+            #[allow(unreachable_patterns)]
+            other => Err(other.unavailable()),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CacheConfig::Redis { .. } => "redis",
+            CacheConfig::Rocksdb { .. } => "rocksdb",
+            CacheConfig::Memory { .. } => "memory",
+        }
+    }
+
+    pub fn is_available(&self) -> bool {
+        match self {
+            CacheConfig::Rocksdb { .. } => cfg!(feature = "rocksdb-storage"),
+            CacheConfig::Redis { .. } | CacheConfig::Memory { .. } => true,
+        }
+    }
+
+    fn unavailable(&self) -> OmniXError {
+        OmniXError::ValidationError(format!("{} cache needs the 'rocksdb-storage' cargo feature", self.kind()))
+    }
+}
+
+impl EncryptionConfig {
+    pub fn open(&self) -> Result<Arc<AESEncryption>, OmniXError> {
+        let encryption = match &self.master_keyfile {
+            Some(path) => AESEncryption::new(&MasterKey::from_keyfile(path)?),
+            None => AESEncryption::from_env()?.ok_or_else(|| {
+                OmniXError::ValidationError(format!(
+                    "Encryption is enabled but neither {} nor {} is set",
+                    MASTER_KEY_ENV, MASTER_KEYFILE_ENV
+                ))
+            })?,
+        };
+        for path in &self.previous_keyfiles {
+            encryption.add_key(&MasterKey::from_keyfile(path)?);
+        }
+        Ok(Arc::new(encryption))
+    }
+}
+
+fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value.split(',').map(str::trim).filter(|entry| !entry.is_empty())
+}
+
+fn parse_env_number(name: &str, value: &str) -> Result<usize, OmniXError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| OmniXError::EnvVarError(format!("{} must be a non-negative integer, got {:?}", name, value)))
+}
+
+fn parse_storage_tier(entry: &str) -> Result<StorageTierConfig, OmniXError> {
+    let (kind, location) = entry.split_once(':').unwrap_or((entry, ""));
+    match kind {
+        "hdf5" => Ok(StorageTierConfig::Hdf5 { path: PathBuf::from(location) }),
+        "parquet" => Ok(StorageTierConfig::Parquet { path: PathBuf::from(location) }),
+        "tiledb" => Ok(StorageTierConfig::Tiledb { uri: location.to_string() }),
+        "fs" => Ok(StorageTierConfig::Fs { path: PathBuf::from(location) }),
+        "memory" if location.is_empty() => Ok(StorageTierConfig::Memory { max_bytes: None }),
+        "memory" => Ok(StorageTierConfig::Memory { max_bytes: Some(parse_env_number("APROAR_STORAGE_TIERS", location)?) }),
+        _ => Err(OmniXError::EnvVarError(format!("Unknown storage tier kind in APROAR_STORAGE_TIERS: {}", kind))),
+    }
+}
+
+fn parse_cache(entry: &str) -> Result<CacheConfig, OmniXError> {
+    let (kind, location) = entry.split_once(':').unwrap_or((entry, ""));
+    match kind {
+        "redis" => Ok(CacheConfig::Redis { url: location.to_string() }),
+        "rocksdb" => Ok(CacheConfig::Rocksdb { path: PathBuf::from(location) }),
+        "memory" if location.is_empty() => Ok(CacheConfig::Memory { max_bytes: None }),
+        "memory" => Ok(CacheConfig::Memory { max_bytes: Some(parse_env_number("APROAR_CACHES", location)?) }),
+        _ => Err(OmniXError::EnvVarError(format!("Unknown cache kind in APROAR_CACHES: {}", kind))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_parses_aproar_table() -> Result<(), OmniXError> {
+        let config = AproarConfig::from_toml_str(
+            r#"
+            [aproar]
+            tier_thresholds = [50]
+
+            [[aproar.storage_tiers]]
+            kind = "fs"
+            path = "/tmp/aproar/hot"
+
+            [[aproar.storage_tiers]]
+            kind = "memory"
+            max_bytes = 1024
+
+            [aproar.compression]
+            large_payload_threshold = 4096
+            large_payload_codec = "zstd"
+            adaptive = { min_throughput = 100.0 }
+            "#,
+        )?;
+
+        assert_eq!(config.storage_tiers.len(), 2);
+        assert_eq!(config.storage_tiers[1], StorageTierConfig::Memory { max_bytes: Some(1024) });
+        assert_eq!(config.tier_thresholds, vec![50]);
+        assert_eq!(config.compression.codec_for(8192), CodecKind::Zstd);
+        assert_eq!(config.compression.codec_for(10), CodecKind::Lz4);
+        let goal = config.compression.adaptive.expect("adaptive goal is set");
+        assert_eq!(goal.min_throughput, 100.0);
+        assert_eq!(goal.min_savings, CompressionGoal::default().min_savings);
+        assert_eq!(config.ntm.memory_size, NTM_MEMORY_SIZE);
+        Ok(())
+    }
+
+    #[test]
+    fn test_shipped_default_config_validates() -> Result<(), OmniXError> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("config/default.toml");
+        let config = AproarConfig::from_toml_file(&path)?;
+
+        config.validate()?;
+        assert_eq!(config.storage_tiers.len(), 3);
+        assert_eq!(config.storage_tiers[0], StorageTierConfig::Fs { path: PathBuf::from("aproar_data/hot") });
+        Ok(())
+    }
+
+    #[test]
+    fn test_env_style_tier_entries() -> Result<(), OmniXError> {
+        assert_eq!(parse_storage_tier("parquet:/data/warm")?, StorageTierConfig::Parquet { path: PathBuf::from("/data/warm") });
+        assert_eq!(parse_cache("redis:redis://cache:6379/")?, CacheConfig::Redis { url: "redis://cache:6379/".to_string() });
+        assert!(parse_storage_tier("s3:bucket").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_tiers_without_their_feature_are_rejected() {
+        let tier = StorageTierConfig::Parquet { path: PathBuf::from("warm") };
+        let config = AproarConfig { storage_tiers: vec![tier.clone()], caches: Vec::new(), ..AproarConfig::default() };
+        if cfg!(feature = "parquet-storage") {
+            assert!(config.validate().is_ok());
+        } else {
+            assert!(matches!(config.validate(), Err(OmniXError::ValidationError(message)) if message.contains("parquet-storage")));
+            assert!(tier.open(&OmniXMetry::init().unwrap()).is_err());
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,360 @@
+// src/aproar/dedup.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[APROAR]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use crate::aproar::versioning::INTERNAL_KEY_PREFIX;
+use crate::aproar::AproarManager;
+use crate::constants::*;
+use crate::omnixtracker::OmniXError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+
+const CHUNK_KEY_PREFIX: &str = "__aproar_cas::";
+const REF_KEY_PREFIX: &str = "__aproar_ref::";
+/// Stored under a deduplicated key in place of its value.
+pub(crate) const DEDUP_STUB: &[u8] = b"APRD\x01";
+
+/// Per-byte random values for the gear rolling hash, generated with
+/// splitmix64 so every build cuts the same boundaries.
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+static GEAR: [u64; 256] = gear_table();
+
+/// Splits `data` at content-defined boundaries using a gear rolling hash, so
+/// an edit only changes the chunks around it instead of shifting every
+/// boundary after it. `avg_size` must be a power of two.
+pub fn content_defined_chunks(data: &[u8], min_size: usize, avg_size: usize, max_size: usize) -> Vec<&[u8]> {
+    let bits = avg_size.trailing_zeros();
+    let mask = if bits == 0 { 0 } else { u64::MAX << (64 - bits) };
+    let mut chunks = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let cut = cut_point(rest, min_size, mask, max_size);
+        let (chunk, tail) = rest.split_at(cut);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    chunks
+}
+
+fn cut_point(data: &[u8], min_size: usize, mask: u64, max_size: usize) -> usize {
+    if data.len() <= min_size {
+        return data.len();
+    }
+    let limit = data.len().min(max_size);
+    let mut hash = 0u64;
+    for (i, &byte) in data.iter().enumerate().take(limit).skip(min_size) {
+        hash = (hash << 1).wrapping_add(GEAR[byte as usize]);
+        if hash & mask == 0 {
+            return i + 1;
+        }
+    }
+    limit
+}
+
+/// What a user key points at: the hashes of its chunks, in order.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ContentManifest {
+    size: u64,
+    chunks: Vec<String>,
+}
+
+fn chunk_key(hash: &str) -> String {
+    format!("{}{}", CHUNK_KEY_PREFIX, hash)
+}
+
+pub(crate) fn ref_key(key: &str) -> String {
+    format!("{}{}", REF_KEY_PREFIX, key)
+}
+
+impl AproarManager {
+    /// The reference counts, rebuilt from the stored manifests the first time
+    /// they are needed and after the reaper deleted a manifest. Chunks
+    /// present in storage but referenced by no manifest count zero and are
+    /// left for `collect_garbage`.
+    async fn loaded_content_refs<'a>(
+        &self,
+        slot: &'a mut Option<HashMap<String, usize>>,
+    ) -> Result<&'a mut HashMap<String, usize>, OmniXError> {
+        if self.content_refs_stale.swap(false, Ordering::SeqCst) {
+            *slot = None;
+        }
+        if slot.is_none() {
+            let mut refs: HashMap<String, usize> = self
+                .list_keys(CHUNK_KEY_PREFIX)
+                .await?
+                .into_iter()
+                .map(|key| (key[CHUNK_KEY_PREFIX.len()..].to_string(), 0))
+                .collect();
+            for manifest_key in self.list_keys(REF_KEY_PREFIX).await? {
+                let manifest = self.read_content_manifest(&manifest_key[REF_KEY_PREFIX.len()..]).await?;
+                for hash in manifest.into_iter().flat_map(|manifest| manifest.chunks) {
+                    *refs.entry(hash).or_insert(0) += 1;
+                }
+            }
+            self.metrics.update_gauge("dedup.chunks".to_string(), refs.len() as f64);
+            *slot = Some(refs);
+        }
+        Ok(slot.get_or_insert_with(HashMap::new))
+    }
+
+    async fn read_content_manifest(&self, key: &str) -> Result<Option<ContentManifest>, OmniXError> {
+        let manifest_key = ref_key(key);
+        if !self.data_exists(&manifest_key).await? {
+            return Ok(None);
+        }
+        let raw = self.retrieve_value(&manifest_key).await?;
+        serde_json::from_slice(&raw).map(Some).map_err(|e| OmniXError::OperationFailed {
+            operation: "Content manifest decoding".to_string(),
+            details: e.to_string(),
+        })
+    }
+
+    fn release_chunks(refs: &mut HashMap<String, usize>, chunks: &[String]) {
+        for hash in chunks {
+            if let Some(count) = refs.get_mut(hash) {
+                *count = count.saturating_sub(1);
+            }
+        }
+    }
+
+    /// Whether the compression policy stores a `len`-byte value of `key` as
+    /// chunks. Internal keys, the chunks and manifests among them, never are.
+    pub(crate) fn deduplicates(&self, key: &str, len: usize) -> bool {
+        !key.starts_with(INTERNAL_KEY_PREFIX) && self.compression_policy.dedup.as_ref().is_some_and(|config| len >= config.min_payload)
+    }
+
+    /// Whether writes and deletes of `key` release the chunks it references.
+    pub(crate) fn dedup_enabled(&self, key: &str) -> bool {
+        self.compression_policy.dedup.is_some() && !key.starts_with(INTERNAL_KEY_PREFIX)
+    }
+
+    /// Stores `data` under `key` as `store_value` does, or as shared chunks
+    /// when the policy deduplicates it, and releases the chunks of the value
+    /// it replaces. Returns the number of bytes written.
+    pub(crate) async fn store_payload(&self, key: &str, data: &[u8], usage_frequency: usize) -> Result<u64, OmniXError> {
+        if self.deduplicates(key, data.len()) {
+            return self.store_content(key, data, usage_frequency).await;
+        }
+        let written = self.store_value(key, data, usage_frequency).await?;
+        if self.dedup_enabled(key) {
+            self.release_content(key).await?;
+        }
+        Ok(written)
+    }
+
+    /// Stores `data` as content-addressed chunks, its manifest, and a stub
+    /// under `key` that reads resolve through the manifest. Chunks already
+    /// stored by any key are referenced instead of written again. Chunks are
+    /// written before the manifest, so a failed store leaves at worst
+    /// unreferenced chunks for `collect_garbage`.
+    async fn store_content(&self, key: &str, data: &[u8], usage_frequency: usize) -> Result<u64, OmniXError> {
+        let mut guard = self.content_refs.lock().await;
+        let refs = self.loaded_content_refs(&mut guard).await?;
+        let mut manifest = ContentManifest { size: data.len() as u64, chunks: Vec::new() };
+        let (mut new_chunks, mut new_bytes, mut written) = (0usize, 0u64, 0u64);
+
+        for chunk in content_defined_chunks(data, CDC_MIN_CHUNK_SIZE, CDC_AVG_CHUNK_SIZE, CDC_MAX_CHUNK_SIZE) {
+            let hash = blake3::hash(chunk).to_hex().to_string();
+            if !refs.contains_key(&hash) {
+                written += self.store_value(&chunk_key(&hash), chunk, usage_frequency).await?;
+                refs.insert(hash.clone(), 0);
+                new_chunks += 1;
+                new_bytes += chunk.len() as u64;
+            }
+            manifest.chunks.push(hash);
+        }
+
+        let previous = self.read_content_manifest(key).await?;
+        let raw = serde_json::to_vec(&manifest).map_err(|e| OmniXError::OperationFailed {
+            operation: "Content manifest encoding".to_string(),
+            details: e.to_string(),
+        })?;
+        written += self.store_value(&ref_key(key), &raw, usage_frequency).await?;
+        written += self.store_value(key, DEDUP_STUB, usage_frequency).await?;
+        for hash in &manifest.chunks {
+            *refs.entry(hash.clone()).or_insert(0) += 1;
+        }
+        if let Some(previous) = previous {
+            Self::release_chunks(refs, &previous.chunks);
+        }
+
+        self.metrics.increment_counter("dedup.chunks.new".to_string(), new_chunks as u64);
+        self.metrics.increment_counter("dedup.chunks.reused".to_string(), (manifest.chunks.len() - new_chunks) as u64);
+        self.metrics.increment_counter("dedup.bytes.saved".to_string(), data.len() as u64 - new_bytes);
+        self.metrics.update_gauge("dedup.chunks".to_string(), refs.len() as f64);
+
+        Ok(written)
+    }
+
+    /// `value` as read from `key`, or the value its manifest reassembles when
+    /// it is a deduplication stub.
+    pub(crate) async fn resolve_content(&self, key: &str, value: Vec<u8>) -> Result<Vec<u8>, OmniXError> {
+        if value != DEDUP_STUB || key.starts_with(INTERNAL_KEY_PREFIX) {
+            return Ok(value);
+        }
+        match self.read_content_manifest(key).await? {
+            Some(manifest) => self.reassemble(key, &manifest).await,
+            None => Ok(value),
+        }
+    }
+
+    /// Checks every chunk against the hash it is stored under.
+    async fn reassemble(&self, key: &str, manifest: &ContentManifest) -> Result<Vec<u8>, OmniXError> {
+        let mut data = Vec::with_capacity(manifest.size as usize);
+        for hash in &manifest.chunks {
+            let chunk = self.retrieve_value(&chunk_key(hash)).await?;
+            if blake3::hash(&chunk).to_hex().as_str() != hash {
+                return Err(OmniXError::DataCorruption {
+                    key: key.to_string(),
+                    details: format!("chunk {} does not match its hash", hash),
+                });
+            }
+            data.extend_from_slice(&chunk);
+        }
+        if data.len() as u64 != manifest.size {
+            return Err(OmniXError::DataCorruption {
+                key: key.to_string(),
+                details: format!("expected {} bytes, reassembled {}", manifest.size, data.len()),
+            });
+        }
+        Ok(data)
+    }
+
+    /// Drops the manifest of `key`, if it has one, and its chunk references.
+    /// The chunks themselves stay until `collect_garbage` finds them
+    /// unreferenced.
+    pub(crate) async fn release_content(&self, key: &str) -> Result<(), OmniXError> {
+        if !self.data_exists(&ref_key(key)).await? {
+            return Ok(());
+        }
+        let mut guard = self.content_refs.lock().await;
+        let refs = self.loaded_content_refs(&mut guard).await?;
+        if let Some(manifest) = self.read_content_manifest(key).await? {
+            self.delete_value(&ref_key(key)).await?;
+            Self::release_chunks(refs, &manifest.chunks);
+        }
+        Ok(())
+    }
+
+    /// Deletes every chunk no key references any more and returns how many
+    /// were removed.
+    pub async fn collect_garbage(&self) -> Result<usize, OmniXError> {
+        let mut guard = self.content_refs.lock().await;
+        let refs = self.loaded_content_refs(&mut guard).await?;
+        let unreferenced: Vec<String> = refs.iter().filter(|(_, count)| **count == 0).map(|(hash, _)| hash.clone()).collect();
+
+        for hash in &unreferenced {
+            self.delete_data(&chunk_key(hash)).await?;
+            refs.remove(hash);
+        }
+
+        self.metrics.increment_counter("dedup.gc.collected".to_string(), unreferenced.len() as u64);
+        self.metrics.update_gauge("dedup.chunks".to_string(), refs.len() as f64);
+        Ok(unreferenced.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aproar::storage::{MemoryStorage, StorageBackend};
+    use crate::aproar::{AproarManagerBuilder, CompressionPolicy, DedupConfig};
+    use crate::omnixtracker::OmniXMetry;
+    use std::sync::Arc;
+    use tokio::io::AsyncReadExt;
+
+    fn noise(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_chunk_boundaries_survive_an_insert() {
+        let original = noise(2 * 1024 * 1024, 7);
+        let mut edited = original.clone();
+        edited.splice(1_000_000..1_000_000, b"inserted".iter().copied());
+
+        let before = content_defined_chunks(&original, CDC_MIN_CHUNK_SIZE, CDC_AVG_CHUNK_SIZE, CDC_MAX_CHUNK_SIZE);
+        let after = content_defined_chunks(&edited, CDC_MIN_CHUNK_SIZE, CDC_AVG_CHUNK_SIZE, CDC_MAX_CHUNK_SIZE);
+        assert_eq!(before.concat(), original);
+        assert!(before.iter().all(|chunk| chunk.len() <= CDC_MAX_CHUNK_SIZE));
+        assert!(before[..before.len() - 1].iter().all(|chunk| chunk.len() >= CDC_MIN_CHUNK_SIZE));
+
+        let changed = after.iter().filter(|chunk| !before.contains(chunk)).count();
+        assert!(changed <= 2, "{} of {} chunks changed", changed, after.len());
+    }
+
+    fn dedup_manager(storage: Arc<MemoryStorage>) -> Result<AproarManager, OmniXError> {
+        let policy = CompressionPolicy { dedup: Some(DedupConfig::default()), ..CompressionPolicy::default() };
+        AproarManagerBuilder::new(OmniXMetry::init()?)
+            .storage_backend(storage)
+            .compression_policy(policy)
+            .background_tasks(false)
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_dedup_shares_chunks_and_collects_garbage() -> Result<(), OmniXError> {
+        let storage = Arc::new(MemoryStorage::new());
+        let manager = dedup_manager(storage.clone())?;
+        let chunks = || storage.list_keys(CHUNK_KEY_PREFIX).map(|keys| keys.len());
+
+        let checkpoint = noise(DEDUP_MIN_PAYLOAD, 42);
+        let mut tuned = checkpoint.clone();
+        tuned[500_000..500_100].copy_from_slice(&[0u8; 100]);
+
+        manager.store_data("ckpt-1", &checkpoint, 0).await?;
+        let first = chunks()?;
+        assert!(first > 4);
+        manager.store_data("ckpt-2", &tuned, 0).await?;
+        let second = chunks()?;
+        assert!(second - first <= 2);
+        manager.store_data("ckpt-copy", &checkpoint, 0).await?;
+        assert_eq!(chunks()?, second);
+        manager.store_data("small", b"stored whole", 0).await?;
+        assert_eq!(chunks()?, second);
+
+        assert_eq!(manager.retrieve_data("ckpt-2").await?, tuned);
+        assert_eq!(manager.retrieve_data("small").await?, b"stored whole");
+        let batch = manager.retrieve_batch(&["ckpt-1".to_string(), "small".to_string()]).await?;
+        assert_eq!(batch, vec![Some(checkpoint.clone()), Some(b"stored whole".to_vec())]);
+        let mut streamed = Vec::new();
+        manager.retrieve_stream("ckpt-copy").await?.read_to_end(&mut streamed).await.map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+        assert_eq!(streamed, checkpoint);
+
+        manager.delete_data("ckpt-1").await?;
+        assert_eq!(manager.collect_garbage().await?, 0);
+        manager.store_data("ckpt-copy", b"overwritten", 0).await?;
+        assert_eq!(manager.retrieve_data("ckpt-copy").await?, b"overwritten");
+        let collected = manager.collect_garbage().await?;
+        assert!((1..=2).contains(&collected));
+
+        // A fresh manager rebuilds the counts from the stored manifests.
+        let reopened = dedup_manager(storage.clone())?;
+        assert_eq!(reopened.collect_garbage().await?, 0);
+        assert_eq!(reopened.retrieve_data("ckpt-2").await?, tuned);
+        assert_eq!(chunks()?, second - collected);
+
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,351 @@
+// src/aproar/dictionary.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[APROAR]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use crate::aproar::compression::{CodecKind, CompressionManager, CompressionStrategy, ZstdCompression, ZstdDictionary};
+use crate::aproar::integrity;
+use crate::aproar::storage::{run_blocking, StorageBackend};
+use crate::aproar::versioning::INTERNAL_KEY_PREFIX;
+use crate::aproar::{AproarManager, DictionaryConfig};
+use crate::constants::ZSTD_DICT_MIN_SAMPLES;
+use crate::omnixtracker::{OmniXError, OmniXMetry};
+use crate::security::AESEncryption;
+use parking_lot::Mutex;
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Dictionaries are stored under this prefix and their id in hex. Any value
+/// may need one, so each is written to every tier.
+const DICTIONARY_KEY_PREFIX: &str = "__aproar_dict::";
+
+fn dictionary_key(id: u32) -> String {
+    format!("{}{:08x}", DICTIONARY_KEY_PREFIX, id)
+}
+
+fn parse_dictionary_key(key: &str) -> Option<u32> {
+    key.strip_prefix(DICTIONARY_KEY_PREFIX).and_then(|id| u32::from_str_radix(id, 16).ok())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DictionaryReport {
+    pub dictionary_id: u32,
+    /// Values sampled for training and evaluation.
+    pub samples: usize,
+    /// Held-out bytes compressed without a dictionary over the same bytes
+    /// compressed with this one.
+    pub improvement: f64,
+    /// Whether the dictionary did better than the active one and replaced it.
+    pub adopted: bool,
+}
+
+/// Trains, persists and loads Zstd dictionaries. Holds its own handles so
+/// it can run on the blocking pool like `ExpiryReaper`.
+pub(crate) struct DictionaryTrainer {
+    storage_backends: Vec<Arc<dyn StorageBackend>>,
+    encryption: Option<Arc<AESEncryption>>,
+    compression_manager: CompressionManager,
+    config: DictionaryConfig,
+    metrics: OmniXMetry,
+    passes: AtomicUsize,
+    /// Keeps a manual run and the background task from picking the same id.
+    training: Mutex<()>,
+}
+
+impl DictionaryTrainer {
+    pub(crate) fn new(
+        storage_backends: Vec<Arc<dyn StorageBackend>>,
+        encryption: Option<Arc<AESEncryption>>,
+        compression_manager: CompressionManager,
+        config: DictionaryConfig,
+        metrics: OmniXMetry,
+    ) -> Self {
+        Self {
+            storage_backends,
+            encryption,
+            compression_manager,
+            config,
+            metrics,
+            passes: AtomicUsize::new(0),
+            training: Mutex::new(()),
+        }
+    }
+
+    /// Trains a dictionary on a sample of stored values and adopts it if it
+    /// compresses the held-out tenth of the sample better than both plain
+    /// Zstd and the active dictionary. `None` when fewer than
+    /// `ZSTD_DICT_MIN_SAMPLES` values are small enough to sample.
+    pub(crate) fn run_once(&self) -> Result<Option<DictionaryReport>, OmniXError> {
+        let _training = self.training.lock();
+        let start_time = Instant::now();
+        let samples = self.collect_samples()?;
+        if samples.len() < ZSTD_DICT_MIN_SAMPLES {
+            return Ok(None);
+        }
+
+        let mut training = Vec::with_capacity(samples.len());
+        let mut held_out = Vec::with_capacity(samples.len() / 10 + 1);
+        for (i, sample) in samples.iter().enumerate() {
+            if i % 10 == 9 {
+                held_out.push(sample.as_slice());
+            } else {
+                training.push(sample.as_slice());
+            }
+        }
+
+        let dictionaries = self.compression_manager.dictionaries();
+        let candidate = Arc::new(ZstdDictionary::train(dictionaries.next_id(), &training, self.config.dictionary_size)?);
+        let plain_size = compressed_size(&ZstdCompression, &held_out)?;
+        let candidate_size = compressed_size(&ZstdCompression.with_dictionary(candidate.clone()), &held_out)?;
+        let active_size = match dictionaries.active() {
+            Some(active) => compressed_size(&ZstdCompression.with_dictionary(active), &held_out)?,
+            None => plain_size,
+        };
+
+        let adopted = candidate_size < active_size;
+        if adopted {
+            self.persist(&candidate)?;
+            dictionaries.insert(candidate.clone());
+            dictionaries.activate(candidate.id())?;
+            self.metrics.update_gauge("compression.dictionary.active".to_string(), candidate.id() as f64);
+            tracing::info!("Adopted Zstd dictionary {} trained on {} values", candidate.id(), samples.len());
+        }
+
+        let improvement = plain_size as f64 / candidate_size.max(1) as f64;
+        self.metrics.record_histogram("compression.dictionary.training.duration".to_string(), start_time.elapsed().as_secs_f64());
+        self.metrics.update_gauge("compression.dictionary.holdout_improvement".to_string(), improvement);
+        self.metrics.increment_counter("compression.dictionary.trained".to_string(), 1);
+        Ok(Some(DictionaryReport { dictionary_id: candidate.id(), samples: samples.len(), improvement, adopted }))
+    }
+
+    /// Runs `run_once` on a blocking thread every `interval` until the runtime shuts down.
+    pub(crate) fn spawn(self: Arc<Self>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let trainer = self.clone();
+                match tokio::task::spawn_blocking(move || trainer.run_once()).await {
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => e.log(),
+                    Err(e) => tracing::error!("Dictionary training task panicked: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Loads every persisted dictionary not loaded yet and activates the newest.
+    pub(crate) fn load(&self) -> Result<usize, OmniXError> {
+        let dictionaries = self.compression_manager.dictionaries();
+        let mut loaded = 0;
+        for backend in &self.storage_backends {
+            for key in backend.list_keys(DICTIONARY_KEY_PREFIX)? {
+                if parse_dictionary_key(&key).is_none_or(|id| dictionaries.contains(id)) {
+                    continue;
+                }
+                // A bad copy is skipped; another tier holds the same dictionary.
+                match backend.retrieve(&key).and_then(|stored| self.note(&key, &stored)) {
+                    Ok(()) => loaded += 1,
+                    Err(e) => e.log(),
+                }
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// Loads the dictionary held in the stored bytes of a dictionary key,
+    /// activating it if it is the newest. Other keys are ignored. A
+    /// different dictionary under an id already loaded is an error, since
+    /// values compressed with either would decode wrongly.
+    pub(crate) fn note(&self, key: &str, stored: &[u8]) -> Result<(), OmniXError> {
+        let id = match parse_dictionary_key(key) {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+        let bytes = self.open(key, stored)?;
+        let dictionaries = self.compression_manager.dictionaries();
+        if let Some(existing) = dictionaries.get(id) {
+            if existing.as_bytes() == bytes.as_slice() {
+                return Ok(());
+            }
+            return Err(OmniXError::ValidationError(format!(
+                "Zstd dictionary {} differs from the dictionary already loaded under that id",
+                id
+            )));
+        }
+
+        dictionaries.insert(Arc::new(ZstdDictionary::new(id, bytes)));
+        if dictionaries.active().is_none_or(|active| active.id() < id) {
+            dictionaries.activate(id)?;
+        }
+        Ok(())
+    }
+
+    /// Reads up to `sample_keys` values, spread evenly over the stored keys.
+    /// Successive passes start at different offsets to see different keys.
+    fn collect_samples(&self) -> Result<Vec<Vec<u8>>, OmniXError> {
+        let mut keys = BTreeSet::new();
+        for backend in &self.storage_backends {
+            keys.extend(backend.list_keys("")?.into_iter().filter(|key| !key.starts_with(INTERNAL_KEY_PREFIX)));
+        }
+        let stride = (keys.len() / self.config.sample_keys.max(1)).max(1);
+        let offset = self.passes.fetch_add(1, Ordering::Relaxed) % stride;
+
+        let mut samples = Vec::new();
+        for key in keys.iter().skip(offset).step_by(stride).take(self.config.sample_keys) {
+            match self.read_value(key) {
+                Ok(Some(value)) if !value.is_empty() && value.len() <= self.config.max_payload => samples.push(value),
+                Ok(_) => {}
+                Err(e) => {
+                    self.metrics.increment_counter("compression.dictionary.sample_failure".to_string(), 1);
+                    e.log();
+                }
+            }
+        }
+        Ok(samples)
+    }
+
+    /// Decodes `key` from the first backend that holds it.
+    fn read_value(&self, key: &str) -> Result<Option<Vec<u8>>, OmniXError> {
+        for backend in &self.storage_backends {
+            if let Ok(stored) = backend.retrieve(key) {
+                return self.open(key, &stored).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reverses `seal`, and `AproarManager::seal_value` for sampled values.
+    fn open(&self, key: &str, stored: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        let body = integrity::unframe(key, stored)?;
+        let sealed = if AESEncryption::is_encrypted(body) {
+            let encryption = self.encryption.as_ref().ok_or_else(|| OmniXError::OperationFailed {
+                operation: "Decryption".to_string(),
+                details: format!("{} is encrypted but no master key is configured", key),
+            })?;
+            Cow::Owned(encryption.decrypt(key.as_bytes(), body)?)
+        } else {
+            Cow::Borrowed(body)
+        };
+        self.compression_manager.unseal(&sealed)
+    }
+
+    /// Dictionaries are trained on stored values, so they are encrypted like
+    /// the values when encryption is on.
+    fn seal(&self, key: &str, data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        let sealed = self.compression_manager.seal(CodecKind::Zstd, data)?;
+        match &self.encryption {
+            Some(encryption) => Ok(integrity::frame(&encryption.encrypt(key.as_bytes(), &sealed)?)),
+            None => Ok(integrity::frame(&sealed)),
+        }
+    }
+
+    fn persist(&self, dictionary: &ZstdDictionary) -> Result<(), OmniXError> {
+        let key = dictionary_key(dictionary.id());
+        let stored = self.seal(&key, dictionary.as_bytes())?;
+        for backend in &self.storage_backends {
+            backend.store(&key, &stored)?;
+        }
+        Ok(())
+    }
+}
+
+fn compressed_size(strategy: &dyn CompressionStrategy, samples: &[&[u8]]) -> Result<usize, OmniXError> {
+    samples.iter().map(|sample| strategy.compress(sample).map(|compressed| compressed.len())).sum()
+}
+
+impl AproarManager {
+    /// Trains a Zstd dictionary now instead of waiting for the background
+    /// task. Fails unless the compression policy enables dictionaries.
+    pub async fn train_compression_dictionary(&self) -> Result<Option<DictionaryReport>, OmniXError> {
+        if self.compression_policy.dictionary.is_none() {
+            return Err(OmniXError::ValidationError("Dictionary compression is not configured".to_string()));
+        }
+        let trainer = self.dictionary_trainer.clone();
+        run_blocking(move || trainer.run_once()).await
+    }
+
+    /// The id of the dictionary new small values are compressed with.
+    pub fn compression_dictionary_id(&self) -> Option<u32> {
+        self.compression_manager.dictionaries().active().map(|dictionary| dictionary.id())
+    }
+
+    pub(crate) fn load_dictionaries(&self) -> Result<usize, OmniXError> {
+        self.dictionary_trainer.load()
+    }
+
+    pub(crate) fn note_dictionary(&self, key: &str, stored: &[u8]) -> Result<(), OmniXError> {
+        self.dictionary_trainer.note(key, stored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aproar::compression::{dictionary_id, Envelope};
+    use crate::aproar::integrity::stream_error;
+    use crate::aproar::storage::MemoryStorage;
+    use crate::aproar::{AproarManagerBuilder, CompressionPolicy};
+    use tokio::io::AsyncReadExt;
+
+    fn context_chunk(i: usize) -> Vec<u8> {
+        format!(
+            r#"{{"session":"{:04}","role":"assistant","tokens":{},"memory_row":{},"text":"context chunk {} recalled from the neural turing machine memory bank"}}"#,
+            i % 97,
+            100 + i * 7 % 400,
+            i % 128,
+            i
+        )
+        .into_bytes()
+    }
+
+    fn manager(storage: Arc<MemoryStorage>, policy: CompressionPolicy) -> Result<AproarManager, OmniXError> {
+        AproarManagerBuilder::new(OmniXMetry::init()?)
+            .storage_backend(storage)
+            .compression_policy(policy)
+            .background_tasks(false)
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_dictionaries_are_trained_persisted_and_used() -> Result<(), OmniXError> {
+        let storage = Arc::new(MemoryStorage::new());
+        let policy = CompressionPolicy {
+            dictionary: Some(DictionaryConfig { dictionary_size: 4096, ..DictionaryConfig::default() }),
+            ..CompressionPolicy::default()
+        };
+        let first = manager(storage.clone(), policy.clone())?;
+        assert!(first.train_compression_dictionary().await?.is_none());
+
+        for i in 0..400 {
+            first.store_data(&format!("chunk/{}", i), &context_chunk(i), 0).await?;
+        }
+        let report = first.train_compression_dictionary().await?.expect("enough values to sample");
+        assert!(report.adopted);
+        assert!(report.improvement > 1.0);
+        assert_eq!(first.compression_dictionary_id(), Some(report.dictionary_id));
+
+        let chunk = context_chunk(1000);
+        first.store_data("chunk/new", &chunk, 0).await?;
+        let stored = storage.retrieve("chunk/new")?;
+        let envelope = Envelope::parse(integrity::unframe("chunk/new", &stored)?)?;
+        assert_eq!(envelope.codec, CodecKind::ZstdDict);
+        assert_eq!(dictionary_id(envelope.payload), Some(report.dictionary_id));
+        assert_eq!(first.retrieve_data("chunk/new").await?, chunk);
+
+        // A fresh manager over the same storage loads the dictionary back.
+        let reopened = manager(storage.clone(), policy)?;
+        assert_eq!(reopened.compression_dictionary_id(), Some(report.dictionary_id));
+        assert_eq!(reopened.retrieve_data("chunk/new").await?, chunk);
+        let mut streamed = Vec::new();
+        reopened
+            .retrieve_stream("chunk/new")
+            .await?
+            .read_to_end(&mut streamed)
+            .await
+            .map_err(|e| stream_error("chunk/new", e))?;
+        assert_eq!(streamed, chunk);
+        Ok(())
+    }
+}
\ No newline at end of file
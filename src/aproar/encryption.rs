@@ -0,0 +1,331 @@
+// src/aproar/encryption.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[APROAR]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use crate::aproar::compression::{AdaptiveSelector, CodecKind, CompressionManager, ZstdDictionary};
+use crate::aproar::integrity::{self, stream_error};
+use crate::aproar::replication::replica_tiers;
+use crate::aproar::storage::{run_blocking, ByteReader, StorageBackend};
+use crate::aproar::tiering::PlacementIndex;
+use crate::aproar::versioning::INTERNAL_KEY_PREFIX;
+use crate::aproar::{AproarManager, CompressionPolicy};
+use crate::omnixtracker::{OmniXError, OmniXMetry};
+use crate::security::{AESEncryption, MasterKey, ENCRYPTION_MAGIC};
+use std::borrow::Cow;
+use std::io::Cursor;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::io::AsyncReadExt;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RotationReport {
+    /// The master key every rewritten blob is now wrapped by.
+    pub key_id: u32,
+    pub reencrypted: usize,
+    /// Keys already encrypted under `key_id`.
+    pub current: usize,
+    /// Keys with a streamed write in flight, which rewrite themselves.
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+impl AproarManager {
+    /// The id of the master key new writes are encrypted under, or `None`
+    /// when encryption at rest is off.
+    pub fn encryption_key_id(&self) -> Option<u32> {
+        self.encryption.as_ref().map(|encryption| encryption.active_key_id())
+    }
+
+    /// Makes `master` the active master key and re-encrypts every stored key,
+    /// internal ones included, under it. The previous master key stays in
+    /// the keyring; retire it through `AESEncryption::retire` once the
+    /// report shows no failures.
+    pub async fn rotate_encryption_key(&self, master: MasterKey) -> Result<RotationReport, OmniXError> {
+        let encryption = self.encryption.as_ref().ok_or_else(encryption_disabled)?;
+        let previous = encryption.rotate(&master);
+        self.metrics.increment_counter("encryption.rotation".to_string(), 1);
+        tracing::info!("Rotating master key {:08x} to {:08x}", previous, master.id());
+        self.reencrypt().await
+    }
+
+    /// Rewrites every stored key that is not encrypted under the active
+    /// master key, including values written before encryption was turned on.
+    /// Each key is rewritten under its placement lock, so a concurrent store
+    /// is never overwritten with older data.
+    pub async fn reencrypt(&self) -> Result<RotationReport, OmniXError> {
+        let start_time = Instant::now();
+        let encryption = self.encryption.clone().ok_or_else(encryption_disabled)?;
+        let mut report = RotationReport { key_id: encryption.active_key_id(), ..RotationReport::default() };
+
+        for key in self.list_stored_keys("").await? {
+            // Indexes keys the placement index has not seen since a restart.
+            if self.locate(&key).await?.is_none() {
+                continue;
+            }
+            let rewriter = Reencrypter {
+                storage_backends: self.storage_backends.clone(),
+                placement_index: self.placement_index.clone(),
+                encryption: encryption.clone(),
+                replicas: self.replicas,
+            };
+            let owned_key = key.clone();
+            match run_blocking(move || rewriter.rewrite(&owned_key)).await {
+                Ok(Some(true)) => {
+                    self.evict_from_caches(&key).await;
+                    report.reencrypted += 1;
+                }
+                Ok(Some(false)) => report.current += 1,
+                Ok(None) => report.skipped += 1,
+                Err(e) => {
+                    e.log();
+                    report.failed += 1;
+                }
+            }
+        }
+
+        self.metrics.record_histogram("encryption.reencrypt.duration".to_string(), start_time.elapsed().as_secs_f64());
+        self.metrics.increment_counter("encryption.reencrypted".to_string(), report.reencrypted as u64);
+        self.metrics.increment_counter("encryption.reencrypt.failure".to_string(), report.failed as u64);
+        Ok(report)
+    }
+
+    /// Compresses `data` with the active dictionary if it is small enough,
+    /// else with the codec the policy or adaptive selector picks, encrypts
+    /// the envelope when encryption is on, and frames the result.
+    pub(crate) fn seal_value(&self, key: &str, data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        self.sealer().seal(key, data)
+    }
+
+    /// Owned handles for `seal_value`, to seal from the blocking pool.
+    pub(crate) fn sealer(&self) -> Sealer {
+        Sealer {
+            compression_manager: self.compression_manager.clone(),
+            compression_policy: self.compression_policy.clone(),
+            compression_selector: self.compression_selector.clone(),
+            encryption: self.encryption.clone(),
+            metrics: self.metrics.clone(),
+        }
+    }
+
+    /// Reverses `seal_value` on stored bytes that still carry their frame.
+    pub(crate) fn open_value(&self, key: &str, stored: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        self.open_body(key, integrity::unframe(key, stored)?)
+    }
+
+    /// Reverses `seal_value` on an already unframed body.
+    pub(crate) fn open_body(&self, key: &str, body: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        self.compression_manager.unseal(&self.sealed_payload(key, body)?)
+    }
+
+    /// The compression envelope inside `body`, decrypted if needed. Values
+    /// written before encryption was turned on are passed through as is.
+    pub(crate) fn sealed_payload<'a>(&self, key: &str, body: &'a [u8]) -> Result<Cow<'a, [u8]>, OmniXError> {
+        if !AESEncryption::is_encrypted(body) {
+            if self.encryption.is_some() {
+                self.metrics.increment_counter("encryption.plaintext_read".to_string(), 1);
+            }
+            return Ok(Cow::Borrowed(body));
+        }
+        let encryption = self.encryption.as_ref().ok_or_else(|| OmniXError::OperationFailed {
+            operation: "Decryption".to_string(),
+            details: format!("{} is encrypted but no master key is configured", key),
+        })?;
+
+        let start_time = Instant::now();
+        let decrypted = encryption.decrypt(key.as_bytes(), body);
+        self.metrics.record_histogram("decryption.duration".to_string(), start_time.elapsed().as_secs_f64());
+        if decrypted.is_err() {
+            self.metrics.increment_counter("decryption.failure".to_string(), 1);
+        }
+        decrypted.map(Cow::Owned)
+    }
+
+    /// Decrypts an unframed stored stream. AES-GCM authenticates a value as
+    /// a whole, so encrypted values are read into memory first; plaintext
+    /// ones keep streaming.
+    pub(crate) async fn decrypt_stream(&self, key: &str, mut reader: ByteReader) -> Result<ByteReader, OmniXError> {
+        let mut prefix = Vec::with_capacity(ENCRYPTION_MAGIC.len());
+        (&mut reader)
+            .take(ENCRYPTION_MAGIC.len() as u64)
+            .read_to_end(&mut prefix)
+            .await
+            .map_err(|e| stream_error(key, e))?;
+        if !AESEncryption::is_encrypted(&prefix) {
+            return Ok(Box::new(Cursor::new(prefix).chain(reader)));
+        }
+
+        let mut body = prefix;
+        reader.read_to_end(&mut body).await.map_err(|e| stream_error(key, e))?;
+        let sealed = self.sealed_payload(key, &body)?.into_owned();
+        Ok(Box::new(Cursor::new(sealed)))
+    }
+
+    /// Fails unless every master key in `key_ids` is in the keyring, so an
+    /// import never writes values this store cannot read back.
+    pub(crate) fn check_encryption_keys(&self, key_ids: impl IntoIterator<Item = u32>) -> Result<(), OmniXError> {
+        for key_id in key_ids {
+            if !self.encryption.as_ref().is_some_and(|encryption| encryption.has_key(key_id)) {
+                return Err(OmniXError::ValidationError(format!(
+                    "Archive holds values encrypted under master key {:08x}, which is not in the keyring",
+                    key_id
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn encryption_disabled() -> OmniXError {
+    OmniXError::ValidationError("Encryption at rest is not configured".to_string())
+}
+
+/// Owned handles for sealing values from the blocking pool.
+pub(crate) struct Sealer {
+    compression_manager: CompressionManager,
+    compression_policy: CompressionPolicy,
+    compression_selector: Option<Arc<AdaptiveSelector>>,
+    encryption: Option<Arc<AESEncryption>>,
+    metrics: OmniXMetry,
+}
+
+impl Sealer {
+    pub(crate) fn seal(&self, key: &str, data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        let sealed = match (self.dictionary_for(key, data.len()), &self.compression_selector) {
+            (Some(dictionary), _) => self.compression_manager.seal_with_dictionary(&dictionary, data)?,
+            (None, Some(selector)) => self.compression_manager.seal_adaptive(selector, data)?,
+            (None, None) => self.compression_manager.seal(self.compression_policy.codec_for(data.len()), data)?,
+        };
+        self.protect(key, sealed)
+    }
+
+    /// Like `seal`, with `codec` in place of whatever the policy would pick,
+    /// for payloads that arrive already compressed.
+    pub(crate) fn seal_with(&self, key: &str, codec: CodecKind, data: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        let sealed = self.compression_manager.seal(codec, data)?;
+        self.protect(key, sealed)
+    }
+
+    /// Encrypts the envelope when encryption is on, and frames the result.
+    fn protect(&self, key: &str, sealed: Vec<u8>) -> Result<Vec<u8>, OmniXError> {
+        match &self.encryption {
+            Some(encryption) => {
+                let start_time = Instant::now();
+                let encrypted = encryption.encrypt(key.as_bytes(), &sealed)?;
+                self.metrics.record_histogram("encryption.duration".to_string(), start_time.elapsed().as_secs_f64());
+                Ok(integrity::frame(&encrypted))
+            }
+            None => Ok(integrity::frame(&sealed)),
+        }
+    }
+
+    /// The dictionary to compress a value of `len` bytes under `key` with,
+    /// if dictionaries are on and one has been trained. Internal keys never
+    /// use one, so dictionaries and markers stay readable on their own.
+    fn dictionary_for(&self, key: &str, len: usize) -> Option<Arc<ZstdDictionary>> {
+        let config = self.compression_policy.dictionary.as_ref()?;
+        if len == 0 || len > config.max_payload || key.starts_with(INTERNAL_KEY_PREFIX) {
+            return None;
+        }
+        self.compression_manager.dictionaries().active()
+    }
+}
+
+/// Owned handles for rewriting one key from the blocking pool.
+struct Reencrypter {
+    storage_backends: Vec<Arc<dyn StorageBackend>>,
+    placement_index: Arc<PlacementIndex>,
+    encryption: Arc<AESEncryption>,
+    replicas: usize,
+}
+
+impl Reencrypter {
+    /// `Some(true)` when the key was rewritten, `Some(false)` when it was
+    /// already current and `None` when it is pinned or no longer indexed.
+    fn rewrite(&self, key: &str) -> Result<Option<bool>, OmniXError> {
+        let mut rewritten = false;
+        let placed = self.placement_index.relocate(key, |primary| {
+            let replica_set = replica_tiers(primary, self.replicas, self.storage_backends.len());
+            let stored = replica_set
+                .iter()
+                .filter_map(|&tier| self.storage_backends[tier].retrieve(key).ok())
+                .find(|stored| integrity::unframe(key, stored).is_ok())
+                .ok_or_else(|| OmniXError::DataCorruption {
+                    key: key.to_string(),
+                    details: "No intact replica to re-encrypt".to_string(),
+                })?;
+            let body = integrity::unframe(key, &stored)?;
+
+            let active = self.encryption.active_key_id();
+            if AESEncryption::key_id(body) == Some(active) {
+                return Ok(primary);
+            }
+            let sealed = if AESEncryption::is_encrypted(body) {
+                Cow::Owned(self.encryption.decrypt(key.as_bytes(), body)?)
+            } else {
+                Cow::Borrowed(body)
+            };
+            let reencrypted = integrity::frame(&self.encryption.encrypt(key.as_bytes(), &sealed)?);
+            for &tier in &replica_set {
+                self.storage_backends[tier].store(key, &reencrypted)?;
+            }
+            rewritten = true;
+            Ok(primary)
+        })?;
+        Ok(placed.map(|_| rewritten))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aproar::retrieval::{MemoryCache, RetrievalCache};
+    use crate::aproar::storage::MemoryStorage;
+    use crate::aproar::AproarManagerBuilder;
+    use crate::omnixtracker::OmniXMetry;
+
+    #[tokio::test]
+    async fn test_values_are_encrypted_at_rest_and_rotated() -> Result<(), OmniXError> {
+        let storage = Arc::new(MemoryStorage::new());
+        let cache = Arc::new(MemoryCache::new());
+        let first = MasterKey::generate();
+        let encryption = Arc::new(AESEncryption::new(&first));
+        let manager = AproarManagerBuilder::new(OmniXMetry::init()?)
+            .storage_backend(storage.clone())
+            .retrieval_cache(cache.clone())
+            .encryption(encryption.clone())
+            .background_tasks(false)
+            .build()?;
+
+        let secret = b"plaintext that must not reach the backend ".repeat(64);
+        manager.store_data("secret", &secret, 0).await?;
+        let stored = storage.retrieve("secret")?;
+        let body = integrity::unframe("secret", &stored)?;
+        assert_eq!(AESEncryption::key_id(body), Some(first.id()));
+        assert!(!stored.windows(9).any(|window| window == b"plaintext"));
+        let cached = cache.get("secret")?.expect("value is cached");
+        assert!(AESEncryption::is_encrypted(integrity::unframe("secret", &cached)?));
+        assert_eq!(manager.retrieve_data("secret").await?, secret);
+
+        let mut stream = manager.retrieve_stream("secret").await?;
+        let mut streamed = Vec::new();
+        stream.read_to_end(&mut streamed).await.map_err(|e| stream_error("secret", e))?;
+        assert_eq!(streamed, secret);
+
+        // A blob moved under another key fails authentication.
+        storage.store("moved", &stored)?;
+        assert!(manager.retrieve_data("moved").await.is_err());
+        manager.delete_data("moved").await?;
+
+        let second = MasterKey::generate();
+        let report = manager.rotate_encryption_key(second.clone()).await?;
+        assert_eq!(report.key_id, second.id());
+        assert_eq!(report.reencrypted, 1);
+        assert_eq!(report.failed, 0);
+        assert!(cache.get("secret")?.is_none());
+
+        encryption.retire(first.id())?;
+        let stored = storage.retrieve("secret")?;
+        assert_eq!(AESEncryption::key_id(integrity::unframe("secret", &stored)?), Some(second.id()));
+        assert_eq!(manager.retrieve_data("secret").await?, secret);
+        assert_eq!(manager.reencrypt().await?.current, 1);
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,555 @@
+// src/aproar/expiry.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[APROAR]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use crate::aproar::dedup::ref_key;
+use crate::aproar::retrieval::RetrievalCache;
+use crate::aproar::storage::{key_not_found, run_blocking, StorageBackend};
+use crate::aproar::tiering::{AccessTracker, PlacementIndex, TierMigrator};
+use crate::aproar::versioning::{inherited, INTERNAL_KEY_PREFIX};
+use crate::aproar::{AproarManager, JournalOp, Namespace, WriteJournal};
+use crate::omnixtracker::{OmniXError, OmniXMetry};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Marker keys hold the expiry deadline of the key they are named after, so
+/// deadlines survive a restart and travel with exports.
+const EXPIRY_KEY_PREFIX: &str = "__aproar_ttl::";
+
+pub(crate) fn expiry_key(key: &str) -> String {
+    format!("{}{}", EXPIRY_KEY_PREFIX, key)
+}
+
+/// The deadline in Unix milliseconds of a write made now with `ttl`.
+pub(crate) fn deadline_after(ttl: Duration) -> i64 {
+    let ttl_ms = i64::try_from(ttl.as_millis()).unwrap_or(i64::MAX);
+    Utc::now().timestamp_millis().saturating_add(ttl_ms)
+}
+
+/// A rule the reaper enforces on every pass, on top of key TTLs. Internal
+/// keys are never deleted by a rule unless `KeepLast` targets an internal
+/// prefix explicitly.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RetentionRule {
+    /// Keeps the `keep` most recently modified keys starting with `prefix`.
+    KeepLast { prefix: String, keep: usize },
+    /// Moves the least recently modified keys off `tier` to a colder one
+    /// until the tier holds at most `max_bytes`. Keys with no colder tier
+    /// to go to are deleted.
+    MaxBytesPerTier { tier: usize, max_bytes: u64 },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReapReport {
+    /// Keys deleted because their TTL ran out.
+    pub expired: usize,
+    /// Keys deleted by a retention rule.
+    pub evicted: usize,
+    /// Keys moved to a colder tier by a tier byte budget.
+    pub demoted: usize,
+    pub failed: usize,
+}
+
+/// Deletes expired keys and enforces the retention rules. Holds its own
+/// handles so it can run on the blocking pool like `TierMigrator`.
+pub(crate) struct ExpiryReaper {
+    storage_backends: Vec<Arc<dyn StorageBackend>>,
+    retrieval_caches: Vec<Arc<dyn RetrievalCache>>,
+    placement_index: Arc<PlacementIndex>,
+    access_tracker: Arc<AccessTracker>,
+    tier_migrator: Arc<TierMigrator>,
+    journal: Option<Arc<dyn WriteJournal>>,
+    expiries: Arc<DashMap<String, i64>>,
+    content_refs_stale: Arc<AtomicBool>,
+    rules: Vec<RetentionRule>,
+    metrics: OmniXMetry,
+}
+
+impl ExpiryReaper {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        storage_backends: Vec<Arc<dyn StorageBackend>>,
+        retrieval_caches: Vec<Arc<dyn RetrievalCache>>,
+        placement_index: Arc<PlacementIndex>,
+        access_tracker: Arc<AccessTracker>,
+        tier_migrator: Arc<TierMigrator>,
+        journal: Option<Arc<dyn WriteJournal>>,
+        expiries: Arc<DashMap<String, i64>>,
+        content_refs_stale: Arc<AtomicBool>,
+        rules: Vec<RetentionRule>,
+        metrics: OmniXMetry,
+    ) -> Self {
+        Self {
+            storage_backends,
+            retrieval_caches,
+            placement_index,
+            access_tracker,
+            tier_migrator,
+            journal,
+            expiries,
+            content_refs_stale,
+            rules,
+            metrics,
+        }
+    }
+
+    pub(crate) fn rules(&self) -> &[RetentionRule] {
+        &self.rules
+    }
+
+    /// Deletes every key past its deadline, then applies each rule in order.
+    /// Failures on single keys are counted and logged without stopping the pass.
+    pub(crate) fn run_once(&self) -> Result<ReapReport, OmniXError> {
+        let start_time = Instant::now();
+        let now_ms = Utc::now().timestamp_millis();
+        let mut report = ReapReport::default();
+
+        let due: Vec<String> = self
+            .expiries
+            .iter()
+            .filter(|entry| *entry.value() <= now_ms)
+            .map(|entry| entry.key().clone())
+            .collect();
+        for key in due {
+            let still_due = || self.expiries.get(&key).is_some_and(|deadline| *deadline <= now_ms);
+            tally(self.remove_key(&key, still_due, now_ms), &mut report.expired, &mut report.failed);
+        }
+
+        for rule in &self.rules {
+            let applied = match rule {
+                RetentionRule::KeepLast { prefix, keep } => self.keep_last(prefix, *keep, now_ms, &mut report),
+                RetentionRule::MaxBytesPerTier { tier, max_bytes } => self.cap_tier(*tier, *max_bytes, now_ms, &mut report),
+            };
+            if let Err(e) = applied {
+                report.failed += 1;
+                e.log();
+            }
+        }
+
+        self.metrics.record_histogram("reaper.duration".to_string(), start_time.elapsed().as_secs_f64());
+        self.metrics.increment_counter("expiry.reaped".to_string(), report.expired as u64);
+        self.metrics.increment_counter("retention.evicted".to_string(), report.evicted as u64);
+        self.metrics.increment_counter("retention.demoted".to_string(), report.demoted as u64);
+        self.metrics.increment_counter("reaper.failed".to_string(), report.failed as u64);
+        self.metrics.update_gauge("expiry.pending".to_string(), self.expiries.len() as f64);
+        Ok(report)
+    }
+
+    fn keep_last(&self, prefix: &str, keep: usize, now_ms: i64, report: &mut ReapReport) -> Result<(), OmniXError> {
+        let include_internal = prefix.starts_with(INTERNAL_KEY_PREFIX);
+        let mut keys = BTreeMap::new();
+        for backend in &self.storage_backends {
+            for key in backend.list_keys(prefix)? {
+                if keys.contains_key(&key) || (!include_internal && key.starts_with(INTERNAL_KEY_PREFIX)) {
+                    continue;
+                }
+                if let Ok(metadata) = backend.metadata(&key) {
+                    keys.insert(key, metadata.modified);
+                }
+            }
+        }
+
+        // Newest first; keys whose backend has no modification time count as oldest.
+        let mut keys: Vec<(String, Option<DateTime<Utc>>)> = keys.into_iter().collect();
+        keys.sort_by(|a, b| b.1.cmp(&a.1));
+        for (key, modified) in keys.into_iter().skip(keep) {
+            let unchanged = || self.modified(&key) == Some(modified);
+            tally(self.remove_key(&key, unchanged, now_ms), &mut report.evicted, &mut report.failed);
+        }
+        Ok(())
+    }
+
+    fn cap_tier(&self, tier: usize, max_bytes: u64, now_ms: i64, report: &mut ReapReport) -> Result<(), OmniXError> {
+        let backend = match self.storage_backends.get(tier) {
+            Some(backend) => backend,
+            None => return Ok(()),
+        };
+        let mut total = 0;
+        let mut candidates = Vec::new();
+        for key in backend.list_keys("")? {
+            let metadata = match backend.metadata(&key) {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            total += metadata.size;
+            if !key.starts_with(INTERNAL_KEY_PREFIX) {
+                candidates.push((key, metadata.modified, metadata.size));
+            }
+        }
+
+        // Oldest first, again with undated keys treated as oldest.
+        candidates.sort_by(|a, b| a.1.cmp(&b.1));
+        for (key, modified, size) in candidates {
+            if total <= max_bytes {
+                break;
+            }
+            let mut moved = 0;
+            tally(self.demote_off(&key, tier), &mut moved, &mut report.failed);
+            if moved == 0 {
+                let unchanged = || backend.metadata(&key).is_ok_and(|metadata| metadata.modified == modified);
+                tally(self.remove_key(&key, unchanged, now_ms), &mut moved, &mut report.failed);
+                report.evicted += moved;
+            } else {
+                report.demoted += moved;
+            }
+            if moved > 0 {
+                total = total.saturating_sub(size);
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves `key` off `tier`, indexing it first if it has not been stored
+    /// or read since a restart. False when no colder tier can take it.
+    fn demote_off(&self, key: &str, tier: usize) -> Result<bool, OmniXError> {
+        if self.placement_index.get(key).is_none() {
+            let primary = match self.storage_backends.iter().position(|backend| backend.exists(key).unwrap_or(false)) {
+                Some(primary) => primary,
+                None => return Ok(false),
+            };
+            self.placement_index.place(key, |current| Ok(current.unwrap_or(primary)))?;
+        }
+        self.tier_migrator.demote_off(key, tier)
+    }
+
+    /// Modification time of `key` on the hottest tier holding it.
+    fn modified(&self, key: &str) -> Option<Option<DateTime<Utc>>> {
+        self.storage_backends.iter().find_map(|backend| backend.metadata(key).ok().map(|metadata| metadata.modified))
+    }
+
+    /// Deletes `key` and, when its deadline has passed, its expiry marker.
+    /// `still_due` is re-checked under the key's placement lock, so a store
+    /// that lands after the key was picked is kept. A deduplicated key loses
+    /// its manifest too, and the chunk counts are rebuilt on next use.
+    fn remove_key(&self, key: &str, still_due: impl FnOnce() -> bool, now_ms: i64) -> Result<bool, OmniXError> {
+        if !self.delete_everywhere(key, still_due)? {
+            return Ok(false);
+        }
+        let manifest = ref_key(key);
+        if self.storage_backends.iter().any(|backend| backend.exists(&manifest).unwrap_or(false)) {
+            self.delete_everywhere(&manifest, || true)?;
+            self.content_refs_stale.store(true, Ordering::SeqCst);
+        }
+        // A store that raced the delete has set a later deadline; its marker stays.
+        if self.expiries.remove_if(key, |_, deadline| *deadline <= now_ms).is_some() {
+            self.delete_everywhere(&expiry_key(key), || true)?;
+        }
+        Ok(true)
+    }
+
+    fn delete_everywhere(&self, key: &str, still_due: impl FnOnce() -> bool) -> Result<bool, OmniXError> {
+        let mut seq = None;
+        let deleted = self.placement_index.evict(key, || {
+            if !still_due() {
+                return Ok(false);
+            }
+            if let Some(journal) = &self.journal {
+                seq = Some(journal.record_intent(JournalOp::Delete { key: key.to_string() })?);
+            }
+            for backend in &self.storage_backends {
+                if backend.exists(key)? {
+                    backend.delete(key)?;
+                }
+            }
+            Ok(true)
+        })?;
+        if !deleted {
+            return Ok(false);
+        }
+
+        self.access_tracker.forget(key);
+        for cache in &self.retrieval_caches {
+            if let Err(e) = cache.delete(key) {
+                self.metrics.increment_counter("cache.delete.failure".to_string(), 1);
+                e.log();
+            }
+        }
+        if let (Some(journal), Some(seq)) = (&self.journal, seq) {
+            if let Err(e) = journal.mark_complete(seq) {
+                self.metrics.increment_counter("journal.complete.failure".to_string(), 1);
+                e.log();
+            }
+        }
+        Ok(true)
+    }
+
+    /// Runs `run_once` on a blocking thread every `interval` until the runtime shuts down.
+    pub(crate) fn spawn(self: Arc<Self>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let reaper = self.clone();
+                match tokio::task::spawn_blocking(move || reaper.run_once()).await {
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => e.log(),
+                    Err(e) => tracing::error!("Expiry reaper task panicked: {}", e),
+                }
+            }
+        });
+    }
+}
+
+fn tally(result: Result<bool, OmniXError>, removed: &mut usize, failed: &mut usize) {
+    match result {
+        Ok(true) => *removed += 1,
+        Ok(false) => {}
+        Err(e) => {
+            *failed += 1;
+            e.log();
+        }
+    }
+}
+
+impl AproarManager {
+    /// Expires keys written to `namespace` or its children `ttl` after each
+    /// write. Keys already stored keep the expiry they were written with.
+    pub fn set_ttl(&self, namespace: &Namespace, ttl: Duration) {
+        self.ttl_policies.insert(namespace.clone(), ttl);
+    }
+
+    pub fn clear_ttl(&self, namespace: &Namespace) {
+        self.ttl_policies.remove(namespace);
+    }
+
+    /// The TTL for `namespace`, inherited from the closest parent that has one.
+    pub fn ttl(&self, namespace: &Namespace) -> Option<Duration> {
+        inherited(&self.ttl_policies, namespace)
+    }
+
+    pub fn retention_rules(&self) -> &[RetentionRule] {
+        self.expiry_reaper.rules()
+    }
+
+    /// Like `store_data`, but expires `key` after `ttl` whatever its namespace.
+    pub async fn store_data_with_ttl(&self, key: &str, data: &[u8], usage_frequency: usize, ttl: Duration) -> Result<(), OmniXError> {
+        self.store_with_expiry(key, data, usage_frequency, Some(ttl)).await
+    }
+
+    /// When `key` expires, if it was stored with a TTL.
+    pub fn expires_at(&self, key: &str) -> Option<DateTime<Utc>> {
+        self.expiries.get(key).and_then(|deadline| DateTime::from_timestamp_millis(*deadline))
+    }
+
+    pub fn is_expired(&self, key: &str) -> bool {
+        self.expiries.get(key).is_some_and(|deadline| *deadline <= Utc::now().timestamp_millis())
+    }
+
+    /// Runs a reaper pass now instead of waiting for the background task.
+    pub async fn reap_expired(&self) -> Result<ReapReport, OmniXError> {
+        let reaper = self.expiry_reaper.clone();
+        run_blocking(move || reaper.run_once()).await
+    }
+
+    /// The TTL `store_data` gives a physical key. Internal keys only expire
+    /// when their writer passes a TTL explicitly.
+    pub(crate) fn namespace_ttl(&self, key: &str) -> Option<Duration> {
+        if self.ttl_policies.is_empty() || key.starts_with(INTERNAL_KEY_PREFIX) {
+            return None;
+        }
+        self.ttl(&Namespace::of_key(key))
+    }
+
+    pub(crate) fn remaining_ttl(&self, key: &str) -> Option<Duration> {
+        let deadline = *self.expiries.get(key)?;
+        let remaining = deadline - Utc::now().timestamp_millis();
+        (remaining > 0).then(|| Duration::from_millis(remaining as u64))
+    }
+
+    pub(crate) fn ensure_live(&self, key: &str) -> Result<(), OmniXError> {
+        if self.is_expired(key) {
+            return Err(key_not_found("APROAR", key));
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn store_with_expiry(
+        &self,
+        key: &str,
+        data: &[u8],
+        usage_frequency: usize,
+        ttl: Option<Duration>,
+    ) -> Result<(), OmniXError> {
+        self.update_expiry(key, ttl).await?;
+        self.store_payload(key, data, usage_frequency).await?;
+        Ok(())
+    }
+
+    /// Records the deadline for a write of `key` that is about to happen, or
+    /// drops the old one when the write has no TTL.
+    pub(crate) async fn update_expiry(&self, key: &str, ttl: Option<Duration>) -> Result<(), OmniXError> {
+        match ttl {
+            Some(ttl) => {
+                let deadline = deadline_after(ttl);
+                // The map is updated first so a reaper pass racing this write
+                // sees the new deadline and leaves the key alone.
+                self.expiries.insert(key.to_string(), deadline);
+                self.store_value(&expiry_key(key), &deadline.to_be_bytes(), 0).await?;
+                Ok(())
+            }
+            None => {
+                if self.expiries.remove(key).is_some() {
+                    self.delete_value(&expiry_key(key)).await?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Caches the stored bytes of `key` for at most its remaining lifetime.
+    pub(crate) async fn cache_stored(&self, key: &str, stored: &[u8]) {
+        if self.retrieval_caches.is_empty() || self.is_expired(key) {
+            return;
+        }
+        let ttl = self.remaining_ttl(key);
+        let (key, stored) = (key.to_string(), stored.to_vec());
+        let cached = self
+            .with_caches(move |caches, metrics| {
+                for cache in caches {
+                    let cached = match ttl {
+                        Some(ttl) => cache.set_with_ttl(&key, &stored, ttl),
+                        None => cache.set(&key, &stored),
+                    };
+                    if let Err(e) = cached {
+                        metrics.increment_counter("cache.set.failure".to_string(), 1);
+                        e.log();
+                    }
+                }
+            })
+            .await;
+        if let Err(e) = cached {
+            e.log();
+        }
+    }
+
+    /// Rebuilds the deadline map from the markers on every tier. Runs from
+    /// `AproarManagerBuilder::build` and returns the number of keys with a TTL.
+    pub(crate) fn load_expiries(&self) -> Result<usize, OmniXError> {
+        for backend in &self.storage_backends {
+            for marker in backend.list_keys(EXPIRY_KEY_PREFIX)? {
+                if self.expiries.contains_key(&marker[EXPIRY_KEY_PREFIX.len()..]) {
+                    continue;
+                }
+                // A bad copy is skipped; another replica may still hold the marker.
+                if let Err(e) = backend.retrieve(&marker).and_then(|stored| self.note_expiry_marker(&marker, &stored)) {
+                    e.log();
+                }
+            }
+        }
+        Ok(self.expiries.len())
+    }
+
+    /// Takes the deadline from the stored bytes of an expiry marker. Keys
+    /// that are not markers are ignored.
+    pub(crate) fn note_expiry_marker(&self, marker: &str, stored: &[u8]) -> Result<(), OmniXError> {
+        let key = match marker.strip_prefix(EXPIRY_KEY_PREFIX) {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+        let raw = self.open_value(marker, stored)?;
+        let deadline: [u8; 8] = raw.as_slice().try_into().map_err(|_| OmniXError::DataCorruption {
+            key: marker.to_string(),
+            details: format!("Expiry marker holds {} bytes instead of 8", raw.len()),
+        })?;
+        self.expiries.insert(key.to_string(), i64::from_be_bytes(deadline));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aproar::retrieval::MemoryCache;
+    use crate::aproar::storage::MemoryStorage;
+    use crate::aproar::AproarManagerBuilder;
+
+    #[tokio::test]
+    async fn test_expired_keys_disappear_and_get_reaped() -> Result<(), OmniXError> {
+        let storage = Arc::new(MemoryStorage::new());
+        let cache = Arc::new(MemoryCache::new());
+        let manager = AproarManagerBuilder::new(OmniXMetry::init()?)
+            .storage_backend(storage.clone())
+            .retrieval_cache(cache.clone())
+            .background_tasks(false)
+            .build()?;
+        let session = Namespace::new("session")?;
+        manager.set_ttl(&session, Duration::from_millis(20));
+
+        manager.store_data_with_ttl("short", b"gone soon", 0, Duration::from_millis(20)).await?;
+        manager.store_data(&session.qualify("turn"), b"namespaced", 0).await?;
+        manager.store_data("kept", b"no ttl", 0).await?;
+        manager.store_data_with_ttl("renewed", b"first", 0, Duration::from_millis(20)).await?;
+        manager.store_data("renewed", b"second", 0).await?;
+        assert!(manager.expires_at("short").is_some());
+        assert!(manager.expires_at("renewed").is_none());
+        assert_eq!(manager.retrieve_data("short").await?, b"gone soon");
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert!(manager.retrieve_data("short").await.is_err());
+        assert!(!manager.data_exists(&session.qualify("turn")).await?);
+        assert!(storage.exists("short")?);
+
+        let report = manager.reap_expired().await?;
+        assert_eq!(report.expired, 2);
+        assert!(!storage.exists("short")? && !storage.exists(&expiry_key("short"))?);
+        assert!(cache.get("short")?.is_none());
+        assert_eq!(manager.retrieve_data("kept").await?, b"no ttl");
+        assert_eq!(manager.retrieve_data("renewed").await?, b"second");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retention_rules_and_reloaded_deadlines() -> Result<(), OmniXError> {
+        let hot = Arc::new(MemoryStorage::new());
+        let cold = Arc::new(MemoryStorage::new());
+        let build = || {
+            AproarManagerBuilder::new(OmniXMetry::init()?)
+                .storage_backend(hot.clone())
+                .storage_backend(cold.clone())
+                .retention_rule(RetentionRule::KeepLast { prefix: "log/".to_string(), keep: 2 })
+                .retention_rule(RetentionRule::MaxBytesPerTier { tier: 0, max_bytes: 2048 })
+                .background_tasks(false)
+                .build()
+        };
+        let manager = build()?;
+
+        for i in 0..4 {
+            manager.store_data(&format!("log/{}", i), b"entry", 0).await?;
+            tokio::time::sleep(Duration::from_millis(2)).await;
+        }
+        // Incompressible, so each copy on the hot tier takes a little over 1000 bytes.
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        for i in 0..3 {
+            let noise: Vec<u8> = (0..1000)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    state as u8
+                })
+                .collect();
+            manager.store_data(&format!("hot/{}", i), &noise, usize::MAX).await?;
+            tokio::time::sleep(Duration::from_millis(2)).await;
+        }
+        manager.store_data_with_ttl("later", b"outlives a restart", 0, Duration::from_secs(3600)).await?;
+
+        let report = manager.reap_expired().await?;
+        assert_eq!((report.evicted, report.demoted), (2, 2));
+        assert_eq!(manager.list_keys("log/").await?, vec!["log/2".to_string(), "log/3".to_string()]);
+        assert!(!hot.exists("hot/0")? && !hot.exists("hot/1")? && hot.exists("hot/2")?);
+        assert!(cold.exists("hot/0")? && cold.exists("hot/1")?);
+        assert_eq!(manager.placement("hot/0"), Some(1));
+        assert_eq!(manager.retrieve_data("hot/1").await?.len(), 1000);
+
+        let restarted = build()?;
+        assert_eq!(restarted.expires_at("later"), manager.expires_at("later"));
+
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,367 @@
+// src/aproar/integrity.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[APROAR]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use crate::aproar::compression::CompressionManager;
+use crate::aproar::storage::{async_view, run_blocking, ByteReader};
+use crate::aproar::AproarManager;
+use crate::omnixtracker::OmniXError;
+use std::io::{self, Cursor};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Instant;
+use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+
+/// Marks a value written with a BLAKE3 trailer. The digest goes at the end so
+/// streamed writes can compute it on the way through.
+pub const INTEGRITY_MAGIC: &[u8; 4] = b"APXI";
+pub const INTEGRITY_DIGEST_LEN: usize = 32;
+
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+fn corruption(key: &str, details: impl Into<String>) -> OmniXError {
+    OmniXError::DataCorruption { key: key.to_string(), details: details.into() }
+}
+
+pub fn is_framed(stored: &[u8]) -> bool {
+    stored.starts_with(INTEGRITY_MAGIC)
+}
+
+/// Wraps `body` as `magic || body || blake3(body)`.
+pub fn frame(body: &[u8]) -> Vec<u8> {
+    let mut framed = Vec::with_capacity(INTEGRITY_MAGIC.len() + body.len() + INTEGRITY_DIGEST_LEN);
+    framed.extend_from_slice(INTEGRITY_MAGIC);
+    framed.extend_from_slice(body);
+    framed.extend_from_slice(blake3::hash(body).as_bytes());
+    framed
+}
+
+/// Checks the digest and returns the body. Values written before integrity
+/// framing existed have no digest and are returned whole.
+pub fn unframe<'a>(key: &str, stored: &'a [u8]) -> Result<&'a [u8], OmniXError> {
+    if !is_framed(stored) {
+        return Ok(stored);
+    }
+    if stored.len() < INTEGRITY_MAGIC.len() + INTEGRITY_DIGEST_LEN {
+        return Err(corruption(key, "value ends before its BLAKE3 digest"));
+    }
+    let (rest, digest) = stored.split_at(stored.len() - INTEGRITY_DIGEST_LEN);
+    let body = &rest[INTEGRITY_MAGIC.len()..];
+    if blake3::hash(body).as_bytes() != digest {
+        return Err(corruption(key, "BLAKE3 digest mismatch"));
+    }
+    Ok(body)
+}
+
+/// Full check of a stored value. Unframed values can only be checked by
+/// decoding them against their envelope checksum, if they have one.
+pub(crate) fn verify(key: &str, stored: &[u8], compression: &CompressionManager) -> Result<(), OmniXError> {
+    if is_framed(stored) {
+        return unframe(key, stored).map(|_| ());
+    }
+    compression.unseal(stored).map(|_| ()).map_err(|e| corruption(key, e.to_string()))
+}
+
+/// Streaming counterpart of `frame`.
+pub fn frame_stream(reader: ByteReader) -> ByteReader {
+    Box::new(Cursor::new(INTEGRITY_MAGIC.to_vec()).chain(DigestAppender::new(reader)))
+}
+
+/// Streaming counterpart of `unframe`. The read that reaches the end of a
+/// framed stream fails with `InvalidData` if the digest does not match.
+pub async fn unframe_stream(key: &str, reader: ByteReader) -> Result<ByteReader, OmniXError> {
+    open_frame(key, reader).await.map(|(_, reader)| reader)
+}
+
+/// Like `unframe_stream`, also reporting whether the stream carried a digest.
+async fn open_frame(key: &str, mut reader: ByteReader) -> Result<(bool, ByteReader), OmniXError> {
+    let mut prefix = Vec::with_capacity(INTEGRITY_MAGIC.len());
+    (&mut reader)
+        .take(INTEGRITY_MAGIC.len() as u64)
+        .read_to_end(&mut prefix)
+        .await
+        .map_err(|e| stream_error(key, e))?;
+
+    if prefix.as_slice() == INTEGRITY_MAGIC {
+        Ok((true, Box::new(DigestVerifier::new(key, reader))))
+    } else {
+        Ok((false, Box::new(Cursor::new(prefix).chain(reader))))
+    }
+}
+
+/// Passes bytes through while hashing them and emits the digest at EOF.
+struct DigestAppender<R> {
+    inner: R,
+    hasher: blake3::Hasher,
+    trailer: Option<([u8; INTEGRITY_DIGEST_LEN], usize)>,
+}
+
+impl<R> DigestAppender<R> {
+    fn new(inner: R) -> Self {
+        Self { inner, hasher: blake3::Hasher::new(), trailer: None }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for DigestAppender<R> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.trailer.is_none() {
+            let had_room = buf.remaining() > 0;
+            let before = buf.filled().len();
+            ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+            let read = &buf.filled()[before..];
+            if !read.is_empty() || !had_room {
+                this.hasher.update(read);
+                return Poll::Ready(Ok(()));
+            }
+            this.trailer = Some((*this.hasher.finalize().as_bytes(), 0));
+        }
+
+        if let Some((digest, written)) = &mut this.trailer {
+            let n = (INTEGRITY_DIGEST_LEN - *written).min(buf.remaining());
+            buf.put_slice(&digest[*written..*written + n]);
+            *written += n;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Holds back the last `INTEGRITY_DIGEST_LEN` bytes seen, since they may be
+/// the trailer, and compares them with the hash of everything before at EOF.
+struct DigestVerifier<R> {
+    inner: R,
+    key: String,
+    hasher: blake3::Hasher,
+    held: Vec<u8>,
+    scratch: Box<[u8]>,
+    eof: bool,
+    verified: bool,
+}
+
+impl<R> DigestVerifier<R> {
+    fn new(key: &str, inner: R) -> Self {
+        Self {
+            inner,
+            key: key.to_string(),
+            hasher: blake3::Hasher::new(),
+            held: Vec::new(),
+            scratch: vec![0u8; STREAM_CHUNK_SIZE].into_boxed_slice(),
+            eof: false,
+            verified: false,
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for DigestVerifier<R> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+
+        loop {
+            if this.held.len() > INTEGRITY_DIGEST_LEN {
+                let n = (this.held.len() - INTEGRITY_DIGEST_LEN).min(buf.remaining());
+                this.hasher.update(&this.held[..n]);
+                buf.put_slice(&this.held[..n]);
+                this.held.drain(..n);
+                return Poll::Ready(Ok(()));
+            }
+
+            if this.eof {
+                if !this.verified {
+                    this.verified = true;
+                    if this.held.len() != INTEGRITY_DIGEST_LEN {
+                        return Poll::Ready(Err(corruption_io(&this.key, "stream ends before its BLAKE3 digest")));
+                    }
+                    if this.hasher.finalize().as_bytes() != this.held.as_slice() {
+                        return Poll::Ready(Err(corruption_io(&this.key, "BLAKE3 digest mismatch")));
+                    }
+                }
+                return Poll::Ready(Ok(()));
+            }
+
+            let mut chunk = ReadBuf::new(&mut this.scratch);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut chunk))?;
+            if chunk.filled().is_empty() {
+                this.eof = true;
+            } else {
+                this.held.extend_from_slice(chunk.filled());
+            }
+        }
+    }
+}
+
+fn corruption_io(key: &str, details: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, corruption(key, details))
+}
+
+/// Maps a read error from an `unframe_stream` reader back to the corruption
+/// error it carries, if any.
+pub(crate) fn stream_error(key: &str, e: io::Error) -> OmniXError {
+    if e.kind() != io::ErrorKind::InvalidData {
+        return OmniXError::OperationFailed {
+            operation: "Integrity check".to_string(),
+            details: e.to_string(),
+        };
+    }
+    match e.into_inner().map(|inner| inner.downcast::<OmniXError>()) {
+        Some(Ok(inner)) => *inner,
+        Some(Err(inner)) => corruption(key, inner.to_string()),
+        None => corruption(key, "invalid data"),
+    }
+}
+
+/// Outcome of `AproarManager::scrub`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScrubReport {
+    pub checked: usize,
+    pub corrupted: usize,
+    pub repaired: usize,
+    /// Corrupted copies left in place, as `(tier, key)`, because no healthy
+    /// copy was found or `repair` was off.
+    pub unrepaired: Vec<(usize, String)>,
+    /// Keys that could not be read at all, usually because they were deleted
+    /// while the scrub was running.
+    pub failed: usize,
+}
+
+impl AproarManager {
+    /// Reads every key on every tier and checks it against its digest. With
+    /// `repair` set, corrupted copies are rewritten from a verified cache or
+    /// replica copy (see `ReplicaRepairer::repair`).
+    pub async fn scrub(&self, repair: bool) -> Result<ScrubReport, OmniXError> {
+        let start_time = Instant::now();
+        let mut report = ScrubReport::default();
+
+        for tier in 0..self.storage_backends.len() {
+            let backend = self.storage_backends[tier].clone();
+            let keys = run_blocking(move || backend.list_keys("")).await?;
+            for key in keys {
+                report.checked += 1;
+                match self.check_copy(tier, &key).await {
+                    Ok(()) => continue,
+                    Err(OmniXError::DataCorruption { .. }) => report.corrupted += 1,
+                    Err(e) => {
+                        report.failed += 1;
+                        e.log();
+                        continue;
+                    }
+                }
+
+                if !repair {
+                    report.unrepaired.push((tier, key));
+                    continue;
+                }
+                let repaired = async {
+                    self.locate(&key).await?;
+                    let (repairer, owned_key) = (self.repairer(), key.clone());
+                    run_blocking(move || repairer.repair(tier, &owned_key)).await
+                };
+                match repaired.await {
+                    Ok(true) => report.repaired += 1,
+                    Ok(false) => report.unrepaired.push((tier, key)),
+                    Err(e) => {
+                        e.log();
+                        report.unrepaired.push((tier, key));
+                    }
+                }
+            }
+        }
+
+        let duration = start_time.elapsed();
+        self.metrics.record_histogram("scrub.duration".to_string(), duration.as_secs_f64());
+        self.metrics.increment_counter("scrub.checked".to_string(), report.checked as u64);
+        self.metrics.increment_counter("scrub.corrupted".to_string(), report.corrupted as u64);
+        self.metrics.increment_counter("scrub.repaired".to_string(), report.repaired as u64);
+        self.metrics.increment_counter("scrub.unrepaired".to_string(), report.unrepaired.len() as u64);
+        self.metrics.increment_counter("scrub.failed".to_string(), report.failed as u64);
+
+        Ok(report)
+    }
+
+    /// Streams one copy through its digest check without buffering it.
+    /// Unframed legacy copies are read whole and decoded instead.
+    async fn check_copy(&self, tier: usize, key: &str) -> Result<(), OmniXError> {
+        let stored = async_view(&self.storage_backends[tier]).retrieve_stream(key).await?;
+        let (framed, mut reader) = open_frame(key, stored).await?;
+        if framed {
+            return tokio::io::copy(&mut reader, &mut tokio::io::sink())
+                .await
+                .map(|_| ())
+                .map_err(|e| stream_error(key, e));
+        }
+
+        let mut stored = Vec::new();
+        reader.read_to_end(&mut stored).await.map_err(|e| stream_error(key, e))?;
+        verify(key, &stored, &self.compression_manager)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aproar::retrieval::{MemoryCache, RetrievalCache};
+    use crate::aproar::storage::{MemoryStorage, StorageBackend};
+    use crate::aproar::AproarManagerBuilder;
+    use crate::omnixtracker::OmniXMetry;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_frame_round_trip_and_streaming() -> Result<(), OmniXError> {
+        let body: Vec<u8> = (0..200_000).map(|i| (i % 251) as u8).collect();
+        let framed = frame(&body);
+        assert_eq!(unframe("k", &framed)?, body.as_slice());
+        assert_eq!(unframe("k", b"legacy")?, b"legacy");
+
+        let mut corrupted = framed.clone();
+        corrupted[1000] ^= 0x01;
+        assert!(matches!(unframe("k", &corrupted), Err(OmniXError::DataCorruption { .. })));
+
+        let mut streamed = Vec::new();
+        frame_stream(Box::new(Cursor::new(body.clone()))).read_to_end(&mut streamed).await.unwrap();
+        assert_eq!(streamed, framed);
+
+        let mut out = Vec::new();
+        unframe_stream("k", Box::new(Cursor::new(framed))).await?.read_to_end(&mut out).await.unwrap();
+        assert_eq!(out, body);
+
+        let mut reader = unframe_stream("k", Box::new(Cursor::new(corrupted))).await?;
+        let err = reader.read_to_end(&mut Vec::new()).await.unwrap_err();
+        assert!(matches!(stream_error("k", err), OmniXError::DataCorruption { .. }));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_reads_fail_and_scrub_repairs_from_cache() -> Result<(), OmniXError> {
+        let storage = Arc::new(MemoryStorage::new());
+        let cache = Arc::new(MemoryCache::new());
+        let manager = AproarManagerBuilder::new(OmniXMetry::init()?)
+            .storage_backend(storage.clone())
+            .retrieval_cache(cache.clone())
+            .background_tasks(false)
+            .build()?;
+
+        manager.store_data("cached", b"kept in the cache too", 0).await?;
+        manager.store_data("lonely", b"only on disk", 0).await?;
+        cache.delete("lonely")?;
+
+        for key in ["cached", "lonely"] {
+            let mut stored = storage.retrieve(key)?;
+            stored[6] ^= 0xff;
+            storage.store(key, &stored)?;
+        }
+
+        assert!(matches!(manager.retrieve_data("lonely").await, Err(OmniXError::DataCorruption { .. })));
+
+        let report = manager.scrub(true).await?;
+        assert_eq!(report.checked, 2);
+        assert_eq!(report.corrupted, 2);
+        assert_eq!(report.repaired, 1);
+        assert_eq!(report.unrepaired, vec![(0, "lonely".to_string())]);
+
+        cache.delete("cached")?;
+        assert_eq!(manager.retrieve_data("cached").await?, b"kept in the cache too");
+
+        Ok(())
+    }
+}
\ No newline at end of file
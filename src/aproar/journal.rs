@@ -0,0 +1,248 @@
+// src/aproar/journal.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[APROAR]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use crate::aproar::integrity;
+use crate::aproar::retrieval::RetrievalCache;
+use crate::aproar::AproarManager;
+use crate::omnixtracker::{OmniXError, OmniXMetry};
+use chrono::Utc;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A write APROAR is about to make. Only the intent is journaled, not the
+/// payload, so replay settles each key on whichever of its old or new value
+/// actually reached the backend.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum JournalOp {
+    Store { key: String, tier: usize },
+    Delete { key: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub seq: u64,
+    pub op: JournalOp,
+    pub recorded_ms: i64,
+}
+
+/// Write-ahead journal for `AproarManager` writes. `record_intent` must be
+/// durable before it returns; `mark_complete` may be lazy, since replaying an
+/// already-finished entry is harmless.
+pub trait WriteJournal: Send + Sync {
+    fn record_intent(&self, op: JournalOp) -> Result<u64, OmniXError>;
+    /// Records several intents, returning their sequence numbers in order.
+    /// Durable journals override this to sync once for the whole batch.
+    fn record_intents(&self, ops: Vec<JournalOp>) -> Result<Vec<u64>, OmniXError> {
+        ops.into_iter().map(|op| self.record_intent(op)).collect()
+    }
+    fn mark_complete(&self, seq: u64) -> Result<(), OmniXError>;
+    /// Entries that were recorded but never completed, oldest first.
+    fn pending(&self) -> Result<Vec<JournalEntry>, OmniXError>;
+}
+
+/// In-process journal for tests and deployments without durable storage.
+/// It does not survive a restart, so it only helps with failures short of a crash.
+pub struct MemoryJournal {
+    next_seq: AtomicU64,
+    entries: Mutex<BTreeMap<u64, JournalEntry>>,
+}
+
+impl MemoryJournal {
+    pub fn new() -> Self {
+        Self { next_seq: AtomicU64::new(1), entries: Mutex::new(BTreeMap::new()) }
+    }
+}
+
+impl Default for MemoryJournal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WriteJournal for MemoryJournal {
+    fn record_intent(&self, op: JournalOp) -> Result<u64, OmniXError> {
+        let seq = self.next_seq.fetch_add(1, Ordering::SeqCst);
+        self.entries.lock().insert(seq, JournalEntry { seq, op, recorded_ms: Utc::now().timestamp_millis() });
+        Ok(seq)
+    }
+
+    fn mark_complete(&self, seq: u64) -> Result<(), OmniXError> {
+        self.entries.lock().remove(&seq);
+        Ok(())
+    }
+
+    fn pending(&self) -> Result<Vec<JournalEntry>, OmniXError> {
+        Ok(self.entries.lock().values().cloned().collect())
+    }
+}
+
+impl AproarManager {
+    pub(crate) async fn journal_intent(&self, op: JournalOp) -> Result<Option<u64>, OmniXError> {
+        let journal = match &self.journal {
+            Some(journal) => journal.clone(),
+            None => return Ok(None),
+        };
+        crate::aproar::storage::run_blocking(move || journal.record_intent(op)).await.map(Some)
+    }
+
+    /// Batch form of `journal_intent`; yields one `None` per op without a journal.
+    pub(crate) async fn journal_intents(&self, ops: Vec<JournalOp>) -> Result<Vec<Option<u64>>, OmniXError> {
+        let journal = match &self.journal {
+            Some(journal) => journal.clone(),
+            None => return Ok(vec![None; ops.len()]),
+        };
+        let seqs = crate::aproar::storage::run_blocking(move || journal.record_intents(ops)).await?;
+        Ok(seqs.into_iter().map(Some).collect())
+    }
+
+    /// Completion failures are only logged: the write itself succeeded, and
+    /// the leftover entry just costs a redundant repair on the next start.
+    pub(crate) fn journal_complete(&self, seq: Option<u64>) {
+        if let (Some(journal), Some(seq)) = (&self.journal, seq) {
+            if let Err(e) = journal.mark_complete(seq) {
+                self.metrics.increment_counter("journal.complete.failure".to_string(), 1);
+                e.log();
+            }
+        }
+    }
+
+    /// Replays every unfinished journal entry so each key ends up on exactly
+    /// one tier with no stale cache copies. Runs from `AproarManagerBuilder::build`
+    /// and returns the number of entries repaired.
+    pub fn recover_from_journal(&self) -> Result<usize, OmniXError> {
+        let journal: Arc<dyn WriteJournal> = match &self.journal {
+            Some(journal) => journal.clone(),
+            None => return Ok(0),
+        };
+
+        let pending = journal.pending()?;
+        for entry in &pending {
+            match &entry.op {
+                JournalOp::Store { key, tier } => self.repair_store(key, *tier)?,
+                JournalOp::Delete { key } => self.repair_delete(key)?,
+            }
+            journal.mark_complete(entry.seq)?;
+        }
+
+        self.metrics.increment_counter("journal.replayed".to_string(), pending.len() as u64);
+        Ok(pending.len())
+    }
+
+    /// If the interrupted store reached its primary tier intact, drop any copy
+    /// left outside its replica set and bring the replicas in line with the
+    /// primary; if it did not, the old value stays where it was. A primary
+    /// copy the crash tore fails its digest and counts as not landed, so it is
+    /// never copied over intact replicas. Either way the caches may disagree
+    /// with the backend, so the key is evicted from them.
+    fn repair_store(&self, key: &str, tier: usize) -> Result<(), OmniXError> {
+        let primary = match tier < self.storage_backends.len() && self.storage_backends[tier].exists(key)? {
+            true => Some(self.storage_backends[tier].retrieve(key)?),
+            false => None,
+        };
+        let landed = primary.filter(|data| match integrity::unframe(key, data) {
+            Ok(_) => true,
+            Err(e) => {
+                self.metrics.increment_counter("journal.torn_write".to_string(), 1);
+                e.log();
+                false
+            }
+        });
+        if let Some(data) = landed {
+            let replica_set = self.replica_tiers(tier);
+            for (other, backend) in self.storage_backends.iter().enumerate() {
+                if !replica_set.contains(&other) && backend.exists(key)? {
+                    backend.delete(key)?;
+                }
+            }
+            for &replica in &replica_set[1..] {
+                self.storage_backends[replica].store(key, &data)?;
+            }
+            self.placement_index.place(key, |_| Ok(tier))?;
+        }
+        evict_cached(&self.retrieval_caches, &self.metrics, key);
+        Ok(())
+    }
+
+    fn repair_delete(&self, key: &str) -> Result<(), OmniXError> {
+        for backend in &self.storage_backends {
+            if backend.exists(key)? {
+                backend.delete(key)?;
+            }
+        }
+        self.placement_index.remove(key);
+        evict_cached(&self.retrieval_caches, &self.metrics, key);
+        Ok(())
+    }
+
+    pub(crate) async fn evict_from_caches(&self, key: &str) {
+        let key = key.to_string();
+        if let Err(e) = self.with_caches(move |caches, metrics| evict_cached(caches, metrics, &key)).await {
+            e.log();
+        }
+    }
+}
+
+/// Deletes `key` from `caches` on the calling thread. Journal recovery runs
+/// synchronously and calls it directly; async paths use `evict_from_caches`.
+fn evict_cached(caches: &[Arc<dyn RetrievalCache>], metrics: &OmniXMetry, key: &str) {
+    for cache in caches {
+        if let Err(e) = cache.delete(key) {
+            metrics.increment_counter("cache.delete.failure".to_string(), 1);
+            e.log();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aproar::AproarManagerBuilder;
+    use crate::aproar::retrieval::{MemoryCache, RetrievalCache};
+    use crate::aproar::storage::{MemoryStorage, StorageBackend};
+    use crate::omnixtracker::OmniXMetry;
+
+    #[tokio::test]
+    async fn test_startup_replays_interrupted_writes() -> Result<(), OmniXError> {
+        let hot = Arc::new(MemoryStorage::new());
+        let cold = Arc::new(MemoryStorage::new());
+        let cache = Arc::new(MemoryCache::new());
+        let journal = Arc::new(MemoryJournal::new());
+
+        // A store to the hot tier that crashed after the backend write but
+        // before the old cold copy and the cache were cleaned up.
+        hot.store("promoted", &integrity::frame(b"new"))?;
+        cold.store("promoted", &integrity::frame(b"old"))?;
+        cache.set("promoted", b"stale")?;
+        journal.record_intent(JournalOp::Store { key: "promoted".to_string(), tier: 0 })?;
+        // A delete that crashed halfway through.
+        cold.store("removed", b"gone")?;
+        journal.record_intent(JournalOp::Delete { key: "removed".to_string() })?;
+        // A store that crashed partway through writing the hot copy.
+        hot.store("torn", b"partial")?;
+        cold.store("torn", &integrity::frame(b"old"))?;
+        journal.record_intent(JournalOp::Store { key: "torn".to_string(), tier: 0 })?;
+
+        let manager = AproarManagerBuilder::new(OmniXMetry::init()?)
+            .storage_backend(hot.clone())
+            .storage_backend(cold.clone())
+            .retrieval_cache(cache.clone())
+            .journal(journal.clone())
+            .background_tasks(false)
+            .build()?;
+
+        assert!(journal.pending()?.is_empty());
+        assert!(!cold.exists("promoted")?);
+        assert_eq!(cache.get("promoted")?, None);
+        assert!(!cold.exists("removed")?);
+        assert_eq!(manager.placement("promoted"), Some(0));
+        assert_eq!(integrity::unframe("torn", &cold.retrieve("torn")?)?, b"old");
+
+        manager.store_data("fresh", b"value", 0).await?;
+        assert!(journal.pending()?.is_empty());
+
+        Ok(())
+    }
+}
\ No newline at end of file
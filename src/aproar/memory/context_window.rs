@@ -76,7 +76,10 @@ impl ContextWindowManager {
             self.metrics.increment_counter("context_window.relevance_updates".to_string(), 1);
             Ok(())
         } else {
-            Err(OmniXError::NotFound("Chunk not found".to_string()))
+            Err(OmniXError::NotFound {
+                backend: "Context window".to_string(),
+                key: chunk_id.to_string(),
+            })
         }
     }
 }
\ No newline at end of file
@@ -1,31 +1,64 @@
 // src/aproar/mod.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[APROAR]Xyn>=====S===t===u===d===i===o===s======[R|$>
 
-use crate::aproar::compression::{CompressionManager, CompressionStrategy, LZ4Compression, ZstdCompression};
-use crate::aproar::storage::{HDF5Storage, ParquetStorage, TileDBStorage, StorageBackend};
-use crate::aproar::retrieval::{RedisCache, RocksDBStorage, RocksDBPersistence, RetrievalCache};
+use crate::aproar::compression::{encode_stream_with, streams, AdaptiveSelector, CompressionChoice, CompressionManager, CompressionStrategy};
+use crate::aproar::storage::{async_view, key_not_found, run_blocking, ByteReader, StorageBackend, StorageMetadata};
+use crate::aproar::retrieval::RetrievalCache;
+use crate::aproar::dedup::DEDUP_STUB;
+use crate::aproar::dictionary::DictionaryTrainer;
+use crate::aproar::expiry::{expiry_key, ExpiryReaper};
 use crate::aproar::memory::{ContextWindowManager, MemoryConsolidator, ContextChunk};
-use crate::aproar::ntm::{NTM, NTMConfig};
+use crate::aproar::ntm::NTM;
 use crate::omnixtracker::{OmniXMetry, OmniXError};
+use crate::security::AESEncryption;
 use crate::constants::*;
 use uuid::Uuid;
 use tokio::task;
+use std::collections::HashMap;
 use std::sync::Arc;
 use dashmap::DashMap;
 use rayon::prelude::*;
-use std::path::PathBuf;
 use std::time::Instant;
 use parking_lot::RwLock;
 use async_trait::async_trait;
-use futures::future::join_all;
 use tokio::time::{Duration, interval};
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use ndarray::Array1;
+use tokio::io::AsyncReadExt;
+use std::io::Cursor;
 
+mod archive;
+mod batch;
+mod builder;
 mod compression;
+mod config;
+mod dedup;
+mod dictionary;
+mod encryption;
+mod expiry;
+mod integrity;
+mod journal;
 mod memory;
 mod ntm;
+mod replication;
 mod retrieval;
+mod snapshot;
 mod storage;
+mod tiering;
+mod versioning;
+
+pub use archive::{read_archive_manifest, ArchiveEntry, ArchiveManifest, ArchiveReport, ARCHIVE_FORMAT_VERSION};
+pub use builder::AproarManagerBuilder;
+pub use compression::{FloatArray, FloatCodec, Quantization};
+pub use dedup::content_defined_chunks;
+pub use dictionary::DictionaryReport;
+pub use encryption::RotationReport;
+pub use expiry::{ReapReport, RetentionRule};
+pub use integrity::{frame, unframe, ScrubReport};
+pub use replication::replica_tiers;
+pub use journal::{JournalEntry, JournalOp, MemoryJournal, WriteJournal};
+pub use config::{AproarConfig, CacheConfig, CompressionPolicy, DedupConfig, DictionaryConfig, EncryptionConfig, NtmDimensions, StorageTierConfig};
+pub use tiering::{tier_for_frequency, AccessStats, AccessTracker, MigrationReport, PlacementIndex, TierMigrator};
+pub use versioning::{Namespace, RetentionPolicy, VersionInfo, INTERNAL_KEY_PREFIX};
 
 #[async_trait]
 pub trait OmniXurge: Send + Sync {
@@ -49,8 +82,31 @@ pub struct AproarManager {
     context_window_manager: Arc<ContextWindowManager>,
     memory_consolidator: Arc<MemoryConsolidator>,
     compression_manager: CompressionManager,
+    compression_policy: CompressionPolicy,
+    /// Set when the policy asks for adaptive codec selection.
+    compression_selector: Option<Arc<AdaptiveSelector>>,
+    dictionary_trainer: Arc<DictionaryTrainer>,
     storage_backends: Vec<Arc<dyn StorageBackend>>,
+    tier_thresholds: Vec<usize>,
+    replicas: usize,
     retrieval_caches: Vec<Arc<dyn RetrievalCache>>,
+    access_tracker: Arc<AccessTracker>,
+    placement_index: Arc<PlacementIndex>,
+    tier_migrator: Arc<TierMigrator>,
+    retention_policies: DashMap<Namespace, RetentionPolicy>,
+    /// Serializes versioned writes per qualified key.
+    versioning_locks: DashMap<String, Arc<tokio::sync::Mutex<()>>>,
+    /// Chunk hash to reference count, loaded on first use of the dedup layer.
+    content_refs: tokio::sync::Mutex<Option<HashMap<String, usize>>>,
+    /// Set by the reaper when it deletes a manifest behind the counts' back.
+    content_refs_stale: Arc<AtomicBool>,
+    ttl_policies: DashMap<Namespace, Duration>,
+    /// Expiry deadline in Unix milliseconds of every key stored with a TTL.
+    expiries: Arc<DashMap<String, i64>>,
+    expiry_reaper: Arc<ExpiryReaper>,
+    /// Encrypts every value after compression when set.
+    encryption: Option<Arc<AESEncryption>>,
+    journal: Option<Arc<dyn WriteJournal>>,
     metrics: OmniXMetry,
     tasks: Arc<DashMap<Uuid, TaskMetadata>>,
     resource_monitor: Arc<RwLock<ResourceMonitor>>,
@@ -60,55 +116,12 @@ pub struct AproarManager {
 
 impl AproarManager {
     pub fn new(metrics: OmniXMetry) -> Result<Self, OmniXError> {
-        let ntm_config = NTMConfig {
-            input_size: NTM_INPUT_SIZE,
-            output_size: NTM_OUTPUT_SIZE,
-            memory_size: NTM_MEMORY_SIZE,
-            memory_vector_size: NTM_MEMORY_VECTOR_SIZE,
-            controller_size: NTM_CONTROLLER_SIZE,
-        };
-
-        let ntm = NTM::new(
-            NTM_INPUT_SIZE,
-            NTM_OUTPUT_SIZE,
-            NTM_MEMORY_SIZE,
-            NTM_MEMORY_VECTOR_SIZE,
-            NTM_CONTROLLER_SIZE,
-            &ntm_config,
-        ).map_err(|e| OmniXError::InitializationError(format!("Failed to initialize NTM: {}", e)))?;
-
-        let context_window_manager = Arc::new(ContextWindowManager::new(CONTEXT_WINDOW_SIZE, metrics.clone()));
-        let memory_consolidator = Arc::new(MemoryConsolidator::new(strategy, metrics.clone()));
-        let compression_manager = CompressionManager::new(metrics.clone());
-
-        let storage_backends: Vec<Arc<dyn StorageBackend>> = vec![
-            Arc::new(HDF5Storage::new(PathBuf::from("data.h5"), metrics.clone())),
-            Arc::new(ParquetStorage::new(PathBuf::from("data.parquet"))),
-            Arc::new(TileDBStorage::new("tiledb_array")),
-        ];
-
-        let retrieval_caches: Vec<Arc<dyn RetrievalCache>> = vec![
-            Arc::new(RedisCache::new("redis://127.0.0.1/", metrics.clone()).map_err(OmniXError::from)?),
-            Arc::new(RocksDBStorage::new(&Path::from("rocksdb_data"), metrics.clone())?),
-        ];
-
-        let manager = AproarManager {
-            ntm: Arc::new(RwLock::new(ntm)),
-            context_window_manager,
-            memory_consolidator,
-            compression_manager,
-            storage_backends,
-            retrieval_caches,
-            metrics: metrics.clone(),
-            tasks: Arc::new(DashMap::new()),
-            resource_monitor: Arc::new(RwLock::new(ResourceMonitor::default())),
-            max_concurrent_tasks: MAX_CONCURRENT_TASKS,
-            current_task_count: Arc::new(AtomicUsize::new(0)),
-        };
+        let config = AproarConfig::load()?;
+        AproarManagerBuilder::from_config(metrics, &config)?.build()
+    }
 
-        manager.start_resource_monitoring();
-        manager.start_metrics_collection();
-        Ok(manager)
+    pub fn builder(metrics: OmniXMetry) -> AproarManagerBuilder {
+        AproarManagerBuilder::new(metrics)
     }
 
     fn start_resource_monitoring(&self) {
@@ -137,6 +150,20 @@ impl AproarManager {
         });
     }
 
+    fn start_tier_migration(&self) {
+        self.tier_migrator.clone().spawn(Duration::from_millis(TIER_MIGRATION_INTERVAL_MS));
+    }
+
+    fn start_expiry_reaper(&self) {
+        self.expiry_reaper.clone().spawn(Duration::from_millis(EXPIRY_REAPER_INTERVAL_MS));
+    }
+
+    fn start_dictionary_training(&self) {
+        if let Some(config) = &self.compression_policy.dictionary {
+            self.dictionary_trainer.clone().spawn(Duration::from_secs(config.retrain_interval_secs));
+        }
+    }
+
     pub async fn process_with_ntm(&self, input: &[f32]) -> Result<Vec<f32>, OmniXError> {
         let input_array = Array1::from_vec(input.to_vec());
         let mut ntm = self.ntm.write();
@@ -178,83 +205,332 @@ impl AproarManager {
     }
 
     pub fn select_storage_backend(&self, usage_frequency: usize) -> Arc<dyn StorageBackend> {
-        if usage_frequency > HIGH_FREQUENCY_THRESHOLD {
-            self.storage_backends[0].clone()
-        } else if usage_frequency > MEDIUM_FREQUENCY_THRESHOLD {
-            self.storage_backends[1].clone()
-        } else {
-            self.storage_backends[2].clone()
+        self.storage_backends[self.select_storage_tier(usage_frequency)].clone()
+    }
+
+    fn select_storage_tier(&self, usage_frequency: usize) -> usize {
+        tier_for_frequency(&self.tier_thresholds, usage_frequency, self.storage_backends.len())
+    }
+
+    /// The tier holding `key`, probing the backends hottest first when the
+    /// placement index has not seen it yet (for example after a restart).
+    async fn locate(&self, key: &str) -> Result<Option<usize>, OmniXError> {
+        if let Some(tier) = self.placement_index.get(key) {
+            return Ok(Some(tier));
         }
+        for (tier, backend) in self.storage_backends.iter().enumerate() {
+            if async_view(backend).exists(key).await? {
+                let tier = self.placement_index.place(key, |current| Ok(current.unwrap_or(tier)))?;
+                return Ok(Some(tier));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn locate_existing(&self, key: &str) -> Result<usize, OmniXError> {
+        self.locate(key).await?.ok_or_else(|| key_not_found("APROAR", key))
+    }
+
+    pub fn access_stats(&self, key: &str) -> Option<AccessStats> {
+        self.access_tracker.get(key)
+    }
+
+    pub fn placement(&self, key: &str) -> Option<usize> {
+        self.placement_index.get(key)
+    }
+
+    /// Runs a single promotion/demotion pass now instead of waiting for the
+    /// background task.
+    pub async fn migrate_tiers(&self) -> Result<MigrationReport, OmniXError> {
+        let migrator = self.tier_migrator.clone();
+        task::spawn_blocking(move || migrator.run_once())
+            .await
+            .map_err(|e| OmniXError::OperationFailed {
+                operation: "Tier migration".to_string(),
+                details: e.to_string(),
+            })?
     }
 
     pub fn select_compression_strategy(&self, data_size: usize) -> Box<dyn CompressionStrategy> {
-        if data_size > MAX_DATA_SIZE {
-            Box::new(ZstdCompression)
-        } else {
-            Box::new(LZ4Compression)
+        self.compression_policy.codec_for(data_size).strategy()
+    }
+
+    /// The codec and level `data` would be stored with: the adaptive
+    /// selector's pick when one is configured, else the size rule.
+    pub fn select_compression(&self, data: &[u8]) -> CompressionChoice {
+        match &self.compression_selector {
+            Some(selector) => selector.select(data).choice,
+            None => self.compression_policy.codec_for(data.len()).into(),
         }
     }
 
+    /// The largest value whose codec depends on more than its being large:
+    /// the size rule's threshold, or the dictionary's payload cap if higher.
+    fn whole_value_limit(&self) -> usize {
+        let dictionary = self.compression_policy.dictionary.as_ref().map_or(0, |config| config.max_payload);
+        self.compression_policy.large_payload_threshold.max(dictionary)
+    }
+
+    /// Stores `data` on the tier matching the larger of `usage_frequency` and
+    /// the key's tracked frequency, plus the replica tiers after it.
+    /// `usage_frequency` is only a hint for keys APROAR has no history for;
+    /// the migration task takes over from there.
+    ///
+    /// The key expires after the TTL of its namespace if one is set, and
+    /// overwriting a key replaces its old expiry.
     pub async fn store_data(&self, key: &str, data: &[u8], usage_frequency: usize) -> Result<(), OmniXError> {
-        let compression_strategy = self.select_compression_strategy(data.len());
-        let compressed_data = self.compression_manager.compress(compression_strategy.as_ref(), data)?;
-        let storage_backend = self.select_storage_backend(usage_frequency);
-        storage_backend.store(key, &compressed_data)?;
-
-        let cache_futures: Vec<_> = self.retrieval_caches.iter().map(|cache| {
-            let cache_key = key.to_string();
-            let cache_data = compressed_data.clone();
-            cache.set(&cache_key, &cache_data)
-        }).collect();
-
-        for result in join_all(cache_futures).await {
-            if let Err(e) = result {
-                self.metrics.increment_counter("cache.set.failure".to_string(), 1);
-                e.log();
+        self.store_with_expiry(key, data, usage_frequency, self.namespace_ttl(key)).await
+    }
+
+    /// Returns the number of bytes stored per replica.
+    async fn store_value(&self, key: &str, data: &[u8], usage_frequency: usize) -> Result<u64, OmniXError> {
+        let compressed_data = self.seal_value(key, data)?;
+        self.store_sealed(key, compressed_data, usage_frequency).await
+    }
+
+    /// Like `store_value`, for bytes `Sealer` has already sealed.
+    async fn store_sealed(&self, key: &str, compressed_data: Vec<u8>, usage_frequency: usize) -> Result<u64, OmniXError> {
+        self.access_tracker.record(key);
+        let target = self.select_storage_tier(usage_frequency.max(self.access_tracker.frequency(key)));
+        let seq = self.journal_intent(JournalOp::Store { key: key.to_string(), tier: target }).await?;
+
+        let compressed_data = self.store_replicas(key, compressed_data, target).await?;
+        self.cache_stored(key, &compressed_data).await;
+        self.journal_complete(seq);
+
+        Ok(compressed_data.len() as u64)
+    }
+
+    /// Falls back to the next replica when a copy cannot be read or fails its
+    /// digest check, and returns `OmniXError::DataCorruption` only when no
+    /// replica is intact. Corrupted cache copies are evicted and skipped.
+    /// Expired keys read as missing even before the reaper removes them.
+    /// Deduplicated values are reassembled from their chunks.
+    pub async fn retrieve_data(&self, key: &str) -> Result<Vec<u8>, OmniXError> {
+        let value = self.retrieve_value(key).await?;
+        self.resolve_content(key, value).await
+    }
+
+    /// Like `retrieve_data`, without resolving deduplication stubs.
+    async fn retrieve_value(&self, key: &str) -> Result<Vec<u8>, OmniXError> {
+        self.ensure_live(key)?;
+        self.access_tracker.record(key);
+        let owned_key = key.to_string();
+        if let Some(cached_data) = self.with_caches(move |caches, metrics| read_cached(caches, metrics, &owned_key)).await? {
+            let decompressed_data = self.open_value(key, &cached_data)?;
+            self.metrics.increment_counter("cache.hit".to_string(), 1);
+            return Ok(decompressed_data);
+        }
+
+        self.metrics.increment_counter("cache.miss".to_string(), 1);
+        let stored_data = self.read_replicas(key).await?;
+        let decompressed_data = self.open_value(key, &stored_data)?;
+        self.cache_stored(key, &stored_data).await;
+
+        Ok(decompressed_data)
+    }
+
+    /// Runs `f` over the retrieval caches on the blocking pool.
+    /// `RetrievalCache` is synchronous, and network caches wait on their
+    /// server inside every call.
+    async fn with_caches<T, F>(&self, f: F) -> Result<T, OmniXError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&[Arc<dyn RetrievalCache>], &OmniXMetry) -> T + Send + 'static,
+    {
+        if self.retrieval_caches.is_empty() {
+            return Ok(f(&[], &self.metrics));
+        }
+        let caches = self.retrieval_caches.clone();
+        let metrics = self.metrics.clone();
+        run_blocking(move || Ok(f(&caches, &metrics))).await
+    }
+
+    /// Streams `reader` into the tier `usage_frequency` selects, compressed
+    /// with the codec `store_data` would pick for a payload of its size,
+    /// holding only codec buffers in memory on backends with native streaming
+    /// support. Replicas are copied from the primary once it is written, and
+    /// the namespace TTL applies as for `store_data`. Returns the number of
+    /// bytes written after compression. Cached copies of the key are dropped
+    /// rather than refreshed.
+    ///
+    /// Up to `whole_value_limit` bytes are read ahead to choose the codec.
+    /// Values that end within them, values whose codec has no stream
+    /// encoder, values stored with encryption on (AES-GCM seals them whole)
+    /// and keys the dedup policy covers are read into memory and stored as
+    /// by `store_data`.
+    pub async fn store_stream(&self, key: &str, mut reader: ByteReader, usage_frequency: usize) -> Result<u64, OmniXError> {
+        let start_time = Instant::now();
+        self.update_expiry(key, self.namespace_ttl(key)).await?;
+
+        let limit = self.whole_value_limit();
+        let mut head = Vec::new();
+        (&mut reader).take(limit as u64 + 1).read_to_end(&mut head).await.map_err(|e| integrity::stream_error(key, e))?;
+        let choice = self.select_compression(&head);
+        let whole = head.len() <= limit || self.encryption.is_some() || !streams(choice.codec) || self.dedup_enabled(key);
+        let mut reader: ByteReader = Box::new(Cursor::new(head).chain(reader));
+        if whole {
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data).await.map_err(|e| integrity::stream_error(key, e))?;
+            let written = self.store_payload(key, &data, usage_frequency).await?;
+            self.metrics.record_histogram("storage.store_stream.duration".to_string(), start_time.elapsed().as_secs_f64());
+            self.metrics.increment_counter("storage.store_stream.bytes".to_string(), written);
+            return Ok(written);
+        }
+        self.access_tracker.record(key);
+        let target = self.select_storage_tier(usage_frequency.max(self.access_tracker.frequency(key)));
+
+        let placement_index = self.placement_index.clone();
+        let owned_key = key.to_string();
+        let _pin = run_blocking(move || Ok(placement_index.pin(&owned_key))).await?;
+        let seq = self.journal_intent(JournalOp::Store { key: key.to_string(), tier: target }).await?;
+
+        let encoded = integrity::frame_stream(encode_stream_with(reader, choice)?);
+        let written = async_view(&self.storage_backends[target]).store_stream(key, encoded).await?;
+        let replica_set = self.replica_tiers(target);
+        for &tier in &replica_set[1..] {
+            let copy = async_view(&self.storage_backends[target]).retrieve_stream(key).await?;
+            async_view(&self.storage_backends[tier]).store_stream(key, copy).await?;
+        }
+
+        let mut previous = None;
+        self.placement_index.place(key, |current| {
+            previous = current;
+            Ok(target)
+        })?;
+        if let Some(previous) = previous {
+            for stale in self.replica_tiers(previous) {
+                if !replica_set.contains(&stale) {
+                    async_view(&self.storage_backends[stale]).delete(key).await?;
+                }
             }
         }
 
+        self.evict_from_caches(key).await;
+        self.journal_complete(seq);
+
+        let duration = start_time.elapsed();
+        self.metrics.record_histogram("storage.store_stream.duration".to_string(), duration.as_secs_f64());
+        self.metrics.increment_counter("storage.store_stream.bytes".to_string(), written);
+
+        Ok(written)
+    }
+
+    /// Streams the decompressed value of `key`. Works for values written by
+    /// either `store_data` or `store_stream`. A digest mismatch surfaces as an
+    /// `InvalidData` error on the read that reaches the end of the value.
+    /// Deduplicated values are reassembled in memory.
+    pub async fn retrieve_stream(&self, key: &str) -> Result<ByteReader, OmniXError> {
+        self.ensure_live(key)?;
+        self.access_tracker.record(key);
+        self.metrics.increment_counter("storage.retrieve_stream".to_string(), 1);
+        let mut reader = self.open_replica_stream(key).await?;
+        let mut head = Vec::new();
+        (&mut reader).take(DEDUP_STUB.len() as u64 + 1).read_to_end(&mut head).await.map_err(|e| integrity::stream_error(key, e))?;
+        if head == DEDUP_STUB {
+            let value = self.resolve_content(key, head).await?;
+            return Ok(Box::new(Cursor::new(value)));
+        }
+        Ok(Box::new(Cursor::new(head).chain(reader)))
+    }
+
+    pub fn storage_backends(&self) -> &[Arc<dyn StorageBackend>] {
+        &self.storage_backends
+    }
+
+    /// Deletes `key` from every tier and cache, along with its expiry and,
+    /// while the dedup policy is on, its chunk references.
+    pub async fn delete_data(&self, key: &str) -> Result<(), OmniXError> {
+        if self.dedup_enabled(key) {
+            self.release_content(key).await?;
+        }
+        self.delete_value(key).await?;
+        if self.expiries.remove(key).is_some() {
+            self.delete_value(&expiry_key(key)).await?;
+        }
         Ok(())
     }
 
-    pub async fn retrieve_data(&self, key: &str, usage_frequency: usize) -> Result<Vec<u8>, OmniXError> {
-        for cache in &self.retrieval_caches {
-            match cache.get(key) {
-                Ok(Some(cached_data)) => {
-                    let compression_strategy = self.select_compression_strategy(cached_data.len());
-                    let decompressed_data = self.compression_manager.decompress(compression_strategy.as_ref(), &cached_data)?;
-                    self.metrics.increment_counter("cache.hit".to_string(), 1);
-                    return Ok(decompressed_data);
-                }
-                Ok(None) => continue,
-                Err(e) => {
-                    self.metrics.increment_counter("cache.get.failure".to_string(), 1);
-                    e.log();
-                }
+    async fn delete_value(&self, key: &str) -> Result<(), OmniXError> {
+        let seq = self.journal_intent(JournalOp::Delete { key: key.to_string() }).await?;
+        for backend in &self.storage_backends {
+            let backend = async_view(backend);
+            if backend.exists(key).await? {
+                backend.delete(key).await?;
             }
         }
+        self.placement_index.remove(key);
+        self.access_tracker.forget(key);
+        self.evict_from_caches(key).await;
+        self.journal_complete(seq);
 
-        self.metrics.increment_counter("cache.miss".to_string(), 1);
-        let storage_backend = self.select_storage_backend(usage_frequency);
-        let stored_data = storage_backend.retrieve(key)?;
-        let compression_strategy = self.select_compression_strategy(stored_data.len());
-        let decompressed_data = self.compression_manager.decompress(compression_strategy.as_ref(), &stored_data)?;
-
-        let cache_futures: Vec<_> = self.retrieval_caches.iter().map(|cache| {
-            let cache_key = key.to_string();
-            let cache_data = stored_data.clone();
-            cache.set(&cache_key, &cache_data)
-        }).collect();
-
-        for result in join_all(cache_futures).await {
-            if let Err(e) = result {
-                self.metrics.increment_counter("cache.set.failure".to_string(), 1);
-                e.log();
+        self.metrics.increment_counter("storage.delete".to_string(), 1);
+        Ok(())
+    }
+
+    pub async fn data_exists(&self, key: &str) -> Result<bool, OmniXError> {
+        if self.is_expired(key) {
+            return Ok(false);
+        }
+        for backend in &self.storage_backends {
+            if async_view(backend).exists(key).await? {
+                return Ok(true);
             }
         }
+        Ok(false)
+    }
 
-        Ok(decompressed_data)
+    pub async fn list_keys(&self, prefix: &str) -> Result<Vec<String>, OmniXError> {
+        let mut keys = self.list_stored_keys(prefix).await?;
+        keys.retain(|key| !self.is_expired(key));
+        Ok(keys)
+    }
+
+    pub async fn data_metadata(&self, key: &str) -> Result<Option<StorageMetadata>, OmniXError> {
+        if self.is_expired(key) {
+            return Ok(None);
+        }
+        for backend in &self.storage_backends {
+            let backend = backend.clone();
+            let owned_key = key.to_string();
+            let metadata = run_blocking(move || match backend.exists(&owned_key)? {
+                true => backend.metadata(&owned_key).map(Some),
+                false => Ok(None),
+            })
+            .await?;
+            if metadata.is_some() {
+                return Ok(metadata);
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// The first verified cached copy of `key`. Corrupted copies are evicted
+/// and skipped.
+fn read_cached(caches: &[Arc<dyn RetrievalCache>], metrics: &OmniXMetry, key: &str) -> Option<Vec<u8>> {
+    for cache in caches {
+        match cache.get(key) {
+            Ok(Some(cached_data)) => {
+                if let Err(e) = integrity::unframe(key, &cached_data) {
+                    // A bad cache copy is not fatal; drop it and read through.
+                    metrics.increment_counter("cache.corruption".to_string(), 1);
+                    e.log();
+                    if let Err(e) = cache.delete(key) {
+                        e.log();
+                    }
+                    continue;
+                }
+                return Some(cached_data);
+            }
+            Ok(None) => continue,
+            Err(e) => {
+                metrics.increment_counter("cache.get.failure".to_string(), 1);
+                e.log();
+            }
+        }
     }
+    None
 }
 
 #[async_trait]
@@ -0,0 +1,251 @@
+// src/aproar/replication.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[APROAR]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use crate::aproar::compression::CompressionManager;
+use crate::aproar::integrity::{unframe, unframe_stream, verify};
+use crate::aproar::retrieval::RetrievalCache;
+use crate::aproar::storage::{async_view, run_blocking, ByteReader, StorageBackend};
+use crate::aproar::tiering::PlacementIndex;
+use crate::aproar::AproarManager;
+use crate::omnixtracker::OmniXError;
+use std::sync::Arc;
+
+/// The tiers holding the copies of a key whose primary copy lives on
+/// `primary`. Replicas go to the next colder tiers first and only wrap to
+/// hotter ones at the cold end, so hot tiers don't fill up with cold data.
+pub fn replica_tiers(primary: usize, replicas: usize, tier_count: usize) -> Vec<usize> {
+    (primary..tier_count).chain((0..primary).rev()).take(replicas.max(1)).collect()
+}
+
+/// Owned handles for rewriting a bad copy from the blocking pool, outside the
+/// lifetime of the manager call that found it.
+#[derive(Clone)]
+pub(crate) struct ReplicaRepairer {
+    storage_backends: Vec<Arc<dyn StorageBackend>>,
+    retrieval_caches: Vec<Arc<dyn RetrievalCache>>,
+    placement_index: Arc<PlacementIndex>,
+    compression: CompressionManager,
+    replicas: usize,
+}
+
+impl ReplicaRepairer {
+    /// Rewrites the copy of `key` on `tier` from the first verified cache or
+    /// replica copy. Runs under the key's placement lock and re-checks the
+    /// copy there, so a store that landed in between is not overwritten.
+    /// Unindexed keys (deleted since) and keys with a streamed write in
+    /// flight are left alone.
+    pub(crate) fn repair(&self, tier: usize, key: &str) -> Result<bool, OmniXError> {
+        let tier_count = self.storage_backends.len();
+        let mut repaired = false;
+        self.placement_index.relocate(key, |primary| {
+            let target = &self.storage_backends[tier];
+            if target.retrieve(key).is_ok_and(|current| verify(key, &current, &self.compression).is_ok()) {
+                // Overwritten by a newer store since the check.
+                repaired = true;
+                return Ok(primary);
+            }
+
+            let replica_set = replica_tiers(primary, self.replicas, tier_count);
+            let strays = (0..tier_count).filter(|other| !replica_set.contains(other));
+            let cached = self.retrieval_caches.iter().filter_map(|cache| cache.get(key).ok().flatten());
+            let copies = replica_set
+                .iter()
+                .copied()
+                .chain(strays)
+                .filter(|&other| other != tier)
+                .filter_map(|other| self.storage_backends[other].retrieve(key).ok());
+
+            if let Some(healthy) = cached.chain(copies).find(|copy| verify(key, copy, &self.compression).is_ok()) {
+                target.store(key, &healthy)?;
+                repaired = true;
+            }
+            Ok(primary)
+        })?;
+        Ok(repaired)
+    }
+}
+
+impl AproarManager {
+    pub(crate) fn replica_tiers(&self, primary: usize) -> Vec<usize> {
+        replica_tiers(primary, self.replicas, self.storage_backends.len())
+    }
+
+    /// Writes `stored` to every tier in the replica set of `target` and drops
+    /// the copies on tiers of the previous set, returning `stored` so the
+    /// caller can cache it.
+    pub(crate) async fn store_replicas(&self, key: &str, stored: Vec<u8>, target: usize) -> Result<Vec<u8>, OmniXError> {
+        // The write runs under the placement entry lock so it cannot interleave
+        // with a tier migration of the same key, and on the blocking pool so
+        // that lock and the backend I/O never stall a runtime worker.
+        let placement_index = self.placement_index.clone();
+        let storage_backends = self.storage_backends.clone();
+        let replicas = self.replicas;
+        let key = key.to_string();
+        run_blocking(move || {
+            placement_index.place(&key, |current| {
+                let replica_set = replica_tiers(target, replicas, storage_backends.len());
+                for &tier in &replica_set {
+                    storage_backends[tier].store(&key, &stored)?;
+                }
+                if let Some(previous) = current {
+                    for stale in replica_tiers(previous, replicas, storage_backends.len()) {
+                        if !replica_set.contains(&stale) {
+                            storage_backends[stale].delete(&key)?;
+                        }
+                    }
+                }
+                Ok(target)
+            })?;
+            Ok(stored)
+        })
+        .await
+    }
+
+    pub(crate) fn repairer(&self) -> ReplicaRepairer {
+        ReplicaRepairer {
+            storage_backends: self.storage_backends.clone(),
+            retrieval_caches: self.retrieval_caches.clone(),
+            placement_index: self.placement_index.clone(),
+            compression: self.compression_manager.clone(),
+            replicas: self.replicas,
+        }
+    }
+
+    /// Reads the stored bytes of `key` from the first replica that returns a
+    /// copy passing its digest check. Replicas that errored or failed the
+    /// check on the way are repaired in the background.
+    pub(crate) async fn read_replicas(&self, key: &str) -> Result<Vec<u8>, OmniXError> {
+        let primary = self.locate_existing(key).await?;
+        let mut bad_tiers = Vec::new();
+        let mut last_error = None;
+
+        for tier in self.replica_tiers(primary) {
+            let result = async_view(&self.storage_backends[tier]).retrieve(key).await.and_then(|stored| {
+                unframe(key, &stored)?;
+                Ok(stored)
+            });
+            match result {
+                Ok(stored) => {
+                    if !bad_tiers.is_empty() {
+                        self.spawn_read_repair(key, bad_tiers);
+                    }
+                    return Ok(stored);
+                }
+                Err(e) => {
+                    if let OmniXError::DataCorruption { .. } = e {
+                        self.metrics.increment_counter("integrity.corruption".to_string(), 1);
+                    }
+                    self.metrics.increment_counter("replication.read_fallback".to_string(), 1);
+                    e.log();
+                    bad_tiers.push(tier);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| OmniXError::OperationFailed {
+            operation: "Data retrieval".to_string(),
+            details: format!("No replica of {} is readable", key),
+        }))
+    }
+
+    /// Streaming counterpart of `read_replicas`. Only failures up to the
+    /// stream header can fall back to another replica; a digest mismatch
+    /// found at the end of the stream is returned to the reader.
+    pub(crate) async fn open_replica_stream(&self, key: &str) -> Result<ByteReader, OmniXError> {
+        let primary = self.locate_existing(key).await?;
+        let mut last_error = None;
+
+        for tier in self.replica_tiers(primary) {
+            let opened = async {
+                let stored = async_view(&self.storage_backends[tier]).retrieve_stream(key).await?;
+                self.compression_manager
+                    .decode_stream(self.decrypt_stream(key, unframe_stream(key, stored).await?).await?)
+                    .await
+            };
+            match opened.await {
+                Ok(reader) => return Ok(reader),
+                Err(e) => {
+                    self.metrics.increment_counter("replication.read_fallback".to_string(), 1);
+                    e.log();
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| OmniXError::OperationFailed {
+            operation: "Stream retrieval".to_string(),
+            details: format!("No replica of {} is readable", key),
+        }))
+    }
+
+    fn spawn_read_repair(&self, key: &str, tiers: Vec<usize>) {
+        let repairer = self.repairer();
+        let metrics = self.metrics.clone();
+        let key = key.to_string();
+        tokio::spawn(async move {
+            for tier in tiers {
+                let (repairer, key) = (repairer.clone(), key.clone());
+                match run_blocking(move || repairer.repair(tier, &key)).await {
+                    Ok(true) => metrics.increment_counter("replication.read_repair".to_string(), 1),
+                    Ok(false) => {}
+                    Err(e) => {
+                        metrics.increment_counter("replication.read_repair.failure".to_string(), 1);
+                        e.log();
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aproar::storage::MemoryStorage;
+    use crate::aproar::AproarManagerBuilder;
+    use crate::omnixtracker::OmniXMetry;
+    use std::time::Duration;
+
+    #[test]
+    fn test_replica_tiers_prefer_colder_tiers() {
+        assert_eq!(replica_tiers(0, 2, 3), vec![0, 1]);
+        assert_eq!(replica_tiers(2, 2, 3), vec![2, 1]);
+        assert_eq!(replica_tiers(1, 3, 3), vec![1, 2, 0]);
+        assert_eq!(replica_tiers(1, 1, 3), vec![1]);
+    }
+
+    #[tokio::test]
+    async fn test_reads_fall_back_and_repair_bad_replicas() -> Result<(), OmniXError> {
+        let tiers: Vec<Arc<MemoryStorage>> = (0..3).map(|_| Arc::new(MemoryStorage::new())).collect();
+        let mut builder = AproarManagerBuilder::new(OmniXMetry::init()?).replicas(2).background_tasks(false);
+        for tier in &tiers {
+            builder = builder.storage_backend(tier.clone());
+        }
+        let manager = builder.build()?;
+
+        // Frequency 0 selects the coldest tier, so the replica goes to tier 1.
+        manager.store_data("corrupted", b"two copies", 0).await?;
+        manager.store_data("missing", b"two copies as well", 0).await?;
+        assert!(tiers[1].exists("corrupted")? && tiers[2].exists("corrupted")?);
+        assert!(!tiers[0].exists("corrupted")?);
+
+        let mut stored = tiers[2].retrieve("corrupted")?;
+        stored[6] ^= 0xff;
+        tiers[2].store("corrupted", &stored)?;
+        tiers[2].delete("missing")?;
+
+        assert_eq!(manager.retrieve_data("corrupted").await?, b"two copies");
+        assert_eq!(manager.retrieve_data("missing").await?, b"two copies as well");
+
+        for _ in 0..100 {
+            if tiers[2].exists("missing")? && tiers[2].retrieve("corrupted")? != stored {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(tiers[2].retrieve("corrupted")?, tiers[1].retrieve("corrupted")?);
+        assert_eq!(tiers[2].retrieve("missing")?, tiers[1].retrieve("missing")?);
+
+        Ok(())
+    }
+}
\ No newline at end of file
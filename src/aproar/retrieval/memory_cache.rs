@@ -0,0 +1,90 @@
+// src/aproar/retrieval/memory_cache.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[RETRIEVAL]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use crate::aproar::storage::LruMap;
+use crate::omnixtracker::{OmniXError, OmniXMetry};
+use super::RetrievalCache;
+use parking_lot::Mutex;
+
+/// In-process cache layer with an optional byte cap and LRU eviction.
+pub struct MemoryCache {
+    entries: Mutex<LruMap>,
+    metrics: Option<OmniXMetry>,
+}
+
+impl MemoryCache {
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(LruMap::new(None)),
+            metrics: None,
+        }
+    }
+
+    pub fn with_max_bytes(max_bytes: usize) -> Self {
+        Self {
+            entries: Mutex::new(LruMap::new(Some(max_bytes))),
+            metrics: None,
+        }
+    }
+
+    pub fn with_metrics(mut self, metrics: OmniXMetry) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub fn total_bytes(&self) -> usize {
+        self.entries.lock().total_bytes()
+    }
+}
+
+impl Default for MemoryCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RetrievalCache for MemoryCache {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, OmniXError> {
+        Ok(self.entries.lock().get(key))
+    }
+
+    fn set(&self, key: &str, value: &[u8]) -> Result<(), OmniXError> {
+        let evicted = self.entries.lock().insert(key, value)?;
+        if let Some(metrics) = &self.metrics {
+            metrics.increment_counter("memory_cache.set.total".to_string(), 1);
+            if !evicted.is_empty() {
+                metrics.increment_counter("memory_cache.evictions".to_string(), evicted.len() as u64);
+            }
+        }
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), OmniXError> {
+        self.entries.lock().delete(key);
+        Ok(())
+    }
+
+    fn scan_prefix(&self, prefix: &str) -> Result<Option<Vec<(String, Vec<u8>)>>, OmniXError> {
+        let mut entries = self.entries.lock();
+        let keys = entries.keys_with_prefix(prefix);
+        Ok(Some(keys.into_iter().filter_map(|key| entries.get(&key).map(|value| (key, value))).collect()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_cache_respects_byte_cap() -> Result<(), OmniXError> {
+        let cache = MemoryCache::with_max_bytes(6);
+        cache.set("a", b"123")?;
+        cache.set("b", b"456")?;
+        cache.set("c", b"789")?;
+
+        assert_eq!(cache.get("a")?, None);
+        assert_eq!(cache.get("c")?, Some(b"789".to_vec()));
+        assert_eq!(cache.total_bytes(), 6);
+
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -1,17 +1,44 @@
 // src/aproar/retrieval/mod.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[RETRIEVAL]Xyn>=====S===t===u===d===i===o===s======[R|$>
 
+mod memory_cache;
 mod redis_cache;
+#[cfg(feature = "rocksdb-storage")]
 mod rocksdb;
 
 use crate::omnixtracker::OmniXError;
 use anyhow::Result;
 use async_trait::async_trait;
+use std::time::Duration;
 
+pub use memory_cache::MemoryCache;
 pub use redis_cache::RedisCache;
-pub use rocksdb::{RocksDBStorage, RocksDBPersistence};
+#[cfg(feature = "rocksdb-storage")]
+pub use rocksdb::{RocksDBJournal, RocksDBStorage, RocksDBPersistence};
 
 #[async_trait]
 pub trait RetrievalCache: Send + Sync {
     fn get(&self, key: &str) -> Result<Option<Vec<u8>>, OmniXError>;
     fn set(&self, key: &str, value: &[u8]) -> Result<(), OmniXError>;
+    fn delete(&self, key: &str) -> Result<(), OmniXError>;
+
+    /// Caches an entry that must not outlive `ttl`. Caches without native
+    /// expiry fall back to `set`; the manager still refuses expired keys.
+    fn set_with_ttl(&self, key: &str, value: &[u8], _ttl: Duration) -> Result<(), OmniXError> {
+        self.set(key, value)
+    }
+
+    /// One entry per key, in order. Caches with a multi-key read override this.
+    fn get_many(&self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>, OmniXError> {
+        keys.iter().map(|key| self.get(key)).collect()
+    }
+
+    fn set_many(&self, entries: &[(String, Vec<u8>)]) -> Result<(), OmniXError> {
+        entries.iter().try_for_each(|(key, value)| self.set(key, value))
+    }
+
+    /// Every cached entry whose key starts with `prefix`, or `None` when the
+    /// cache cannot enumerate its keys.
+    fn scan_prefix(&self, _prefix: &str) -> Result<Option<Vec<(String, Vec<u8>)>>, OmniXError> {
+        Ok(None)
+    }
 }
\ No newline at end of file
@@ -2,16 +2,23 @@
 
 use crate::omnixtracker::{OmniXError, OmniXMetry};
 use super::RetrievalCache;
-use redis::{AsyncCommands, Client};
+use redis::aio::ConnectionManager;
+use redis::{AsyncCommands, Client, RedisResult};
 use anyhow::{Context, Result};
 use tokio::runtime::Runtime;
-use parking_lot::RwLock;
+use tokio::sync::OnceCell;
+use std::future::Future;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 
 pub struct RedisCache {
     client: Client,
-    runtime: Arc<Runtime>,
+    /// Opened on first use and shared by every call; it reconnects by itself
+    /// after the server goes away.
+    connection: Arc<OnceCell<ConnectionManager>>,
+    /// Only taken on drop.
+    runtime: Option<Runtime>,
     metrics: OmniXMetry,
 }
 
@@ -30,75 +37,125 @@ impl RedisCache {
 
         Ok(Self {
             client,
-            runtime: Arc::new(runtime),
+            connection: Arc::new(OnceCell::new()),
+            runtime: Some(runtime),
             metrics,
         })
     }
-}
 
-impl RetrievalCache for RedisCache {
-    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, OmniXError> {
+    /// Runs `future` on the cache's own runtime and waits for it. Spawning it
+    /// rather than calling `block_on` keeps the cache usable from threads
+    /// that are already inside a runtime.
+    fn run<T, F>(&self, future: F) -> Result<T, OmniXError>
+    where
+        T: Send + 'static,
+        F: Future<Output = Result<T, OmniXError>> + Send + 'static,
+    {
+        let runtime = self.runtime.as_ref().expect("the runtime is only taken on drop");
+        futures::executor::block_on(runtime.spawn(future)).map_err(|e| OmniXError::OperationFailed {
+            operation: "Redis cache task".to_string(),
+            details: e.to_string(),
+        })?
+    }
+
+    /// Runs `op` against the shared connection and records its duration and
+    /// outcome under `redis.<op_name>.*`.
+    fn with_connection<T, F, Fut>(&self, op_name: &'static str, op: F) -> Result<T, OmniXError>
+    where
+        T: Send + 'static,
+        F: FnOnce(ConnectionManager) -> Fut + Send + 'static,
+        Fut: Future<Output = RedisResult<T>> + Send + 'static,
+    {
         let client = self.client.clone();
-        let key = key.to_string();
+        let connection = self.connection.clone();
         let metrics = self.metrics.clone();
 
-        self.runtime.block_on(async move {
-            let start_time = std::time::Instant::now();
-            let mut con = client
-                .get_async_connection()
+        self.run(async move {
+            let con = connection
+                .get_or_try_init(|| client.get_connection_manager())
                 .await
                 .with_context(|| "Failed to get Redis connection")
-                .map_err(|e| OmniXError::NetworkError(e.to_string()))?;
+                .map_err(|e| OmniXError::NetworkError(e.to_string()))?
+                .clone();
 
-            let result: Result<Option<Vec<u8>>, redis::RedisError> = con.get(key).await;
+            let start_time = Instant::now();
+            let result = op(con).await;
             let duration = start_time.elapsed();
 
-            metrics.record_histogram("redis.get.duration".to_string(), duration.as_secs_f64());
-            metrics.increment_counter("redis.get.total".to_string(), 1);
+            metrics.record_histogram(format!("redis.{}.duration", op_name), duration.as_secs_f64());
+            metrics.increment_counter(format!("redis.{}.total", op_name), 1);
 
             match result {
                 Ok(value) => {
-                    metrics.increment_counter("redis.get.success".to_string(), 1);
+                    metrics.increment_counter(format!("redis.{}.success", op_name), 1);
                     Ok(value)
                 }
                 Err(e) => {
-                    metrics.increment_counter("redis.get.failure".to_string(), 1);
+                    metrics.increment_counter(format!("redis.{}.failure", op_name), 1);
                     Err(OmniXError::NetworkError(e.to_string()))
                 }
             }
         })
     }
+}
+
+impl Drop for RedisCache {
+    // Dropping a runtime blocks, which panics when the last manager goes away
+    // on a runtime worker.
+    fn drop(&mut self) {
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+impl RetrievalCache for RedisCache {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, OmniXError> {
+        let key = key.to_string();
+        self.with_connection("get", move |mut con| async move { con.get(key).await })
+    }
 
     fn set(&self, key: &str, value: &[u8]) -> Result<(), OmniXError> {
-        let client = self.client.clone();
         let key = key.to_string();
         let value = value.to_vec();
-        let metrics = self.metrics.clone();
+        self.with_connection("set", move |mut con| async move { con.set(key, value).await })
+    }
 
-        self.runtime.block_on(async move {
-            let start_time = std::time::Instant::now();
-            let mut con = client
-                .get_async_connection()
-                .await
-                .with_context(|| "Failed to get Redis connection")
-                .map_err(|e| OmniXError::NetworkError(e.to_string()))?;
+    fn set_with_ttl(&self, key: &str, value: &[u8], ttl: Duration) -> Result<(), OmniXError> {
+        let key = key.to_string();
+        let value = value.to_vec();
+        let millis = ttl.as_millis().clamp(1, u64::MAX as u128) as u64;
+        self.with_connection("set_with_ttl", move |mut con| async move { con.pset_ex(key, value, millis).await })
+    }
 
-            let result: Result<(), redis::RedisError> = con.set(key, value).await;
-            let duration = start_time.elapsed();
+    fn delete(&self, key: &str) -> Result<(), OmniXError> {
+        let key = key.to_string();
+        self.with_connection("delete", move |mut con| async move { con.del(key).await })
+    }
 
-            metrics.record_histogram("redis.set.duration".to_string(), duration.as_secs_f64());
-            metrics.increment_counter("redis.set.total".to_string(), 1);
+    fn get_many(&self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>, OmniXError> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.metrics.increment_counter("redis.get_many.keys".to_string(), keys.len() as u64);
+
+        let mut pipe = redis::pipe();
+        for key in keys {
+            pipe.get(key);
+        }
+        self.with_connection("get_many", move |mut con| async move { pipe.query_async(&mut con).await })
+    }
 
-            match result {
-                Ok(_) => {
-                    metrics.increment_counter("redis.set.success".to_string(), 1);
-                    Ok(())
-                }
-                Err(e) => {
-                    metrics.increment_counter("redis.set.failure".to_string(), 1);
-                    Err(OmniXError::NetworkError(e.to_string()))
-                }
-            }
-        })
+    fn set_many(&self, entries: &[(String, Vec<u8>)]) -> Result<(), OmniXError> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        self.metrics.increment_counter("redis.set_many.keys".to_string(), entries.len() as u64);
+
+        let mut pipe = redis::pipe();
+        for (key, value) in entries {
+            pipe.set(key, value.as_slice()).ignore();
+        }
+        self.with_connection("set_many", move |mut con| async move { pipe.query_async(&mut con).await })
     }
 }
\ No newline at end of file
@@ -1,13 +1,17 @@
 // src/aproar/retrieval/rocksdb.rs  ~=#######D]======A===r===c====M===o===o===n=====<Lord[RETRIEVAL]Xyn>=====S===t===u===d===i===o===s======[R|$>
 
 use rocksdb::{DB, Options, ColumnFamilyDescriptor, WriteBatch, WriteOptions, ReadOptions, IteratorMode};
+use crate::aproar::{JournalEntry, JournalOp, WriteJournal};
+use super::RetrievalCache;
 use crate::omnixtracker::{OmniXMetry, OmniXError};
 use crate::constants::*;
+use chrono::Utc;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 use parking_lot::{Mutex, RwLock}; // Using Mutex for RocksDBStorage and RwLock for RocksDBPersistence
 use serde::{Serialize, Deserialize};
-use bincode;
 use anyhow::{Context, Result};
 
 pub struct RocksDBStorage {
@@ -38,12 +42,12 @@ impl RocksDBStorage {
         })
     }
 
-    pub async fn put<T: Serialize>(&self, key: &[u8], value: &T) -> Result<(), OmniXError> {
+    pub fn put<T: Serialize + ?Sized>(&self, key: &[u8], value: &T) -> Result<(), OmniXError> {
         let start = std::time::Instant::now();
         let serialized = bincode::serialize(value)
-            .map_err(|e| OmniXError::SerializationError(e.to_string()))?;
+            .map_err(|e| OmniXError::DatabaseError(format!("Failed to encode value: {}", e)))?;
 
-        let db = self.db.lock().await;
+        let db = self.db.lock();
         db.put(key, &serialized)
             .map_err(|e| OmniXError::DatabaseError(format!("Failed to put data: {}", e)))?;
 
@@ -52,9 +56,9 @@ impl RocksDBStorage {
         Ok(())
     }
 
-    pub async fn get<T: for<'de> Deserialize<'de>>(&self, key: &[u8]) -> Result<Option<T>, OmniXError> {
+    pub fn get<T: for<'de> Deserialize<'de>>(&self, key: &[u8]) -> Result<Option<T>, OmniXError> {
         let start = std::time::Instant::now();
-        let db = self.db.lock().await;
+        let db = self.db.lock();
         let result = db.get(key)
             .map_err(|e| OmniXError::DatabaseError(format!("Failed to get data: {}", e)))?;
 
@@ -65,16 +69,16 @@ impl RocksDBStorage {
         match result {
             Some(data) => {
                 let deserialized = bincode::deserialize(&data)
-                    .map_err(|e| OmniXError::DeserializationError(e.to_string()))?;
+                    .map_err(|e| OmniXError::DatabaseError(format!("Failed to decode value: {}", e)))?;
                 Ok(Some(deserialized))
             }
             None => Ok(None),
         }
     }
 
-    pub async fn delete(&self, key: &[u8]) -> Result<(), OmniXError> {
+    pub fn delete(&self, key: &[u8]) -> Result<(), OmniXError> {
         let start = std::time::Instant::now();
-        let db = self.db.lock().await;
+        let db = self.db.lock();
         db.delete(key)
             .map_err(|e| OmniXError::DatabaseError(format!("Failed to delete data: {}", e)))?;
 
@@ -83,16 +87,16 @@ impl RocksDBStorage {
         Ok(())
     }
 
-    pub async fn batch_write<T: Serialize>(&self, writes: Vec<(Vec<u8>, T)>) -> Result<(), OmniXError> {
+    pub fn batch_write<T: Serialize>(&self, writes: Vec<(Vec<u8>, T)>) -> Result<(), OmniXError> {
         let start = std::time::Instant::now();
         let mut batch = WriteBatch::default();
         for (key, value) in writes {
             let serialized = bincode::serialize(&value)
-                .map_err(|e| OmniXError::SerializationError(e.to_string()))?;
+                .map_err(|e| OmniXError::DatabaseError(format!("Failed to encode value: {}", e)))?;
             batch.put(&key, &serialized);
         }
 
-        let db = self.db.lock().await;
+        let db = self.db.lock();
         let mut write_opts = WriteOptions::default();
         write_opts.set_sync(false);
         db.write_opt(batch, &write_opts)
@@ -103,9 +107,10 @@ impl RocksDBStorage {
         Ok(())
     }
 
-    pub async fn range_scan<T: for<'de> Deserialize<'de>>(&self, start: &[u8], end: &[u8]) -> Result<Vec<(Vec<u8>, T)>, OmniXError> {
-        let start = std::time::Instant::now();
-        let db = self.db.lock().await;
+    /// Entries with keys in `[start, end)`, in key order.
+    pub fn range_scan<T: for<'de> Deserialize<'de>>(&self, start: &[u8], end: &[u8]) -> Result<Vec<(Vec<u8>, T)>, OmniXError> {
+        let start_time = std::time::Instant::now();
+        let db = self.db.lock();
         let mut read_opts = ReadOptions::default();
         read_opts.set_iterate_lower_bound(start.to_vec());
         read_opts.set_iterate_upper_bound(end.to_vec());
@@ -116,18 +121,18 @@ impl RocksDBStorage {
         for item in iter {
             let (key, value) = item.map_err(|e| OmniXError::DatabaseError(format!("Failed to iterate: {}", e)))?;
             let deserialized: T = bincode::deserialize(&value)
-                .map_err(|e| OmniXError::DeserializationError(e.to_string()))?;
+                .map_err(|e| OmniXError::DatabaseError(format!("Failed to decode value: {}", e)))?;
             result.push((key.to_vec(), deserialized));
         }
 
-        self.metrics.record_histogram("rocksdb.range_scan.duration".to_string(), start.elapsed().as_secs_f64());
+        self.metrics.record_histogram("rocksdb.range_scan.duration".to_string(), start_time.elapsed().as_secs_f64());
         self.metrics.increment_counter("rocksdb.range_scan.count".to_string(), 1);
         Ok(result)
     }
 
-    pub async fn compact(&self) -> Result<(), OmniXError> {
+    pub fn compact(&self) -> Result<(), OmniXError> {
         let start = std::time::Instant::now();
-        let db = self.db.lock().await;
+        let db = self.db.lock();
         db.compact_range::<&[u8], &[u8]>(None, None);
 
         self.metrics.record_histogram("rocksdb.compact.duration".to_string(), start.elapsed().as_secs_f64());
@@ -136,6 +141,85 @@ impl RocksDBStorage {
     }
 }
 
+/// The smallest key greater than every key starting with `prefix`, or `None`
+/// when no such key exists (an empty or all-`0xff` prefix).
+fn prefix_upper_bound(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < u8::MAX {
+            end.push(last + 1);
+            return Some(end);
+        }
+    }
+    None
+}
+
+/// A cached value as `RocksDBStorage` stores it, bincode-encoded like the
+/// rest of its data. Entries cached with a TTL carry the Unix time in
+/// milliseconds at which they expire, and reads drop them after that.
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    value: Vec<u8>,
+    expires_ms: Option<i64>,
+}
+
+impl CacheEntry {
+    fn new(value: &[u8], ttl: Option<Duration>) -> Self {
+        let expires_ms = ttl.map(|ttl| {
+            let millis = i64::try_from(ttl.as_millis()).unwrap_or(i64::MAX);
+            Utc::now().timestamp_millis().saturating_add(millis)
+        });
+        Self { value: value.to_vec(), expires_ms }
+    }
+
+    fn is_expired(&self, now_ms: i64) -> bool {
+        self.expires_ms.is_some_and(|expires_ms| expires_ms <= now_ms)
+    }
+}
+
+impl RetrievalCache for RocksDBStorage {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, OmniXError> {
+        match RocksDBStorage::get::<CacheEntry>(self, key.as_bytes())? {
+            Some(entry) if entry.is_expired(Utc::now().timestamp_millis()) => {
+                RocksDBStorage::delete(self, key.as_bytes())?;
+                Ok(None)
+            }
+            entry => Ok(entry.map(|entry| entry.value)),
+        }
+    }
+
+    fn set(&self, key: &str, value: &[u8]) -> Result<(), OmniXError> {
+        self.put(key.as_bytes(), &CacheEntry::new(value, None))
+    }
+
+    fn set_with_ttl(&self, key: &str, value: &[u8], ttl: Duration) -> Result<(), OmniXError> {
+        self.put(key.as_bytes(), &CacheEntry::new(value, Some(ttl)))
+    }
+
+    fn delete(&self, key: &str) -> Result<(), OmniXError> {
+        RocksDBStorage::delete(self, key.as_bytes())
+    }
+
+    fn set_many(&self, entries: &[(String, Vec<u8>)]) -> Result<(), OmniXError> {
+        self.batch_write(entries.iter().map(|(key, value)| (key.as_bytes().to_vec(), CacheEntry::new(value, None))).collect())
+    }
+
+    fn scan_prefix(&self, prefix: &str) -> Result<Option<Vec<(String, Vec<u8>)>>, OmniXError> {
+        let end = match prefix_upper_bound(prefix.as_bytes()) {
+            Some(end) => end,
+            None => return Ok(None),
+        };
+        let now_ms = Utc::now().timestamp_millis();
+        let entries = self
+            .range_scan::<CacheEntry>(prefix.as_bytes(), &end)?
+            .into_iter()
+            .filter(|(_, entry)| !entry.is_expired(now_ms))
+            .filter_map(|(key, entry)| String::from_utf8(key).ok().map(|key| (key, entry.value)))
+            .collect();
+        Ok(Some(entries))
+    }
+}
+
 pub struct RocksDBPersistence {
     db: Arc<RwLock<DB>>,
     metrics: OmniXMetry,
@@ -154,6 +238,11 @@ impl RocksDBPersistence {
             metrics,
         })
     }
+
+    /// A write-ahead journal that lives in this same RocksDB instance.
+    pub fn journal(&self) -> Result<RocksDBJournal, OmniXError> {
+        RocksDBJournal::from_db(self.db.clone(), self.metrics.clone())
+    }
 }
 
 impl RetrievalCache for RocksDBPersistence {
@@ -196,4 +285,155 @@ impl RetrievalCache for RocksDBPersistence {
             }
         }
     }
+
+    fn delete(&self, key: &str) -> Result<(), OmniXError> {
+        let start_time = std::time::Instant::now();
+        let result = self.db.write().delete(key.as_bytes());
+        let duration = start_time.elapsed();
+
+        self.metrics.record_histogram("rocksdb.delete.duration".to_string(), duration.as_secs_f64());
+        self.metrics.increment_counter("rocksdb.delete.total".to_string(), 1);
+
+        match result {
+            Ok(_) => {
+                self.metrics.increment_counter("rocksdb.delete.success".to_string(), 1);
+                Ok(())
+            }
+            Err(e) => {
+                self.metrics.increment_counter("rocksdb.delete.failure".to_string(), 1);
+                Err(OmniXError::DatabaseError(e.to_string()))
+            }
+        }
+    }
+}
+
+const JOURNAL_KEY_PREFIX: &str = "__aproar_journal::";
+// One past ':' so the range covers exactly the keys under JOURNAL_KEY_PREFIX.
+const JOURNAL_KEY_END: &str = "__aproar_journal:;";
+
+/// `WriteJournal` on RocksDB. Intents are written with a synced WAL so they
+/// survive a crash; completions are not synced, since losing one only means
+/// a harmless replay.
+pub struct RocksDBJournal {
+    db: Arc<RwLock<DB>>,
+    next_seq: AtomicU64,
+    metrics: OmniXMetry,
+}
+
+impl RocksDBJournal {
+    pub fn open(db_path: &Path, metrics: OmniXMetry) -> Result<Self, OmniXError> {
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        let db = DB::open(&opts, db_path)
+            .with_context(|| format!("Failed to open RocksDB journal at {}", db_path.display()))
+            .map_err(|e| OmniXError::DatabaseError(e.to_string()))?;
+        Self::from_db(Arc::new(RwLock::new(db)), metrics)
+    }
+
+    fn from_db(db: Arc<RwLock<DB>>, metrics: OmniXMetry) -> Result<Self, OmniXError> {
+        let journal = Self { db, next_seq: AtomicU64::new(1), metrics };
+        let last_seq = journal.pending()?.last().map(|entry| entry.seq).unwrap_or(0);
+        journal.next_seq.store(last_seq + 1, Ordering::SeqCst);
+        Ok(journal)
+    }
+
+    fn entry_key(seq: u64) -> String {
+        format!("{}{:020}", JOURNAL_KEY_PREFIX, seq)
+    }
+}
+
+impl WriteJournal for RocksDBJournal {
+    fn record_intent(&self, op: JournalOp) -> Result<u64, OmniXError> {
+        let start_time = std::time::Instant::now();
+        let seq = self.next_seq.fetch_add(1, Ordering::SeqCst);
+        let entry = JournalEntry { seq, op, recorded_ms: Utc::now().timestamp_millis() };
+        let serialized = serde_json::to_vec(&entry)
+            .map_err(|e| OmniXError::DatabaseError(format!("Failed to encode journal entry: {}", e)))?;
+
+        let mut write_opts = WriteOptions::default();
+        write_opts.set_sync(true);
+        self.db
+            .read()
+            .put_opt(Self::entry_key(seq).as_bytes(), &serialized, &write_opts)
+            .map_err(|e| OmniXError::DatabaseError(format!("Failed to journal intent: {}", e)))?;
+
+        self.metrics.record_histogram("journal.intent.duration".to_string(), start_time.elapsed().as_secs_f64());
+        self.metrics.increment_counter("journal.intent.count".to_string(), 1);
+        Ok(seq)
+    }
+
+    fn record_intents(&self, ops: Vec<JournalOp>) -> Result<Vec<u64>, OmniXError> {
+        let start_time = std::time::Instant::now();
+        let recorded_ms = Utc::now().timestamp_millis();
+        let mut batch = WriteBatch::default();
+        let mut seqs = Vec::with_capacity(ops.len());
+        for op in ops {
+            let seq = self.next_seq.fetch_add(1, Ordering::SeqCst);
+            let serialized = serde_json::to_vec(&JournalEntry { seq, op, recorded_ms })
+                .map_err(|e| OmniXError::DatabaseError(format!("Failed to encode journal entry: {}", e)))?;
+            batch.put(Self::entry_key(seq).as_bytes(), &serialized);
+            seqs.push(seq);
+        }
+
+        let mut write_opts = WriteOptions::default();
+        write_opts.set_sync(true);
+        self.db
+            .read()
+            .write_opt(batch, &write_opts)
+            .map_err(|e| OmniXError::DatabaseError(format!("Failed to journal intents: {}", e)))?;
+
+        self.metrics.record_histogram("journal.intent.duration".to_string(), start_time.elapsed().as_secs_f64());
+        self.metrics.increment_counter("journal.intent.count".to_string(), seqs.len() as u64);
+        Ok(seqs)
+    }
+
+    fn mark_complete(&self, seq: u64) -> Result<(), OmniXError> {
+        self.db
+            .read()
+            .delete(Self::entry_key(seq).as_bytes())
+            .map_err(|e| OmniXError::DatabaseError(format!("Failed to complete journal entry: {}", e)))?;
+        self.metrics.increment_counter("journal.complete.count".to_string(), 1);
+        Ok(())
+    }
+
+    fn pending(&self) -> Result<Vec<JournalEntry>, OmniXError> {
+        let db = self.db.read();
+        let mut read_opts = ReadOptions::default();
+        read_opts.set_iterate_lower_bound(JOURNAL_KEY_PREFIX.as_bytes().to_vec());
+        read_opts.set_iterate_upper_bound(JOURNAL_KEY_END.as_bytes().to_vec());
+
+        let mut entries = Vec::new();
+        for item in db.iterator_opt(IteratorMode::Start, read_opts) {
+            let (_, value) = item.map_err(|e| OmniXError::DatabaseError(format!("Failed to iterate journal: {}", e)))?;
+            let entry: JournalEntry = serde_json::from_slice(&value)
+                .map_err(|e| OmniXError::DatabaseError(format!("Failed to decode journal entry: {}", e)))?;
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_rocksdb_cache_drops_expired_entries() -> Result<(), OmniXError> {
+        let dir = tempdir().unwrap();
+        let cache = RocksDBStorage::new(dir.path(), OmniXMetry::init()?)?;
+
+        cache.set_with_ttl("ttl/short", b"short", Duration::from_millis(1))?;
+        cache.set_with_ttl("ttl/long", b"long", Duration::from_secs(3600))?;
+        cache.set("ttl/plain", b"plain")?;
+        std::thread::sleep(Duration::from_millis(10));
+
+        assert_eq!(RetrievalCache::get(&cache, "ttl/short")?, None);
+        assert_eq!(RetrievalCache::get(&cache, "ttl/long")?, Some(b"long".to_vec()));
+        assert_eq!(RetrievalCache::get(&cache, "ttl/plain")?, Some(b"plain".to_vec()));
+        let keys: Vec<String> = cache.scan_prefix("ttl/")?.unwrap_or_default().into_iter().map(|(key, _)| key).collect();
+        assert_eq!(keys, vec!["ttl/long".to_string(), "ttl/plain".to_string()]);
+
+        Ok(())
+    }
 }
\ No newline at end of file
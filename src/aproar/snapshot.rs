@@ -0,0 +1,80 @@
+// src/aproar/snapshot.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[APROAR]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use crate::aproar::compression::{CodecKind, FloatArray, FloatCodec};
+use crate::aproar::AproarManager;
+use crate::omnixtracker::OmniXError;
+
+impl AproarManager {
+    /// Stores `values`, rows of `row_len` such as an NTM memory matrix or a
+    /// batch of embeddings, encoded with `codec`. Read it back with
+    /// `retrieve_float_array`; each value comes back within
+    /// `codec.error_bound(values, row_len)` of what was stored. The encoding
+    /// is already compressed, so it is stored without a second codec pass.
+    pub async fn store_float_array(
+        &self,
+        key: &str,
+        values: &[f32],
+        row_len: usize,
+        codec: FloatCodec,
+        usage_frequency: usize,
+    ) -> Result<(), OmniXError> {
+        let encoded = codec.encode(values, row_len)?;
+        if !values.is_empty() {
+            self.metrics.record_histogram(
+                "storage.float_array.ratio".to_string(),
+                (values.len() * 4) as f64 / encoded.len() as f64,
+            );
+        }
+        let sealed = self.sealer().seal_with(key, CodecKind::Uncompressed, &encoded)?;
+        self.update_expiry(key, self.namespace_ttl(key)).await?;
+        self.store_sealed(key, sealed, usage_frequency).await?;
+        Ok(())
+    }
+
+    pub async fn retrieve_float_array(&self, key: &str) -> Result<FloatArray, OmniXError> {
+        let encoded = self.retrieve_data(key).await?;
+        FloatCodec::decode(&encoded).map_err(|e| OmniXError::DataCorruption {
+            key: key.to_string(),
+            details: e.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aproar::compression::{CodecKind, Envelope, FloatCodec, Quantization};
+    use crate::aproar::storage::{MemoryStorage, StorageBackend};
+    use crate::aproar::unframe;
+    use crate::aproar::AproarManagerBuilder;
+    use crate::omnixtracker::{OmniXError, OmniXMetry};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_memory_snapshots_round_trip_at_a_fraction_of_the_size() -> Result<(), OmniXError> {
+        let storage = Arc::new(MemoryStorage::new());
+        let manager = AproarManagerBuilder::new(OmniXMetry::init()?)
+            .storage_backend(storage.clone())
+            .background_tasks(false)
+            .build()?;
+        let (rows, row_len) = (128, 64);
+        let memory: Vec<f32> = (0..rows * row_len)
+            .map(|i| ((i * 37 % 1000) as f32 / 1000.0 - 0.5) * (1 + i / row_len % 5) as f32)
+            .collect();
+
+        let codec = FloatCodec::new(Quantization::Int8);
+        manager.store_float_array("ntm/memory", &memory, row_len, codec, 1).await?;
+        let restored = manager.retrieve_float_array("ntm/memory").await?;
+        assert_eq!((restored.rows(), restored.row_len), (rows, row_len));
+        let bound = codec.error_bound(&memory, row_len);
+        assert!(memory.iter().zip(&restored.values).all(|(&a, &b)| (a as f64 - b as f64).abs() <= bound));
+
+        let stored = manager.data_metadata("ntm/memory").await?.expect("snapshot is stored").size;
+        assert!(stored * 3 < (memory.len() * 4) as u64, "{} bytes stored", stored);
+        let raw = storage.retrieve("ntm/memory")?;
+        assert_eq!(Envelope::parse(unframe("ntm/memory", &raw)?)?.codec, CodecKind::Uncompressed);
+
+        manager.store_data("not/a/snapshot", b"plain bytes", 1).await?;
+        assert!(matches!(manager.retrieve_float_array("not/a/snapshot").await, Err(OmniXError::DataCorruption { .. })));
+        Ok(())
+    }
+}
\ No newline at end of file
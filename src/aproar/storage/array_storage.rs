@@ -0,0 +1,212 @@
+// src/aproar/storage/array_storage.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[STORAGE]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use crate::omnixtracker::OmniXError;
+use ndarray::ArrayD;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// Attribute names the array backends use for their own bookkeeping.
+pub const RESERVED_ARRAY_ATTRIBUTES: [&str; 3] = ["aproar_dtype", "created_at", "modified_at"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DType {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    F32,
+    F64,
+}
+
+impl DType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DType::I8 => "i8",
+            DType::I16 => "i16",
+            DType::I32 => "i32",
+            DType::I64 => "i64",
+            DType::U8 => "u8",
+            DType::U16 => "u16",
+            DType::U32 => "u32",
+            DType::U64 => "u64",
+            DType::F32 => "f32",
+            DType::F64 => "f64",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "i8" => Some(DType::I8),
+            "i16" => Some(DType::I16),
+            "i32" => Some(DType::I32),
+            "i64" => Some(DType::I64),
+            "u8" => Some(DType::U8),
+            "u16" => Some(DType::U16),
+            "u32" => Some(DType::U32),
+            "u64" => Some(DType::U64),
+            "f32" => Some(DType::F32),
+            "f64" => Some(DType::F64),
+            _ => None,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            DType::I8 | DType::U8 => 1,
+            DType::I16 | DType::U16 => 2,
+            DType::I32 | DType::U32 | DType::F32 => 4,
+            DType::I64 | DType::U64 | DType::F64 => 8,
+        }
+    }
+}
+
+/// Element types the array backends can persist. With HDF5 enabled every
+/// element must also be an HDF5 native type so datasets keep their dtype.
+#[cfg(feature = "hdf5-storage")]
+pub trait ArrayElement: Copy + Default + Send + Sync + 'static + hdf5::H5Type {
+    const DTYPE: DType;
+    fn encode(values: &[Self]) -> Vec<u8>;
+    fn decode(bytes: &[u8]) -> Vec<Self>;
+}
+
+#[cfg(not(feature = "hdf5-storage"))]
+pub trait ArrayElement: Copy + Default + Send + Sync + 'static {
+    const DTYPE: DType;
+    fn encode(values: &[Self]) -> Vec<u8>;
+    fn decode(bytes: &[u8]) -> Vec<Self>;
+}
+
+macro_rules! impl_array_element {
+    ($($ty:ty => $dtype:ident),* $(,)?) => {
+        $(
+            impl ArrayElement for $ty {
+                const DTYPE: DType = DType::$dtype;
+
+                fn encode(values: &[Self]) -> Vec<u8> {
+                    values.iter().flat_map(|value| value.to_ne_bytes()).collect()
+                }
+
+                fn decode(bytes: &[u8]) -> Vec<Self> {
+                    bytes
+                        .chunks_exact(std::mem::size_of::<$ty>())
+                        .map(|chunk| <$ty>::from_ne_bytes(chunk.try_into().expect("chunk matches element size")))
+                        .collect()
+                }
+            }
+        )*
+    };
+}
+
+impl_array_element!(
+    i8 => I8, i16 => I16, i32 => I32, i64 => I64,
+    u8 => U8, u16 => U16, u32 => U32, u64 => U64,
+    f32 => F32, f64 => F64,
+);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AttributeValue {
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+/// Layout hints and user attributes for `store_array`. Without a chunk shape
+/// the backend stores the array as a single chunk.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ArrayOptions {
+    pub chunk_shape: Option<Vec<usize>>,
+    pub attributes: BTreeMap<String, AttributeValue>,
+}
+
+impl ArrayOptions {
+    pub fn chunked(chunk_shape: Vec<usize>) -> Self {
+        Self { chunk_shape: Some(chunk_shape), ..Self::default() }
+    }
+
+    pub fn attribute(mut self, name: &str, value: AttributeValue) -> Self {
+        self.attributes.insert(name.to_string(), value);
+        self
+    }
+
+    pub(crate) fn validate(&self, shape: &[usize]) -> Result<(), OmniXError> {
+        if let Some(chunk_shape) = &self.chunk_shape {
+            if chunk_shape.len() != shape.len() || chunk_shape.iter().any(|&extent| extent == 0) {
+                return Err(OmniXError::ValidationError(format!(
+                    "Chunk shape {:?} does not fit array shape {:?}",
+                    chunk_shape, shape
+                )));
+            }
+        }
+        if let Some(name) = self.attributes.keys().find(|name| RESERVED_ARRAY_ATTRIBUTES.contains(&name.as_str())) {
+            return Err(OmniXError::ValidationError(format!("Attribute name {} is reserved", name)));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArrayInfo {
+    pub shape: Vec<usize>,
+    pub dtype: DType,
+    pub chunk_shape: Option<Vec<usize>>,
+    pub attributes: BTreeMap<String, AttributeValue>,
+}
+
+/// A rectangular selection with one half-open range per dimension.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hyperslab {
+    pub ranges: Vec<Range<usize>>,
+}
+
+impl Hyperslab {
+    pub fn new(ranges: Vec<Range<usize>>) -> Self {
+        Self { ranges }
+    }
+
+    pub fn shape(&self) -> Vec<usize> {
+        self.ranges.iter().map(|range| range.len()).collect()
+    }
+
+    pub(crate) fn validate(&self, shape: &[usize]) -> Result<(), OmniXError> {
+        let fits = self.ranges.len() == shape.len()
+            && self.ranges.iter().zip(shape).all(|(range, &extent)| range.start <= range.end && range.end <= extent);
+        if fits {
+            Ok(())
+        } else {
+            Err(OmniXError::ValidationError(format!(
+                "Hyperslab {:?} is out of bounds for shape {:?}",
+                self.ranges, shape
+            )))
+        }
+    }
+}
+
+/// Typed n-dimensional storage for backends that are array stores at heart.
+/// Unlike `StorageBackend` this keeps shape, dtype, chunking and attributes,
+/// and can read a sub-region without loading the whole array.
+pub trait ArrayStorage: Send + Sync {
+    fn store_array<T: ArrayElement>(&self, key: &str, array: &ArrayD<T>, options: &ArrayOptions) -> Result<(), OmniXError>;
+    fn retrieve_array<T: ArrayElement>(&self, key: &str) -> Result<ArrayD<T>, OmniXError>;
+    fn retrieve_slab<T: ArrayElement>(&self, key: &str, slab: &Hyperslab) -> Result<ArrayD<T>, OmniXError>;
+    fn array_info(&self, key: &str) -> Result<ArrayInfo, OmniXError>;
+}
+
+pub(crate) fn check_dtype<T: ArrayElement>(key: &str, stored: DType) -> Result<(), OmniXError> {
+    if stored == T::DTYPE {
+        Ok(())
+    } else {
+        Err(OmniXError::ValidationError(format!(
+            "Array {} holds {} elements, requested {}",
+            key,
+            stored.name(),
+            T::DTYPE.name()
+        )))
+    }
+}
\ No newline at end of file
@@ -0,0 +1,101 @@
+// src/aproar/storage/async_storage.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[STORAGE]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use super::StorageBackend;
+use crate::omnixtracker::OmniXError;
+use async_trait::async_trait;
+use std::io::Cursor;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Boxed byte stream handed to and returned from the streaming calls.
+pub type ByteReader = Box<dyn AsyncRead + Send + Unpin>;
+
+/// Async twin of `StorageBackend` for the hot path. `store_stream` and
+/// `retrieve_stream` move payloads without holding them in memory, so
+/// artifacts larger than RAM can be stored on backends that support it.
+#[async_trait]
+pub trait AsyncStorageBackend: Send + Sync {
+    async fn store(&self, key: &str, data: &[u8]) -> Result<(), OmniXError>;
+    async fn retrieve(&self, key: &str) -> Result<Vec<u8>, OmniXError>;
+    async fn delete(&self, key: &str) -> Result<(), OmniXError>;
+    async fn exists(&self, key: &str) -> Result<bool, OmniXError>;
+    /// Stores everything `reader` yields under `key` and returns the byte count.
+    async fn store_stream(&self, key: &str, reader: ByteReader) -> Result<u64, OmniXError>;
+    async fn retrieve_stream(&self, key: &str) -> Result<ByteReader, OmniXError>;
+}
+
+/// Runs a blocking storage call on tokio's blocking pool so async callers
+/// never stall a runtime worker.
+pub(crate) async fn run_blocking<T, F>(f: F) -> Result<T, OmniXError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, OmniXError> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f).await.map_err(|e| OmniXError::OperationFailed {
+        operation: "Blocking storage task".to_string(),
+        details: e.to_string(),
+    })?
+}
+
+/// Drives a synchronous backend from async code. Streams are buffered in
+/// full because the underlying backend only takes whole payloads; backends
+/// that can do better implement `AsyncStorageBackend` themselves and return
+/// it from `StorageBackend::as_async`.
+pub struct BlockingBackend {
+    inner: Arc<dyn StorageBackend>,
+}
+
+impl BlockingBackend {
+    pub fn new(inner: Arc<dyn StorageBackend>) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl AsyncStorageBackend for BlockingBackend {
+    async fn store(&self, key: &str, data: &[u8]) -> Result<(), OmniXError> {
+        let (inner, key, data) = (self.inner.clone(), key.to_string(), data.to_vec());
+        run_blocking(move || inner.store(&key, &data)).await
+    }
+
+    async fn retrieve(&self, key: &str) -> Result<Vec<u8>, OmniXError> {
+        let (inner, key) = (self.inner.clone(), key.to_string());
+        run_blocking(move || inner.retrieve(&key)).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), OmniXError> {
+        let (inner, key) = (self.inner.clone(), key.to_string());
+        run_blocking(move || inner.delete(&key)).await
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, OmniXError> {
+        let (inner, key) = (self.inner.clone(), key.to_string());
+        run_blocking(move || inner.exists(&key)).await
+    }
+
+    async fn store_stream(&self, key: &str, mut reader: ByteReader) -> Result<u64, OmniXError> {
+        let mut data = Vec::new();
+        reader
+            .read_to_end(&mut data)
+            .await
+            .map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+        let size = data.len() as u64;
+        let (inner, key) = (self.inner.clone(), key.to_string());
+        run_blocking(move || inner.store(&key, &data)).await?;
+        Ok(size)
+    }
+
+    async fn retrieve_stream(&self, key: &str) -> Result<ByteReader, OmniXError> {
+        let data = AsyncStorageBackend::retrieve(self, key).await?;
+        Ok(Box::new(Cursor::new(data)))
+    }
+}
+
+/// The async view of `backend`: its native implementation if it has one,
+/// otherwise a `BlockingBackend` around it.
+pub fn async_view(backend: &Arc<dyn StorageBackend>) -> Arc<dyn AsyncStorageBackend> {
+    backend
+        .clone()
+        .as_async()
+        .unwrap_or_else(|| Arc::new(BlockingBackend::new(backend.clone())))
+}
\ No newline at end of file
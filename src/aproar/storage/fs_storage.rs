@@ -0,0 +1,459 @@
+// src/aproar/storage/fs_storage.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[STORAGE]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use super::{key_not_found, AsyncStorageBackend, ByteReader, StorageBackend, StorageMetadata};
+use crate::omnixtracker::{OmniXError, OmniXMetry};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::io::AsyncWriteExt;
+
+const MAX_FILE_NAME_LEN: usize = 240;
+const TEMP_FILE_PREFIX: &str = ".tmp-";
+
+/// Plain-filesystem backend with one file per key. Files live under two
+/// levels of shard directories derived from a stable hash of the key, and
+/// every write goes through a temp file that is fsynced and renamed into place.
+pub struct FsStorage {
+    root: PathBuf,
+    metrics: OmniXMetry,
+    temp_counter: AtomicU64,
+}
+
+impl FsStorage {
+    pub fn new(root: PathBuf, metrics: OmniXMetry) -> Result<Self, OmniXError> {
+        fs::create_dir_all(&root)
+            .with_context(|| format!("Failed to create storage directory {}", root.display()))
+            .map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+
+        Ok(Self {
+            root,
+            metrics,
+            temp_counter: AtomicU64::new(0),
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn key_path(&self, key: &str) -> Result<PathBuf, OmniXError> {
+        let file_name = escape_key(key);
+        if file_name.len() > MAX_FILE_NAME_LEN {
+            return Err(OmniXError::ValidationError(format!(
+                "Key is too long for filesystem storage ({} bytes once escaped)",
+                file_name.len()
+            )));
+        }
+
+        let hash = fnv1a(key.as_bytes());
+        Ok(self
+            .root
+            .join(format!("{:02x}", hash & 0xff))
+            .join(format!("{:02x}", (hash >> 8) & 0xff))
+            .join(file_name))
+    }
+
+    fn write_atomic(&self, path: &Path, data: &[u8]) -> Result<(), OmniXError> {
+        let dir = path.parent().expect("key paths always have a shard directory");
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create shard directory {}", dir.display()))
+            .map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+
+        let tmp_path = self.temp_path(dir);
+
+        let result = (|| -> Result<()> {
+            let mut file = OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&tmp_path)
+                .with_context(|| "Failed to create temp file")?;
+            file.write_all(data).with_context(|| "Failed to write temp file")?;
+            file.sync_all().with_context(|| "Failed to fsync temp file")?;
+            fs::rename(&tmp_path, path).with_context(|| "Failed to rename temp file into place")?;
+            sync_dir(dir).with_context(|| "Failed to fsync shard directory")?;
+            Ok(())
+        })();
+
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp_path);
+        }
+        result.map_err(|e| OmniXError::FileSystemError(e.to_string()))
+    }
+
+    fn temp_path(&self, dir: &Path) -> PathBuf {
+        dir.join(format!(
+            "{}{}-{}",
+            TEMP_FILE_PREFIX,
+            std::process::id(),
+            self.temp_counter.fetch_add(1, Ordering::Relaxed)
+        ))
+    }
+
+    /// Same protocol as `write_atomic`, but copies from `reader` through a
+    /// fixed-size buffer so the payload never has to fit in memory.
+    async fn write_stream_atomic(&self, path: &Path, mut reader: ByteReader) -> Result<u64, OmniXError> {
+        let dir = path.parent().expect("key paths always have a shard directory").to_path_buf();
+        tokio::fs::create_dir_all(&dir)
+            .await
+            .map_err(|e| OmniXError::FileSystemError(format!("Failed to create shard directory {}: {}", dir.display(), e)))?;
+
+        let tmp_path = self.temp_path(&dir);
+        let result = async {
+            let mut file = tokio::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&tmp_path)
+                .await?;
+            let size = tokio::io::copy(&mut reader, &mut file).await?;
+            file.flush().await?;
+            file.sync_all().await?;
+            tokio::fs::rename(&tmp_path, path).await?;
+            let dir = dir.clone();
+            tokio::task::spawn_blocking(move || sync_dir(&dir)).await??;
+            Ok::<u64, std::io::Error>(size)
+        }
+        .await;
+
+        if result.is_err() {
+            let _ = tokio::fs::remove_file(&tmp_path).await;
+        }
+        result.map_err(|e| OmniXError::FileSystemError(e.to_string()))
+    }
+}
+
+impl StorageBackend for FsStorage {
+    fn store(&self, key: &str, data: &[u8]) -> Result<(), OmniXError> {
+        let start_time = std::time::Instant::now();
+        let path = self.key_path(key)?;
+        self.write_atomic(&path, data)?;
+
+        let duration = start_time.elapsed();
+        self.metrics.record_histogram("fs.store.duration".to_string(), duration.as_secs_f64());
+        self.metrics.increment_counter("fs.store.success".to_string(), 1);
+
+        Ok(())
+    }
+
+    fn retrieve(&self, key: &str) -> Result<Vec<u8>, OmniXError> {
+        let start_time = std::time::Instant::now();
+        let path = self.key_path(key)?;
+        let data = fs::read(&path).map_err(|e| match e.kind() {
+            ErrorKind::NotFound => key_not_found("Filesystem", key),
+            _ => OmniXError::FileSystemError(e.to_string()),
+        })?;
+
+        let duration = start_time.elapsed();
+        self.metrics.record_histogram("fs.retrieve.duration".to_string(), duration.as_secs_f64());
+        self.metrics.increment_counter("fs.retrieve.success".to_string(), 1);
+
+        Ok(data)
+    }
+
+    fn delete(&self, key: &str) -> Result<(), OmniXError> {
+        let path = self.key_path(key)?;
+        match fs::remove_file(&path) {
+            Ok(()) => {
+                if let Some(dir) = path.parent() {
+                    sync_dir(dir).map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+                }
+                self.metrics.increment_counter("fs.delete.success".to_string(), 1);
+                Ok(())
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(OmniXError::FileSystemError(e.to_string())),
+        }
+    }
+
+    fn exists(&self, key: &str) -> Result<bool, OmniXError> {
+        Ok(self.key_path(key)?.is_file())
+    }
+
+    fn list_keys(&self, prefix: &str) -> Result<Vec<String>, OmniXError> {
+        let mut keys = Vec::new();
+        for shard in read_dir_sorted(&self.root)? {
+            if !shard.is_dir() {
+                continue;
+            }
+            for sub_shard in read_dir_sorted(&shard)? {
+                if !sub_shard.is_dir() {
+                    continue;
+                }
+                for entry in read_dir_sorted(&sub_shard)? {
+                    let name = match entry.file_name().and_then(|name| name.to_str()) {
+                        Some(name) if !name.starts_with('.') => name,
+                        _ => continue,
+                    };
+                    if let Some(key) = unescape_key(name) {
+                        if key.starts_with(prefix) {
+                            keys.push(key);
+                        }
+                    }
+                }
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+
+    fn metadata(&self, key: &str) -> Result<StorageMetadata, OmniXError> {
+        let path = self.key_path(key)?;
+        let meta = fs::metadata(&path).map_err(|e| match e.kind() {
+            ErrorKind::NotFound => key_not_found("Filesystem", key),
+            _ => OmniXError::FileSystemError(e.to_string()),
+        })?;
+
+        Ok(StorageMetadata {
+            size: meta.len(),
+            created: meta.created().ok().map(DateTime::<Utc>::from),
+            modified: meta.modified().ok().map(DateTime::<Utc>::from),
+        })
+    }
+
+    fn as_async(self: Arc<Self>) -> Option<Arc<dyn AsyncStorageBackend>> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl AsyncStorageBackend for FsStorage {
+    async fn store(&self, key: &str, data: &[u8]) -> Result<(), OmniXError> {
+        let reader: ByteReader = Box::new(std::io::Cursor::new(data.to_vec()));
+        AsyncStorageBackend::store_stream(self, key, reader).await.map(|_| ())
+    }
+
+    async fn retrieve(&self, key: &str) -> Result<Vec<u8>, OmniXError> {
+        let start_time = std::time::Instant::now();
+        let path = self.key_path(key)?;
+        let data = tokio::fs::read(&path).await.map_err(|e| match e.kind() {
+            ErrorKind::NotFound => key_not_found("Filesystem", key),
+            _ => OmniXError::FileSystemError(e.to_string()),
+        })?;
+
+        let duration = start_time.elapsed();
+        self.metrics.record_histogram("fs.retrieve.duration".to_string(), duration.as_secs_f64());
+        self.metrics.increment_counter("fs.retrieve.success".to_string(), 1);
+
+        Ok(data)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), OmniXError> {
+        let path = self.key_path(key)?;
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => {
+                if let Some(dir) = path.parent().map(Path::to_path_buf) {
+                    tokio::task::spawn_blocking(move || sync_dir(&dir))
+                        .await
+                        .map_err(|e| OmniXError::FileSystemError(e.to_string()))?
+                        .map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+                }
+                self.metrics.increment_counter("fs.delete.success".to_string(), 1);
+                Ok(())
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(OmniXError::FileSystemError(e.to_string())),
+        }
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, OmniXError> {
+        let path = self.key_path(key)?;
+        Ok(tokio::fs::metadata(&path).await.map(|meta| meta.is_file()).unwrap_or(false))
+    }
+
+    async fn store_stream(&self, key: &str, reader: ByteReader) -> Result<u64, OmniXError> {
+        let start_time = std::time::Instant::now();
+        let path = self.key_path(key)?;
+        let size = self.write_stream_atomic(&path, reader).await?;
+
+        let duration = start_time.elapsed();
+        self.metrics.record_histogram("fs.store.duration".to_string(), duration.as_secs_f64());
+        self.metrics.increment_counter("fs.store.success".to_string(), 1);
+        self.metrics.increment_counter("fs.store.bytes".to_string(), size);
+
+        Ok(size)
+    }
+
+    async fn retrieve_stream(&self, key: &str) -> Result<ByteReader, OmniXError> {
+        let path = self.key_path(key)?;
+        let file = tokio::fs::File::open(&path).await.map_err(|e| match e.kind() {
+            ErrorKind::NotFound => key_not_found("Filesystem", key),
+            _ => OmniXError::FileSystemError(e.to_string()),
+        })?;
+        self.metrics.increment_counter("fs.retrieve_stream.success".to_string(), 1);
+        Ok(Box::new(file))
+    }
+}
+
+/// Percent-escapes everything outside `[A-Za-z0-9_.-]`, plus a leading `.`,
+/// so keys can never name a parent directory, a temp file or a path separator.
+/// Uppercase letters are escaped too, so keys differing only in case stay
+/// apart on case-insensitive filesystems.
+pub(crate) fn escape_key(key: &str) -> String {
+    let mut escaped = String::with_capacity(key.len());
+    for (i, byte) in key.bytes().enumerate() {
+        let safe = byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'_' || byte == b'-' || (byte == b'.' && i > 0);
+        if safe {
+            escaped.push(byte as char);
+        } else {
+            escaped.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    if escaped.is_empty() {
+        escaped.push('%');
+    }
+    escaped
+}
+
+fn unescape_key(name: &str) -> Option<String> {
+    if name == "%" {
+        return Some(String::new());
+    }
+    let bytes = name.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = name.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// FNV-1a, used for shard placement because it is stable across builds.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+fn read_dir_sorted(dir: &Path) -> Result<Vec<PathBuf>, OmniXError> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(OmniXError::FileSystemError(e.to_string())),
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()
+        .map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+    paths.sort();
+    Ok(paths)
+}
+
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> std::io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aproar::storage::is_not_found;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_fs_storage_round_trip_and_listing() -> Result<(), OmniXError> {
+        let dir = tempdir().unwrap();
+        let storage = FsStorage::new(dir.path().to_path_buf(), OmniXMetry::init()?)?;
+
+        storage.store("session/1", b"first")?;
+        storage.store("session/2", b"second")?;
+        storage.store("../escape", b"nope")?;
+        storage.store("session/1", b"replaced")?;
+
+        assert_eq!(storage.retrieve("session/1")?, b"replaced");
+        assert_eq!(storage.list_keys("session/")?, vec!["session/1", "session/2"]);
+        assert_eq!(storage.metadata("session/2")?.size, 6);
+        assert!(storage.exists("../escape")?);
+        assert!(!dir.path().parent().unwrap().join("escape").exists());
+
+        storage.delete("session/1")?;
+        assert!(!storage.exists("session/1")?);
+        assert!(storage.retrieve("session/1").is_err_and(|e| is_not_found(&e)));
+
+        Ok(())
+    }
+
+    /// Serves `data` and, once half of it has been read, records how many
+    /// bytes have reached the temp file.
+    struct ProbeReader {
+        data: Vec<u8>,
+        pos: usize,
+        dir: PathBuf,
+        probed: bool,
+        on_disk_midway: Arc<AtomicU64>,
+    }
+
+    impl tokio::io::AsyncRead for ProbeReader {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            if !self.probed && self.pos >= self.data.len() / 2 {
+                let written: u64 = fs::read_dir(&self.dir)?
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.file_name().to_string_lossy().starts_with(TEMP_FILE_PREFIX))
+                    .map(|entry| entry.metadata().map(|metadata| metadata.len()).unwrap_or(0))
+                    .sum();
+                self.on_disk_midway.store(written, Ordering::SeqCst);
+                self.probed = true;
+            }
+            let end = (self.pos + buf.remaining()).min(self.data.len());
+            let pos = self.pos;
+            buf.put_slice(&self.data[pos..end]);
+            self.pos = end;
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fs_storage_streams_without_buffering() -> Result<(), OmniXError> {
+        use tokio::io::AsyncReadExt;
+
+        let dir = tempdir().unwrap();
+        let storage = FsStorage::new(dir.path().to_path_buf(), OmniXMetry::init()?)?;
+        let payload: Vec<u8> = (0..4 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+
+        // The shard directory has to exist for the probe to look into it.
+        let shard = storage.key_path("artifact")?.parent().unwrap().to_path_buf();
+        fs::create_dir_all(&shard).unwrap();
+        let on_disk_midway = Arc::new(AtomicU64::new(0));
+        let probe = ProbeReader { data: payload.clone(), pos: 0, dir: shard, probed: false, on_disk_midway: on_disk_midway.clone() };
+        let written = AsyncStorageBackend::store_stream(&storage, "artifact", Box::new(probe)).await?;
+        assert_eq!(written, payload.len() as u64);
+        let on_disk = on_disk_midway.load(Ordering::SeqCst);
+        assert!(on_disk >= payload.len() as u64 / 4, "only {} bytes on disk halfway through", on_disk);
+
+        let mut read_back = Vec::new();
+        AsyncStorageBackend::retrieve_stream(&storage, "artifact")
+            .await?
+            .read_to_end(&mut read_back)
+            .await
+            .unwrap();
+        assert_eq!(read_back, payload);
+        assert!(AsyncStorageBackend::retrieve_stream(&storage, "missing").await.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_escaping_round_trips() {
+        for key in ["plain", ".hidden", "a/b\\c", "spaces and %", "ünïcode", "", "MixedCase"] {
+            assert_eq!(unescape_key(&escape_key(key)).as_deref(), Some(key));
+        }
+        assert_ne!(escape_key("Key").to_lowercase(), escape_key("key").to_lowercase());
+    }
+}
\ No newline at end of file
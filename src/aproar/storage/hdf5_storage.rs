@@ -1,21 +1,139 @@
 // src/aproar/storage/hdf5_storage.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[STORAGE]Xyn>=====S===t===u===d===i===o===s======[R|$>
 
-use super::StorageBackend;
+use super::{key_not_found, StorageBackend, StorageMetadata};
+use super::array_storage::{
+    check_dtype, ArrayElement, ArrayInfo, ArrayOptions, ArrayStorage, AttributeValue, DType, Hyperslab,
+    RESERVED_ARRAY_ATTRIBUTES,
+};
 use crate::omnixtracker::{OmniXError, OmniXMetry};
 use anyhow::{Context, Result};
-use hdf5::File;
+use chrono::{TimeZone, Utc};
+use hdf5::types::VarLenUnicode;
+use hdf5::{Dataset, File, SliceOrIndex};
+use ndarray::{ArrayD, IxDyn};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 
+/// Stores each key as a dataset at the root of one HDF5 file. HDF5 treats
+/// '/' in a name as a group separator, so keys are escaped into flat dataset
+/// names and every key stays visible to `list_keys`.
+///
+/// HDF5 does not give back the space of an unlinked dataset: deleting or
+/// overwriting a key leaves its old bytes in the file until it is rewritten
+/// offline with `h5repack`.
 pub struct HDF5Storage {
     file_path: PathBuf,
     metrics: OmniXMetry,
 }
 
+/// The root dataset name for `key`: '%' and '/' are percent-escaped, as is a
+/// leading '.', which HDF5 reserves.
+fn dataset_name(key: &str) -> String {
+    let mut name = String::with_capacity(key.len());
+    for (i, c) in key.chars().enumerate() {
+        match c {
+            '%' => name.push_str("%25"),
+            '/' => name.push_str("%2F"),
+            '.' if i == 0 => name.push_str("%2E"),
+            c => name.push(c),
+        }
+    }
+    name
+}
+
+/// Inverse of `dataset_name`; `None` for names it cannot have produced.
+fn key_name(name: &str) -> Option<String> {
+    let mut key = String::with_capacity(name.len());
+    let mut rest = name;
+    while let Some(index) = rest.find('%') {
+        key.push_str(&rest[..index]);
+        let escaped = rest.get(index + 1..index + 3)?;
+        key.push(u8::from_str_radix(escaped, 16).ok().filter(|b| b.is_ascii())? as char);
+        rest = &rest[index + 3..];
+    }
+    key.push_str(rest);
+    Some(key)
+}
+
 impl HDF5Storage {
     pub fn new(file_path: PathBuf, metrics: OmniXMetry) -> Self {
         Self { file_path, metrics }
     }
+
+    fn open_read(&self) -> Result<Option<File>, OmniXError> {
+        if !self.file_path.exists() {
+            return Ok(None);
+        }
+        File::open(&self.file_path)
+            .with_context(|| "Failed to open HDF5 file")
+            .map(Some)
+            .map_err(|e| OmniXError::FileSystemError(e.to_string()))
+    }
+
+    fn write_timestamp(dataset: &Dataset, name: &str, timestamp: i64) -> Result<(), OmniXError> {
+        let attr = match dataset.attr(name) {
+            Ok(attr) => attr,
+            Err(_) => dataset.new_attr::<i64>().create(name).map_err(|e| OmniXError::OperationFailed {
+                operation: "HDF5 attribute creation".to_string(),
+                details: e.to_string(),
+            })?,
+        };
+        attr.write_scalar(&timestamp).map_err(|e| OmniXError::OperationFailed {
+            operation: "HDF5 attribute write".to_string(),
+            details: e.to_string(),
+        })
+    }
+
+    fn open_rw(&self) -> Result<File, OmniXError> {
+        File::open_rw(&self.file_path)
+            .or_else(|_| File::create(&self.file_path))
+            .with_context(|| "Failed to open or create HDF5 file")
+            .map_err(|e| OmniXError::FileSystemError(e.to_string()))
+    }
+
+    fn array_dataset(&self, key: &str) -> Result<(File, Dataset), OmniXError> {
+        let file = self.open_read()?.ok_or_else(|| key_not_found("HDF5", key))?;
+        let dataset = file.dataset(&dataset_name(key)).map_err(|_| key_not_found("HDF5", key))?;
+        Ok((file, dataset))
+    }
+
+    fn write_attribute(dataset: &Dataset, name: &str, value: &AttributeValue) -> Result<(), OmniXError> {
+        let result = match value {
+            AttributeValue::Int(value) => dataset.new_attr::<i64>().create(name).and_then(|attr| attr.write_scalar(value)),
+            AttributeValue::Float(value) => dataset.new_attr::<f64>().create(name).and_then(|attr| attr.write_scalar(value)),
+            AttributeValue::Text(value) => {
+                let value: VarLenUnicode = value
+                    .parse()
+                    .map_err(|e: hdf5::types::StringError| OmniXError::ValidationError(e.to_string()))?;
+                dataset.new_attr::<VarLenUnicode>().create(name).and_then(|attr| attr.write_scalar(&value))
+            }
+        };
+        result.map_err(|e| OmniXError::OperationFailed {
+            operation: "HDF5 attribute write".to_string(),
+            details: e.to_string(),
+        })
+    }
+
+    fn read_attribute(dataset: &Dataset, name: &str) -> Option<AttributeValue> {
+        let attr = dataset.attr(name).ok()?;
+        let dtype = attr.dtype().ok()?;
+        if dtype.is::<i64>() {
+            attr.read_scalar::<i64>().ok().map(AttributeValue::Int)
+        } else if dtype.is::<f64>() {
+            attr.read_scalar::<f64>().ok().map(AttributeValue::Float)
+        } else {
+            attr.read_scalar::<VarLenUnicode>().ok().map(|value| AttributeValue::Text(value.as_str().to_string()))
+        }
+    }
+
+    fn read_timestamp(dataset: &Dataset, name: &str) -> Option<chrono::DateTime<Utc>> {
+        dataset
+            .attr(name)
+            .and_then(|attr| attr.read_scalar::<i64>())
+            .ok()
+            .and_then(|millis| Utc.timestamp_millis_opt(millis).single())
+    }
 }
 
 impl StorageBackend for HDF5Storage {
@@ -26,10 +144,24 @@ impl StorageBackend for HDF5Storage {
             .with_context(|| "Failed to open or create HDF5 file")
             .map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
 
+        let name = dataset_name(key);
+        let now = Utc::now().timestamp_millis();
+        let created_at = file
+            .dataset(&name)
+            .ok()
+            .and_then(|existing| existing.attr("created_at").and_then(|attr| attr.read_scalar::<i64>()).ok())
+            .unwrap_or(now);
+        if file.link_exists(&name) {
+            file.unlink(&name).map_err(|e| OmniXError::OperationFailed {
+                operation: "HDF5 dataset replacement".to_string(),
+                details: e.to_string(),
+            })?;
+        }
+
         let dataset = file
             .new_dataset::<u8>()
             .shape(data.len())
-            .create(key)
+            .create(name.as_str())
             .with_context(|| "Failed to create HDF5 dataset")
             .map_err(|e| OmniXError::OperationFailed {
                 operation: "HDF5 dataset creation".to_string(),
@@ -40,6 +172,8 @@ impl StorageBackend for HDF5Storage {
             operation: "HDF5 write".to_string(),
             details: e.to_string(),
         })?;
+        Self::write_timestamp(&dataset, "created_at", created_at)?;
+        Self::write_timestamp(&dataset, "modified_at", now)?;
 
         let duration = start_time.elapsed();
         self.metrics.record_histogram("hdf5.store.duration".to_string(), duration.as_secs_f64());
@@ -50,14 +184,7 @@ impl StorageBackend for HDF5Storage {
 
     fn retrieve(&self, key: &str) -> Result<Vec<u8>, OmniXError> {
         let start_time = std::time::Instant::now();
-        let file = File::open(&self.file_path)
-            .with_context(|| "Failed to open HDF5 file")
-            .map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
-
-        let dataset = file.dataset(key).map_err(|e| OmniXError::OperationFailed {
-            operation: "HDF5 dataset access".to_string(),
-            details: e.to_string(),
-        })?;
+        let (_file, dataset) = self.array_dataset(key)?;
 
         let data: Vec<u8> = dataset.read_raw().map_err(|e| OmniXError::OperationFailed {
             operation: "HDF5 read".to_string(),
@@ -70,4 +197,252 @@ impl StorageBackend for HDF5Storage {
 
         Ok(data)
     }
+
+    fn delete(&self, key: &str) -> Result<(), OmniXError> {
+        let start_time = std::time::Instant::now();
+        if !self.file_path.exists() {
+            return Ok(());
+        }
+        let file = File::open_rw(&self.file_path)
+            .with_context(|| "Failed to open HDF5 file")
+            .map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+
+        let name = dataset_name(key);
+        if file.link_exists(&name) {
+            file.unlink(&name).map_err(|e| OmniXError::OperationFailed {
+                operation: "HDF5 delete".to_string(),
+                details: e.to_string(),
+            })?;
+        }
+
+        let duration = start_time.elapsed();
+        self.metrics.record_histogram("hdf5.delete.duration".to_string(), duration.as_secs_f64());
+        self.metrics.increment_counter("hdf5.delete.success".to_string(), 1);
+
+        Ok(())
+    }
+
+    fn exists(&self, key: &str) -> Result<bool, OmniXError> {
+        Ok(self.open_read()?.map(|file| file.link_exists(&dataset_name(key))).unwrap_or(false))
+    }
+
+    fn list_keys(&self, prefix: &str) -> Result<Vec<String>, OmniXError> {
+        let file = match self.open_read()? {
+            Some(file) => file,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut keys: Vec<String> = file
+            .member_names()
+            .map_err(|e| OmniXError::OperationFailed {
+                operation: "HDF5 member listing".to_string(),
+                details: e.to_string(),
+            })?
+            .into_iter()
+            .filter_map(|name| key_name(&name))
+            .filter(|key| key.starts_with(prefix))
+            .collect();
+        keys.sort();
+
+        Ok(keys)
+    }
+
+    fn metadata(&self, key: &str) -> Result<StorageMetadata, OmniXError> {
+        let file = self.open_read()?.ok_or_else(|| key_not_found("HDF5", key))?;
+        let dataset = file.dataset(&dataset_name(key)).map_err(|_| key_not_found("HDF5", key))?;
+
+        Ok(StorageMetadata {
+            size: dataset.size() as u64,
+            created: Self::read_timestamp(&dataset, "created_at"),
+            modified: Self::read_timestamp(&dataset, "modified_at"),
+        })
+    }
+}
+
+impl ArrayStorage for HDF5Storage {
+    fn store_array<T: ArrayElement>(&self, key: &str, array: &ArrayD<T>, options: &ArrayOptions) -> Result<(), OmniXError> {
+        let start_time = std::time::Instant::now();
+        options.validate(array.shape())?;
+        let file = self.open_rw()?;
+
+        let name = dataset_name(key);
+        let now = Utc::now().timestamp_millis();
+        let created_at = file
+            .dataset(&name)
+            .ok()
+            .and_then(|existing| existing.attr("created_at").and_then(|attr| attr.read_scalar::<i64>()).ok())
+            .unwrap_or(now);
+        if file.link_exists(&name) {
+            file.unlink(&name).map_err(|e| OmniXError::OperationFailed {
+                operation: "HDF5 dataset replacement".to_string(),
+                details: e.to_string(),
+            })?;
+        }
+
+        let mut builder = file.new_dataset::<T>().shape(array.shape());
+        if let Some(chunk_shape) = &options.chunk_shape {
+            builder = builder.chunk(chunk_shape.as_slice());
+        }
+        let dataset = builder.create(name.as_str()).map_err(|e| OmniXError::OperationFailed {
+            operation: "HDF5 dataset creation".to_string(),
+            details: e.to_string(),
+        })?;
+
+        dataset.write(array.view()).map_err(|e| OmniXError::OperationFailed {
+            operation: "HDF5 array write".to_string(),
+            details: e.to_string(),
+        })?;
+        Self::write_attribute(&dataset, "aproar_dtype", &AttributeValue::Text(T::DTYPE.name().to_string()))?;
+        Self::write_timestamp(&dataset, "created_at", created_at)?;
+        Self::write_timestamp(&dataset, "modified_at", now)?;
+        for (name, value) in &options.attributes {
+            Self::write_attribute(&dataset, name, value)?;
+        }
+
+        let duration = start_time.elapsed();
+        self.metrics.record_histogram("hdf5.store_array.duration".to_string(), duration.as_secs_f64());
+        self.metrics.increment_counter("hdf5.store_array.success".to_string(), 1);
+
+        Ok(())
+    }
+
+    fn retrieve_array<T: ArrayElement>(&self, key: &str) -> Result<ArrayD<T>, OmniXError> {
+        let start_time = std::time::Instant::now();
+        let info = self.array_info(key)?;
+        check_dtype::<T>(key, info.dtype)?;
+        let (_file, dataset) = self.array_dataset(key)?;
+
+        let array = dataset.read_dyn::<T>().map_err(|e| OmniXError::OperationFailed {
+            operation: "HDF5 array read".to_string(),
+            details: e.to_string(),
+        })?;
+
+        let duration = start_time.elapsed();
+        self.metrics.record_histogram("hdf5.retrieve_array.duration".to_string(), duration.as_secs_f64());
+        self.metrics.increment_counter("hdf5.retrieve_array.success".to_string(), 1);
+
+        Ok(array)
+    }
+
+    fn retrieve_slab<T: ArrayElement>(&self, key: &str, slab: &Hyperslab) -> Result<ArrayD<T>, OmniXError> {
+        let start_time = std::time::Instant::now();
+        let info = self.array_info(key)?;
+        check_dtype::<T>(key, info.dtype)?;
+        slab.validate(&info.shape)?;
+        let (_file, dataset) = self.array_dataset(key)?;
+
+        let selection: Vec<SliceOrIndex> = slab.ranges.iter().cloned().map(SliceOrIndex::from).collect();
+        let array = dataset
+            .read_slice::<T, _, IxDyn>(hdf5::Hyperslab::from(selection))
+            .map_err(|e| OmniXError::OperationFailed {
+                operation: "HDF5 hyperslab read".to_string(),
+                details: e.to_string(),
+            })?;
+
+        let duration = start_time.elapsed();
+        self.metrics.record_histogram("hdf5.retrieve_slab.duration".to_string(), duration.as_secs_f64());
+        self.metrics.increment_counter("hdf5.retrieve_slab.success".to_string(), 1);
+
+        Ok(array)
+    }
+
+    fn array_info(&self, key: &str) -> Result<ArrayInfo, OmniXError> {
+        let (_file, dataset) = self.array_dataset(key)?;
+        // Datasets written through `store` carry no dtype attribute and are raw bytes.
+        let dtype = match Self::read_attribute(&dataset, "aproar_dtype") {
+            Some(AttributeValue::Text(name)) => DType::from_name(&name).ok_or_else(|| {
+                OmniXError::ValidationError(format!("Array {} has unknown dtype {}", key, name))
+            })?,
+            _ => DType::U8,
+        };
+
+        let attr_names = dataset.attr_names().map_err(|e| OmniXError::OperationFailed {
+            operation: "HDF5 attribute listing".to_string(),
+            details: e.to_string(),
+        })?;
+        let attributes: BTreeMap<String, AttributeValue> = attr_names
+            .into_iter()
+            .filter(|name| !RESERVED_ARRAY_ATTRIBUTES.contains(&name.as_str()))
+            .filter_map(|name| Self::read_attribute(&dataset, &name).map(|value| (name, value)))
+            .collect();
+
+        Ok(ArrayInfo {
+            shape: dataset.shape(),
+            dtype,
+            chunk_shape: dataset.chunk(),
+            attributes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aproar::storage::is_not_found;
+    use ndarray::{Array, IxDyn};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_hdf5_array_round_trip_with_hyperslab() -> Result<(), OmniXError> {
+        let dir = tempdir().unwrap();
+        let storage = HDF5Storage::new(dir.path().join("arrays.h5"), OmniXMetry::init()?);
+
+        let memory = Array::from_shape_fn(IxDyn(&[4, 6]), |index| (index[0] * 6 + index[1]) as f32);
+        let options = ArrayOptions::chunked(vec![2, 6])
+            .attribute("step", AttributeValue::Int(42))
+            .attribute("source", AttributeValue::Text("ntm".to_string()));
+        storage.store_array("ntm_memory", &memory, &options)?;
+
+        assert_eq!(storage.retrieve_array::<f32>("ntm_memory")?, memory);
+        let info = storage.array_info("ntm_memory")?;
+        assert_eq!(info.shape, vec![4, 6]);
+        assert_eq!(info.dtype, DType::F32);
+        assert_eq!(info.chunk_shape, Some(vec![2, 6]));
+        assert_eq!(info.attributes, options.attributes);
+
+        let slab = storage.retrieve_slab::<f32>("ntm_memory", &Hyperslab::new(vec![1..3, 2..4]))?;
+        assert_eq!(slab.shape(), &[2, 2]);
+        assert_eq!(slab.iter().copied().collect::<Vec<_>>(), vec![8.0, 9.0, 14.0, 15.0]);
+
+        assert!(storage.retrieve_array::<f64>("ntm_memory").is_err());
+        assert!(storage.retrieve_slab::<f32>("ntm_memory", &Hyperslab::new(vec![0..5, 0..1])).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hdf5_lists_keys_containing_slashes() -> Result<(), OmniXError> {
+        let dir = tempdir().unwrap();
+        let storage = HDF5Storage::new(dir.path().join("store.h5"), OmniXMetry::init()?);
+
+        for key in ["ns", "ns/a", "ns/b/c", "100%/.x", ".hidden"] {
+            storage.store(key, key.as_bytes())?;
+        }
+        assert_eq!(storage.list_keys("ns/")?, vec!["ns/a".to_string(), "ns/b/c".to_string()]);
+        assert_eq!(storage.list_keys("")?.len(), 5);
+        for key in ["ns", "ns/b/c", "100%/.x", ".hidden"] {
+            assert_eq!(storage.retrieve(key)?, key.as_bytes());
+            assert_eq!(storage.metadata(key)?.size, key.len() as u64);
+        }
+
+        storage.delete("ns/b/c")?;
+        assert!(!storage.exists("ns/b/c")?);
+        assert!(storage.exists("ns")?);
+        assert_eq!(storage.list_keys("ns")?, vec!["ns".to_string(), "ns/a".to_string()]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hdf5_reports_deleted_keys_as_not_found() -> Result<(), OmniXError> {
+        let dir = tempdir().unwrap();
+        let storage = HDF5Storage::new(dir.path().join("store.h5"), OmniXMetry::init()?);
+
+        assert!(storage.retrieve("key").is_err_and(|e| is_not_found(&e)));
+        storage.store("key", b"value")?;
+        storage.delete("key")?;
+        assert!(storage.retrieve("key").is_err_and(|e| is_not_found(&e)));
+
+        Ok(())
+    }
 }
\ No newline at end of file
@@ -0,0 +1,227 @@
+// src/aproar/storage/memory_storage.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[STORAGE]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use super::{key_not_found, StorageBackend, StorageMetadata};
+use crate::omnixtracker::OmniXError;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, HashMap};
+
+struct LruEntry {
+    data: Vec<u8>,
+    created: DateTime<Utc>,
+    modified: DateTime<Utc>,
+    tick: u64,
+}
+
+/// Byte map with an optional size cap. When an insert pushes the total past
+/// the cap, the least recently used entries are evicted until it fits again.
+pub(crate) struct LruMap {
+    entries: HashMap<String, LruEntry>,
+    recency: BTreeMap<u64, String>,
+    next_tick: u64,
+    total_bytes: usize,
+    max_bytes: Option<usize>,
+}
+
+impl LruMap {
+    pub(crate) fn new(max_bytes: Option<usize>) -> Self {
+        Self {
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            next_tick: 0,
+            total_bytes: 0,
+            max_bytes,
+        }
+    }
+
+    fn touch(&mut self, key: &str) {
+        let tick = self.next_tick;
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.recency.remove(&entry.tick);
+            entry.tick = tick;
+            self.recency.insert(tick, key.to_string());
+            self.next_tick += 1;
+        }
+    }
+
+    pub(crate) fn get(&mut self, key: &str) -> Option<Vec<u8>> {
+        self.touch(key);
+        self.entries.get(key).map(|entry| entry.data.clone())
+    }
+
+    /// Inserts `data` and returns the keys evicted to make room for it.
+    /// Values larger than the whole cap are rejected rather than flushing
+    /// everything else out.
+    pub(crate) fn insert(&mut self, key: &str, data: &[u8]) -> Result<Vec<String>, OmniXError> {
+        if let Some(max_bytes) = self.max_bytes {
+            if data.len() > max_bytes {
+                return Err(OmniXError::ValidationError(format!(
+                    "Value of {} bytes exceeds the {} byte memory cap",
+                    data.len(),
+                    max_bytes
+                )));
+            }
+        }
+
+        let now = Utc::now();
+        let created = self.remove(key).map(|entry| entry.created).unwrap_or(now);
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        self.total_bytes += data.len();
+        self.recency.insert(tick, key.to_string());
+        self.entries.insert(key.to_string(), LruEntry {
+            data: data.to_vec(),
+            created,
+            modified: now,
+            tick,
+        });
+
+        let mut evicted = Vec::new();
+        if let Some(max_bytes) = self.max_bytes {
+            while self.total_bytes > max_bytes {
+                let oldest = match self.recency.values().next() {
+                    Some(key) => key.clone(),
+                    None => break,
+                };
+                self.remove(&oldest);
+                evicted.push(oldest);
+            }
+        }
+        Ok(evicted)
+    }
+
+    fn remove(&mut self, key: &str) -> Option<LruEntry> {
+        let entry = self.entries.remove(key)?;
+        self.recency.remove(&entry.tick);
+        self.total_bytes -= entry.data.len();
+        Some(entry)
+    }
+
+    pub(crate) fn delete(&mut self, key: &str) -> bool {
+        self.remove(key).is_some()
+    }
+
+    pub(crate) fn contains(&self, key: &str) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    pub(crate) fn keys_with_prefix(&self, prefix: &str) -> Vec<String> {
+        let mut keys: Vec<String> = self.entries.keys().filter(|key| key.starts_with(prefix)).cloned().collect();
+        keys.sort();
+        keys
+    }
+
+    pub(crate) fn total_bytes(&self) -> usize {
+        self.total_bytes
+    }
+}
+
+/// Process-local backend for tests and short-lived sessions. Nothing survives
+/// a restart.
+pub struct MemoryStorage {
+    entries: Mutex<LruMap>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self { entries: Mutex::new(LruMap::new(None)) }
+    }
+
+    /// Caps the stored bytes, evicting the least recently used keys beyond it.
+    pub fn with_max_bytes(max_bytes: usize) -> Self {
+        Self { entries: Mutex::new(LruMap::new(Some(max_bytes))) }
+    }
+
+    pub fn total_bytes(&self) -> usize {
+        self.entries.lock().total_bytes()
+    }
+}
+
+impl Default for MemoryStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StorageBackend for MemoryStorage {
+    fn store(&self, key: &str, data: &[u8]) -> Result<(), OmniXError> {
+        self.entries.lock().insert(key, data).map(|_| ())
+    }
+
+    fn retrieve(&self, key: &str) -> Result<Vec<u8>, OmniXError> {
+        self.entries.lock().get(key).ok_or_else(|| key_not_found("Memory", key))
+    }
+
+    fn delete(&self, key: &str) -> Result<(), OmniXError> {
+        self.entries.lock().delete(key);
+        Ok(())
+    }
+
+    fn exists(&self, key: &str) -> Result<bool, OmniXError> {
+        Ok(self.entries.lock().contains(key))
+    }
+
+    fn list_keys(&self, prefix: &str) -> Result<Vec<String>, OmniXError> {
+        Ok(self.entries.lock().keys_with_prefix(prefix))
+    }
+
+    fn metadata(&self, key: &str) -> Result<StorageMetadata, OmniXError> {
+        let entries = self.entries.lock();
+        let entry = entries.entries.get(key).ok_or_else(|| key_not_found("Memory", key))?;
+        Ok(StorageMetadata {
+            size: entry.data.len() as u64,
+            created: Some(entry.created),
+            modified: Some(entry.modified),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aproar::storage::is_not_found;
+
+    #[test]
+    fn test_memory_storage_evicts_least_recently_used() -> Result<(), OmniXError> {
+        let storage = MemoryStorage::with_max_bytes(10);
+        storage.store("a", b"1234")?;
+        storage.store("b", b"5678")?;
+        storage.retrieve("a")?;
+        storage.store("c", b"9012")?;
+
+        assert!(storage.exists("a")?);
+        assert!(!storage.exists("b")?);
+        assert!(storage.exists("c")?);
+        assert_eq!(storage.total_bytes(), 8);
+        assert!(storage.store("huge", &[0u8; 11]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_storage_keeps_created_time_on_overwrite() -> Result<(), OmniXError> {
+        let storage = MemoryStorage::new();
+        storage.store("key", b"first")?;
+        let created = storage.metadata("key")?.created;
+        storage.store("key", b"second!")?;
+
+        let metadata = storage.metadata("key")?;
+        assert_eq!(metadata.created, created);
+        assert_eq!(metadata.size, 7);
+        assert_eq!(storage.retrieve("key")?, b"second!");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_storage_reports_deleted_keys_as_not_found() -> Result<(), OmniXError> {
+        let storage = MemoryStorage::new();
+        storage.store("key", b"value")?;
+        storage.delete("key")?;
+
+        assert!(storage.retrieve("key").is_err_and(|e| is_not_found(&e)));
+        assert!(storage.retrieve("never-stored").is_err_and(|e| is_not_found(&e)));
+
+        Ok(())
+    }
+}
\ No newline at end of file
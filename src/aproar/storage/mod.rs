@@ -1,15 +1,76 @@
 // src/aproar/storage/mod.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[STORAGE]Xyn>=====S===t===u===d===i===o===s======[R|$>
 
+mod array_storage;
+mod async_storage;
+mod fs_storage;
+mod memory_storage;
+#[cfg(feature = "hdf5-storage")]
 mod hdf5_storage;
+#[cfg(feature = "parquet-storage")]
 mod parquet_storage;
+#[cfg(feature = "tiledb-storage")]
 mod tiledb_storage;
 
-use crate::omnixtracker::OmnixError;
+use crate::omnixtracker::OmniXError;
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
 
-pub trait StorageBackend {
-    fn store(&self, key: &str, data: &[u8]) -> Result<(), OmnixError>;
-    fn retrieve(&self, key: &str) -> Result<Vec<u8>, OmnixError>;
+/// Size and timestamps of a single stored key. Backends that cannot track a
+/// timestamp report `None` for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageMetadata {
+    pub size: u64,
+    pub created: Option<DateTime<Utc>>,
+    pub modified: Option<DateTime<Utc>>,
 }
 
+pub trait StorageBackend: Send + Sync {
+    fn store(&self, key: &str, data: &[u8]) -> Result<(), OmniXError>;
+    fn retrieve(&self, key: &str) -> Result<Vec<u8>, OmniXError>;
+    fn delete(&self, key: &str) -> Result<(), OmniXError>;
+    fn exists(&self, key: &str) -> Result<bool, OmniXError>;
+    fn list_keys(&self, prefix: &str) -> Result<Vec<String>, OmniXError>;
+    fn metadata(&self, key: &str) -> Result<StorageMetadata, OmniXError>;
+
+    /// Stores every entry. Backends that can write several keys in one
+    /// operation override this; the default stores them one at a time.
+    fn store_batch(&self, entries: &[(String, Vec<u8>)]) -> Result<(), OmniXError> {
+        entries.iter().try_for_each(|(key, data)| self.store(key, data))
+    }
+
+    /// One result per key, in order, so a missing key doesn't fail the rest.
+    fn retrieve_batch(&self, keys: &[String]) -> Vec<Result<Vec<u8>, OmniXError>> {
+        keys.iter().map(|key| self.retrieve(key)).collect()
+    }
+
+    /// Backends with a native async implementation return it here; the rest
+    /// are driven through `BlockingBackend`.
+    fn as_async(self: Arc<Self>) -> Option<Arc<dyn AsyncStorageBackend>> {
+        None
+    }
+}
+
+pub(crate) fn key_not_found(backend: &str, key: &str) -> OmniXError {
+    OmniXError::NotFound {
+        backend: backend.to_string(),
+        key: key.to_string(),
+    }
+}
+
+/// Whether `error` reports a missing key.
+pub(crate) fn is_not_found(error: &OmniXError) -> bool {
+    matches!(error, OmniXError::NotFound { .. })
+}
+
+pub use array_storage::{ArrayElement, ArrayInfo, ArrayOptions, ArrayStorage, AttributeValue, DType, Hyperslab};
+pub use async_storage::{async_view, AsyncStorageBackend, BlockingBackend, ByteReader};
+pub(crate) use async_storage::run_blocking;
+pub use fs_storage::FsStorage;
+pub use memory_storage::MemoryStorage;
+pub(crate) use memory_storage::LruMap;
+#[cfg(feature = "hdf5-storage")]
 pub use hdf5_storage::HDF5Storage;
-// TODO: Implement and export ParquetStorage and TileDBStorage
\ No newline at end of file
+#[cfg(feature = "parquet-storage")]
+pub use parquet_storage::ParquetStorage;
+#[cfg(feature = "tiledb-storage")]
+pub use tiledb_storage::TileDBStorage;
\ No newline at end of file
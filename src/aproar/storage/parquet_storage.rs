@@ -1,27 +1,124 @@
 // src/aproar/storage/parquet_storage.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[STORAGE]Xyn>=====S===t===u===d===i===o===s======[R|$>
 
-use super::StorageBackend;
+use super::{key_not_found, StorageBackend, StorageMetadata};
 use crate::omnixtracker::OmniXError;
+use crate::constants::{PARQUET_COMPACTION_FANOUT, PARQUET_ROW_GROUP_SIZE, PARQUET_SEGMENT_MAX_ROWS};
 use anyhow::{Context, Result};
+use chrono::{TimeZone, Utc};
+use parking_lot::Mutex;
+use parquet::basic::Compression;
+use parquet::data_type::{ByteArray, ByteArrayType, Int64Type};
 use parquet::file::properties::WriterProperties;
+use parquet::file::reader::{FileReader, SerializedFileReader};
 use parquet::file::writer::SerializedFileWriter;
-use parquet::file::reader::SerializedFileReader;
+use parquet::record::RowAccessor;
 use parquet::schema::parser::parse_message_type;
-use parquet::basic::Compression;
-use parquet::record::{Row, RowAccessor};
-use std::fs::File;
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+const INDEX_FILE_NAME: &str = "index.jsonl";
+const SEGMENT_PREFIX: &str = "segment-";
+const SEGMENT_EXTENSION: &str = "parquet";
+
+/// Position of the live version of a key inside the segment files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RecordLocation {
+    segment: u64,
+    row_group: usize,
+    row: usize,
+    size: u64,
+    created: i64,
+    modified: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum IndexLogEntry {
+    Put { key: String, location: RecordLocation },
+    Delete { key: String },
+}
+
+struct ParquetRecord {
+    key: String,
+    data: Vec<u8>,
+    written_at: i64,
+}
+
+struct ParquetState {
+    index: HashMap<String, RecordLocation>,
+    live_rows: BTreeMap<u64, usize>,
+    /// Rows written to each segment file on disk, live or not.
+    segments: BTreeMap<u64, usize>,
+    next_segment: u64,
+    index_log: BufWriter<File>,
+    /// Lines in the index log since it was last rewritten.
+    log_entries: usize,
+}
+
+/// Append-only Parquet store. Every write lands in a new segment file and a
+/// sidecar JSON-lines log maps each key to its (segment, row group, row).
+/// Segments of similar size are merged as they accumulate and mostly-dead
+/// ones are rewritten, so each record is rewritten a logarithmic number of
+/// times; `compact` folds everything into as few segments as possible.
 pub struct ParquetStorage {
-    file_path: PathBuf,
+    root: PathBuf,
     schema: Arc<parquet::schema::types::Type>,
+    state: Mutex<ParquetState>,
 }
 
 impl ParquetStorage {
-    pub fn new(file_path: PathBuf) -> Self {
+    pub fn new(root: PathBuf) -> Result<Self, OmniXError> {
+        fs::create_dir_all(&root)
+            .with_context(|| format!("Failed to create Parquet directory {}", root.display()))
+            .map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+
         let schema = Arc::new(Self::build_parquet_schema());
-        Self { file_path, schema }
+        let segments = Self::list_segments(&root)?;
+        let index_path = root.join(INDEX_FILE_NAME);
+
+        let index = if index_path.exists() {
+            Self::replay_index_log(&index_path)?
+        } else {
+            Self::rebuild_index(&root, &segments)?
+        };
+
+        let mut live_rows = BTreeMap::new();
+        for location in index.values() {
+            *live_rows.entry(location.segment).or_insert(0) += 1;
+        }
+        let next_segment = segments.last().map(|segment| segment + 1).unwrap_or(0);
+        let segments = segments
+            .into_iter()
+            .map(|segment| {
+                let rows = Self::open_segment(&root, segment)?.metadata().file_metadata().num_rows();
+                Ok((segment, rows as usize))
+            })
+            .collect::<Result<BTreeMap<u64, usize>, OmniXError>>()?;
+
+        let storage = Self {
+            schema,
+            state: Mutex::new(ParquetState {
+                index,
+                live_rows,
+                segments,
+                next_segment,
+                index_log: Self::open_index_log(&index_path, false)?,
+                log_entries: 0,
+            }),
+            root,
+        };
+
+        // Start from a clean snapshot so appends never follow a torn line.
+        {
+            let mut state = storage.state.lock();
+            storage.rewrite_index_log(&mut state)?;
+        }
+
+        Ok(storage)
     }
 
     fn build_parquet_schema() -> parquet::schema::types::Type {
@@ -30,16 +127,195 @@ impl ParquetStorage {
             message schema {
                 REQUIRED BYTE_ARRAY key (UTF8);
                 REQUIRED BINARY data;
+                REQUIRED INT64 written_at (TIMESTAMP_MILLIS);
             }
             ",
         )
         .expect("Failed to parse Parquet schema")
     }
-}
 
-impl StorageBackend for ParquetStorage {
-    fn store(&self, key: &str, data: &[u8]) -> Result<(), OmniXError> {
-        let file = File::create(&self.file_path)
+    fn segment_path(root: &Path, segment: u64) -> PathBuf {
+        root.join(format!("{}{:08}.{}", SEGMENT_PREFIX, segment, SEGMENT_EXTENSION))
+    }
+
+    fn list_segments(root: &Path) -> Result<Vec<u64>, OmniXError> {
+        let entries = fs::read_dir(root)
+            .with_context(|| "Failed to read Parquet directory")
+            .map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+
+        let mut segments: Vec<u64> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                name.strip_prefix(SEGMENT_PREFIX)?
+                    .strip_suffix(&format!(".{}", SEGMENT_EXTENSION))?
+                    .parse()
+                    .ok()
+            })
+            .collect();
+        segments.sort_unstable();
+
+        Ok(segments)
+    }
+
+    fn open_index_log(path: &Path, truncate: bool) -> Result<BufWriter<File>, OmniXError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(!truncate)
+            .write(true)
+            .truncate(truncate)
+            .open(path)
+            .with_context(|| "Failed to open Parquet index log")
+            .map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+        Ok(BufWriter::new(file))
+    }
+
+    fn replay_index_log(path: &Path) -> Result<HashMap<String, RecordLocation>, OmniXError> {
+        let file = File::open(path)
+            .with_context(|| "Failed to open Parquet index log")
+            .map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+
+        let mut index = HashMap::new();
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            // A torn final line from a crash mid-append is dropped. The segment
+            // it described then holds no live rows, and the next compaction
+            // removes it.
+            match serde_json::from_str::<IndexLogEntry>(&line) {
+                Ok(IndexLogEntry::Put { key, location }) => {
+                    index.insert(key, location);
+                }
+                Ok(IndexLogEntry::Delete { key }) => {
+                    index.remove(&key);
+                }
+                Err(_) => break,
+            }
+        }
+
+        Ok(index)
+    }
+
+    /// Rebuilds the key index from the segment files alone. Later segments
+    /// supersede earlier ones, which matches the order writes were made in.
+    fn rebuild_index(root: &Path, segments: &[u64]) -> Result<HashMap<String, RecordLocation>, OmniXError> {
+        let mut index: HashMap<String, RecordLocation> = HashMap::new();
+        for &segment in segments {
+            let reader = Self::open_segment(root, segment)?;
+            for row_group in 0..reader.num_row_groups() {
+                for (row, record) in Self::read_row_group(&reader, row_group)?.into_iter().enumerate() {
+                    let created = index.get(&record.key).map(|prev| prev.created).unwrap_or(record.written_at);
+                    index.insert(record.key, RecordLocation {
+                        segment,
+                        row_group,
+                        row,
+                        size: record.data.len() as u64,
+                        created,
+                        modified: record.written_at,
+                    });
+                }
+            }
+        }
+        Ok(index)
+    }
+
+    fn append_index_log(state: &mut ParquetState, entries: &[IndexLogEntry]) -> Result<(), OmniXError> {
+        for entry in entries {
+            let line = serde_json::to_string(entry)
+                .map_err(|e| OmniXError::OperationFailed {
+                    operation: "Parquet index serialization".to_string(),
+                    details: e.to_string(),
+                })?;
+            writeln!(state.index_log, "{}", line).map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+        }
+        state.index_log.flush().map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+        state.index_log.get_ref().sync_data().map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+        state.log_entries += entries.len();
+        Ok(())
+    }
+
+    /// Replaces the index log with a snapshot of the live index.
+    fn rewrite_index_log(&self, state: &mut ParquetState) -> Result<(), OmniXError> {
+        let index_path = self.root.join(INDEX_FILE_NAME);
+        let tmp_path = self.root.join(format!("{}.tmp", INDEX_FILE_NAME));
+        {
+            let mut writer = Self::open_index_log(&tmp_path, true)?;
+            for (key, location) in &state.index {
+                let line = serde_json::to_string(&IndexLogEntry::Put { key: key.clone(), location: location.clone() })
+                    .map_err(|e| OmniXError::OperationFailed {
+                        operation: "Parquet index serialization".to_string(),
+                        details: e.to_string(),
+                    })?;
+                writeln!(writer, "{}", line).map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+            }
+            writer.flush().map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+            writer.get_ref().sync_all().map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+        }
+        fs::rename(&tmp_path, &index_path)
+            .with_context(|| "Failed to replace Parquet index log")
+            .map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+        state.index_log = Self::open_index_log(&index_path, false)?;
+        state.log_entries = state.index.len();
+        Ok(())
+    }
+
+    fn open_segment(root: &Path, segment: u64) -> Result<SerializedFileReader<File>, OmniXError> {
+        let file = File::open(Self::segment_path(root, segment))
+            .with_context(|| format!("Failed to open Parquet segment {}", segment))
+            .map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+
+        SerializedFileReader::new(file)
+            .with_context(|| "Failed to create Parquet reader")
+            .map_err(|e| OmniXError::OperationFailed {
+                operation: "Parquet reader creation".to_string(),
+                details: e.to_string(),
+            })
+    }
+
+    fn read_row_group(reader: &SerializedFileReader<File>, row_group: usize) -> Result<Vec<ParquetRecord>, OmniXError> {
+        let row_group_reader = reader.get_row_group(row_group).map_err(|e| OmniXError::OperationFailed {
+            operation: "Parquet row group access".to_string(),
+            details: e.to_string(),
+        })?;
+
+        let rows = row_group_reader.get_row_iter(None).map_err(|e| OmniXError::OperationFailed {
+            operation: "Parquet row iteration".to_string(),
+            details: e.to_string(),
+        })?;
+
+        let mut records = Vec::new();
+        for row in rows {
+            let row = row.map_err(|e| OmniXError::OperationFailed {
+                operation: "Parquet row read".to_string(),
+                details: e.to_string(),
+            })?;
+            records.push(Self::decode_row(&row)?);
+        }
+        Ok(records)
+    }
+
+    fn decode_row(row: &parquet::record::Row) -> Result<ParquetRecord, OmniXError> {
+        let map_err = |e: parquet::errors::ParquetError| OmniXError::OperationFailed {
+            operation: "Parquet row decoding".to_string(),
+            details: e.to_string(),
+        };
+        Ok(ParquetRecord {
+            key: row.get_string(0).map_err(map_err)?.clone(),
+            data: row.get_bytes(1).map_err(map_err)?.data().to_vec(),
+            written_at: row.get_timestamp_millis(2).map_err(map_err)?,
+        })
+    }
+
+    /// Writes `records` to a fresh segment file and returns each record's
+    /// (row group, row) position. The file is renamed into place only once
+    /// it is complete, so readers never observe a half-written segment.
+    fn write_segment(&self, segment: u64, records: &[ParquetRecord]) -> Result<Vec<(usize, usize)>, OmniXError> {
+        let final_path = Self::segment_path(&self.root, segment);
+        let tmp_path = final_path.with_extension("parquet.tmp");
+
+        let file = File::create(&tmp_path)
             .with_context(|| "Failed to create Parquet file")
             .map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
 
@@ -47,59 +323,415 @@ impl StorageBackend for ParquetStorage {
             .set_compression(Compression::SNAPPY)
             .build();
 
-        let mut writer = SerializedFileWriter::new(file, self.schema.clone(), props)
+        let mut writer = SerializedFileWriter::new(file, self.schema.clone(), Arc::new(props))
             .with_context(|| "Failed to create Parquet writer")
             .map_err(|e| OmniXError::OperationFailed {
                 operation: "Parquet writer creation".to_string(),
                 details: e.to_string(),
             })?;
 
-        let mut row_group_writer = writer.next_row_group().unwrap();
-        let mut key_column_writer = row_group_writer.next_column().unwrap().unwrap();
-        if let parquet::column::writer::ColumnWriter::ByteArrayColumnWriter(ref mut typed_writer) = key_column_writer {
-            let key_value = parquet::data_type::ByteArray::from(key.as_bytes());
-            typed_writer.write_batch(&[key_value], None, None).unwrap();
+        let write_err = |e: parquet::errors::ParquetError| OmniXError::OperationFailed {
+            operation: "Parquet write".to_string(),
+            details: e.to_string(),
+        };
+
+        let mut positions = Vec::with_capacity(records.len());
+        for (row_group, chunk) in records.chunks(PARQUET_ROW_GROUP_SIZE).enumerate() {
+            let keys: Vec<ByteArray> = chunk.iter().map(|r| ByteArray::from(r.key.as_bytes())).collect();
+            let data: Vec<ByteArray> = chunk.iter().map(|r| ByteArray::from(r.data.as_slice())).collect();
+            let written_at: Vec<i64> = chunk.iter().map(|r| r.written_at).collect();
+
+            let mut row_group_writer = writer.next_row_group().map_err(write_err)?;
+
+            let mut key_column = row_group_writer.next_column().map_err(write_err)?.expect("key column");
+            key_column.typed::<ByteArrayType>().write_batch(&keys, None, None).map_err(write_err)?;
+            key_column.close().map_err(write_err)?;
+
+            let mut data_column = row_group_writer.next_column().map_err(write_err)?.expect("data column");
+            data_column.typed::<ByteArrayType>().write_batch(&data, None, None).map_err(write_err)?;
+            data_column.close().map_err(write_err)?;
+
+            let mut written_at_column = row_group_writer.next_column().map_err(write_err)?.expect("written_at column");
+            written_at_column.typed::<Int64Type>().write_batch(&written_at, None, None).map_err(write_err)?;
+            written_at_column.close().map_err(write_err)?;
+
+            row_group_writer.close().map_err(write_err)?;
+            positions.extend((0..chunk.len()).map(|row| (row_group, row)));
+        }
+
+        let file = writer.into_inner().map_err(write_err)?;
+        file.sync_all().map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+        fs::rename(&tmp_path, &final_path)
+            .with_context(|| "Failed to move Parquet segment into place")
+            .map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+
+        Ok(positions)
+    }
+
+    /// Appends `records` as a new segment and points the index at them.
+    fn append_records(&self, records: Vec<ParquetRecord>) -> Result<(), OmniXError> {
+        let mut state = self.state.lock();
+        let segment = state.next_segment;
+        state.next_segment += 1;
+
+        let positions = self.write_segment(segment, &records)?;
+        state.segments.insert(segment, records.len());
+
+        let mut log_entries = Vec::with_capacity(records.len());
+        for (record, (row_group, row)) in records.into_iter().zip(positions) {
+            let created = match state.index.get(&record.key) {
+                Some(previous) => {
+                    let previous_segment = previous.segment;
+                    let created = previous.created;
+                    Self::release_row(&mut state, previous_segment);
+                    created
+                }
+                None => record.written_at,
+            };
+            let location = RecordLocation {
+                segment,
+                row_group,
+                row,
+                size: record.data.len() as u64,
+                created,
+                modified: record.written_at,
+            };
+            *state.live_rows.entry(segment).or_insert(0) += 1;
+            state.index.insert(record.key.clone(), location.clone());
+            log_entries.push(IndexLogEntry::Put { key: record.key, location });
+        }
+        Self::append_index_log(&mut state, &log_entries)?;
+        self.maintain(&mut state)
+    }
+
+    fn release_row(state: &mut ParquetState, segment: u64) {
+        if let Some(count) = state.live_rows.get_mut(&segment) {
+            *count = count.saturating_sub(1);
+        }
+    }
+
+    /// Stores several records in a single segment.
+    pub fn store_many(&self, records: &[(&str, &[u8])]) -> Result<(), OmniXError> {
+        let written_at = Utc::now().timestamp_millis();
+        self.append_records(
+            records
+                .iter()
+                .map(|(key, data)| ParquetRecord { key: key.to_string(), data: data.to_vec(), written_at })
+                .collect(),
+        )
+    }
+
+    /// Rewrites every live record into as few segments as possible, drops
+    /// superseded and deleted versions, and removes the old segment files.
+    pub fn compact(&self) -> Result<(), OmniXError> {
+        let mut state = self.state.lock();
+        let segments: Vec<u64> = state.segments.keys().copied().collect();
+        if segments.len() <= 1 {
+            return Ok(());
+        }
+        self.merge_segments(&mut state, &segments)?;
+        self.rewrite_index_log(&mut state)
+    }
+
+    /// Removes segments with no live rows, runs the compaction steps
+    /// `plan_compaction` picks, and rewrites the index log once superseded
+    /// lines outnumber live ones.
+    fn maintain(&self, state: &mut ParquetState) -> Result<(), OmniXError> {
+        let empty: Vec<u64> = state
+            .segments
+            .keys()
+            .copied()
+            .filter(|segment| state.live_rows.get(segment).copied().unwrap_or(0) == 0)
+            .collect();
+        for segment in empty {
+            self.remove_segment(state, segment)?;
+        }
+        while let Some(segments) = Self::plan_compaction(state) {
+            self.merge_segments(state, &segments)?;
+        }
+        if state.log_entries > 2 * state.index.len() + PARQUET_ROW_GROUP_SIZE {
+            self.rewrite_index_log(state)?;
+        }
+        Ok(())
+    }
+
+    /// Size-tiered compaction. Segments are grouped by live row count into
+    /// powers of `PARQUET_COMPACTION_FANOUT`, and once a group holds that
+    /// many segments they are merged into one of the next size up, so a
+    /// record is rewritten about log_F(N) times over the life of the store.
+    /// Segments already an F-th of `PARQUET_SEGMENT_MAX_ROWS` stay out of
+    /// the groups; any segment more than half dead is rewritten on its own.
+    fn plan_compaction(state: &ParquetState) -> Option<Vec<u64>> {
+        let live = |segment: &u64| state.live_rows.get(segment).copied().unwrap_or(0);
+        let mut tiers: BTreeMap<u32, Vec<u64>> = BTreeMap::new();
+        for segment in state.segments.keys() {
+            let rows = live(segment);
+            if rows > 0 && rows < PARQUET_SEGMENT_MAX_ROWS / PARQUET_COMPACTION_FANOUT {
+                tiers.entry(rows.ilog(PARQUET_COMPACTION_FANOUT)).or_default().push(*segment);
+            }
+        }
+        if let Some(segments) = tiers.into_values().find(|segments| segments.len() >= PARQUET_COMPACTION_FANOUT) {
+            return Some(segments);
+        }
+        state
+            .segments
+            .iter()
+            .filter(|&(segment, &rows)| live(segment) > 0 && live(segment) * 2 < rows)
+            .max_by_key(|&(segment, &rows)| rows - live(segment))
+            .map(|(segment, _)| vec![*segment])
+    }
+
+    /// Copies the live records of `segments` into new segments, points the
+    /// index at the copies and removes the old files.
+    fn merge_segments(&self, state: &mut ParquetState, segments: &[u64]) -> Result<(), OmniXError> {
+        let merging: BTreeSet<u64> = segments.iter().copied().collect();
+        let mut by_location: Vec<(String, RecordLocation)> = state
+            .index
+            .iter()
+            .filter(|(_, location)| merging.contains(&location.segment))
+            .map(|(key, location)| (key.clone(), location.clone()))
+            .collect();
+        by_location.sort_by_key(|(_, location)| (location.segment, location.row_group, location.row));
+
+        let mut pending = Vec::new();
+        let mut pending_created = Vec::new();
+        let mut log_entries = Vec::with_capacity(by_location.len());
+        let mut open_segment: Option<(u64, SerializedFileReader<File>)> = None;
+        let mut cached_row_group: Option<(u64, usize, Vec<ParquetRecord>)> = None;
+
+        for (key, location) in by_location {
+            if open_segment.as_ref().map(|(segment, _)| *segment) != Some(location.segment) {
+                open_segment = Some((location.segment, Self::open_segment(&self.root, location.segment)?));
+            }
+            let cached = cached_row_group
+                .as_ref()
+                .map(|(segment, row_group, _)| (*segment, *row_group) == (location.segment, location.row_group))
+                .unwrap_or(false);
+            if !cached {
+                let (_, reader) = open_segment.as_ref().expect("segment opened above");
+                cached_row_group = Some((location.segment, location.row_group, Self::read_row_group(reader, location.row_group)?));
+            }
+            let (_, _, rows) = cached_row_group.as_mut().expect("row group cached above");
+            let record = std::mem::replace(
+                &mut rows[location.row],
+                ParquetRecord { key: String::new(), data: Vec::new(), written_at: 0 },
+            );
+            debug_assert_eq!(record.key, key);
+            pending_created.push(location.created);
+            pending.push(record);
+
+            if pending.len() >= PARQUET_SEGMENT_MAX_ROWS {
+                self.flush_compacted(state, &mut pending, &mut pending_created, &mut log_entries)?;
+            }
+        }
+        if !pending.is_empty() {
+            self.flush_compacted(state, &mut pending, &mut pending_created, &mut log_entries)?;
         }
-        row_group_writer.close_column(key_column_writer).unwrap();
+        drop(open_segment);
 
-        let mut data_column_writer = row_group_writer.next_column().unwrap().unwrap();
-        if let parquet::column::writer::ColumnWriter::ByteArrayColumnWriter(ref mut typed_writer) = data_column_writer {
-            let data_value = parquet::data_type::ByteArray::from(data);
-            typed_writer.write_batch(&[data_value], None, None).unwrap();
+        // The copies are durable in the log before the originals go, so a
+        // crash in between leaves only unreferenced files behind.
+        Self::append_index_log(state, &log_entries)?;
+        for segment in merging {
+            self.remove_segment(state, segment)?;
         }
-        row_group_writer.close_column(data_column_writer).unwrap();
+        Ok(())
+    }
 
-        writer.close_row_group(row_group_writer).unwrap();
-        writer.close().unwrap();
+    fn flush_compacted(
+        &self,
+        state: &mut ParquetState,
+        pending: &mut Vec<ParquetRecord>,
+        pending_created: &mut Vec<i64>,
+        log_entries: &mut Vec<IndexLogEntry>,
+    ) -> Result<(), OmniXError> {
+        let segment = state.next_segment;
+        state.next_segment += 1;
 
+        let positions = self.write_segment(segment, pending)?;
+        state.segments.insert(segment, pending.len());
+        for ((record, created), (row_group, row)) in pending.drain(..).zip(pending_created.drain(..)).zip(positions) {
+            let location = RecordLocation {
+                segment,
+                row_group,
+                row,
+                size: record.data.len() as u64,
+                created,
+                modified: record.written_at,
+            };
+            state.index.insert(record.key.clone(), location.clone());
+            *state.live_rows.entry(segment).or_insert(0) += 1;
+            log_entries.push(IndexLogEntry::Put { key: record.key, location });
+        }
         Ok(())
     }
 
+    fn remove_segment(&self, state: &mut ParquetState, segment: u64) -> Result<(), OmniXError> {
+        state.segments.remove(&segment);
+        state.live_rows.remove(&segment);
+        fs::remove_file(Self::segment_path(&self.root, segment))
+            .with_context(|| format!("Failed to remove compacted Parquet segment {}", segment))
+            .map_err(|e| OmniXError::FileSystemError(e.to_string()))
+    }
+
+    fn locate(&self, key: &str) -> Option<RecordLocation> {
+        self.state.lock().index.get(key).cloned()
+    }
+}
+
+impl StorageBackend for ParquetStorage {
+    fn store(&self, key: &str, data: &[u8]) -> Result<(), OmniXError> {
+        self.store_many(&[(key, data)])
+    }
+
+    fn store_batch(&self, entries: &[(String, Vec<u8>)]) -> Result<(), OmniXError> {
+        let records: Vec<(&str, &[u8])> = entries.iter().map(|(key, data)| (key.as_str(), data.as_slice())).collect();
+        self.store_many(&records)
+    }
+
     fn retrieve(&self, key: &str) -> Result<Vec<u8>, OmniXError> {
-        let file = File::open(&self.file_path)
-            .with_context(|| "Failed to open Parquet file")
-            .map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+        let location = self.locate(key).ok_or_else(|| key_not_found("Parquet", key))?;
+        let reader = Self::open_segment(&self.root, location.segment)?;
+        let row_group_reader = reader.get_row_group(location.row_group).map_err(|e| OmniXError::OperationFailed {
+            operation: "Parquet row group access".to_string(),
+            details: e.to_string(),
+        })?;
 
-        let reader = SerializedFileReader::new(file)
-            .with_context(|| "Failed to create Parquet reader")
+        let row = row_group_reader
+            .get_row_iter(None)
             .map_err(|e| OmniXError::OperationFailed {
-                operation: "Parquet reader creation".to_string(),
+                operation: "Parquet row iteration".to_string(),
+                details: e.to_string(),
+            })?
+            .nth(location.row)
+            .ok_or_else(|| key_not_found("Parquet", key))?
+            .map_err(|e| OmniXError::OperationFailed {
+                operation: "Parquet row read".to_string(),
                 details: e.to_string(),
             })?;
 
-        let iter = reader.get_row_iter(None).unwrap();
+        let record = Self::decode_row(&row)?;
+        if record.key != key {
+            return Err(OmniXError::OperationFailed {
+                operation: "Parquet retrieval".to_string(),
+                details: format!("Index points at {} instead of {}", record.key, key),
+            });
+        }
+        Ok(record.data)
+    }
 
-        for record in iter {
-            let record_key = record.get_string(0).unwrap();
-            if record_key == key {
-                let data = record.get_bytes(1).unwrap();
-                return Ok(data.to_vec());
-            }
+    fn delete(&self, key: &str) -> Result<(), OmniXError> {
+        let mut state = self.state.lock();
+        if let Some(previous) = state.index.remove(key) {
+            Self::release_row(&mut state, previous.segment);
+            Self::append_index_log(&mut state, &[IndexLogEntry::Delete { key: key.to_string() }])?;
+            self.maintain(&mut state)?;
         }
+        Ok(())
+    }
 
-        Err(OmniXError::OperationFailed {
-            operation: "Parquet retrieval".to_string(),
-            details: "Key not found".to_string(),
+    fn exists(&self, key: &str) -> Result<bool, OmniXError> {
+        Ok(self.state.lock().index.contains_key(key))
+    }
+
+    fn list_keys(&self, prefix: &str) -> Result<Vec<String>, OmniXError> {
+        let mut keys: Vec<String> = self
+            .state
+            .lock()
+            .index
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+
+    fn metadata(&self, key: &str) -> Result<StorageMetadata, OmniXError> {
+        let location = self.locate(key).ok_or_else(|| key_not_found("Parquet", key))?;
+        Ok(StorageMetadata {
+            size: location.size,
+            created: Utc.timestamp_millis_opt(location.created).single(),
+            modified: Utc.timestamp_millis_opt(location.modified).single(),
         })
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aproar::storage::is_not_found;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parquet_keeps_every_key_across_writes_and_reopen() -> Result<(), OmniXError> {
+        let dir = tempdir().unwrap();
+        let storage = ParquetStorage::new(dir.path().to_path_buf())?;
+
+        for i in 0..200 {
+            storage.store(&format!("key-{}", i), format!("value-{}", i).as_bytes())?;
+        }
+        storage.store("key-7", b"updated")?;
+        storage.delete("key-8")?;
+
+        let reopened = ParquetStorage::new(dir.path().to_path_buf())?;
+        assert_eq!(reopened.retrieve("key-0")?, b"value-0");
+        assert_eq!(reopened.retrieve("key-199")?, b"value-199");
+        assert_eq!(reopened.retrieve("key-7")?, b"updated");
+        assert!(!reopened.exists("key-8")?);
+        assert_eq!(reopened.list_keys("key-")?.len(), 199);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parquet_compaction_drops_superseded_versions() -> Result<(), OmniXError> {
+        let dir = tempdir().unwrap();
+        let storage = ParquetStorage::new(dir.path().to_path_buf())?;
+
+        storage.store_many(&[("a", b"1"), ("b", b"2")])?;
+        storage.store("a", b"3")?;
+        storage.compact()?;
+
+        assert_eq!(ParquetStorage::list_segments(dir.path())?.len(), 1);
+        assert_eq!(storage.retrieve("a")?, b"3");
+        assert_eq!(storage.retrieve("b")?, b"2");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parquet_single_writes_merge_by_size_tier() -> Result<(), OmniXError> {
+        let dir = tempdir().unwrap();
+        let storage = ParquetStorage::new(dir.path().to_path_buf())?;
+
+        let writes = PARQUET_COMPACTION_FANOUT * PARQUET_COMPACTION_FANOUT;
+        for i in 0..writes {
+            storage.store(&format!("key-{}", i), b"value")?;
+        }
+        // Every tier below the top holds fewer than FANOUT segments.
+        assert!(ParquetStorage::list_segments(dir.path())?.len() < 2 * PARQUET_COMPACTION_FANOUT);
+        assert_eq!(storage.state.lock().segments.len(), ParquetStorage::list_segments(dir.path())?.len());
+
+        for i in 0..writes {
+            storage.delete(&format!("key-{}", i))?;
+        }
+        assert!(ParquetStorage::list_segments(dir.path())?.is_empty());
+        assert!(storage.list_keys("key-")?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parquet_reports_deleted_keys_as_not_found() -> Result<(), OmniXError> {
+        let dir = tempdir().unwrap();
+        let storage = ParquetStorage::new(dir.path().to_path_buf())?;
+
+        assert!(storage.retrieve("key").is_err_and(|e| is_not_found(&e)));
+        storage.store("key", b"value")?;
+        storage.delete("key")?;
+        assert!(storage.retrieve("key").is_err_and(|e| is_not_found(&e)));
+
+        Ok(())
+    }
 }
\ No newline at end of file
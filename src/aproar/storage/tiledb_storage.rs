@@ -1,8 +1,13 @@
 // src/aproar/storage/tiledb_storage.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[STORAGE]Xyn>=====S===t===u===d===i===o===s======[R|$>
 
-use super::StorageBackend;
+use super::{key_not_found, StorageBackend, StorageMetadata};
+use super::array_storage::{check_dtype, ArrayElement, ArrayInfo, ArrayOptions, ArrayStorage, DType, Hyperslab};
+use super::fs_storage::escape_key;
 use crate::omnixtracker::OmniXError;
 use anyhow::{Context, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use ndarray::{ArrayD, IxDyn};
+use serde::{Deserialize, Serialize};
 use tiledb::Context;
 use tiledb::Array;
 use tiledb::Config;
@@ -54,6 +59,18 @@ impl TileDBStorage {
                 details: e.to_string(),
             })?;
 
+        let created_attr = tiledb::Attribute::new(&self.ctx, "created_at", Datatype::Int64)
+            .map_err(|e| OmniXError::OperationFailed {
+                operation: "TileDB Attribute creation".to_string(),
+                details: e.to_string(),
+            })?;
+
+        let modified_attr = tiledb::Attribute::new(&self.ctx, "modified_at", Datatype::Int64)
+            .map_err(|e| OmniXError::OperationFailed {
+                operation: "TileDB Attribute creation".to_string(),
+                details: e.to_string(),
+            })?;
+
         let array_schema = array_schema
             .set_domain(&domain)
             .map_err(|e| OmniXError::OperationFailed {
@@ -61,6 +78,16 @@ impl TileDBStorage {
                 details: e.to_string(),
             })?
             .add_attribute(&attr)
+            .map_err(|e| OmniXError::OperationFailed {
+                operation: "Adding Attribute to ArraySchema".to_string(),
+                details: e.to_string(),
+            })?
+            .add_attribute(&created_attr)
+            .map_err(|e| OmniXError::OperationFailed {
+                operation: "Adding Attribute to ArraySchema".to_string(),
+                details: e.to_string(),
+            })?
+            .add_attribute(&modified_attr)
             .map_err(|e| OmniXError::OperationFailed {
                 operation: "Adding Attribute to ArraySchema".to_string(),
                 details: e.to_string(),
@@ -74,6 +101,256 @@ impl TileDBStorage {
 
         Ok(())
     }
+
+    /// Arrays created before cells carried timestamps have no `created_at`
+    /// or `modified_at` attributes; their cells are read and written without.
+    fn has_timestamps(array: &Array) -> Result<bool, OmniXError> {
+        array
+            .schema()
+            .and_then(|schema| schema.has_attribute("modified_at"))
+            .map_err(|e| OmniXError::OperationFailed {
+                operation: "Reading TileDB ArraySchema".to_string(),
+                details: e.to_string(),
+            })
+    }
+
+    /// Reads every cell, or only the cell for `key`, along with its timestamps.
+    fn read_cells(&self, key: Option<&str>) -> Result<Vec<TileDBCell>, OmniXError> {
+        if !Array::exists(&self.ctx, &self.array_uri) {
+            return Ok(Vec::new());
+        }
+
+        let array = Array::open(&self.ctx, &self.array_uri, tiledb::QueryType::Read)
+            .map_err(|e| OmniXError::OperationFailed {
+                operation: "Opening TileDB Array for reading".to_string(),
+                details: e.to_string(),
+            })?;
+
+        let mut query = Query::new(&self.ctx, &array, tiledb::QueryType::Read);
+        query
+            .set_layout(tiledb::Layout::Unordered)
+            .map_err(|e| OmniXError::OperationFailed {
+                operation: "Setting query layout".to_string(),
+                details: e.to_string(),
+            })?;
+
+        if let Some(key) = key {
+            query.set_subarray(&[key]).map_err(|e| OmniXError::OperationFailed {
+                operation: "Setting subarray".to_string(),
+                details: e.to_string(),
+            })?;
+        }
+
+        query.submit().map_err(|e| OmniXError::OperationFailed {
+            operation: "Submitting TileDB query".to_string(),
+            details: e.to_string(),
+        })?;
+
+        let keys = query.result_var_buffer::<u8>("key").map_err(|e| OmniXError::OperationFailed {
+            operation: "Retrieving key buffer".to_string(),
+            details: e.to_string(),
+        })?;
+        let data = query.result_var_buffer::<u8>("data").map_err(|e| OmniXError::OperationFailed {
+            operation: "Retrieving data buffer".to_string(),
+            details: e.to_string(),
+        })?;
+        let (created, modified) = if Self::has_timestamps(&array)? {
+            let created = query.result_buffer::<i64>("created_at").map_err(|e| OmniXError::OperationFailed {
+                operation: "Retrieving created_at buffer".to_string(),
+                details: e.to_string(),
+            })?;
+            let modified = query.result_buffer::<i64>("modified_at").map_err(|e| OmniXError::OperationFailed {
+                operation: "Retrieving modified_at buffer".to_string(),
+                details: e.to_string(),
+            })?;
+            (created.to_vec(), modified.to_vec())
+        } else {
+            (Vec::new(), Vec::new())
+        };
+        let timestamp = |millis: Option<&i64>| millis.and_then(|&millis| Utc.timestamp_millis_opt(millis).single());
+
+        let cells = keys
+            .into_iter()
+            .zip(data)
+            .enumerate()
+            .map(|(i, (key, data))| TileDBCell {
+                key: String::from_utf8_lossy(&key).into_owned(),
+                data,
+                created: timestamp(created.get(i)),
+                modified: timestamp(modified.get(i)),
+            })
+            .collect();
+
+        array.close().map_err(|e| OmniXError::OperationFailed {
+            operation: "Closing TileDB Array".to_string(),
+            details: e.to_string(),
+        })?;
+
+        Ok(cells)
+    }
+}
+
+/// What `store_array` records in the array metadata next to the dense cells.
+#[derive(Serialize, Deserialize)]
+struct StoredArrayInfo {
+    #[serde(flatten)]
+    info: ArrayInfo,
+    created_at: i64,
+    modified_at: i64,
+}
+
+const ARRAY_INFO_METADATA_KEY: &str = "aproar_info";
+
+fn tiledb_datatype(dtype: DType) -> Datatype {
+    match dtype {
+        DType::I8 => Datatype::Int8,
+        DType::I16 => Datatype::Int16,
+        DType::I32 => Datatype::Int32,
+        DType::I64 => Datatype::Int64,
+        DType::U8 => Datatype::UInt8,
+        DType::U16 => Datatype::UInt16,
+        DType::U32 => Datatype::UInt32,
+        DType::U64 => Datatype::UInt64,
+        DType::F32 => Datatype::Float32,
+        DType::F64 => Datatype::Float64,
+    }
+}
+
+impl TileDBStorage {
+    /// Typed arrays live as dense arrays next to the key/value array, one per key.
+    fn dense_array_uri(&self, key: &str) -> String {
+        format!("{}_arrays/{}", self.array_uri, escape_key(key))
+    }
+
+    fn create_dense_array(&self, uri: &str, dtype: DType, shape: &[usize], chunk_shape: &[usize]) -> Result<(), OmniXError> {
+        let mut domain = tiledb::Domain::new(&self.ctx).map_err(|e| OmniXError::OperationFailed {
+            operation: "TileDB Domain creation".to_string(),
+            details: e.to_string(),
+        })?;
+        for (axis, (&extent, &tile)) in shape.iter().zip(chunk_shape).enumerate() {
+            let dim = tiledb::Dimension::new(&self.ctx, &format!("d{}", axis), Datatype::UInt64)
+                .and_then(|dim| dim.set_domain(&[0u64, extent as u64 - 1]))
+                .and_then(|dim| dim.set_tile_extent(tile as u64))
+                .map_err(|e| OmniXError::OperationFailed {
+                    operation: "TileDB Dimension creation".to_string(),
+                    details: e.to_string(),
+                })?;
+            domain = domain.add_dimension(&dim).map_err(|e| OmniXError::OperationFailed {
+                operation: "Adding dimension to Domain".to_string(),
+                details: e.to_string(),
+            })?;
+        }
+
+        let attr = tiledb::Attribute::new(&self.ctx, "value", tiledb_datatype(dtype))
+            .map_err(|e| OmniXError::OperationFailed {
+                operation: "TileDB Attribute creation".to_string(),
+                details: e.to_string(),
+            })?;
+
+        let array_schema = tiledb::ArraySchema::new(&self.ctx, tiledb::ArrayType::Dense)
+            .and_then(|schema| schema.set_domain(&domain))
+            .and_then(|schema| schema.set_cell_order(tiledb::Layout::RowMajor))
+            .and_then(|schema| schema.set_tile_order(tiledb::Layout::RowMajor))
+            .and_then(|schema| schema.add_attribute(&attr))
+            .map_err(|e| OmniXError::OperationFailed {
+                operation: "TileDB ArraySchema creation".to_string(),
+                details: e.to_string(),
+            })?;
+
+        Array::create(&self.ctx, uri, &array_schema).map_err(|e| OmniXError::OperationFailed {
+            operation: "Creating TileDB Array".to_string(),
+            details: e.to_string(),
+        })
+    }
+
+    fn read_array_info(&self, key: &str) -> Result<StoredArrayInfo, OmniXError> {
+        let uri = self.dense_array_uri(key);
+        if !Array::exists(&self.ctx, &uri) {
+            return Err(key_not_found("TileDB", key));
+        }
+
+        let array = Array::open(&self.ctx, &uri, tiledb::QueryType::Read)
+            .map_err(|e| OmniXError::OperationFailed {
+                operation: "Opening TileDB Array for reading".to_string(),
+                details: e.to_string(),
+            })?;
+        let raw = array.get_metadata(ARRAY_INFO_METADATA_KEY).map_err(|e| OmniXError::OperationFailed {
+            operation: "Reading TileDB array metadata".to_string(),
+            details: e.to_string(),
+        })?;
+        array.close().map_err(|e| OmniXError::OperationFailed {
+            operation: "Closing TileDB Array".to_string(),
+            details: e.to_string(),
+        })?;
+
+        serde_json::from_slice(&raw).map_err(|e| OmniXError::OperationFailed {
+            operation: "Decoding TileDB array metadata".to_string(),
+            details: e.to_string(),
+        })
+    }
+
+    fn read_dense<T: ArrayElement>(&self, key: &str, ranges: &[std::ops::Range<usize>]) -> Result<ArrayD<T>, OmniXError> {
+        let shape: Vec<usize> = ranges.iter().map(|range| range.len()).collect();
+        let cell_count: usize = shape.iter().product();
+        if cell_count == 0 {
+            return ArrayD::from_shape_vec(IxDyn(&shape), Vec::new())
+                .map_err(|e| OmniXError::ValidationError(e.to_string()));
+        }
+
+        let array = Array::open(&self.ctx, &self.dense_array_uri(key), tiledb::QueryType::Read)
+            .map_err(|e| OmniXError::OperationFailed {
+                operation: "Opening TileDB Array for reading".to_string(),
+                details: e.to_string(),
+            })?;
+
+        let subarray: Vec<u64> = ranges
+            .iter()
+            .flat_map(|range| [range.start as u64, range.end as u64 - 1])
+            .collect();
+        let mut query = Query::new(&self.ctx, &array, tiledb::QueryType::Read);
+        query
+            .set_layout(tiledb::Layout::RowMajor)
+            .map_err(|e| OmniXError::OperationFailed {
+                operation: "Setting query layout".to_string(),
+                details: e.to_string(),
+            })?
+            .set_subarray(&subarray)
+            .map_err(|e| OmniXError::OperationFailed {
+                operation: "Setting subarray".to_string(),
+                details: e.to_string(),
+            })?
+            .set_buffer("value", vec![0u8; cell_count * T::DTYPE.size()])
+            .map_err(|e| OmniXError::OperationFailed {
+                operation: "Setting value buffer".to_string(),
+                details: e.to_string(),
+            })?;
+
+        query.submit().map_err(|e| OmniXError::OperationFailed {
+            operation: "Submitting TileDB query".to_string(),
+            details: e.to_string(),
+        })?;
+
+        let bytes = query.result_buffer::<u8>("value").map_err(|e| OmniXError::OperationFailed {
+            operation: "Retrieving result buffer".to_string(),
+            details: e.to_string(),
+        })?;
+        array.close().map_err(|e| OmniXError::OperationFailed {
+            operation: "Closing TileDB Array".to_string(),
+            details: e.to_string(),
+        })?;
+
+        ArrayD::from_shape_vec(IxDyn(&shape), T::decode(&bytes)).map_err(|e| OmniXError::OperationFailed {
+            operation: "TileDB array read".to_string(),
+            details: e.to_string(),
+        })
+    }
+}
+
+struct TileDBCell {
+    key: String,
+    data: Vec<u8>,
+    created: Option<DateTime<Utc>>,
+    modified: Option<DateTime<Utc>>,
 }
 
 impl StorageBackend for TileDBStorage {
@@ -82,6 +359,14 @@ impl StorageBackend for TileDBStorage {
             self.create_array()?;
         }
 
+        let now = Utc::now().timestamp_millis();
+        let created_at = self
+            .read_cells(Some(key))?
+            .into_iter()
+            .find_map(|cell| cell.created)
+            .map(|created| created.timestamp_millis())
+            .unwrap_or(now);
+
         let array = Array::open(&self.ctx, &self.array_uri, tiledb::QueryType::Write)
             .map_err(|e| OmniXError::OperationFailed {
                 operation: "Opening TileDB Array for writing".to_string(),
@@ -106,50 +391,148 @@ impl StorageBackend for TileDBStorage {
                 operation: "Setting data buffer".to_string(),
                 details: e.to_string(),
             })?;
+        if Self::has_timestamps(&array)? {
+            query
+                .set_buffer("created_at", vec![created_at])
+                .map_err(|e| OmniXError::OperationFailed {
+                    operation: "Setting created_at buffer".to_string(),
+                    details: e.to_string(),
+                })?
+                .set_buffer("modified_at", vec![now])
+                .map_err(|e| OmniXError::OperationFailed {
+                    operation: "Setting modified_at buffer".to_string(),
+                    details: e.to_string(),
+                })?;
+        }
 
         query.submit().map_err(|e| OmniXError::OperationFailed {
             operation: "Submitting TileDB query".to_string(),
             details: e.to_string(),
         })?;
 
-        array.close().unwrap();
+        array.close().map_err(|e| OmniXError::OperationFailed {
+            operation: "Closing TileDB Array".to_string(),
+            details: e.to_string(),
+        })?;
 
         Ok(())
     }
 
     fn retrieve(&self, key: &str) -> Result<Vec<u8>, OmniXError> {
+        self.read_cells(Some(key))?
+            .into_iter()
+            .next()
+            .map(|cell| cell.data)
+            .ok_or_else(|| key_not_found("TileDB", key))
+    }
+
+    fn delete(&self, key: &str) -> Result<(), OmniXError> {
         if !Array::exists(&self.ctx, &self.array_uri) {
-            return Err(OmniXError::OperationFailed {
-                operation: "TileDB retrieval".to_string(),
-                details: "Array does not exist".to_string(),
-            });
+            return Ok(());
         }
 
-        let array = Array::open(&self.ctx, &self.array_uri, tiledb::QueryType::Read)
+        let array = Array::open(&self.ctx, &self.array_uri, tiledb::QueryType::Delete)
             .map_err(|e| OmniXError::OperationFailed {
-                operation: "Opening TileDB Array for reading".to_string(),
+                operation: "Opening TileDB Array for deleting".to_string(),
                 details: e.to_string(),
             })?;
 
-        let mut query = Query::new(&self.ctx, &array, tiledb::QueryType::Read);
-
-        query
-            .set_layout(tiledb::Layout::Unordered)
+        let condition = tiledb::QueryCondition::new(&self.ctx, "key", key, tiledb::QueryConditionOp::Equal)
             .map_err(|e| OmniXError::OperationFailed {
-                operation: "Setting query layout".to_string(),
+                operation: "Creating TileDB delete condition".to_string(),
                 details: e.to_string(),
-            })?
-            .set_subarray(&[key])
+            })?;
+
+        let mut query = Query::new(&self.ctx, &array, tiledb::QueryType::Delete);
+        query.set_condition(&condition).map_err(|e| OmniXError::OperationFailed {
+            operation: "Setting delete condition".to_string(),
+            details: e.to_string(),
+        })?;
+
+        query.submit().map_err(|e| OmniXError::OperationFailed {
+            operation: "Submitting TileDB delete query".to_string(),
+            details: e.to_string(),
+        })?;
+
+        array.close().map_err(|e| OmniXError::OperationFailed {
+            operation: "Closing TileDB Array".to_string(),
+            details: e.to_string(),
+        })?;
+
+        Ok(())
+    }
+
+    fn exists(&self, key: &str) -> Result<bool, OmniXError> {
+        Ok(!self.read_cells(Some(key))?.is_empty())
+    }
+
+    fn list_keys(&self, prefix: &str) -> Result<Vec<String>, OmniXError> {
+        let mut keys: Vec<String> = self
+            .read_cells(None)?
+            .into_iter()
+            .map(|cell| cell.key)
+            .filter(|cell_key| cell_key.starts_with(prefix))
+            .collect();
+        keys.sort();
+        keys.dedup();
+        Ok(keys)
+    }
+
+    fn metadata(&self, key: &str) -> Result<StorageMetadata, OmniXError> {
+        let cell = self
+            .read_cells(Some(key))?
+            .into_iter()
+            .next()
+            .ok_or_else(|| key_not_found("TileDB", key))?;
+
+        Ok(StorageMetadata {
+            size: cell.data.len() as u64,
+            created: cell.created,
+            modified: cell.modified,
+        })
+    }
+}
+
+impl ArrayStorage for TileDBStorage {
+    fn store_array<T: ArrayElement>(&self, key: &str, array: &ArrayD<T>, options: &ArrayOptions) -> Result<(), OmniXError> {
+        options.validate(array.shape())?;
+        if array.shape().iter().any(|&extent| extent == 0) {
+            return Err(OmniXError::ValidationError(format!(
+                "TileDB cannot store the empty array shape {:?}",
+                array.shape()
+            )));
+        }
+
+        let now = Utc::now().timestamp_millis();
+        let created_at = self.read_array_info(key).map(|stored| stored.created_at).unwrap_or(now);
+        let uri = self.dense_array_uri(key);
+        if Array::exists(&self.ctx, &uri) {
+            tiledb::Object::remove(&self.ctx, &uri).map_err(|e| OmniXError::OperationFailed {
+                operation: "Removing TileDB Array".to_string(),
+                details: e.to_string(),
+            })?;
+        }
+
+        let chunk_shape = options.chunk_shape.clone().unwrap_or_else(|| array.shape().to_vec());
+        self.create_dense_array(&uri, T::DTYPE, array.shape(), &chunk_shape)?;
+
+        let tiledb_array = Array::open(&self.ctx, &uri, tiledb::QueryType::Write)
             .map_err(|e| OmniXError::OperationFailed {
-                operation: "Setting subarray".to_string(),
+                operation: "Opening TileDB Array for writing".to_string(),
                 details: e.to_string(),
             })?;
 
-        let data: Vec<u8> = Vec::new();
+        let values: Vec<T> = array.iter().copied().collect();
+        let mut query = Query::new(&self.ctx, &tiledb_array, tiledb::QueryType::Write);
         query
-            .set_buffer("data", data)
+            .set_layout(tiledb::Layout::RowMajor)
             .map_err(|e| OmniXError::OperationFailed {
-                operation: "Setting data buffer".to_string(),
+                operation: "Setting query layout".to_string(),
+                details: e.to_string(),
+            })?
+            .set_buffer("value", T::encode(&values))
+            .map_err(|e| OmniXError::OperationFailed {
+                operation: "Setting value buffer".to_string(),
                 details: e.to_string(),
             })?;
 
@@ -158,16 +541,71 @@ impl StorageBackend for TileDBStorage {
             details: e.to_string(),
         })?;
 
-        let result_data = query
-            .result_buffer::<u8>("data")
+        let stored = StoredArrayInfo {
+            info: ArrayInfo {
+                shape: array.shape().to_vec(),
+                dtype: T::DTYPE,
+                chunk_shape: Some(chunk_shape),
+                attributes: options.attributes.clone(),
+            },
+            created_at,
+            modified_at: now,
+        };
+        let raw = serde_json::to_vec(&stored).map_err(|e| OmniXError::OperationFailed {
+            operation: "Encoding TileDB array metadata".to_string(),
+            details: e.to_string(),
+        })?;
+        tiledb_array
+            .put_metadata(ARRAY_INFO_METADATA_KEY, Datatype::StringUtf8, &raw)
             .map_err(|e| OmniXError::OperationFailed {
-                operation: "Retrieving result buffer".to_string(),
+                operation: "Writing TileDB array metadata".to_string(),
                 details: e.to_string(),
-            })?
-            .to_vec();
+            })?;
+
+        tiledb_array.close().map_err(|e| OmniXError::OperationFailed {
+            operation: "Closing TileDB Array".to_string(),
+            details: e.to_string(),
+        })?;
 
-        array.close().unwrap();
+        Ok(())
+    }
+
+    fn retrieve_array<T: ArrayElement>(&self, key: &str) -> Result<ArrayD<T>, OmniXError> {
+        let stored = self.read_array_info(key)?;
+        check_dtype::<T>(key, stored.info.dtype)?;
+        let ranges: Vec<_> = stored.info.shape.iter().map(|&extent| 0..extent).collect();
+        self.read_dense(key, &ranges)
+    }
 
-        Ok(result_data)
+    fn retrieve_slab<T: ArrayElement>(&self, key: &str, slab: &Hyperslab) -> Result<ArrayD<T>, OmniXError> {
+        let stored = self.read_array_info(key)?;
+        check_dtype::<T>(key, stored.info.dtype)?;
+        slab.validate(&stored.info.shape)?;
+        self.read_dense(key, &slab.ranges)
+    }
+
+    fn array_info(&self, key: &str) -> Result<ArrayInfo, OmniXError> {
+        self.read_array_info(key).map(|stored| stored.info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aproar::storage::is_not_found;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_tiledb_reports_deleted_keys_as_not_found() -> Result<(), OmniXError> {
+        let dir = tempdir().unwrap();
+        let storage = TileDBStorage::new(&dir.path().join("store").to_string_lossy());
+
+        assert!(storage.retrieve("key").is_err_and(|e| is_not_found(&e)));
+        storage.store("key", b"value")?;
+        assert_eq!(storage.retrieve("key")?, b"value");
+        storage.delete("key")?;
+        assert!(storage.retrieve("key").is_err_and(|e| is_not_found(&e)));
+
+        Ok(())
     }
 }
\ No newline at end of file
@@ -0,0 +1,475 @@
+// src/aproar/tiering.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[APROAR]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use crate::aproar::integrity::unframe;
+use crate::aproar::replication::replica_tiers;
+use crate::aproar::storage::StorageBackend;
+use crate::omnixtracker::{OmniXMetry, OmniXError};
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Maps a usage frequency onto a tier index using descending thresholds.
+/// Anything below every threshold lands on the coldest tier, and deployments
+/// with fewer tiers than thresholds collapse the colder ones onto the last backend.
+pub fn tier_for_frequency(thresholds: &[usize], usage_frequency: usize, tier_count: usize) -> usize {
+    let tier = thresholds
+        .iter()
+        .position(|&threshold| usage_frequency > threshold)
+        .unwrap_or(thresholds.len());
+    tier.min(tier_count.saturating_sub(1))
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AccessStats {
+    /// Access count that is halved (or scaled by the configured decay) on
+    /// every migration pass, so it reflects recent rather than lifetime use.
+    pub score: f64,
+    pub total: u64,
+    pub last_access: Instant,
+}
+
+/// Per-key access counts and recency, fed by every store and retrieve.
+pub struct AccessTracker {
+    stats: DashMap<String, AccessStats>,
+}
+
+impl AccessTracker {
+    pub fn new() -> Self {
+        Self { stats: DashMap::new() }
+    }
+
+    pub fn record(&self, key: &str) {
+        let now = Instant::now();
+        self.stats
+            .entry(key.to_string())
+            .and_modify(|stats| {
+                stats.score += 1.0;
+                stats.total += 1;
+                stats.last_access = now;
+            })
+            .or_insert(AccessStats { score: 1.0, total: 1, last_access: now });
+    }
+
+    pub fn get(&self, key: &str) -> Option<AccessStats> {
+        self.stats.get(key).map(|stats| *stats)
+    }
+
+    pub fn frequency(&self, key: &str) -> usize {
+        self.get(key).map(|stats| stats.score.round() as usize).unwrap_or(0)
+    }
+
+    pub fn decay(&self, factor: f64) {
+        for mut stats in self.stats.iter_mut() {
+            stats.score *= factor;
+        }
+    }
+
+    pub fn forget(&self, key: &str) {
+        self.stats.remove(key);
+    }
+}
+
+impl Default for AccessTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Which tier currently holds each key. Updates take a per-key lock, so a
+/// store and a migration of the same key never interleave, while the map
+/// itself is only locked to read and commit a tier and never across backend
+/// I/O. Streaming writes cannot hold the key lock across awaits, so they pin
+/// the key instead, which keeps the migrator away from it until the pin drops.
+pub struct PlacementIndex {
+    tiers: DashMap<String, usize>,
+    pinned: DashMap<String, usize>,
+    locks: DashMap<String, Arc<Mutex<()>>>,
+}
+
+impl PlacementIndex {
+    pub fn new() -> Self {
+        Self { tiers: DashMap::new(), pinned: DashMap::new(), locks: DashMap::new() }
+    }
+
+    /// Runs `f` holding the lock of `key` alone. The lock is dropped from
+    /// the map once no one else holds or waits for it.
+    fn with_key_lock<T>(&self, key: &str, f: impl FnOnce() -> T) -> T {
+        let lock = self.locks.entry(key.to_string()).or_default().clone();
+        let result = {
+            let _guard = lock.lock();
+            f()
+        };
+        self.locks.remove_if(key, |_, held| Arc::strong_count(held) == 2);
+        result
+    }
+
+    /// Blocks migrations of `key` until the returned guard is dropped. Waits
+    /// for a migration already moving the key to finish, so call it from a
+    /// blocking context.
+    pub fn pin(self: &Arc<Self>, key: &str) -> PlacementPin {
+        *self.pinned.entry(key.to_string()).or_insert(0) += 1;
+        // Taking the key lock once waits out any relocation in flight; later
+        // ones see the pin and skip the key.
+        self.with_key_lock(key, || ());
+        PlacementPin { index: self.clone(), key: key.to_string() }
+    }
+
+    pub fn get(&self, key: &str) -> Option<usize> {
+        self.tiers.get(key).map(|tier| *tier)
+    }
+
+    pub fn is_pinned(&self, key: &str) -> bool {
+        self.pinned.contains_key(key)
+    }
+
+    /// Runs `f` with the key's current tier while holding its key lock and
+    /// records the tier it returns.
+    pub fn place<F>(&self, key: &str, f: F) -> Result<usize, OmniXError>
+    where
+        F: FnOnce(Option<usize>) -> Result<usize, OmniXError>,
+    {
+        self.with_key_lock(key, || {
+            let tier = f(self.get(key))?;
+            self.tiers.insert(key.to_string(), tier);
+            Ok(tier)
+        })
+    }
+
+    /// Like `place`, but does nothing for keys that are not indexed, so a
+    /// migration racing a delete cannot resurrect the key.
+    pub(crate) fn relocate<F>(&self, key: &str, f: F) -> Result<Option<usize>, OmniXError>
+    where
+        F: FnOnce(usize) -> Result<usize, OmniXError>,
+    {
+        self.with_key_lock(key, || {
+            let current = match self.get(key) {
+                Some(_) if self.is_pinned(key) => return Ok(None),
+                Some(current) => current,
+                None => return Ok(None),
+            };
+            let tier = f(current)?;
+            match self.tiers.get_mut(key) {
+                Some(mut entry) => {
+                    *entry = tier;
+                    Ok(Some(tier))
+                }
+                None => Ok(None),
+            }
+        })
+    }
+
+    /// Runs `f` while holding the key lock, indexed or not, and drops the key
+    /// from the index when `f` returns true. Lets a delete re-check its
+    /// condition without racing a store of the same key.
+    pub(crate) fn evict<F>(&self, key: &str, f: F) -> Result<bool, OmniXError>
+    where
+        F: FnOnce() -> Result<bool, OmniXError>,
+    {
+        self.with_key_lock(key, || {
+            let evicted = f()?;
+            if evicted {
+                self.tiers.remove(key);
+            }
+            Ok(evicted)
+        })
+    }
+
+    pub fn remove(&self, key: &str) -> Option<usize> {
+        self.tiers.remove(key).map(|(_, tier)| tier)
+    }
+
+    pub fn entries(&self) -> Vec<(String, usize)> {
+        self.tiers.iter().map(|entry| (entry.key().clone(), *entry.value())).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiers.is_empty()
+    }
+}
+
+impl Default for PlacementIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct PlacementPin {
+    index: Arc<PlacementIndex>,
+    key: String,
+}
+
+impl Drop for PlacementPin {
+    fn drop(&mut self) {
+        self.index.pinned.remove_if_mut(&self.key, |_, count| {
+            *count -= 1;
+            *count == 0
+        });
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MigrationReport {
+    pub promoted: usize,
+    pub demoted: usize,
+    pub failed: usize,
+}
+
+/// Moves keys between tiers based on their tracked access statistics. Only
+/// keys in the placement index are considered; a key enters the index when
+/// it is stored or first read.
+pub struct TierMigrator {
+    storage_backends: Vec<Arc<dyn StorageBackend>>,
+    tier_thresholds: Vec<usize>,
+    replicas: usize,
+    access_tracker: Arc<AccessTracker>,
+    placement_index: Arc<PlacementIndex>,
+    metrics: OmniXMetry,
+    idle_demotion: Duration,
+    decay_factor: f64,
+    max_moves_per_pass: usize,
+}
+
+impl TierMigrator {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        storage_backends: Vec<Arc<dyn StorageBackend>>,
+        tier_thresholds: Vec<usize>,
+        replicas: usize,
+        access_tracker: Arc<AccessTracker>,
+        placement_index: Arc<PlacementIndex>,
+        metrics: OmniXMetry,
+        idle_demotion: Duration,
+        decay_factor: f64,
+        max_moves_per_pass: usize,
+    ) -> Self {
+        Self {
+            storage_backends,
+            tier_thresholds,
+            replicas,
+            access_tracker,
+            placement_index,
+            metrics,
+            idle_demotion,
+            decay_factor,
+            max_moves_per_pass,
+        }
+    }
+
+    /// The tier a key should live on right now. Keys idle for longer than the
+    /// demotion window go straight to the coldest tier.
+    pub fn target_tier(&self, key: &str) -> usize {
+        let coldest = self.storage_backends.len() - 1;
+        match self.access_tracker.get(key) {
+            Some(stats) if stats.last_access.elapsed() < self.idle_demotion => tier_for_frequency(
+                &self.tier_thresholds,
+                stats.score.round() as usize,
+                self.storage_backends.len(),
+            ),
+            _ => coldest,
+        }
+    }
+
+    /// One promotion/demotion pass followed by a decay of the access scores.
+    pub fn run_once(&self) -> Result<MigrationReport, OmniXError> {
+        let start_time = Instant::now();
+        let mut report = MigrationReport::default();
+
+        for (key, current) in self.placement_index.entries() {
+            if report.promoted + report.demoted >= self.max_moves_per_pass {
+                break;
+            }
+            let target = self.target_tier(&key);
+            if target == current {
+                continue;
+            }
+
+            let moved = self.placement_index.relocate(&key, |from| {
+                if from == target {
+                    return Ok(from);
+                }
+                self.move_replicas(&key, from, target)?;
+                Ok(target)
+            });
+
+            match moved {
+                Ok(Some(_)) if target < current => report.promoted += 1,
+                Ok(Some(_)) => report.demoted += 1,
+                Ok(None) => {}
+                Err(e) => {
+                    report.failed += 1;
+                    e.log();
+                }
+            }
+        }
+
+        self.access_tracker.decay(self.decay_factor);
+
+        let duration = start_time.elapsed();
+        self.metrics.record_histogram("tiering.migration.duration".to_string(), duration.as_secs_f64());
+        self.metrics.increment_counter("tiering.promoted".to_string(), report.promoted as u64);
+        self.metrics.increment_counter("tiering.demoted".to_string(), report.demoted as u64);
+        self.metrics.increment_counter("tiering.failed".to_string(), report.failed as u64);
+        self.metrics.update_gauge("tiering.indexed_keys".to_string(), self.placement_index.len() as f64);
+
+        Ok(report)
+    }
+
+    /// Moves `key` to the hottest primary colder than its current one whose
+    /// replica set leaves out `tier`. Returns false when no such tier
+    /// exists, or when the key is pinned or not indexed.
+    pub(crate) fn demote_off(&self, key: &str, tier: usize) -> Result<bool, OmniXError> {
+        let tier_count = self.storage_backends.len();
+        let mut demoted = false;
+        self.placement_index.relocate(key, |from| {
+            let target = (from + 1..tier_count).find(|&to| !replica_tiers(to, self.replicas, tier_count).contains(&tier));
+            match target {
+                Some(to) => {
+                    self.move_replicas(key, from, to)?;
+                    demoted = true;
+                    Ok(to)
+                }
+                None => Ok(from),
+            }
+        })?;
+        if demoted {
+            self.metrics.increment_counter("tiering.demoted".to_string(), 1);
+        }
+        Ok(demoted)
+    }
+
+    /// Moves the replica set of `key` from the one rooted at `from` to the
+    /// one rooted at `to`, copying only to tiers that lack a copy and
+    /// deleting only from tiers that leave the set.
+    fn move_replicas(&self, key: &str, from: usize, to: usize) -> Result<(), OmniXError> {
+        let tier_count = self.storage_backends.len();
+        let old_set = replica_tiers(from, self.replicas, tier_count);
+        let new_set = replica_tiers(to, self.replicas, tier_count);
+
+        // Never spread a corrupted copy; the scrub job deals with those.
+        let mut last_error = None;
+        let mut data = None;
+        for &tier in &old_set {
+            let copy = self.storage_backends[tier].retrieve(key).and_then(|stored| {
+                unframe(key, &stored)?;
+                Ok(stored)
+            });
+            match copy {
+                Ok(stored) => {
+                    data = Some(stored);
+                    break;
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        let data = match (data, last_error) {
+            (Some(data), _) => data,
+            (None, Some(e)) => return Err(e),
+            (None, None) => return Ok(()),
+        };
+
+        for &tier in new_set.iter().filter(|tier| !old_set.contains(tier)) {
+            self.storage_backends[tier].store(key, &data)?;
+        }
+        for &tier in old_set.iter().filter(|tier| !new_set.contains(tier)) {
+            self.storage_backends[tier].delete(key)?;
+        }
+        Ok(())
+    }
+
+    /// Runs `run_once` on a blocking thread every `interval` until the runtime shuts down.
+    pub fn spawn(self: Arc<Self>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let migrator = self.clone();
+                match tokio::task::spawn_blocking(move || migrator.run_once()).await {
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => e.log(),
+                    Err(e) => tracing::error!("Tier migration task panicked: {}", e),
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aproar::storage::MemoryStorage;
+
+    #[test]
+    fn test_migrator_promotes_hot_and_demotes_idle_keys() -> Result<(), OmniXError> {
+        let hot = Arc::new(MemoryStorage::new());
+        let cold = Arc::new(MemoryStorage::new());
+        let tracker = Arc::new(AccessTracker::new());
+        let placement = Arc::new(PlacementIndex::new());
+
+        cold.store("popular", b"p")?;
+        placement.place("popular", |_| Ok(1))?;
+        for _ in 0..5 {
+            tracker.record("popular");
+        }
+        hot.store("stale", b"s")?;
+        placement.place("stale", |_| Ok(0))?;
+
+        let migrator = TierMigrator::new(
+            vec![hot.clone(), cold.clone()],
+            vec![3],
+            1,
+            tracker.clone(),
+            placement.clone(),
+            OmniXMetry::init()?,
+            Duration::from_secs(60),
+            0.5,
+            16,
+        );
+        let report = migrator.run_once()?;
+
+        assert_eq!(report, MigrationReport { promoted: 1, demoted: 1, failed: 0 });
+        assert_eq!(placement.get("popular"), Some(0));
+        assert_eq!(placement.get("stale"), Some(1));
+        assert!(hot.exists("popular")? && !cold.exists("popular")?);
+        assert!(cold.exists("stale")? && !hot.exists("stale")?);
+        assert_eq!(tracker.frequency("popular"), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tier_for_frequency_clamps_to_available_tiers() {
+        assert_eq!(tier_for_frequency(&[100, 10], 500, 3), 0);
+        assert_eq!(tier_for_frequency(&[100, 10], 50, 3), 1);
+        assert_eq!(tier_for_frequency(&[100, 10], 5, 3), 2);
+        assert_eq!(tier_for_frequency(&[100, 10], 5, 2), 1);
+        assert_eq!(tier_for_frequency(&[100, 10], 5, 1), 0);
+    }
+
+    #[test]
+    fn test_placement_io_runs_outside_the_map_lock() -> Result<(), OmniXError> {
+        let placement = Arc::new(PlacementIndex::new());
+        placement.place("a", |_| Ok(0))?;
+
+        // Other keys, and reads of the key itself, stay available while a
+        // slow store of "a" runs.
+        let reader = placement.clone();
+        placement.place("a", |current| {
+            assert_eq!(current, Some(0));
+            let other = std::thread::spawn(move || {
+                reader.place("b", |_| Ok(1))?;
+                Ok::<_, OmniXError>(reader.get("a"))
+            });
+            assert_eq!(other.join().expect("placement thread panicked")?, Some(0));
+            Ok(1)
+        })?;
+        assert_eq!((placement.get("a"), placement.get("b")), (Some(1), Some(1)));
+        assert!(placement.locks.is_empty());
+        Ok(())
+    }
+}
\ No newline at end of file
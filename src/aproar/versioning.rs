@@ -0,0 +1,473 @@
+// src/aproar/versioning.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[APROAR]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use crate::aproar::AproarManager;
+use crate::omnixtracker::OmniXError;
+use chrono::Utc;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+const NAMESPACE_SEPARATOR: &str = "::";
+const VERSION_KEY_PREFIX: &str = "__aproar_v::";
+const MANIFEST_KEY_PREFIX: &str = "__aproar_m::";
+/// Prefix shared by every key APROAR writes for its own bookkeeping.
+pub const INTERNAL_KEY_PREFIX: &str = "__aproar_";
+
+/// A scope for keys such as a tenant, a model or a session. Namespaces nest
+/// (`tenant-a/model-x/session-1`) and map onto a key prefix, so every backend
+/// and cache layer gets them without changes. The root namespace adds no
+/// prefix and addresses the keys written before namespaces existed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Namespace {
+    segments: Vec<String>,
+}
+
+impl Namespace {
+    pub fn root() -> Self {
+        Self::default()
+    }
+
+    /// Parses a `/`-separated path. Segments may only use `[A-Za-z0-9_-]`.
+    pub fn new(path: &str) -> Result<Self, OmniXError> {
+        path.split('/')
+            .filter(|segment| !segment.is_empty())
+            .try_fold(Self::root(), |namespace, segment| namespace.child(segment))
+    }
+
+    pub fn child(&self, segment: &str) -> Result<Self, OmniXError> {
+        let valid = !segment.is_empty()
+            && segment.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-');
+        if !valid {
+            return Err(OmniXError::ValidationError(format!("Invalid namespace segment: {:?}", segment)));
+        }
+        let mut segments = self.segments.clone();
+        segments.push(segment.to_string());
+        Ok(Self { segments })
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// The physical key `key` is stored under in this namespace.
+    pub fn qualify(&self, key: &str) -> String {
+        if self.is_root() {
+            key.to_string()
+        } else {
+            format!("{}{}{}", self.segments.join("."), NAMESPACE_SEPARATOR, key)
+        }
+    }
+
+    /// The namespace a physical key was qualified in. Internal keys and keys
+    /// without a valid namespace prefix belong to the root namespace.
+    pub(crate) fn of_key(physical: &str) -> Self {
+        if physical.starts_with(INTERNAL_KEY_PREFIX) {
+            return Self::root();
+        }
+        physical
+            .split_once(NAMESPACE_SEPARATOR)
+            .and_then(|(prefix, _)| prefix.split('.').try_fold(Self::root(), |namespace, segment| namespace.child(segment)).ok())
+            .unwrap_or_default()
+    }
+
+    fn strip<'a>(&self, physical: &'a str) -> Option<&'a str> {
+        if self.is_root() {
+            return Some(physical);
+        }
+        physical
+            .strip_prefix(self.segments.join(".").as_str())
+            .and_then(|rest| rest.strip_prefix(NAMESPACE_SEPARATOR))
+    }
+}
+
+impl fmt::Display for Namespace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "/{}", self.segments.join("/"))
+    }
+}
+
+/// How many historical versions of a key to keep. Setting a policy on a
+/// namespace turns versioning on for it. The latest version is never pruned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RetentionPolicy {
+    pub max_versions: Option<usize>,
+    pub max_age: Option<Duration>,
+}
+
+impl RetentionPolicy {
+    pub fn keep_last(max_versions: usize) -> Self {
+        Self { max_versions: Some(max_versions), max_age: None }
+    }
+
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Splits `versions` (oldest first) into the ones to keep and the ones to drop.
+    fn prune(&self, versions: Vec<VersionInfo>, now_ms: i64) -> (Vec<VersionInfo>, Vec<VersionInfo>) {
+        let latest = match versions.last() {
+            Some(latest) => latest.version,
+            None => return (versions, Vec::new()),
+        };
+        let keep_from = self
+            .max_versions
+            .map(|max| versions.len().saturating_sub(max.max(1)))
+            .unwrap_or(0);
+        let max_age_ms = self.max_age.map(|age| age.as_millis() as i64);
+
+        let mut kept = Vec::new();
+        let mut dropped = Vec::new();
+        for (index, info) in versions.into_iter().enumerate() {
+            let within_count = index >= keep_from;
+            let within_age = max_age_ms.map_or(true, |max| now_ms - info.created_ms <= max);
+            if info.version == latest || (within_count && within_age) {
+                kept.push(info);
+            } else {
+                dropped.push(info);
+            }
+        }
+        (kept, dropped)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionInfo {
+    pub version: u64,
+    pub created_ms: i64,
+    pub size: u64,
+}
+
+/// Versions of one key, oldest first. `next_version` only ever grows, so a
+/// version number is never reused after pruning.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct VersionManifest {
+    next_version: u64,
+    versions: Vec<VersionInfo>,
+}
+
+fn version_key(qualified: &str, version: u64) -> String {
+    format!("{}{}{}{:020}", VERSION_KEY_PREFIX, qualified, NAMESPACE_SEPARATOR, version)
+}
+
+fn manifest_key(qualified: &str) -> String {
+    format!("{}{}", MANIFEST_KEY_PREFIX, qualified)
+}
+
+/// Root keys containing the namespace separator read like namespaced keys,
+/// so versioning one would share its manifest and versions with a key in a
+/// child namespace. They are only ever stored as plain keys.
+fn versionable(namespace: &Namespace, key: &str) -> bool {
+    !namespace.is_root() || !key.contains(NAMESPACE_SEPARATOR)
+}
+
+fn version_not_found(key: &str, version: u64) -> OmniXError {
+    OmniXError::OperationFailed {
+        operation: "Version retrieval".to_string(),
+        details: format!("Version {} of {} not found", version, key),
+    }
+}
+
+/// The setting for `namespace`, taken from the closest ancestor that has one.
+pub(crate) fn inherited<T: Copy>(settings: &DashMap<Namespace, T>, namespace: &Namespace) -> Option<T> {
+    let mut segments = namespace.segments.clone();
+    loop {
+        let scope = Namespace { segments: segments.clone() };
+        if let Some(setting) = settings.get(&scope) {
+            return Some(*setting);
+        }
+        if segments.pop().is_none() {
+            return None;
+        }
+    }
+}
+
+impl AproarManager {
+    pub fn set_retention(&self, namespace: &Namespace, policy: RetentionPolicy) {
+        self.retention_policies.insert(namespace.clone(), policy);
+    }
+
+    pub fn clear_retention(&self, namespace: &Namespace) {
+        self.retention_policies.remove(namespace);
+    }
+
+    /// The policy for `namespace`, inherited from the closest parent that has one.
+    pub fn retention(&self, namespace: &Namespace) -> Option<RetentionPolicy> {
+        inherited(&self.retention_policies, namespace)
+    }
+
+    /// Runs `f` holding the versioning lock of `qualified` alone, so writes
+    /// to different keys never wait on each other. The lock is dropped from
+    /// the map once no one else holds or waits for it.
+    async fn with_version_lock<T, Fut>(&self, qualified: &str, f: impl FnOnce() -> Fut) -> T
+    where
+        Fut: Future<Output = T>,
+    {
+        let lock = self.versioning_locks.entry(qualified.to_string()).or_default().clone();
+        let result = {
+            let _guard = lock.lock().await;
+            f().await
+        };
+        self.versioning_locks.remove_if(qualified, |_, held| Arc::strong_count(held) == 2);
+        result
+    }
+
+    async fn read_manifest(&self, qualified: &str) -> Result<Option<VersionManifest>, OmniXError> {
+        let key = manifest_key(qualified);
+        if !self.data_exists(&key).await? {
+            return Ok(None);
+        }
+        let raw = self.retrieve_data(&key).await?;
+        serde_json::from_slice(&raw).map(Some).map_err(|e| OmniXError::OperationFailed {
+            operation: "Version manifest decoding".to_string(),
+            details: e.to_string(),
+        })
+    }
+
+    /// The manifest of `key` in `namespace`; root keys that cannot be
+    /// versioned have none, whatever a child namespace stored.
+    async fn object_manifest(&self, namespace: &Namespace, key: &str) -> Result<Option<VersionManifest>, OmniXError> {
+        match versionable(namespace, key) {
+            true => self.read_manifest(&namespace.qualify(key)).await,
+            false => Ok(None),
+        }
+    }
+
+    async fn write_manifest(
+        &self,
+        qualified: &str,
+        manifest: &VersionManifest,
+        usage_frequency: usize,
+        ttl: Option<Duration>,
+    ) -> Result<(), OmniXError> {
+        let raw = serde_json::to_vec(manifest).map_err(|e| OmniXError::OperationFailed {
+            operation: "Version manifest encoding".to_string(),
+            details: e.to_string(),
+        })?;
+        self.store_with_expiry(&manifest_key(qualified), &raw, usage_frequency, ttl).await
+    }
+
+    /// Stores `data` under `key` in `namespace`. When the namespace has a
+    /// retention policy the write becomes a new version, older versions past
+    /// the policy are removed, and the new version number is returned. The
+    /// namespace TTL, if any, applies to each version and the manifest. Root
+    /// keys containing `::` cannot be versioned.
+    pub async fn store_object(&self, namespace: &Namespace, key: &str, data: &[u8], usage_frequency: usize) -> Result<Option<u64>, OmniXError> {
+        let qualified = namespace.qualify(key);
+        let policy = match self.retention(namespace) {
+            Some(policy) => policy,
+            None => {
+                self.store_data(&qualified, data, usage_frequency).await?;
+                return Ok(None);
+            }
+        };
+        if !versionable(namespace, key) {
+            return Err(OmniXError::ValidationError(format!(
+                "Root key {:?} contains {:?} and cannot be versioned",
+                key, NAMESPACE_SEPARATOR
+            )));
+        }
+
+        let version = self
+            .with_version_lock(&qualified, || async {
+                let mut manifest = self.read_manifest(&qualified).await?.unwrap_or_default();
+                manifest.next_version += 1;
+                let version = manifest.next_version;
+                let now_ms = Utc::now().timestamp_millis();
+                let ttl = self.ttl(namespace);
+
+                self.store_with_expiry(&version_key(&qualified, version), data, usage_frequency, ttl).await?;
+                manifest.versions.push(VersionInfo { version, created_ms: now_ms, size: data.len() as u64 });
+
+                let (kept, dropped) = policy.prune(std::mem::take(&mut manifest.versions), now_ms);
+                manifest.versions = kept;
+                self.write_manifest(&qualified, &manifest, usage_frequency, ttl).await?;
+                for info in dropped {
+                    self.delete_data(&version_key(&qualified, info.version)).await?;
+                }
+                Ok::<_, OmniXError>(version)
+            })
+            .await?;
+        self.metrics.increment_counter("versioning.store".to_string(), 1);
+
+        Ok(Some(version))
+    }
+
+    /// The latest value of `key`, whether or not it is versioned.
+    pub async fn retrieve_object(&self, namespace: &Namespace, key: &str) -> Result<Vec<u8>, OmniXError> {
+        let qualified = namespace.qualify(key);
+        match self.object_manifest(namespace, key).await?.and_then(|manifest| manifest.versions.last().copied()) {
+            Some(latest) => self.retrieve_data(&version_key(&qualified, latest.version)).await,
+            None => self.retrieve_data(&qualified).await,
+        }
+    }
+
+    pub async fn retrieve_version(&self, namespace: &Namespace, key: &str, version: u64) -> Result<Vec<u8>, OmniXError> {
+        let qualified = namespace.qualify(key);
+        let manifest = self.object_manifest(namespace, key).await?.ok_or_else(|| version_not_found(key, version))?;
+        if !manifest.versions.iter().any(|info| info.version == version) {
+            return Err(version_not_found(key, version));
+        }
+        self.retrieve_data(&version_key(&qualified, version)).await
+    }
+
+    /// Versions of `key` still retained, oldest first.
+    pub async fn list_versions(&self, namespace: &Namespace, key: &str) -> Result<Vec<VersionInfo>, OmniXError> {
+        Ok(self
+            .object_manifest(namespace, key)
+            .await?
+            .map(|manifest| manifest.versions)
+            .unwrap_or_default())
+    }
+
+    /// Removes `key` from `namespace` along with every retained version.
+    pub async fn delete_object(&self, namespace: &Namespace, key: &str) -> Result<(), OmniXError> {
+        let qualified = namespace.qualify(key);
+        self.with_version_lock(&qualified, || async {
+            if let Some(manifest) = self.object_manifest(namespace, key).await? {
+                for info in manifest.versions {
+                    self.delete_data(&version_key(&qualified, info.version)).await?;
+                }
+                self.delete_data(&manifest_key(&qualified)).await?;
+            }
+            self.delete_data(&qualified).await
+        })
+        .await
+    }
+
+    /// Keys in `namespace` starting with `prefix`, without the namespace
+    /// prefix. Listing the root namespace also returns the qualified keys of
+    /// child namespaces, since those are plain keys there.
+    pub async fn list_objects(&self, namespace: &Namespace, prefix: &str) -> Result<Vec<String>, OmniXError> {
+        let qualified_prefix = namespace.qualify(prefix);
+        let mut keys: Vec<String> = self
+            .list_keys(&qualified_prefix)
+            .await?
+            .into_iter()
+            .filter(|key| !key.starts_with(INTERNAL_KEY_PREFIX))
+            .filter_map(|key| namespace.strip(&key).map(str::to_string))
+            .collect();
+        keys.extend(
+            self.list_keys(&manifest_key(&qualified_prefix))
+                .await?
+                .into_iter()
+                .filter_map(|key| key.strip_prefix(MANIFEST_KEY_PREFIX).and_then(|key| namespace.strip(key)).map(str::to_string)),
+        );
+        keys.sort();
+        keys.dedup();
+        Ok(keys)
+    }
+
+    /// Applies the retention policy of `namespace` to every versioned key in
+    /// it. Age limits only take effect on write otherwise. Child namespaces
+    /// are left to their own call, even when they inherit the policy.
+    pub async fn enforce_retention(&self, namespace: &Namespace) -> Result<usize, OmniXError> {
+        let policy = match self.retention(namespace) {
+            Some(policy) => policy,
+            None => return Ok(0),
+        };
+
+        let now_ms = Utc::now().timestamp_millis();
+        let mut removed = 0;
+        for manifest_physical in self.list_keys(&manifest_key(&namespace.qualify(""))).await? {
+            let qualified = &manifest_physical[MANIFEST_KEY_PREFIX.len()..];
+            if Namespace::of_key(qualified) != *namespace {
+                continue;
+            }
+            removed += self
+                .with_version_lock(qualified, || async {
+                    let mut manifest = match self.read_manifest(qualified).await? {
+                        Some(manifest) => manifest,
+                        None => return Ok(0),
+                    };
+                    let (kept, dropped) = policy.prune(std::mem::take(&mut manifest.versions), now_ms);
+                    if dropped.is_empty() {
+                        return Ok(0);
+                    }
+                    manifest.versions = kept;
+                    let ttl = self.remaining_ttl(&manifest_key(qualified));
+                    self.write_manifest(qualified, &manifest, 0, ttl).await?;
+                    for info in &dropped {
+                        self.delete_data(&version_key(qualified, info.version)).await?;
+                    }
+                    Ok::<_, OmniXError>(dropped.len())
+                })
+                .await?;
+        }
+
+        self.metrics.increment_counter("versioning.pruned".to_string(), removed as u64);
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aproar::AproarManagerBuilder;
+    use crate::aproar::storage::MemoryStorage;
+    use crate::omnixtracker::OmniXMetry;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_versioned_namespace_keeps_last_versions() -> Result<(), OmniXError> {
+        let manager = AproarManagerBuilder::new(OmniXMetry::init()?)
+            .storage_backend(Arc::new(MemoryStorage::new()))
+            .background_tasks(false)
+            .build()?;
+        let tenant = Namespace::new("tenant-a/model-x")?;
+        manager.set_retention(&tenant, RetentionPolicy::keep_last(2));
+
+        for payload in [b"one".as_slice(), b"two", b"three"] {
+            manager.store_object(&tenant, "weights", payload, 0).await?;
+        }
+        manager.store_object(&Namespace::root(), "weights", b"root", 0).await?;
+
+        let versions: Vec<u64> = manager.list_versions(&tenant, "weights").await?.iter().map(|info| info.version).collect();
+        assert_eq!(versions, vec![2, 3]);
+        assert_eq!(manager.retrieve_object(&tenant, "weights").await?, b"three");
+        assert_eq!(manager.retrieve_version(&tenant, "weights", 2).await?, b"two");
+        assert!(manager.retrieve_version(&tenant, "weights", 1).await.is_err());
+        assert_eq!(manager.retrieve_object(&Namespace::root(), "weights").await?, b"root");
+        assert_eq!(manager.list_objects(&tenant, "").await?, vec!["weights"]);
+
+        manager.delete_object(&tenant, "weights").await?;
+        assert!(manager.list_versions(&tenant, "weights").await?.is_empty());
+        assert!(manager.list_objects(&tenant, "").await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_root_keys_never_share_a_namespaced_manifest() -> Result<(), OmniXError> {
+        let manager = AproarManagerBuilder::new(OmniXMetry::init()?)
+            .storage_backend(Arc::new(MemoryStorage::new()))
+            .background_tasks(false)
+            .build()?;
+        let (root, tenant) = (Namespace::root(), Namespace::new("tenant-a")?);
+        manager.set_retention(&root, RetentionPolicy::keep_last(1));
+        manager.set_retention(&tenant, RetentionPolicy::keep_last(2));
+
+        manager.store_object(&tenant, "weights", b"one", 0).await?;
+        manager.store_object(&tenant, "weights", b"two", 0).await?;
+        let lookalike = tenant.qualify("weights");
+        assert!(matches!(manager.store_object(&root, &lookalike, b"root", 0).await, Err(OmniXError::ValidationError(_))));
+        assert!(manager.list_versions(&root, &lookalike).await?.is_empty());
+
+        // The root policy keeps one version; the tenant's keeps two.
+        assert_eq!(manager.enforce_retention(&root).await?, 0);
+        assert_eq!(manager.list_versions(&tenant, "weights").await?.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_namespace_rejects_invalid_segments() {
+        assert_eq!(Namespace::new("tenant/session-1").unwrap().qualify("k"), "tenant.session-1::k");
+        assert_eq!(Namespace::root().qualify("k"), "k");
+        assert!(Namespace::new("bad segment").is_err());
+        assert!(Namespace::root().child("a::b").is_err());
+    }
+}
\ No newline at end of file
@@ -57,6 +57,8 @@ pub const MAX_GPU_KERNELS: usize = 8;
 // Password and security-related constants
 pub const PASSWORD_SALT_LENGTH: usize = 32;
 pub const PASSWORD_HASH_ITERATIONS: u32 = 100_000;
+pub const MASTER_KEY_ENV: &str = "APROAR_MASTER_KEY"; // Hex-encoded AES-256 master key for APROAR encryption at rest
+pub const MASTER_KEYFILE_ENV: &str = "APROAR_MASTER_KEYFILE"; // Path of a keyfile read when APROAR_MASTER_KEY is unset
 pub const JWT_EXPIRATION: i64 = 3600;
 pub const RATE_LIMIT_WINDOW: u64 = 60;
 pub const RATE_LIMIT_MAX_REQUESTS: u32 = 100;
@@ -100,3 +102,41 @@ pub const NTM_MEMORY_SIZE: usize = 2048; // Number of memory locations (same as
 pub const NTM_MEMORY_VECTOR_SIZE: usize = 64; // Size of each memory vector (same as DEFAULT_MEMORY_VECTOR_SIZE)
 pub const NTM_CONTROLLER_SIZE: usize = 256; // Size of controller hidden state (same as DEFAULT_CONTROLLER_SIZE)
 pub const CONTEXT_WINDOW_SIZE: usize = 10000; // Number of recent items to keep in context (increased significantly)
+
+// APROAR - Storage constants
+pub const HIGH_FREQUENCY_THRESHOLD: usize = 100; // Usage frequency above which data goes to the hottest tier
+pub const MEDIUM_FREQUENCY_THRESHOLD: usize = 10; // Usage frequency above which data goes to the middle tier
+pub const MAX_DATA_SIZE: usize = 1024 * 1024; // Payloads larger than this are compressed with Zstd instead of LZ4
+pub const ZSTD_COMPRESSION_LEVEL: i32 = 3; // Zstd level used by ZstdCompression (zstd's own default)
+pub const LZ4_COMPRESSION_LEVEL: u32 = 9; // LZ4 frame level used by LZ4Compression; 3 and above select LZ4-HC
+pub const PARALLEL_COMPRESSION_THRESHOLD: usize = 8 * 1024 * 1024; // Payloads this large are compressed on every core
+pub const PARALLEL_LZ4_BLOCKS_PER_THREAD: usize = 4; // 64 KiB blocks buffered per worker by the parallel LZ4 writer
+pub const ADAPTIVE_SAMPLE_WINDOW: usize = 4096; // Bytes in each window the adaptive selector samples
+pub const ADAPTIVE_SAMPLE_WINDOWS: usize = 4; // Windows sampled, spread evenly across the payload
+pub const ADAPTIVE_MIN_PAYLOAD: usize = 64; // Smaller payloads are stored uncompressed by the adaptive selector
+pub const ADAPTIVE_SKIP_ENTROPY: f64 = 7.6; // Sampled bits per byte at or above which a payload is stored uncompressed
+pub const ADAPTIVE_ZSTD_LEVELS: [i32; 4] = [1, 3, 9, 19]; // Zstd levels the adaptive selector chooses between
+pub const ADAPTIVE_PRIOR_WEIGHT: f64 = 8.0; // Observations the built-in codec estimates count as when blended with measurements
+pub const ADAPTIVE_EXPLORE_INTERVAL: u64 = 64; // Every this many selections try the runner-up codec to keep its estimates fresh
+pub const ZSTD_DICT_MAX_PAYLOAD: usize = 32 * 1024; // Payloads up to this size are compressed with the active Zstd dictionary
+pub const ZSTD_DICT_SIZE: usize = 64 * 1024; // Upper bound on the size of a trained Zstd dictionary
+pub const ZSTD_DICT_SAMPLE_KEYS: usize = 2000; // Keys sampled when training a Zstd dictionary
+pub const ZSTD_DICT_MIN_SAMPLES: usize = 32; // Fewer usable samples than this skip training
+pub const ZSTD_DICT_RETRAIN_INTERVAL_SECS: u64 = 6 * 3600; // How often the background task retrains the Zstd dictionary
+pub const ZSTD_DICT_COMPARE_INTERVAL: u64 = 32; // Every this many dictionary payloads are also compressed without it to measure the gain
+pub const BROTLI_QUALITY: u32 = 5; // Brotli quality (0-11) used by BrotliCompression; 5 trades ratio for speed like HTTP servers do
+pub const BROTLI_WINDOW_BITS: u32 = 22; // Base-2 log of the Brotli sliding window (4 MiB)
+pub const BROTLI_BUFFER_SIZE: usize = 64 * 1024; // Buffer the Brotli encoder and decoder stream through
+pub const GZIP_COMPRESSION_LEVEL: u32 = 6; // Level (0-9) used by GzipCompression and DeflateCompression (gzip's own default)
+pub const TIER_MIGRATION_INTERVAL_MS: u64 = 60_000; // How often the background task promotes and demotes keys
+pub const TIER_IDLE_DEMOTION_SECS: u64 = 3600; // Keys untouched for this long are demoted to the coldest tier
+pub const TIER_ACCESS_DECAY: f64 = 0.5; // Factor applied to access scores after each migration pass
+pub const TIER_MAX_MOVES_PER_PASS: usize = 1024; // Upper bound on keys moved by a single migration pass
+pub const PARQUET_ROW_GROUP_SIZE: usize = 1024; // Rows per Parquet row group when writing segments
+pub const PARQUET_SEGMENT_MAX_ROWS: usize = 65536; // Rows per segment file produced by compaction
+pub const PARQUET_COMPACTION_FANOUT: usize = 8; // Parquet segments of one size tier merged together by size-tiered compaction
+pub const CDC_MIN_CHUNK_SIZE: usize = 16 * 1024; // Smallest chunk content-defined chunking cuts, except at the end of a blob
+pub const CDC_AVG_CHUNK_SIZE: usize = 64 * 1024; // Target chunk size for content-defined chunking; must be a power of two
+pub const CDC_MAX_CHUNK_SIZE: usize = 256 * 1024; // Chunks are cut here even without a content boundary
+pub const DEDUP_MIN_PAYLOAD: usize = 1024 * 1024; // Values at least this large are chunked and deduplicated when the policy enables it
+pub const EXPIRY_REAPER_INTERVAL_MS: u64 = 60_000; // How often the background task deletes expired keys and applies retention rules
\ No newline at end of file
@@ -24,6 +24,14 @@ pub use aproar::{
         ZstdCompression,
         CompressionStrategy,
         CompressionManager,
+        AdaptiveSelector,
+        CompressionGoal,
+        CodecKind,
+        CodecRegistry,
+        CompressionChoice,
+        NoCompression,
+        FloatCodec,
+        Quantization,
     },
     memory::{
         AddressingMechanism,
@@ -38,17 +46,45 @@ pub use aproar::{
         ReadHead,
     },
     storage::{
-        HDF5Storage,
-        ParquetStorage,
-        TileDBStorage,
+        ArrayOptions,
+        ArrayStorage,
+        FsStorage,
+        Hyperslab,
+        MemoryStorage,
+        StorageBackend,
+        StorageMetadata,
     },
     retrieval::{
+        MemoryCache,
         RedisCache,
-        RocksDBPersistence,
     },
     AproarManager,
+    AproarManagerBuilder,
+    ArchiveReport,
+    DictionaryReport,
+    Namespace,
+    ReapReport,
+    RetentionPolicy,
+    RetentionRule,
+    RotationReport,
+    ScrubReport,
 };
 
+#[cfg(feature = "brotli-compression")]
+pub use aproar::compression::BrotliCompression;
+#[cfg(feature = "gzip-compression")]
+pub use aproar::compression::{DeflateCompression, GzipCompression};
+#[cfg(feature = "snappy-compression")]
+pub use aproar::compression::SnappyCompression;
+#[cfg(feature = "hdf5-storage")]
+pub use aproar::storage::HDF5Storage;
+#[cfg(feature = "parquet-storage")]
+pub use aproar::storage::ParquetStorage;
+#[cfg(feature = "tiledb-storage")]
+pub use aproar::storage::TileDBStorage;
+#[cfg(feature = "rocksdb-storage")]
+pub use aproar::retrieval::RocksDBPersistence;
+
 pub use omnixtracker::{
     OmniXError,
     OmniXMetry,
@@ -102,6 +138,7 @@ pub use multi_modal::{
 
 pub use security::{
     AESEncryption,
+    MasterKey,
     WebAuthnAuthentication,
 };
 
@@ -70,6 +70,12 @@ pub enum OmniXError {
     #[error("Validation error: {0}")]
     ValidationError(String),
     
+    #[error("Data corruption detected for {key}: {details}")]
+    DataCorruption { key: String, details: String },
+    
+    #[error("Key not found in {backend}: {key}")]
+    NotFound { backend: String, key: String },
+    
     // Integrate NTMError variants into OmniXError
     #[error("Shape mismatch: expected {expected:?}, actual {actual:?}")]
     NTMShapeMismatch { expected: Vec<usize>, actual: Vec<usize> },
@@ -88,7 +94,9 @@ pub enum OmniXError {
 impl OmniXError {
     pub fn log(&self) {
         match self {
-            OmniXError::OperationFailed { .. } | OmniXError::RetryLimitExceeded { .. } => {
+            OmniXError::OperationFailed { .. }
+            | OmniXError::RetryLimitExceeded { .. }
+            | OmniXError::DataCorruption { .. } => {
                 error!("{}", self);
             }
             OmniXError::CircuitBreakerActivated { .. } | OmniXError::OperationTimeout { .. } => {
@@ -137,6 +137,16 @@ impl OmniXMetry {
         self.metrics_data.write().add_histogram(key_name, value);
     }
 
+    /// The most recent values recorded for a histogram, oldest first.
+    pub fn histogram_values(&self, key_name: &str) -> Vec<f64> {
+        self.metrics_data
+            .read()
+            .histograms
+            .get(key_name)
+            .map(|values| values.iter().map(|(_, value)| *value).collect())
+            .unwrap_or_default()
+    }
+
     pub fn rotate_log_file(&self) -> AnyhowResult<()> {
         let mut log_file_lock = self.log_file.write();
         if let Some(mut file) = log_file_lock.take() {
@@ -1 +1,313 @@
-// src/security/aes_encryption.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[SECURITY]Xyn>=====S===t===u===d===i===o===s======[R|$>
\ No newline at end of file
+// src/security/aes_encryption.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[SECURITY]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+use crate::constants::*;
+use crate::omnixtracker::OmniXError;
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use parking_lot::RwLock;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// Marks a blob as encrypted. Compression envelopes start with `APRX`, so an
+/// encrypted blob is never mistaken for a plaintext one.
+pub const ENCRYPTION_MAGIC: [u8; 4] = *b"APRE";
+pub const ENCRYPTION_VERSION: u8 = 1;
+/// magic (4) + version (1) + master key id (4) + wrap nonce (12) +
+/// wrapped data key (32 + 16 tag) + data nonce (12)
+pub const ENCRYPTION_HEADER_LEN: usize = 81;
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+/// magic + version + master key id, bound into both AEAD operations.
+const KEY_ID_END: usize = 9;
+const WRAP_NONCE_END: usize = KEY_ID_END + NONCE_LEN;
+const WRAPPED_KEY_END: usize = WRAP_NONCE_END + KEY_LEN + TAG_LEN;
+const KEY_ID_CONTEXT: &str = "xage aproar master key id v1";
+
+/// A 256-bit key-encryption key. Its id is derived from the key material,
+/// so the same key gets the same id whether it comes from the environment
+/// or a keyfile, and the id alone reveals nothing about the key.
+#[derive(Clone)]
+pub struct MasterKey {
+    id: u32,
+    key: Zeroizing<[u8; KEY_LEN]>,
+}
+
+impl MasterKey {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, OmniXError> {
+        let key: [u8; KEY_LEN] = bytes.try_into().map_err(|_| {
+            OmniXError::ValidationError(format!("Master key must be {} bytes, got {}", KEY_LEN, bytes.len()))
+        })?;
+        let derived = blake3::derive_key(KEY_ID_CONTEXT, &key);
+        let id = u32::from_le_bytes(derived[..4].try_into().expect("slice is 4 bytes"));
+        Ok(Self { id, key: Zeroizing::new(key) })
+    }
+
+    /// Parses a hex-encoded key, ignoring surrounding whitespace.
+    pub fn from_hex(encoded: &str) -> Result<Self, OmniXError> {
+        let bytes = Zeroizing::new(
+            hex::decode(encoded.trim())
+                .map_err(|e| OmniXError::ValidationError(format!("Master key is not valid hex: {}", e)))?,
+        );
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn from_env(var: &str) -> Result<Self, OmniXError> {
+        let encoded = Zeroizing::new(
+            std::env::var(var).map_err(|e| OmniXError::ValidationError(format!("Cannot read master key from {}: {}", var, e)))?,
+        );
+        Self::from_hex(&encoded)
+    }
+
+    /// Reads a keyfile holding either the 32 raw key bytes or their hex encoding.
+    pub fn from_keyfile(path: impl AsRef<Path>) -> Result<Self, OmniXError> {
+        let path = path.as_ref();
+        let contents = Zeroizing::new(
+            std::fs::read(path)
+                .map_err(|e| OmniXError::FileSystemError(format!("Failed to read keyfile {}: {}", path.display(), e)))?,
+        );
+        if contents.len() == KEY_LEN {
+            return Self::from_bytes(&contents);
+        }
+        let encoded = std::str::from_utf8(&contents)
+            .map_err(|_| OmniXError::ValidationError(format!("Keyfile {} holds neither a raw nor a hex key", path.display())))?;
+        Self::from_hex(encoded)
+    }
+
+    pub fn generate() -> Self {
+        let mut key = Zeroizing::new([0u8; KEY_LEN]);
+        OsRng.fill_bytes(&mut key[..]);
+        Self::from_bytes(&key[..]).expect("generated key has the right length")
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new_from_slice(&self.key[..]).expect("master key is 32 bytes")
+    }
+}
+
+impl fmt::Debug for MasterKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MasterKey").field("id", &format_args!("{:08x}", self.id)).finish_non_exhaustive()
+    }
+}
+
+struct Keyring {
+    active: u32,
+    ciphers: BTreeMap<u32, Aes256Gcm>,
+}
+
+/// AES-256-GCM envelope encryption. Every blob gets a fresh data key, which
+/// is stored next to the ciphertext wrapped by the active master key; the
+/// header records the master key's id so blobs written before a rotation
+/// stay readable for as long as their master key is in the keyring.
+pub struct AESEncryption {
+    keyring: RwLock<Keyring>,
+}
+
+impl AESEncryption {
+    pub fn new(master: &MasterKey) -> Self {
+        let mut ciphers = BTreeMap::new();
+        ciphers.insert(master.id, master.cipher());
+        Self { keyring: RwLock::new(Keyring { active: master.id, ciphers }) }
+    }
+
+    /// Loads the master key from the hex in `APROAR_MASTER_KEY` or, when
+    /// that is unset, from the keyfile named by `APROAR_MASTER_KEYFILE`.
+    /// Returns `None` when neither is set.
+    pub fn from_env() -> Result<Option<Self>, OmniXError> {
+        if std::env::var_os(MASTER_KEY_ENV).is_some() {
+            return Ok(Some(Self::new(&MasterKey::from_env(MASTER_KEY_ENV)?)));
+        }
+        match std::env::var_os(MASTER_KEYFILE_ENV) {
+            Some(path) => Ok(Some(Self::new(&MasterKey::from_keyfile(path)?))),
+            None => Ok(None),
+        }
+    }
+
+    /// Makes `master` available for decryption without writing with it.
+    pub fn add_key(&self, master: &MasterKey) {
+        self.keyring.write().ciphers.insert(master.id, master.cipher());
+    }
+
+    /// Encrypts everything from now on under `master` and returns the id of
+    /// the key it replaces, which stays in the keyring for reads.
+    pub fn rotate(&self, master: &MasterKey) -> u32 {
+        let mut keyring = self.keyring.write();
+        keyring.ciphers.insert(master.id, master.cipher());
+        std::mem::replace(&mut keyring.active, master.id)
+    }
+
+    /// Drops a retired master key. Blobs still wrapped by it become
+    /// unreadable, so only retire a key once nothing references it.
+    pub fn retire(&self, key_id: u32) -> Result<bool, OmniXError> {
+        let mut keyring = self.keyring.write();
+        if keyring.active == key_id {
+            return Err(OmniXError::ValidationError(format!("Master key {:08x} is active and cannot be retired", key_id)));
+        }
+        Ok(keyring.ciphers.remove(&key_id).is_some())
+    }
+
+    pub fn active_key_id(&self) -> u32 {
+        self.keyring.read().active
+    }
+
+    pub fn key_ids(&self) -> Vec<u32> {
+        self.keyring.read().ciphers.keys().copied().collect()
+    }
+
+    pub fn has_key(&self, key_id: u32) -> bool {
+        self.keyring.read().ciphers.contains_key(&key_id)
+    }
+
+    pub fn is_encrypted(blob: &[u8]) -> bool {
+        blob.starts_with(&ENCRYPTION_MAGIC)
+    }
+
+    /// The id of the master key that wrapped `blob`'s data key, or `None`
+    /// when `blob` is not encrypted.
+    pub fn key_id(blob: &[u8]) -> Option<u32> {
+        if !Self::is_encrypted(blob) || blob.len() < KEY_ID_END {
+            return None;
+        }
+        Some(u32::from_le_bytes(blob[5..KEY_ID_END].try_into().expect("slice is 4 bytes")))
+    }
+
+    /// Encrypts `plaintext` under a fresh data key. `context` is
+    /// authenticated but not stored, and `decrypt` must be given the same
+    /// bytes, which stops a blob from being replayed under another name.
+    pub fn encrypt(&self, context: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        let mut data_key = Zeroizing::new([0u8; KEY_LEN]);
+        OsRng.fill_bytes(&mut data_key[..]);
+        let mut wrap_nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut wrap_nonce);
+        let mut data_nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut data_nonce);
+
+        let mut blob = Vec::with_capacity(ENCRYPTION_HEADER_LEN + plaintext.len() + TAG_LEN);
+        blob.extend_from_slice(&ENCRYPTION_MAGIC);
+        blob.push(ENCRYPTION_VERSION);
+        {
+            let keyring = self.keyring.read();
+            blob.extend_from_slice(&keyring.active.to_le_bytes());
+            let master = &keyring.ciphers[&keyring.active];
+            let wrapped = master
+                .encrypt(Nonce::from_slice(&wrap_nonce), Payload { msg: &data_key[..], aad: &aad(&blob, context) })
+                .map_err(|_| crypto_error("Encryption", "Failed to wrap the data key"))?;
+            blob.extend_from_slice(&wrap_nonce);
+            blob.extend_from_slice(&wrapped);
+        }
+        blob.extend_from_slice(&data_nonce);
+
+        let cipher = Aes256Gcm::new_from_slice(&data_key[..]).expect("data key is 32 bytes");
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&data_nonce), Payload { msg: plaintext, aad: &aad(&blob, context) })
+            .map_err(|_| crypto_error("Encryption", "Failed to encrypt the payload"))?;
+        blob.extend_from_slice(&ciphertext);
+        Ok(blob)
+    }
+
+    pub fn decrypt(&self, context: &[u8], blob: &[u8]) -> Result<Vec<u8>, OmniXError> {
+        if !Self::is_encrypted(blob) {
+            return Err(crypto_error("Decryption", "Blob is not encrypted"));
+        }
+        if blob.len() < ENCRYPTION_HEADER_LEN + TAG_LEN {
+            return Err(crypto_error("Decryption", format!("Truncated blob: {} bytes", blob.len())));
+        }
+        if blob[4] != ENCRYPTION_VERSION {
+            return Err(crypto_error("Decryption", format!("Unsupported encryption version {}", blob[4])));
+        }
+        let key_id = Self::key_id(blob).expect("header length checked above");
+
+        let data_key = {
+            let keyring = self.keyring.read();
+            let master = keyring
+                .ciphers
+                .get(&key_id)
+                .ok_or_else(|| crypto_error("Decryption", format!("Master key {:08x} is not in the keyring", key_id)))?;
+            let wrapped = Payload { msg: &blob[WRAP_NONCE_END..WRAPPED_KEY_END], aad: &aad(&blob[..KEY_ID_END], context) };
+            Zeroizing::new(
+                master
+                    .decrypt(Nonce::from_slice(&blob[KEY_ID_END..WRAP_NONCE_END]), wrapped)
+                    .map_err(|_| crypto_error("Decryption", "Data key failed authentication"))?,
+            )
+        };
+
+        let cipher = Aes256Gcm::new_from_slice(&data_key).map_err(|_| crypto_error("Decryption", "Data key has the wrong length"))?;
+        let payload = Payload { msg: &blob[ENCRYPTION_HEADER_LEN..], aad: &aad(&blob[..ENCRYPTION_HEADER_LEN], context) };
+        cipher
+            .decrypt(Nonce::from_slice(&blob[WRAPPED_KEY_END..ENCRYPTION_HEADER_LEN]), payload)
+            .map_err(|_| crypto_error("Decryption", "Payload failed authentication"))
+    }
+}
+
+fn aad(header: &[u8], context: &[u8]) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(header.len() + context.len());
+    aad.extend_from_slice(header);
+    aad.extend_from_slice(context);
+    aad
+}
+
+fn crypto_error(operation: &str, details: impl Into<String>) -> OmniXError {
+    OmniXError::OperationFailed {
+        operation: operation.to_string(),
+        details: details.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_binds_context_and_survives_rotation() -> Result<(), OmniXError> {
+        let first = MasterKey::generate();
+        let encryption = AESEncryption::new(&first);
+        let blob = encryption.encrypt(b"alpha", b"secret payload")?;
+        assert!(AESEncryption::is_encrypted(&blob));
+        assert_eq!(AESEncryption::key_id(&blob), Some(first.id()));
+        assert_eq!(blob.len(), ENCRYPTION_HEADER_LEN + b"secret payload".len() + TAG_LEN);
+        assert_eq!(encryption.decrypt(b"alpha", &blob)?, b"secret payload");
+        assert!(encryption.decrypt(b"beta", &blob).is_err());
+
+        let mut tampered = blob.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(encryption.decrypt(b"alpha", &tampered).is_err());
+
+        let second = MasterKey::generate();
+        assert_eq!(encryption.rotate(&second), first.id());
+        let rotated = encryption.encrypt(b"alpha", b"secret payload")?;
+        assert_eq!(AESEncryption::key_id(&rotated), Some(second.id()));
+        assert_eq!(encryption.decrypt(b"alpha", &blob)?, b"secret payload");
+
+        assert!(encryption.retire(second.id()).is_err());
+        assert!(encryption.retire(first.id())?);
+        assert!(encryption.decrypt(b"alpha", &blob).is_err());
+        assert_eq!(encryption.decrypt(b"alpha", &rotated)?, b"secret payload");
+        Ok(())
+    }
+
+    #[test]
+    fn test_master_key_loading() -> Result<(), OmniXError> {
+        let dir = tempfile::tempdir().map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+        let raw = [7u8; KEY_LEN];
+        let raw_path = dir.path().join("raw.key");
+        let hex_path = dir.path().join("hex.key");
+        std::fs::write(&raw_path, raw).map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+        std::fs::write(&hex_path, format!("{}\n", hex::encode(raw))).map_err(|e| OmniXError::FileSystemError(e.to_string()))?;
+
+        let from_raw = MasterKey::from_keyfile(&raw_path)?;
+        let from_hex = MasterKey::from_keyfile(&hex_path)?;
+        assert_eq!(from_raw.id(), from_hex.id());
+        assert_eq!(format!("{:?}", from_raw), format!("MasterKey {{ id: {:08x}, .. }}", from_raw.id()));
+        assert!(MasterKey::from_hex("abcd").is_err());
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -1 +1,5 @@
-// src/security/mod.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[SECURITY]Xyn>=====S===t===u===d===i===o===s======[R|$>
\ No newline at end of file
+// src/security/mod.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[SECURITY]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+pub mod aes_encryption;
+
+pub use aes_encryption::{AESEncryption, MasterKey, ENCRYPTION_HEADER_LEN, ENCRYPTION_MAGIC, ENCRYPTION_VERSION};
\ No newline at end of file
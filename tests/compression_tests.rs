@@ -1 +1,69 @@
-// tests/compression_tests.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[TESTS]Xyn>=====S===t===u===d===i===o===s======[R|$>
\ No newline at end of file
+// tests/compression_tests.rs ~=#######D]======A===r===c====M===o===o===n=====<Lord[TESTS]Xyn>=====S===t===u===d===i===o===s======[R|$>
+
+#[cfg(test)]
+mod tests {
+    use xage::{CodecKind, CompressionManager, CompressionStrategy, OmniXError, OmniXMetry};
+    use std::io::Read;
+
+    fn payload() -> Vec<u8> {
+        b"layer 12, head 3: attention weights for the context window. ".iter().copied().cycle().take(128 * 1024).collect()
+    }
+
+    fn assert_round_trips(strategy: &dyn CompressionStrategy, codec: CodecKind) -> Result<(), OmniXError> {
+        let data = payload();
+        let compressed = strategy.compress(&data)?;
+        if codec == CodecKind::Uncompressed {
+            assert_eq!(compressed, data);
+        } else {
+            assert!(compressed.len() < data.len());
+        }
+        assert_eq!(strategy.decompress(&compressed)?, data);
+        assert_eq!(strategy.decompress(&strategy.compress(&[])?)?, Vec::<u8>::new());
+
+        let mut streamed = Vec::new();
+        strategy
+            .decoder(Box::new(compressed.as_slice()))?
+            .read_to_end(&mut streamed)
+            .map_err(|e| OmniXError::OperationFailed { operation: codec.name().to_string(), details: e.to_string() })?;
+        assert_eq!(streamed, data);
+
+        let manager = CompressionManager::new(OmniXMetry::init()?);
+        assert_eq!(manager.unseal(&manager.seal(codec, &data)?)?, data);
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_compression_round_trip() -> Result<(), OmniXError> {
+        assert_round_trips(&xage::NoCompression, CodecKind::Uncompressed)
+    }
+
+    #[test]
+    fn test_lz4_round_trip() -> Result<(), OmniXError> {
+        assert_round_trips(&xage::LZ4Compression, CodecKind::Lz4)
+    }
+
+    #[test]
+    fn test_zstd_round_trip() -> Result<(), OmniXError> {
+        assert_round_trips(&xage::ZstdCompression, CodecKind::Zstd)
+    }
+
+    #[cfg(feature = "brotli-compression")]
+    #[test]
+    fn test_brotli_round_trip() -> Result<(), OmniXError> {
+        assert_round_trips(&xage::BrotliCompression, CodecKind::Brotli)
+    }
+
+    #[cfg(feature = "snappy-compression")]
+    #[test]
+    fn test_snappy_round_trip() -> Result<(), OmniXError> {
+        assert_round_trips(&xage::SnappyCompression, CodecKind::Snappy)
+    }
+
+    #[cfg(feature = "gzip-compression")]
+    #[test]
+    fn test_gzip_round_trip() -> Result<(), OmniXError> {
+        assert_round_trips(&xage::GzipCompression, CodecKind::Gzip)?;
+        assert!(xage::GzipCompression.compress(&payload())?.starts_with(&[0x1f, 0x8b]));
+        assert_round_trips(&xage::DeflateCompression, CodecKind::Deflate)
+    }
+}
\ No newline at end of file